@@ -0,0 +1,91 @@
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::client::{ChatMessage, Client, InferenceRequest};
+use super::{stream_to_terminal, GenerationParams};
+
+const HELP: &str = "Commands: /reset clears the conversation, /exit quits (or Ctrl+D).";
+
+fn started_at() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default()
+}
+
+/// Interactive chat: every turn sends the whole conversation so far, and the
+/// engine truncates it to the model's context if configured to.
+pub async fn repl(
+    client: &Client,
+    model: String,
+    system: Option<String>,
+    params: &GenerationParams,
+) -> Result<(), String> {
+    let initial: Vec<ChatMessage> = system
+        .into_iter()
+        .map(|content| ChatMessage {
+            role: "system".to_string(),
+            content,
+        })
+        .collect();
+    let mut messages = initial.clone();
+    // Lets llama.cpp keep the conversation on one slot between turns.
+    let session_id = format!("chat-{}-{}", std::process::id(), started_at());
+
+    eprintln!("Chatting with {}. {}", model, HELP);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+
+        let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| format!("Failed to read input: {}", e))?
+        else {
+            println!();
+            return Ok(());
+        };
+
+        match line.trim() {
+            "" => continue,
+            "/exit" | "/quit" => return Ok(()),
+            "/reset" => {
+                messages = initial.clone();
+                eprintln!("Conversation cleared.");
+                continue;
+            }
+            "/help" => {
+                eprintln!("{}", HELP);
+                continue;
+            }
+            _ => {}
+        }
+
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: line,
+        });
+        let req = InferenceRequest {
+            model_id: model.clone(),
+            prompt: None,
+            messages: Some(messages.clone()),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            session_id: Some(session_id.clone()),
+        };
+
+        match stream_to_terminal(client, &req, params).await {
+            Ok(reply) => messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: reply,
+            }),
+            Err(e) => {
+                // Drop the failed turn so it can be retried.
+                messages.pop();
+                eprintln!("error: {}", e);
+            }
+        }
+    }
+}
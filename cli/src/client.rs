@@ -0,0 +1,242 @@
+use futures::StreamExt;
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct Model {
+    pub id: String,
+    pub name: String,
+    pub inference: String,
+    pub context: u32,
+    #[serde(default)]
+    pub quant: Option<String>,
+    pub loaded: bool,
+}
+
+#[derive(Deserialize)]
+struct ModelListResponse {
+    models: Vec<Model>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegisterModelRequest {
+    pub id: String,
+    pub name: String,
+    pub inference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quant: Option<String>,
+    pub capabilities: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+}
+
+/// Where the backend should run a model, for Ollama and in-process llama.cpp.
+#[derive(clap::Args, Debug, Default, Serialize)]
+pub struct Placement {
+    #[arg(long, help = "GPU that holds the model")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main_gpu: Option<u32>,
+
+    #[arg(long, help = "Layers to offload to the GPU (0 keeps the model on the CPU)")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_gpu_layers: Option<u32>,
+
+    #[arg(long, help = "CPU threads to run the model with")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+
+    #[arg(long, help = "NUMA strategy (distribute, isolate, numactl)")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numa: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoadModelRequest<'a> {
+    model_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quant: Option<&'a str>,
+    #[serde(flatten)]
+    placement: &'a Placement,
+}
+
+#[derive(Deserialize)]
+pub struct ActionResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InferenceRequest {
+    pub model_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<ChatMessage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StreamToken {
+    token: String,
+}
+
+/// Final `usage` event of a stream.
+#[derive(Debug, Deserialize)]
+pub struct StreamUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_ms: u64,
+    pub tokens_per_second: f64,
+}
+
+/// Thin wrapper around the engine's HTTP API.
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+
+    /// Sends a request and fails with the server's error body on non-2xx.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, String> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", self.base_url, e))?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(format!("{}: {}", status, error_message(&body)))
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, String> {
+        self.send(request)
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<Model>, String> {
+        let response: ModelListResponse = self.json(self.request(Method::GET, "/v1/models")).await?;
+        Ok(response.models)
+    }
+
+    pub async fn register_model(&self, req: &RegisterModelRequest) -> Result<ActionResponse, String> {
+        self.json(self.request(Method::POST, "/v1/models/register").json(req)).await
+    }
+
+    pub async fn load_model(
+        &self,
+        model_id: &str,
+        quant: Option<&str>,
+        placement: &Placement,
+    ) -> Result<ActionResponse, String> {
+        let body = LoadModelRequest {
+            model_id,
+            quant,
+            placement,
+        };
+        self.json(self.request(Method::POST, "/v1/models/load").json(&body)).await
+    }
+
+    /// Streams a completion, calling `on_token` for every token as it
+    /// arrives. Returns the usage summary sent at the end of the stream.
+    pub async fn stream(
+        &self,
+        req: &InferenceRequest,
+        mut on_token: impl FnMut(&str),
+    ) -> Result<Option<StreamUsage>, String> {
+        let response = self
+            .send(self.request(Method::POST, "/v1/inference/stream").json(req))
+            .await?;
+
+        let mut body = response.bytes_stream();
+        let mut buffer = String::new();
+        let mut usage = None;
+
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| format!("Stream interrupted: {}", e))?;
+            buffer.push_str(&String::from_utf8_lossy(&chunk).replace("\r\n", "\n"));
+
+            while let Some(end) = buffer.find("\n\n") {
+                let event: String = buffer.drain(..end + 2).collect();
+                let (name, data) = parse_event(&event);
+                match name {
+                    "token" => {
+                        if let Ok(token) = serde_json::from_str::<StreamToken>(&data) {
+                            on_token(&token.token);
+                        }
+                    }
+                    "usage" => usage = serde_json::from_str(&data).ok(),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(usage)
+    }
+}
+
+/// Splits a server-sent event into its name (`message` by default) and data.
+fn parse_event(event: &str) -> (&str, String) {
+    let mut name = "message";
+    let mut data: Vec<&str> = Vec::new();
+    for line in event.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    (name, data.join("\n"))
+}
+
+/// Pulls the message and request id out of the engine's JSON error bodies;
+/// errors from anything in between may be plain text.
+fn error_message(body: &str) -> String {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return body.trim().to_string();
+    };
+    let error = &value["error"];
+    match (error["message"].as_str(), error["request_id"].as_str()) {
+        (Some(message), Some(request_id)) => format!("{} (request_id: {})", message, request_id),
+        (Some(message), None) => message.to_string(),
+        _ => body.trim().to_string(),
+    }
+}
@@ -0,0 +1,238 @@
+mod chat;
+mod client;
+
+use clap::{Parser, Subcommand};
+use std::io::Write;
+
+use client::{Client, InferenceRequest, Placement, RegisterModelRequest};
+
+#[derive(Parser, Debug)]
+#[command(name = "openllm", version, about = "Command-line client for the OpenLLM inference engine")]
+struct Cli {
+    #[arg(long, env = "OPENLLM_URL", default_value = "http://localhost:8080", global = true)]
+    #[arg(help = "Base URL of the engine")]
+    url: String,
+
+    #[arg(long, env = "OPENLLM_API_KEY", hide_env_values = true, global = true)]
+    #[arg(help = "API key sent as a bearer token")]
+    api_key: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List, register and load models
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Run a single prompt and stream the response to the terminal
+    Run {
+        #[arg(help = "Model to run")]
+        model: String,
+
+        #[arg(help = "Prompt to send; read from stdin when omitted")]
+        prompt: Option<String>,
+
+        #[command(flatten)]
+        params: GenerationParams,
+    },
+    /// Chat with a model interactively
+    Chat {
+        #[arg(help = "Model to chat with")]
+        model: String,
+
+        #[arg(long)]
+        #[arg(help = "System message that starts the conversation")]
+        system: Option<String>,
+
+        #[command(flatten)]
+        params: GenerationParams,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelsCommand {
+    /// List registered models
+    List,
+    /// Register a model with the engine
+    Register {
+        #[arg(help = "Model id, as known to its backend")]
+        id: String,
+
+        #[arg(long, help = "Display name; defaults to the id")]
+        name: Option<String>,
+
+        #[arg(long, help = "Inference backend (ollama, llama, huggingface, openai, azure_openai, anthropic, gemini, bedrock, sagemaker, vertex, mistral, cohere, groq, openrouter, deepseek, xai, together, fireworks, replicate, tgi, lmstudio, koboldcpp, mlx, triton, llama_local, candle, mock)")]
+        inference: String,
+
+        #[arg(long, help = "Context window in tokens; read from Ollama or the GGUF file when omitted")]
+        context: Option<u32>,
+
+        #[arg(long = "capability", default_value = "chat", help = "Model capability (repeatable)")]
+        capabilities: Vec<String>,
+
+        #[arg(long, help = "Quantization, e.g. q4_k_m")]
+        quant: Option<String>,
+
+        #[arg(long, help = "Latency profile (extreme, fast, slow)")]
+        latency: Option<String>,
+
+        #[arg(long, help = "Chat template: chatml, llama3, mistral or a Jinja template")]
+        chat_template: Option<String>,
+
+        #[arg(long, help = "Generations the model runs at once")]
+        max_concurrency: Option<u32>,
+    },
+    /// Load a registered model
+    Load {
+        #[arg(help = "Model id")]
+        id: String,
+
+        #[arg(long, help = "Quant variant to load (largest that fits in memory by default)")]
+        quant: Option<String>,
+
+        #[command(flatten)]
+        placement: Placement,
+    },
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct GenerationParams {
+    #[arg(long, help = "Maximum tokens to generate")]
+    max_tokens: Option<u32>,
+
+    #[arg(long, help = "Sampling temperature")]
+    temperature: Option<f32>,
+
+    #[arg(long, help = "Print token counts and speed after the response")]
+    stats: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let client = Client::new(&cli.url, cli.api_key.clone());
+
+    let result = match cli.command {
+        Command::Models { command } => models(&client, command).await,
+        Command::Run { model, prompt, params } => run(&client, model, prompt, &params).await,
+        Command::Chat { model, system, params } => chat::repl(&client, model, system, &params).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn models(client: &Client, command: ModelsCommand) -> Result<(), String> {
+    match command {
+        ModelsCommand::List => {
+            let models = client.list_models().await?;
+            if models.is_empty() {
+                println!("No models registered");
+                return Ok(());
+            }
+            let width = models.iter().map(|m| m.id.len()).max().unwrap_or(0).max(2);
+            println!("{:<width$}  {:<12}  {:>8}  {:<8}  LOADED", "ID", "BACKEND", "CONTEXT", "QUANT");
+            for model in models {
+                println!(
+                    "{:<width$}  {:<12}  {:>8}  {:<8}  {}",
+                    model.id,
+                    model.inference,
+                    model.context,
+                    model.quant.as_deref().unwrap_or("-"),
+                    if model.loaded { "yes" } else { "no" },
+                );
+                if model.name != model.id {
+                    println!("{:<width$}  {}", "", model.name);
+                }
+            }
+        }
+        ModelsCommand::Register {
+            id,
+            name,
+            inference,
+            context,
+            capabilities,
+            quant,
+            latency,
+            chat_template,
+            max_concurrency,
+        } => {
+            let response = client
+                .register_model(&RegisterModelRequest {
+                    name: name.unwrap_or_else(|| id.clone()),
+                    id,
+                    inference,
+                    context,
+                    quant,
+                    capabilities,
+                    latency,
+                    chat_template,
+                    max_concurrency,
+                })
+                .await?;
+            println!("{}", response.message);
+        }
+        ModelsCommand::Load { id, quant, placement } => {
+            let response = client.load_model(&id, quant.as_deref(), &placement).await?;
+            println!("{}", response.message);
+        }
+    }
+    Ok(())
+}
+
+async fn run(client: &Client, model: String, prompt: Option<String>, params: &GenerationParams) -> Result<(), String> {
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => std::io::read_to_string(std::io::stdin())
+            .map_err(|e| format!("Failed to read stdin: {}", e))?
+            .trim_end()
+            .to_string(),
+    };
+
+    let req = InferenceRequest {
+        model_id: model,
+        prompt: Some(prompt),
+        messages: None,
+        max_tokens: params.max_tokens,
+        temperature: params.temperature,
+        session_id: None,
+    };
+    stream_to_terminal(client, &req, params).await.map(|_| ())
+}
+
+/// Streams a response to stdout and returns the full text.
+async fn stream_to_terminal(
+    client: &Client,
+    req: &InferenceRequest,
+    params: &GenerationParams,
+) -> Result<String, String> {
+    let mut text = String::new();
+    let mut stdout = std::io::stdout();
+    let usage = client
+        .stream(req, |token| {
+            text.push_str(token);
+            let _ = write!(stdout, "{}", token);
+            let _ = stdout.flush();
+        })
+        .await;
+    if usage.is_ok() || !text.is_empty() {
+        println!();
+    }
+
+    let usage = usage?;
+    if params.stats
+        && let Some(usage) = usage
+    {
+        eprintln!(
+            "[{} prompt + {} completion tokens, {:.1} tokens/s, {} ms]",
+            usage.prompt_tokens, usage.completion_tokens, usage.tokens_per_second, usage.total_ms
+        );
+    }
+    Ok(text)
+}
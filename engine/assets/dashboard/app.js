@@ -0,0 +1,261 @@
+"use strict";
+
+const REFRESH_MS = 2000;
+const KEY_STORAGE = "openllm.apiKey";
+
+const $ = (id) => document.getElementById(id);
+
+let apiKey = sessionStorage.getItem(KEY_STORAGE) || "";
+let lastTotals = null;
+let streamController = null;
+
+function headers(json) {
+  const h = {};
+  if (apiKey) h["Authorization"] = `Bearer ${apiKey}`;
+  if (json) h["Content-Type"] = "application/json";
+  return h;
+}
+
+async function api(path, options = {}) {
+  const response = await fetch(path, {
+    ...options,
+    headers: headers(options.body !== undefined),
+  });
+  if (!response.ok) {
+    throw new Error(await errorMessage(response));
+  }
+  return response.json();
+}
+
+async function errorMessage(response) {
+  try {
+    const body = await response.json();
+    return body.error?.message || response.statusText;
+  } catch {
+    return `${response.status} ${response.statusText}`;
+  }
+}
+
+function setStatus(text, kind) {
+  const status = $("status");
+  status.textContent = text;
+  status.className = `badge ${kind}`;
+}
+
+function format(n, digits = 0) {
+  return n === undefined || n === null ? "–" : Number(n).toLocaleString(undefined, { maximumFractionDigits: digits });
+}
+
+async function refresh() {
+  try {
+    const health = await api("/health");
+    setStatus(health.status, health.status === "healthy" ? "ok" : "warn");
+    renderSaturation(health.saturation);
+  } catch (e) {
+    setStatus("offline", "bad");
+    return;
+  }
+
+  try {
+    const [models, stats] = await Promise.all([api("/v1/models"), api("/v1/stats")]);
+    renderStats(stats);
+    renderModels(models.models, stats.models);
+  } catch (e) {
+    setStatus(e.message, "bad");
+  }
+}
+
+function renderSaturation(saturation) {
+  if (!saturation) {
+    $("running").textContent = "–";
+    $("queued").textContent = "–";
+    $("limits").textContent = "Requests are not bounded (--max-concurrent-requests)";
+    return;
+  }
+  $("running").textContent = saturation.running;
+  $("queued").textContent = saturation.queued;
+  $("limits").textContent =
+    `Limits: ${saturation.max_running} running, ${saturation.max_queued} queued` +
+    (saturation.saturated ? " — saturated" : "");
+}
+
+function renderStats(stats) {
+  const totals = stats.totals;
+  const now = performance.now();
+  if (lastTotals) {
+    const seconds = (now - lastTotals.at) / 1000;
+    const tokens = totals.completion_tokens - lastTotals.completion_tokens;
+    $("throughput").textContent = format(Math.max(tokens, 0) / seconds, 1);
+  }
+  lastTotals = { at: now, completion_tokens: totals.completion_tokens };
+
+  $("total-requests").textContent = format(totals.requests);
+  $("total-errors").textContent = format(totals.errors);
+  $("total-tokens").textContent = format(totals.prompt_tokens + totals.completion_tokens);
+  $("total-ttft").textContent = totals.average_ttft_ms ? `${format(totals.average_ttft_ms)} ms` : "–";
+
+  renderSparkline(stats.buckets.slice(-60).map((bucket) => bucket.completion_tokens));
+}
+
+function renderSparkline(values) {
+  const svg = $("sparkline");
+  if (values.length < 2) {
+    svg.innerHTML = "";
+    return;
+  }
+  const max = Math.max(...values, 1);
+  const step = 120 / (values.length - 1);
+  const points = values.map((v, i) => `${(i * step).toFixed(1)},${(30 - (v / max) * 28 - 1).toFixed(1)}`);
+  svg.innerHTML = `<polyline points="${points.join(" ")}"></polyline>`;
+}
+
+function renderModels(models, modelStats) {
+  const tbody = $("models");
+  tbody.replaceChildren();
+  for (const model of models) {
+    const stats = modelStats.find((s) => s.model_id === model.id && s.tenant === model.tenant);
+    const row = document.createElement("tr");
+    const cells = [
+      model.name === model.id ? model.id : `${model.name} (${model.id})`,
+      model.inference,
+      model.quant || "–",
+      model.latency || "–",
+      format(stats?.requests),
+      format(stats?.tokens_per_second, 1),
+    ];
+    for (const [i, text] of cells.entries()) {
+      const td = document.createElement("td");
+      td.textContent = text;
+      if (i >= 4) td.className = "numeric";
+      row.appendChild(td);
+    }
+
+    const state = document.createElement("td");
+    const badge = document.createElement("span");
+    badge.className = `badge ${model.loaded ? "ok" : ""}`;
+    badge.textContent = model.loaded ? "loaded" : "unloaded";
+    state.appendChild(badge);
+    row.appendChild(state);
+
+    const actions = document.createElement("td");
+    const button = document.createElement("button");
+    button.type = "button";
+    button.textContent = model.loaded ? "Unload" : "Load";
+    button.onclick = () => toggleModel(model, button);
+    actions.appendChild(button);
+    row.appendChild(actions);
+
+    tbody.appendChild(row);
+  }
+  renderModelOptions(models.filter((model) => model.loaded));
+}
+
+function renderModelOptions(loaded) {
+  const select = $("model");
+  const selected = select.value;
+  select.replaceChildren();
+  for (const model of loaded) {
+    const option = document.createElement("option");
+    option.value = model.id;
+    option.textContent = model.name;
+    select.appendChild(option);
+  }
+  if (loaded.some((model) => model.id === selected)) select.value = selected;
+}
+
+async function toggleModel(model, button) {
+  button.disabled = true;
+  try {
+    if (model.loaded) {
+      await api(`/v1/models/unload/${encodeURIComponent(model.id)}`, { method: "POST" });
+    } else {
+      await api("/v1/models/load", { method: "POST", body: JSON.stringify({ model_id: model.id }) });
+    }
+  } catch (e) {
+    alert(e.message);
+  }
+  refresh();
+}
+
+async function streamPrompt(event) {
+  event.preventDefault();
+  const output = $("output");
+  output.textContent = "";
+  output.classList.remove("error");
+  $("usage").textContent = "";
+  $("send").disabled = true;
+  $("stop").disabled = false;
+  streamController = new AbortController();
+
+  try {
+    const response = await fetch("/v1/inference/stream", {
+      method: "POST",
+      headers: headers(true),
+      signal: streamController.signal,
+      body: JSON.stringify({
+        model_id: $("model").value,
+        prompt: $("prompt").value,
+        max_tokens: Number($("max-tokens").value),
+        temperature: Number($("temperature").value),
+      }),
+    });
+    if (!response.ok) throw new Error(await errorMessage(response));
+
+    for await (const [name, data] of sseEvents(response.body)) {
+      if (name === "token") {
+        output.textContent += JSON.parse(data).token;
+      } else if (name === "usage") {
+        const usage = JSON.parse(data);
+        $("usage").textContent =
+          `${usage.completion_tokens} tokens, first after ${usage.time_to_first_token_ms} ms, ` +
+          `${format(usage.tokens_per_second, 1)} tokens/s (${usage.finish_reason})`;
+      }
+    }
+  } catch (e) {
+    if (e.name !== "AbortError") {
+      output.classList.add("error");
+      output.textContent += (output.textContent ? "\n\n" : "") + e.message;
+    }
+  } finally {
+    $("send").disabled = false;
+    $("stop").disabled = true;
+    streamController = null;
+  }
+}
+
+/** Yields `[event, data]` for each server-sent event in a response body. */
+async function* sseEvents(body) {
+  const reader = body.pipeThrough(new TextDecoderStream()).getReader();
+  let buffer = "";
+  for (;;) {
+    const { value, done } = await reader.read();
+    if (done) return;
+    buffer += value;
+    let end;
+    while ((end = buffer.indexOf("\n\n")) !== -1) {
+      const block = buffer.slice(0, end);
+      buffer = buffer.slice(end + 2);
+      let name = "message";
+      const data = [];
+      for (const line of block.split("\n")) {
+        if (line.startsWith("event:")) name = line.slice(6).trim();
+        else if (line.startsWith("data:")) data.push(line.slice(5).replace(/^ /, ""));
+      }
+      if (data.length) yield [name, data.join("\n")];
+    }
+  }
+}
+
+$("auth").onsubmit = (event) => {
+  event.preventDefault();
+  apiKey = $("api-key").value.trim();
+  sessionStorage.setItem(KEY_STORAGE, apiKey);
+  $("api-key").value = "";
+  lastTotals = null;
+  refresh();
+};
+$("playground").onsubmit = streamPrompt;
+$("stop").onclick = () => streamController?.abort();
+
+refresh();
+setInterval(refresh, REFRESH_MS);
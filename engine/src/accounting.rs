@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use super::ModelPricing;
+
+pub fn estimate_cost(pricing: &ModelPricing, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+    (prompt_tokens as f64 / 1000.0) * pricing.prompt_per_1k
+        + (completion_tokens as f64 / 1000.0) * pricing.completion_per_1k
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct AccountUsage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Cumulative usage and estimated spend, keyed by the caller identity that
+/// made the request.
+#[derive(Debug, Default)]
+pub struct Accounting {
+    accounts: HashMap<String, AccountUsage>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountSnapshot {
+    pub account: String,
+    #[serde(flatten)]
+    pub usage: AccountUsage,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountingSnapshot {
+    pub total_cost_usd: f64,
+    pub accounts: Vec<AccountSnapshot>,
+}
+
+impl Accounting {
+    pub fn record(
+        &mut self,
+        account: &str,
+        tenant: Option<&str>,
+        prompt_tokens: u32,
+        completion_tokens: u32,
+        cost_usd: f64,
+    ) {
+        let usage = self.accounts.entry(account.to_string()).or_default();
+        usage.tenant = tenant.map(str::to_string);
+        usage.requests += 1;
+        usage.prompt_tokens += prompt_tokens as u64;
+        usage.completion_tokens += completion_tokens as u64;
+        usage.cost_usd += cost_usd;
+    }
+
+    /// Spend across all accounts, or only those of `tenant` when given.
+    pub fn snapshot(&self, tenant: Option<&str>) -> AccountingSnapshot {
+        let mut accounts: Vec<AccountSnapshot> = self
+            .accounts
+            .iter()
+            .filter(|(_, usage)| tenant.is_none() || usage.tenant.as_deref() == tenant)
+            .map(|(account, usage)| AccountSnapshot {
+                account: account.clone(),
+                usage: usage.clone(),
+            })
+            .collect();
+        accounts.sort_by(|a, b| a.account.cmp(&b.account));
+
+        AccountingSnapshot {
+            total_cost_usd: accounts.iter().map(|a| a.usage.cost_usd).sum(),
+            accounts,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::stream::StreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use utoipa::ToSchema;
+
+use super::error::ApiError;
+use super::v1::inference::EventStream;
+use super::AppState;
+
+/// Bounds the generation requests the engine runs at once, and those waiting
+/// for a turn, so that under load new requests are turned away with a 429
+/// instead of queueing for ever longer.
+#[derive(Debug)]
+pub struct Admission {
+    /// `--max-concurrent-requests`; unbounded when unset.
+    max_running: Option<usize>,
+    /// `--max-queued-requests`.
+    max_queued: usize,
+    running: Arc<Semaphore>,
+    queued: AtomicUsize,
+    /// A moving average of how long admitted requests took, for the
+    /// `Retry-After` of rejected ones.
+    average_ms: AtomicU64,
+}
+
+/// A request turned away because every slot and queue place is taken.
+#[derive(Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct SaturationError {
+    #[serde(skip)]
+    pub message: String,
+    pub queue_depth: usize,
+    pub max_queued: usize,
+    #[serde(skip)]
+    pub retry_after_secs: u64,
+}
+
+/// How busy the engine is, for `/health`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SaturationStatus {
+    pub running: usize,
+    pub max_running: usize,
+    pub queued: usize,
+    pub max_queued: usize,
+    /// Whether new requests are being turned away.
+    pub saturated: bool,
+}
+
+impl Default for Admission {
+    fn default() -> Self {
+        Self::new(None, 0)
+    }
+}
+
+impl Admission {
+    pub fn new(max_running: Option<usize>, max_queued: usize) -> Self {
+        Self {
+            max_running,
+            max_queued,
+            running: Arc::new(Semaphore::new(max_running.unwrap_or(0))),
+            queued: AtomicUsize::new(0),
+            average_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for a slot, or fails at once when the queue is full. `None`
+    /// when requests are not bounded.
+    async fn admit(&self) -> Result<Option<OwnedSemaphorePermit>, SaturationError> {
+        if self.max_running.is_none() {
+            return Ok(None);
+        }
+        if let Ok(permit) = self.running.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+
+        let queued = Queued::join(&self.queued);
+        if queued.ahead >= self.max_queued {
+            return Err(SaturationError {
+                message: format!("The server is at capacity ({} queued); try again later", queued.ahead),
+                queue_depth: queued.ahead,
+                max_queued: self.max_queued,
+                retry_after_secs: self.retry_after_secs(queued.ahead),
+            });
+        }
+        // The semaphore is never closed.
+        Ok(self.running.clone().acquire_owned().await.ok())
+    }
+
+    /// Roughly when a request behind `queued` others would get a slot.
+    fn retry_after_secs(&self, queued: usize) -> u64 {
+        let running = self.max_running.unwrap_or(1).max(1) as u64;
+        let average_ms = self.average_ms.load(Ordering::SeqCst);
+        (average_ms * (queued as u64 + 1) / running).div_ceil(1000).max(1)
+    }
+
+    fn record(&self, elapsed_ms: u64) {
+        // Weighs the latest request at an eighth; races only lose a sample.
+        let average = self.average_ms.load(Ordering::SeqCst);
+        let average = if average == 0 { elapsed_ms } else { (average * 7 + elapsed_ms) / 8 };
+        self.average_ms.store(average, Ordering::SeqCst);
+    }
+
+    pub fn status(&self) -> Option<SaturationStatus> {
+        let max_running = self.max_running?;
+        let queued = self.queued.load(Ordering::SeqCst);
+        Some(SaturationStatus {
+            running: max_running - self.running.available_permits(),
+            max_running,
+            queued,
+            max_queued: self.max_queued,
+            saturated: queued >= self.max_queued && self.running.available_permits() == 0,
+        })
+    }
+}
+
+/// A place in the queue, given up when the request gets a slot, is turned
+/// away, or its client leaves.
+struct Queued<'a> {
+    queued: &'a AtomicUsize,
+    ahead: usize,
+}
+
+impl<'a> Queued<'a> {
+    fn join(queued: &'a AtomicUsize) -> Self {
+        let ahead = queued.fetch_add(1, Ordering::SeqCst);
+        Self { queued, ahead }
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Holds a request's slot until its response body, such as a stream, has
+/// been sent or dropped.
+struct Admitted {
+    admission: Arc<Admission>,
+    _permit: OwnedSemaphorePermit,
+    started: Instant,
+}
+
+impl Drop for Admitted {
+    fn drop(&mut self) {
+        self.admission.record(self.started.elapsed().as_millis() as u64);
+    }
+}
+
+/// Runs generation requests within `--max-concurrent-requests`, queueing up
+/// to `--max-queued-requests` more and rejecting the rest with a 429.
+pub async fn admit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let permit = match state.admission.admit().await {
+        Ok(Some(permit)) => permit,
+        Ok(None) => return next.run(req).await,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let admitted = Admitted {
+        admission: state.admission.clone(),
+        _permit: permit,
+        started: Instant::now(),
+    };
+
+    let (parts, body) = next.run(req).await.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &admitted;
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
+/// Turns on models registered with a `max_concurrency`, keyed by model id.
+/// These apply on top of `--max-concurrent-requests`: a request admitted by
+/// the engine still waits for its model.
+#[derive(Debug, Default)]
+pub struct ModelConcurrency {
+    models: HashMap<String, (u32, Arc<Semaphore>)>,
+}
+
+impl ModelConcurrency {
+    /// The semaphore a model's requests take turns on, made afresh if the
+    /// model's cap has changed.
+    pub fn semaphore(&mut self, model_id: &str, max_concurrency: u32) -> Arc<Semaphore> {
+        match self.models.get(model_id) {
+            Some((max, semaphore)) if *max == max_concurrency => semaphore.clone(),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(max_concurrency as usize));
+                self.models.insert(model_id.to_string(), (max_concurrency, semaphore.clone()));
+                semaphore
+            }
+        }
+    }
+
+    /// Generations a capped model is running.
+    pub fn in_flight(&self, model_id: &str) -> u32 {
+        self.models
+            .get(model_id)
+            .map_or(0, |(max, semaphore)| max.saturating_sub(semaphore.available_permits() as u32))
+    }
+
+    pub fn forget(&mut self, model_id: &str) {
+        self.models.remove(model_id);
+    }
+}
+
+/// Waits for a turn on the model, if it is capped. The turn is given back
+/// when the permit is dropped.
+pub async fn model_turn(state: &AppState, model_id: &str, max_concurrency: Option<u32>) -> Option<OwnedSemaphorePermit> {
+    let semaphore = state.model_concurrency.lock().await.semaphore(model_id, max_concurrency?);
+    // The semaphore is never closed.
+    semaphore.acquire_owned().await.ok()
+}
+
+/// Starts `inner` once the model has a turn, and holds the turn until the
+/// stream ends or is dropped. Waiting inside the stream lets the response
+/// start, and pings flow, while the request waits.
+pub fn after_model_turn(
+    state: AppState,
+    model_id: String,
+    max_concurrency: Option<u32>,
+    mut inner: EventStream,
+) -> EventStream {
+    if max_concurrency.is_none() {
+        return inner;
+    }
+    Box::pin(async_stream::stream! {
+        let _turn = model_turn(&state, &model_id, max_concurrency).await;
+        while let Some(item) = inner.next().await {
+            yield item;
+        }
+    })
+}
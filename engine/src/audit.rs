@@ -0,0 +1,188 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+const AUDIT_FILE_NAME: &str = "audit.jsonl";
+const PROMPT_HASH_LEN: usize = 16;
+const TRUNCATED_PROMPT_CHARS: usize = 256;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum PromptRedaction {
+    /// Only record a hash of the prompt
+    Hash,
+    /// Record the hash plus the first characters of the prompt
+    Truncate,
+    /// Record the full prompt text
+    Full,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+    pub max_files: usize,
+    pub redaction: PromptRedaction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub request_id: String,
+    pub model_id: String,
+    pub stream: bool,
+    pub caller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub prompt_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Handle to the background audit writer. Records are queued and written
+/// by a dedicated task so request handlers never wait on disk I/O.
+#[derive(Clone)]
+pub struct AuditLog {
+    tx: mpsc::UnboundedSender<AuditRecord>,
+    redaction: PromptRedaction,
+}
+
+impl AuditLog {
+    pub async fn start(config: AuditConfig) -> std::io::Result<Self> {
+        fs::create_dir_all(&config.dir).await?;
+        let writer = RotatingWriter::open(&config).await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_writer(writer, rx));
+
+        Ok(Self {
+            tx,
+            redaction: config.redaction,
+        })
+    }
+
+    /// Applies the configured redaction policy to a prompt, returning the
+    /// prompt hash and the (possibly truncated) text to store.
+    pub fn redact(&self, prompt: &str) -> (String, Option<String>) {
+        let hash = prompt_hash(prompt);
+        let text = match self.redaction {
+            PromptRedaction::Hash => None,
+            PromptRedaction::Truncate => Some(prompt.chars().take(TRUNCATED_PROMPT_CHARS).collect()),
+            PromptRedaction::Full => Some(prompt.to_string()),
+        };
+        (hash, text)
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        if self.tx.send(record).is_err() {
+            tracing::warn!("Audit writer stopped; dropping audit record");
+        }
+    }
+}
+
+fn prompt_hash(prompt: &str) -> String {
+    let digest = Sha256::digest(prompt.as_bytes());
+    digest
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .chars()
+        .take(PROMPT_HASH_LEN)
+        .collect()
+}
+
+struct RotatingWriter {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingWriter {
+    async fn open(config: &AuditConfig) -> std::io::Result<Self> {
+        let path = config.dir.join(AUDIT_FILE_NAME);
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        let written = file.metadata().await?.len();
+
+        Ok(Self {
+            dir: config.dir.clone(),
+            max_bytes: config.max_bytes,
+            max_files: config.max_files,
+            file,
+            written,
+        })
+    }
+
+    async fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate().await?;
+        }
+        self.file.write_all(line).await?;
+        self.file.flush().await?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts audit.N.jsonl to audit.N+1.jsonl, dropping files beyond
+    /// `max_files`, and starts a fresh audit.jsonl.
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        let oldest = rotated_path(&self.dir, self.max_files);
+        if fs::try_exists(&oldest).await? {
+            fs::remove_file(&oldest).await?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = rotated_path(&self.dir, index);
+            if fs::try_exists(&from).await? {
+                fs::rename(&from, rotated_path(&self.dir, index + 1)).await?;
+            }
+        }
+
+        let current = self.dir.join(AUDIT_FILE_NAME);
+        if self.max_files > 0 {
+            fs::rename(&current, rotated_path(&self.dir, 1)).await?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&current)
+            .await?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("audit.{}.jsonl", index))
+}
+
+async fn run_writer(mut writer: RotatingWriter, mut rx: mpsc::UnboundedReceiver<AuditRecord>) {
+    while let Some(record) = rx.recv().await {
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Failed to serialize audit record: {}", e);
+                continue;
+            }
+        };
+        line.push(b'\n');
+
+        if let Err(e) = writer.write_line(&line).await {
+            tracing::warn!("Failed to write audit record: {}", e);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use utoipa::ToSchema;
+
+use super::error::ApiError;
+use super::jwt::looks_like_jwt;
+use super::quota::{QuotaStatus, QuotaUsage, TokenQuota};
+use super::rate_limit::DEFAULT_TIER;
+use super::AppState;
+
+const KEY_PREFIX: &str = "ollm_";
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthSettings {
+    pub allow_anonymous: bool,
+    pub admin_key_hash: Option<String>,
+}
+
+/// What a caller may do. `Inference` callers can generate and read stats;
+/// `Admin` callers can additionally manage models and keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Inference,
+    Admin,
+}
+
+/// The authenticated identity behind a request, stored in the request
+/// extensions by `require_api_key`.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub id: String,
+    pub role: Role,
+    /// Rate limit tier; `None` means the caller is never rate limited.
+    pub tier: Option<String>,
+    /// Tenant the caller belongs to. Tenant callers only see their tenant's
+    /// models, keys and usage; callers without one see everything.
+    pub tenant: Option<String>,
+}
+
+impl Caller {
+    /// Key that rate limits are tracked under: shared by all callers of a
+    /// tenant, per caller otherwise.
+    pub fn rate_limit_key(&self) -> String {
+        match &self.tenant {
+            Some(tenant) => format!("tenant:{}", tenant),
+            None => self.id.clone(),
+        }
+    }
+
+    /// Whether a resource owned by `tenant` is visible to this caller.
+    pub fn can_see(&self, tenant: Option<&str>) -> bool {
+        self.tenant.is_none() || self.tenant.as_deref() == tenant
+    }
+}
+
+/// An issued API key. Only the SHA-256 hash of the secret is kept, in memory
+/// and on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default = "default_tier")]
+    pub tier: String,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub quota: TokenQuota,
+    #[serde(default)]
+    pub usage: QuotaUsage,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+fn default_tier() -> String {
+    DEFAULT_TIER.to_string()
+}
+
+#[derive(Debug, Default)]
+pub struct ApiKeyStore {
+    keys: Vec<ApiKeyRecord>,
+    path: Option<PathBuf>,
+    /// Set when usage counters changed since the last save.
+    dirty: bool,
+}
+
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn generate_key() -> String {
+    format!(
+        "{}{}{}",
+        KEY_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+impl ApiKeyStore {
+    /// Opens the key store, loading existing keys from `path` when given.
+    pub async fn open(path: Option<PathBuf>) -> std::io::Result<Self> {
+        let keys = match &path {
+            Some(path) => match tokio::fs::read(path).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e),
+            },
+            None => Vec::new(),
+        };
+        Ok(Self {
+            keys,
+            path,
+            dirty: false,
+        })
+    }
+
+    async fn save(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&self.keys)?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Saves pending usage counters, if any changed since the last save.
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        if self.dirty {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[ApiKeyRecord] {
+        &self.keys
+    }
+
+    /// Issues a new key and returns its record together with the plaintext
+    /// secret, which is never stored and cannot be retrieved again.
+    pub async fn create(
+        &mut self,
+        name: String,
+        role: Role,
+        tier: Option<String>,
+        tenant: Option<String>,
+        quota: TokenQuota,
+    ) -> std::io::Result<(ApiKeyRecord, String)> {
+        let secret = generate_key();
+        let record = ApiKeyRecord {
+            id: format!("key_{}", uuid::Uuid::new_v4().simple()),
+            name,
+            prefix: secret.chars().take(DISPLAY_PREFIX_LEN).collect(),
+            key_hash: hash_key(&secret),
+            role,
+            tier: tier.unwrap_or_else(default_tier),
+            tenant,
+            quota,
+            usage: QuotaUsage::default(),
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+        self.keys.push(record.clone());
+        self.save().await?;
+        Ok((record, secret))
+    }
+
+    /// Marks a key as revoked. Returns `None` if no such key exists.
+    pub async fn revoke(&mut self, key_id: &str) -> std::io::Result<Option<ApiKeyRecord>> {
+        let Some(record) = self.keys.iter_mut().find(|k| k.id == key_id) else {
+            return Ok(None);
+        };
+        if record.revoked_at.is_none() {
+            record.revoked_at = Some(Utc::now());
+        }
+        let record = record.clone();
+        self.save().await?;
+        Ok(Some(record))
+    }
+
+    pub fn record_usage(&mut self, key_id: &str, tokens: u64) {
+        if let Some(record) = self.keys.iter_mut().find(|k| k.id == key_id) {
+            record.usage.add(tokens, Utc::now());
+            self.dirty = true;
+        }
+    }
+
+    /// Quota status for an issued key; `None` for callers without a key
+    /// record (admin and anonymous), which have no quota.
+    pub fn quota_status(&self, key_id: &str) -> Option<QuotaStatus> {
+        self.keys
+            .iter()
+            .find(|k| k.id == key_id)
+            .map(|k| QuotaStatus::compute(&k.quota, &k.usage, Utc::now()))
+    }
+
+    pub fn authenticate(&self, key: &str) -> Option<&ApiKeyRecord> {
+        let hash = hash_key(key);
+        self.keys
+            .iter()
+            .find(|k| k.revoked_at.is_none() && k.key_hash == hash)
+    }
+}
+
+fn unauthorized(message: &str) -> Response {
+    ApiError::Unauthorized(message.to_string()).into_response()
+}
+
+fn bearer_token(req: &Request) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Resolves the `Authorization: Bearer` credential (admin key, JWT when an
+/// issuer is configured, or issued API key) into a `Caller`. Requests without
+/// a credential are let through as anonymous only when `--allow-anonymous`
+/// is set.
+pub async fn require_api_key(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    let mut caller = match bearer_token(&req) {
+        Some(token) => {
+            if state.auth.admin_key_hash.as_deref() == Some(hash_key(token).as_str()) {
+                Caller {
+                    id: "admin".to_string(),
+                    role: Role::Admin,
+                    tier: None,
+                    tenant: None,
+                }
+            } else if let Some(jwt) = state.jwt.as_ref().filter(|_| looks_like_jwt(token)) {
+                match jwt.validate(token).await {
+                    Ok(identity) => Caller {
+                        id: format!("jwt:{}", identity.subject),
+                        role: if identity.roles.iter().any(|r| r == jwt.admin_role()) {
+                            Role::Admin
+                        } else {
+                            Role::Inference
+                        },
+                        tier: Some(default_tier()),
+                        tenant: identity.tenant,
+                    },
+                    Err(e) => return unauthorized(&e),
+                }
+            } else {
+                let keys = state.api_keys.lock().await;
+                match keys.authenticate(token) {
+                    Some(record) => Caller {
+                        id: record.id.clone(),
+                        role: record.role,
+                        tier: Some(record.tier.clone()),
+                        tenant: record.tenant.clone(),
+                    },
+                    None => return unauthorized("Invalid or revoked API key"),
+                }
+            }
+        }
+        None if state.auth.allow_anonymous => {
+            let remote = req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            Caller {
+                id: format!("anonymous@{}", remote),
+                role: if state.auth.admin_key_hash.is_none() {
+                    Role::Admin
+                } else {
+                    Role::Inference
+                },
+                tier: Some(default_tier()),
+                tenant: None,
+            }
+        }
+        None => return unauthorized("Missing API key. Send it as 'Authorization: Bearer <key>'."),
+    };
+
+    if let Some(tenant) = &caller.tenant
+        && let Some(tier) = state.tenants.lock().await.tier(tenant)
+    {
+        caller.tier = Some(tier);
+    }
+
+    req.extensions_mut().insert(caller);
+    next.run(req).await
+}
+
+/// Restricts a route group to callers with the admin role. Must run after
+/// `require_api_key`.
+pub async fn require_admin(req: Request, next: Next) -> Response {
+    match req.extensions().get::<Caller>() {
+        Some(caller) if caller.role == Role::Admin => next.run(req).await,
+        Some(_) => ApiError::Forbidden("Admin role required".to_string()).into_response(),
+        None => unauthorized("Missing API key. Send it as 'Authorization: Bearer <key>'."),
+    }
+}
+
+/// Restricts a route group to admins that are not scoped to a tenant.
+/// Must run after `require_api_key`.
+pub async fn require_global_admin(req: Request, next: Next) -> Response {
+    match req.extensions().get::<Caller>() {
+        Some(caller) if caller.role == Role::Admin && caller.tenant.is_none() => next.run(req).await,
+        Some(_) => ApiError::Forbidden("Global admin role required".to_string()).into_response(),
+        None => unauthorized("Missing API key. Send it as 'Authorization: Bearer <key>'."),
+    }
+}
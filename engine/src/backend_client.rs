@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use super::InferenceBackend;
+use super::config;
+
+/// Prefix of the environment variables that configure a backend, matching
+/// the existing `<PREFIX>_URL` variables.
+fn env_prefix(backend: &InferenceBackend) -> &'static str {
+    match backend {
+        InferenceBackend::Ollama => "OLLAMA",
+        InferenceBackend::Llama => "LLAMA_CPP",
+        InferenceBackend::HuggingFace => "HUGGINGFACE",
+        InferenceBackend::OpenAI => "OPENAI",
+        InferenceBackend::AzureOpenAI => "AZURE_OPENAI",
+        InferenceBackend::Anthropic => "ANTHROPIC",
+        InferenceBackend::Gemini => "GEMINI",
+        InferenceBackend::Bedrock => "BEDROCK",
+        InferenceBackend::SageMaker => "SAGEMAKER",
+        InferenceBackend::Vertex => "VERTEX",
+        InferenceBackend::Mistral => "MISTRAL",
+        InferenceBackend::Cohere => "COHERE",
+        InferenceBackend::Groq => "GROQ",
+        InferenceBackend::OpenRouter => "OPENROUTER",
+        InferenceBackend::DeepSeek => "DEEPSEEK",
+        InferenceBackend::Xai => "XAI",
+        InferenceBackend::Together => "TOGETHER",
+        InferenceBackend::Fireworks => "FIREWORKS",
+        InferenceBackend::Replicate => "REPLICATE",
+        InferenceBackend::Tgi => "TGI",
+        InferenceBackend::LmStudio => "LM_STUDIO",
+        InferenceBackend::KoboldCpp => "KOBOLDCPP",
+        InferenceBackend::Mlx => "MLX",
+        InferenceBackend::Triton => "TRITON",
+        InferenceBackend::LlamaLocal => "LLAMA_LOCAL",
+        InferenceBackend::Candle => "CANDLE",
+        InferenceBackend::Mock => "MOCK",
+    }
+}
+
+fn read_pem(var: &str) -> Result<Option<Vec<u8>>, String> {
+    match config::var(var) {
+        Ok(path) => std::fs::read(&path)
+            .map(Some)
+            .map_err(|e| format!("{}: failed to read {}: {}", var, path, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Builds the client for one backend from `<PREFIX>_CA_CERT` (extra trusted
+/// roots, PEM bundle) and `<PREFIX>_CLIENT_CERT` / `<PREFIX>_CLIENT_KEY`
+/// (client certificate and PKCS#8 key for mTLS). Returns `None` when none
+/// of them are set.
+fn build_client(backend: &InferenceBackend) -> Result<Option<reqwest::Client>, String> {
+    let prefix = env_prefix(backend);
+    let ca = read_pem(&format!("{}_CA_CERT", prefix))?;
+    let cert = read_pem(&format!("{}_CLIENT_CERT", prefix))?;
+    let key = read_pem(&format!("{}_CLIENT_KEY", prefix))?;
+
+    if ca.is_none() && cert.is_none() && key.is_none() {
+        return Ok(None);
+    }
+
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(ca) = ca {
+        let roots = reqwest::Certificate::from_pem_bundle(&ca)
+            .map_err(|e| format!("{}_CA_CERT: {}", prefix, e))?;
+        for root in roots {
+            builder = builder.add_root_certificate(root);
+        }
+    }
+
+    match (cert, key) {
+        (Some(cert), Some(key)) => {
+            let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key)
+                .map_err(|e| format!("{}_CLIENT_CERT/{}_CLIENT_KEY: {}", prefix, prefix, e))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(format!(
+                "{}_CLIENT_CERT and {}_CLIENT_KEY must be set together",
+                prefix, prefix
+            ));
+        }
+    }
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("{}: failed to build HTTP client: {}", prefix, e))
+}
+
+/// Outbound HTTP clients, one per backend with custom TLS settings and a
+/// shared default for the rest. Clients are built once at startup so their
+/// connection pools are reused across requests.
+#[derive(Debug, Default)]
+pub struct BackendClients {
+    custom: HashMap<InferenceBackend, reqwest::Client>,
+    default: reqwest::Client,
+}
+
+impl BackendClients {
+    pub fn from_env() -> Result<Self, String> {
+        let backends = [
+            InferenceBackend::Ollama,
+            InferenceBackend::Llama,
+            InferenceBackend::HuggingFace,
+            InferenceBackend::OpenAI,
+            InferenceBackend::AzureOpenAI,
+            InferenceBackend::Anthropic,
+            InferenceBackend::Gemini,
+            InferenceBackend::Bedrock,
+            InferenceBackend::SageMaker,
+            InferenceBackend::Vertex,
+            InferenceBackend::Mistral,
+            InferenceBackend::Cohere,
+            InferenceBackend::Groq,
+            InferenceBackend::OpenRouter,
+            InferenceBackend::DeepSeek,
+            InferenceBackend::Xai,
+            InferenceBackend::Together,
+            InferenceBackend::Fireworks,
+            InferenceBackend::Replicate,
+            InferenceBackend::Tgi,
+            InferenceBackend::LmStudio,
+            InferenceBackend::KoboldCpp,
+            InferenceBackend::Mlx,
+            InferenceBackend::Triton,
+        ];
+
+        let mut custom = HashMap::new();
+        for backend in backends {
+            if let Some(client) = build_client(&backend)? {
+                tracing::info!("Using custom TLS settings for {:?} backend", backend);
+                custom.insert(backend, client);
+            }
+        }
+
+        Ok(Self {
+            custom,
+            default: reqwest::Client::new(),
+        })
+    }
+
+    pub fn get(&self, backend: &InferenceBackend) -> reqwest::Client {
+        self.custom.get(backend).unwrap_or(&self.default).clone()
+    }
+}
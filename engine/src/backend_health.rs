@@ -0,0 +1,394 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use super::backend_client::BackendClients;
+use super::backends::{
+    anthropic, candle, cohere, deepseek, gemini, groq, hosted_oss, koboldcpp, llama_local, lmstudio, mistral,
+    mlx, ollama, openrouter, replicate, tgi, triton, xai,
+};
+use super::config;
+use super::v1::inference::{azure_api_version, get_backend_url};
+use super::{InferenceBackend, ModelCapability};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// The Hub API, which knows every model whether or not the Inference API
+/// serves it.
+const HUGGINGFACE_HUB_URL: &str = "https://huggingface.co/api/models";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackendStatus {
+    pub backend: InferenceBackend,
+    pub url: String,
+    pub status: ProbeStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BackendHealthReport {
+    pub checked_at: DateTime<Utc>,
+    pub backends: Vec<BackendStatus>,
+}
+
+impl BackendHealthReport {
+    pub fn is_degraded(&self) -> bool {
+        self.backends.iter().any(|b| b.status == ProbeStatus::Down)
+    }
+}
+
+/// Last probe results, reused until they are older than `CACHE_TTL` so that
+/// frequent health polling does not hammer the backends.
+#[derive(Debug, Default)]
+pub struct BackendHealthCache {
+    last: Option<(Instant, BackendHealthReport)>,
+}
+
+impl BackendHealthCache {
+    pub fn fresh(&self) -> Option<&BackendHealthReport> {
+        self.last
+            .as_ref()
+            .filter(|(at, _)| at.elapsed() < CACHE_TTL)
+            .map(|(_, report)| report)
+    }
+
+    pub fn latest(&self) -> Option<&BackendHealthReport> {
+        self.last.as_ref().map(|(_, report)| report)
+    }
+
+    pub fn store(&mut self, report: BackendHealthReport) {
+        self.last = Some((Instant::now(), report));
+    }
+}
+
+/// A backend counts as configured when a registered model uses it or its URL
+/// has been set explicitly through the environment.
+pub fn configured_backends(registered: &[InferenceBackend]) -> Vec<InferenceBackend> {
+    let candidates = [
+        (InferenceBackend::Ollama, Some("OLLAMA_URL")),
+        (InferenceBackend::Llama, Some("LLAMA_CPP_URL")),
+        (InferenceBackend::HuggingFace, Some("HUGGINGFACE_URL")),
+        (InferenceBackend::OpenAI, Some("OPENAI_URL")),
+        (InferenceBackend::AzureOpenAI, Some("AZURE_OPENAI_URL")),
+        (InferenceBackend::Anthropic, Some("ANTHROPIC_URL")),
+        (InferenceBackend::Gemini, Some("GEMINI_URL")),
+        (InferenceBackend::Bedrock, Some("BEDROCK_URL")),
+        (InferenceBackend::SageMaker, Some("SAGEMAKER_URL")),
+        (InferenceBackend::Vertex, Some("VERTEX_PROJECT")),
+        (InferenceBackend::Mistral, Some("MISTRAL_URL")),
+        (InferenceBackend::Cohere, Some("COHERE_URL")),
+        (InferenceBackend::Groq, Some("GROQ_URL")),
+        (InferenceBackend::OpenRouter, Some("OPENROUTER_URL")),
+        (InferenceBackend::DeepSeek, Some("DEEPSEEK_URL")),
+        (InferenceBackend::Xai, Some("XAI_URL")),
+        (InferenceBackend::Together, Some("TOGETHER_URL")),
+        (InferenceBackend::Fireworks, Some("FIREWORKS_URL")),
+        (InferenceBackend::Replicate, Some("REPLICATE_URL")),
+        (InferenceBackend::Tgi, Some("TGI_URL")),
+        (InferenceBackend::LmStudio, Some("LM_STUDIO_URL")),
+        (InferenceBackend::KoboldCpp, Some("KOBOLDCPP_URL")),
+        (InferenceBackend::Mlx, Some("MLX_URL")),
+        (InferenceBackend::Triton, Some("TRITON_URL")),
+        (InferenceBackend::LlamaLocal, None),
+        (InferenceBackend::Candle, None),
+        (InferenceBackend::Mock, None),
+    ];
+
+    candidates
+        .into_iter()
+        .filter(|(backend, env)| registered.contains(backend) || env.is_some_and(|env| config::var(env).is_ok()))
+        .map(|(backend, _)| backend)
+        .collect()
+}
+
+pub async fn probe_backends(clients: &BackendClients, backends: &[InferenceBackend]) -> BackendHealthReport {
+    let probes = backends
+        .iter()
+        .map(|backend| probe_backend(clients.get(backend), backend.clone()));
+    BackendHealthReport {
+        checked_at: Utc::now(),
+        backends: futures::future::join_all(probes).await,
+    }
+}
+
+async fn probe_backend(client: reqwest::Client, backend: InferenceBackend) -> BackendStatus {
+    let url = get_backend_url(&backend);
+    let started = Instant::now();
+
+    let request = match backend {
+        InferenceBackend::Ollama => client.get(format!("{}/api/version", url)),
+        InferenceBackend::Llama => client.get(format!("{}/health", url)),
+        InferenceBackend::HuggingFace => {
+            let token = config::var("HUGGINGFACE_TOKEN").unwrap_or_default();
+            client.get(&url).bearer_auth(token)
+        }
+        InferenceBackend::OpenAI => {
+            let api_key = config::var("OPENAI_API_KEY").unwrap_or_default();
+            client.get(format!("{}/models", url)).bearer_auth(api_key)
+        }
+        InferenceBackend::AzureOpenAI => {
+            let api_key = config::var("AZURE_OPENAI_API_KEY").unwrap_or_default();
+            client
+                .get(format!("{}/openai/models?api-version={}", url, azure_api_version()))
+                .header("api-key", api_key)
+        }
+        InferenceBackend::Anthropic => anthropic::authorize(client.get(format!("{}/v1/models", url))),
+        InferenceBackend::Gemini => gemini::authorize(client.get(format!("{}/v1beta/models", url))),
+        // The runtime API has no unauthenticated endpoint; any non-5xx answer
+        // shows it is reachable.
+        InferenceBackend::Bedrock => client.get(&url),
+        InferenceBackend::Mistral => mistral::authorize(client.get(format!("{}/v1/models", url))),
+        InferenceBackend::Cohere => cohere::authorize(client.get(format!("{}/v1/models", url))),
+        InferenceBackend::Groq => groq::authorize(client.get(format!("{}/models", url))),
+        InferenceBackend::OpenRouter => openrouter::authorize(client.get(format!("{}/models", url))),
+        InferenceBackend::Together | InferenceBackend::Fireworks => {
+            hosted_oss::authorize(&backend, client.get(format!("{}/models", url)))
+        }
+        InferenceBackend::Replicate => replicate::authorize(client.get(format!("{}/v1/account", url))),
+        InferenceBackend::Tgi => tgi::authorize(client.get(format!("{}/health", url))),
+        InferenceBackend::LmStudio => lmstudio::authorize(client.get(format!("{}/api/v1/models", url))),
+        InferenceBackend::KoboldCpp => koboldcpp::authorize(client.get(format!("{}/api/v1/model", url))),
+        InferenceBackend::Mlx => client.get(format!("{}/v1/models", url)),
+        InferenceBackend::LlamaLocal => {
+            return BackendStatus {
+                backend,
+                url,
+                status: ProbeStatus::Down,
+                latency_ms: 0,
+                version: None,
+                error: Some(llama_local::UNAVAILABLE.to_string()),
+            };
+        }
+        InferenceBackend::Candle => {
+            return BackendStatus {
+                backend,
+                url,
+                status: if candle::unavailable().is_some() { ProbeStatus::Down } else { ProbeStatus::Up },
+                latency_ms: 0,
+                version: None,
+                error: candle::unavailable().map(str::to_string),
+            };
+        }
+        InferenceBackend::Triton => triton::authorize(client.get(format!("{}/v2", url))),
+        InferenceBackend::SageMaker => client.get(&url),
+        InferenceBackend::Vertex => client.get(&url),
+        InferenceBackend::DeepSeek => deepseek::authorize(client.get(format!("{}/models", url))),
+        InferenceBackend::Xai => xai::authorize(client.get(format!("{}/models", url))),
+        // Runs in-process, so there is nothing to reach.
+        InferenceBackend::Mock => {
+            return BackendStatus {
+                backend,
+                url,
+                status: ProbeStatus::Up,
+                latency_ms: 0,
+                version: None,
+                error: None,
+            };
+        }
+    };
+
+    let result = request.timeout(PROBE_TIMEOUT).send().await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(response) if !response.status().is_server_error() => {
+            let version = match backend {
+                InferenceBackend::Ollama | InferenceBackend::Triton => response
+                    .json::<serde_json::Value>()
+                    .await
+                    .ok()
+                    .and_then(|body| body["version"].as_str().map(str::to_string)),
+                _ => None,
+            };
+            BackendStatus {
+                backend,
+                url,
+                status: ProbeStatus::Up,
+                latency_ms,
+                version,
+                error: None,
+            }
+        }
+        Ok(response) => BackendStatus {
+            backend,
+            url,
+            status: ProbeStatus::Down,
+            latency_ms,
+            version: None,
+            error: Some(format!("Backend returned {}", response.status())),
+        },
+        Err(e) => BackendStatus {
+            backend,
+            url,
+            status: ProbeStatus::Down,
+            latency_ms,
+            version: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// What a backend said about a model.
+#[derive(Debug)]
+pub enum ModelLookup {
+    /// The backend has the model, with its capabilities when the backend
+    /// reports them.
+    Found(Option<Vec<ModelCapability>>),
+    Missing,
+    /// The backend is reachable but cannot list its models.
+    Unknown,
+}
+
+/// How a backend is asked whether it has a model.
+enum Lookup {
+    /// A request for the model itself, which answers 404 when it is missing.
+    Get(reqwest::RequestBuilder),
+    /// An OpenAI-style model list, searched for the model's id.
+    List(reqwest::RequestBuilder),
+    /// A catalog the engine already knows how to read.
+    Catalog,
+}
+
+/// Asks `backend` whether it has `model`, and what it can do. An error
+/// means the backend could not be asked.
+pub async fn check_model(
+    client: reqwest::Client,
+    backend: InferenceBackend,
+    model: &str,
+) -> Result<ModelLookup, String> {
+    let url = get_backend_url(&backend);
+
+    let lookup = match backend {
+        InferenceBackend::Ollama => Lookup::Get(
+            client
+                .post(format!("{}/api/show", url))
+                .json(&serde_json::json!({ "model": model })),
+        ),
+        InferenceBackend::HuggingFace => {
+            let request = client.get(format!("{}/{}", HUGGINGFACE_HUB_URL, model));
+            match config::var("HUGGINGFACE_TOKEN") {
+                Ok(token) => Lookup::Get(request.bearer_auth(token)),
+                Err(_) => Lookup::Get(request),
+            }
+        }
+        InferenceBackend::OpenAI => {
+            let api_key = config::var("OPENAI_API_KEY").unwrap_or_default();
+            Lookup::Get(client.get(format!("{}/models/{}", url, model)).bearer_auth(api_key))
+        }
+        InferenceBackend::Anthropic => {
+            Lookup::Get(anthropic::authorize(client.get(format!("{}/v1/models/{}", url, model))))
+        }
+        InferenceBackend::Gemini => {
+            Lookup::Get(gemini::authorize(client.get(format!("{}/v1beta/models/{}", url, model))))
+        }
+        InferenceBackend::Mistral => Lookup::List(mistral::authorize(client.get(format!("{}/v1/models", url)))),
+        InferenceBackend::Groq => Lookup::List(groq::authorize(client.get(format!("{}/models", url)))),
+        InferenceBackend::OpenRouter => {
+            Lookup::List(openrouter::authorize(client.get(format!("{}/models", url))))
+        }
+        InferenceBackend::DeepSeek => Lookup::List(deepseek::authorize(client.get(format!("{}/models", url)))),
+        InferenceBackend::Xai => Lookup::List(xai::authorize(client.get(format!("{}/models", url)))),
+        InferenceBackend::Together | InferenceBackend::Fireworks | InferenceBackend::LmStudio | InferenceBackend::Mlx => {
+            Lookup::Catalog
+        }
+        _ => {
+            let status = probe_backend(client, backend).await;
+            return match status.status {
+                ProbeStatus::Up => Ok(ModelLookup::Unknown),
+                ProbeStatus::Down => Err(status.error.unwrap_or_else(|| "Backend is down".to_string())),
+            };
+        }
+    };
+
+    match lookup {
+        Lookup::Get(request) => {
+            let response = request.timeout(PROBE_TIMEOUT).send().await.map_err(|e| e.to_string())?;
+            match response.status() {
+                status if status.is_success() => {
+                    let body: serde_json::Value = response.json().await.unwrap_or_default();
+                    let capabilities = match backend {
+                        InferenceBackend::Ollama => ollama::capabilities(&body),
+                        InferenceBackend::HuggingFace => huggingface_capabilities(&body),
+                        InferenceBackend::OpenAI => openai_capabilities(model),
+                        _ => None,
+                    };
+                    Ok(ModelLookup::Found(capabilities))
+                }
+                reqwest::StatusCode::NOT_FOUND => Ok(ModelLookup::Missing),
+                status => Err(format!("Backend returned {}", status)),
+            }
+        }
+        Lookup::List(request) => {
+            let response = request.timeout(PROBE_TIMEOUT).send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("Backend returned {}", response.status()));
+            }
+            let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+            let entries = body["data"].as_array().cloned().unwrap_or_default();
+            match entries.iter().any(|entry| entry["id"].as_str() == Some(model)) {
+                true => Ok(ModelLookup::Found(None)),
+                false => Ok(ModelLookup::Missing),
+            }
+        }
+        Lookup::Catalog => {
+            let models = match backend {
+                InferenceBackend::LmStudio => lmstudio::list_models(&client, &url).await,
+                InferenceBackend::Mlx => mlx::list_models(&client, &url).await,
+                _ => hosted_oss::list_models(&backend, &client, &url).await,
+            }?;
+            match models.into_iter().find(|entry| entry.id == model) {
+                // MLX guesses capabilities from repo names, which do not
+                // say whether a model takes images.
+                Some(_) if backend == InferenceBackend::Mlx => Ok(ModelLookup::Found(None)),
+                Some(entry) => Ok(ModelLookup::Found(Some(entry.capabilities))),
+                None => Ok(ModelLookup::Missing),
+            }
+        }
+    }
+}
+
+/// Capabilities from a Hub model's `pipeline_tag`.
+fn huggingface_capabilities(model: &serde_json::Value) -> Option<Vec<ModelCapability>> {
+    let capabilities = match model["pipeline_tag"].as_str()? {
+        "text-generation" | "text2text-generation" => vec![ModelCapability::Chat, ModelCapability::Completion],
+        "image-text-to-text" => vec![ModelCapability::Chat, ModelCapability::Completion, ModelCapability::Vision],
+        "feature-extraction" | "sentence-similarity" => vec![ModelCapability::Embedding],
+        "text-ranking" => vec![ModelCapability::Rerank],
+        _ => return None,
+    };
+    Some(capabilities)
+}
+
+/// Capabilities of OpenAI's model families, which the API does not report,
+/// by model id. `None` for ids outside the families below.
+fn openai_capabilities(model: &str) -> Option<Vec<ModelCapability>> {
+    const VISION: &[&str] = &["gpt-4o", "chatgpt-4o", "gpt-4.1", "gpt-4.5", "gpt-4-turbo", "gpt-5", "o1", "o3", "o4"];
+    const TEXT_ONLY: &[&str] = &["o1-mini", "o1-preview", "o3-mini"];
+
+    if model.starts_with("text-embedding") {
+        Some(vec![ModelCapability::Embedding])
+    } else if model.ends_with("-instruct") || model.starts_with("davinci") || model.starts_with("babbage") {
+        Some(vec![ModelCapability::Completion])
+    } else if TEXT_ONLY.iter().any(|family| model.starts_with(family)) {
+        Some(vec![ModelCapability::Chat])
+    } else if VISION.iter().any(|family| model.starts_with(family)) && !model.contains("audio") {
+        Some(vec![ModelCapability::Chat, ModelCapability::Vision])
+    } else if model.starts_with("gpt-") {
+        Some(vec![ModelCapability::Chat])
+    } else {
+        None
+    }
+}
@@ -0,0 +1,309 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, FinishReason, Generation, StreamToken, ToolCall, ToolDefinition,
+};
+
+pub const DEFAULT_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+
+/// Temperatures above 1 are rejected by the Messages API.
+const MAX_TEMPERATURE: f32 = 1.0;
+
+#[derive(Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: &'static str,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    /// Block types the engine does not use, such as extended thinking.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    input_schema: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    output_tokens: u32,
+}
+
+/// Adds the API key and version headers every Anthropic call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("ANTHROPIC_API_KEY").unwrap_or_default();
+    request.header("x-api-key", api_key).header("anthropic-version", API_VERSION)
+}
+
+/// Maps the unified conversation onto the Messages API: system messages move
+/// to the top-level `system` field, tool results become `tool_result` blocks
+/// in a user turn, and consecutive turns from the same role are merged.
+fn to_messages(messages: Vec<ChatMessage>) -> (Option<String>, Vec<Message>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<Message> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message.role.as_str() {
+            "system" => {
+                system.push(message.content);
+                continue;
+            }
+            "assistant" => {
+                let mut blocks = Vec::new();
+                if !message.content.is_empty() {
+                    blocks.push(ContentBlock::Text { text: message.content });
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    blocks.push(ContentBlock::ToolUse {
+                        id: call.id,
+                        name: call.name,
+                        input: call.arguments,
+                    });
+                }
+                ("assistant", blocks)
+            }
+            "tool" => (
+                "user",
+                vec![ContentBlock::ToolResult {
+                    tool_use_id: message.tool_call_id.unwrap_or_default(),
+                    content: message.content,
+                }],
+            ),
+            _ => ("user", vec![ContentBlock::Text { text: message.content }]),
+        };
+
+        match turns.last_mut() {
+            Some(last) if last.role == role => last.content.extend(blocks),
+            _ => turns.push(Message { role, content: blocks }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, turns)
+}
+
+fn to_tools(tools: Vec<ToolDefinition>) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|tool| Tool {
+            name: tool.name,
+            description: tool.description,
+            input_schema: tool.parameters,
+        })
+        .collect()
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> MessagesRequest {
+    let (system, messages) = to_messages(call.messages.clone());
+    MessagesRequest {
+        model: call.model.clone(),
+        max_tokens: call.max_tokens,
+        temperature: call.temperature.min(MAX_TEMPERATURE),
+        system,
+        messages,
+        tools: to_tools(call.tools.clone()),
+        stream,
+    }
+}
+
+/// Reads the Messages API's `stop_reason`.
+fn finish_reason(stop_reason: &str) -> FinishReason {
+    match stop_reason {
+        "max_tokens" | "model_context_window_exceeded" => FinishReason::Length,
+        "tool_use" => FinishReason::ToolCalls,
+        "refusal" => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("ANTHROPIC_API_KEY").is_err() {
+        return Err("ANTHROPIC_API_KEY not set. Set ANTHROPIC_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/v1/messages", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("Anthropic request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Anthropic API error: {} - {}", status, error_text));
+    }
+
+    let resp: MessagesResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Anthropic response: {}", e))?;
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in resp.content {
+        match block {
+            ContentBlock::Text { text: part } => text.push_str(&part),
+            ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                id,
+                name,
+                arguments: input,
+            }),
+            ContentBlock::ToolResult { .. } | ContentBlock::Other => {}
+        }
+    }
+
+    Ok(Generation {
+        text,
+        tokens: resp.usage.output_tokens,
+        tool_calls,
+        citations: Vec::new(),
+        reasoning: None,
+        finish_reason: resp.stop_reason.as_deref().map(finish_reason).unwrap_or_default(),
+        upstream: None,
+        draft: None,
+    })
+}
+
+/// Streams text deltas as `token` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/v1/messages", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Anthropic stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            yield Err(std::io::Error::other(format!("Anthropic API error: {}", response.status())));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+        // Sent in `message_delta`, ahead of `message_stop`.
+        let mut stop_reason = FinishReason::Stop;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Anthropic read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                // Every data payload repeats its event name in `type`, so the
+                // `event:` lines can be skipped.
+                let Some(data) = line.trim_end().strip_prefix("data: ") else {
+                    continue;
+                };
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(data) else {
+                    continue;
+                };
+
+                let (text, complete) = match event["type"].as_str() {
+                    Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+                        (event["delta"]["text"].as_str().unwrap_or(""), false)
+                    }
+                    Some("message_delta") => {
+                        if let Some(reason) = event["delta"]["stop_reason"].as_str() {
+                            stop_reason = finish_reason(reason);
+                        }
+                        continue;
+                    }
+                    Some("message_stop") => ("", true),
+                    Some("error") => {
+                        let message = event["error"]["message"].as_str().unwrap_or("unknown error");
+                        yield Err(std::io::Error::other(format!("Anthropic stream error: {}", message)));
+                        return;
+                    }
+                    _ => continue,
+                };
+
+                if text.is_empty() && !complete {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete,
+                    request_id: call.request_id.clone(),
+                    finish_reason: complete.then_some(stop_reason),
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if complete {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, FinishReason, Generation, StreamToken, ToolCall, ToolDefinition,
+};
+use super::eventstream;
+use super::sigv4::{self, AwsCredentials};
+
+const SERVICE: &str = "bedrock";
+
+/// The regional Bedrock runtime endpoint, unless `BEDROCK_URL` overrides it
+/// (e.g. for a VPC endpoint).
+pub fn default_url() -> String {
+    format!("https://bedrock-runtime.{}.amazonaws.com", sigv4::region())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConverseRequest {
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    system: Vec<SystemBlock>,
+    inference_config: InferenceConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<ToolConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Serialize)]
+struct SystemBlock {
+    text: String,
+}
+
+/// Converse content blocks are objects with a single key naming their type.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ContentBlock {
+    Text(String),
+    Image(MediaBlock),
+    Document(MediaBlock),
+    ToolUse(ToolUse),
+    ToolResult(ToolResult),
+    /// Block types the engine does not use, such as reasoning content.
+    #[serde(untagged)]
+    Other(serde_json::Value),
+}
+
+#[derive(Serialize, Deserialize)]
+struct MediaBlock {
+    format: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    source: MediaSource,
+}
+
+/// Raw bytes are base64-encoded in the JSON protocol.
+#[derive(Serialize, Deserialize)]
+struct MediaSource {
+    bytes: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolUse {
+    tool_use_id: String,
+    name: String,
+    input: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolResult {
+    tool_use_id: String,
+    content: Vec<ToolResultContent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ToolResultContent {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InferenceConfig {
+    max_tokens: u32,
+    temperature: f32,
+}
+
+#[derive(Serialize)]
+struct ToolConfig {
+    tools: Vec<Tool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Tool {
+    tool_spec: ToolSpec,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolSpec {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    input_schema: InputSchema,
+}
+
+#[derive(Serialize)]
+struct InputSchema {
+    json: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConverseResponse {
+    output: ConverseOutput,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    usage: Usage,
+}
+
+/// Reads the Converse API's `stopReason`.
+fn finish_reason(stop_reason: &str) -> FinishReason {
+    match stop_reason {
+        "max_tokens" | "model_context_window_exceeded" => FinishReason::Length,
+        "tool_use" => FinishReason::ToolCalls,
+        "guardrail_intervened" | "content_filtered" => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+#[derive(Deserialize)]
+struct ConverseOutput {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Usage {
+    output_tokens: u32,
+}
+
+/// Converse takes images and documents; other attachments are dropped.
+fn media_block(mime_type: &str, data: String) -> Option<ContentBlock> {
+    let (kind, format) = mime_type.split_once('/')?;
+    let format = match format {
+        "jpg" => "jpeg",
+        "plain" => "txt",
+        "vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "vnd.ms-excel" => "xls",
+        "msword" => "doc",
+        other => other,
+    };
+    let block = MediaBlock {
+        format: format.to_string(),
+        name: None,
+        source: MediaSource { bytes: data },
+    };
+    match kind {
+        "image" => Some(ContentBlock::Image(block)),
+        "application" | "text" => Some(ContentBlock::Document(MediaBlock {
+            name: Some("document".to_string()),
+            ..block
+        })),
+        _ => None,
+    }
+}
+
+/// Maps the unified conversation onto Converse messages: system messages go
+/// to `system`, tool results become `toolResult` blocks in a user turn, and
+/// consecutive turns from the same role are merged.
+fn to_messages(messages: Vec<ChatMessage>) -> (Vec<SystemBlock>, Vec<Message>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<Message> = Vec::new();
+
+    for message in messages {
+        let mut blocks = Vec::new();
+        let role = match message.role.as_str() {
+            "system" => {
+                system.push(SystemBlock { text: message.content });
+                continue;
+            }
+            "assistant" => {
+                if !message.content.is_empty() {
+                    blocks.push(ContentBlock::Text(message.content));
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    blocks.push(ContentBlock::ToolUse(ToolUse {
+                        tool_use_id: call.id,
+                        name: call.name,
+                        input: call.arguments,
+                    }));
+                }
+                "assistant"
+            }
+            "tool" => {
+                blocks.push(ContentBlock::ToolResult(ToolResult {
+                    tool_use_id: message.tool_call_id.unwrap_or_default(),
+                    content: vec![ToolResultContent { text: message.content }],
+                }));
+                "user"
+            }
+            _ => {
+                if !message.content.is_empty() {
+                    blocks.push(ContentBlock::Text(message.content));
+                }
+                "user"
+            }
+        };
+
+        for attachment in message.attachments.unwrap_or_default() {
+            blocks.extend(media_block(&attachment.mime_type, attachment.data));
+        }
+
+        match turns.last_mut() {
+            Some(last) if last.role == role => last.content.extend(blocks),
+            _ => turns.push(Message {
+                role: role.to_string(),
+                content: blocks,
+            }),
+        }
+    }
+
+    (system, turns)
+}
+
+fn to_tool_config(tools: Vec<ToolDefinition>) -> Option<ToolConfig> {
+    if tools.is_empty() {
+        return None;
+    }
+    Some(ToolConfig {
+        tools: tools
+            .into_iter()
+            .map(|tool| Tool {
+                tool_spec: ToolSpec {
+                    name: tool.name,
+                    description: tool.description,
+                    input_schema: InputSchema { json: tool.parameters },
+                },
+            })
+            .collect(),
+    })
+}
+
+/// Builds a signed Converse request. `action` is `converse` or
+/// `converse-stream`.
+fn signed_request(call: &BackendRequest, action: &str) -> Result<reqwest::RequestBuilder, String> {
+    let credentials = AwsCredentials::from_env()?;
+    let (system, messages) = to_messages(call.messages.clone());
+    let body = serde_json::to_vec(&ConverseRequest {
+        messages,
+        system,
+        inference_config: InferenceConfig {
+            max_tokens: call.max_tokens,
+            temperature: call.temperature,
+        },
+        tool_config: to_tool_config(call.tools.clone()),
+    })
+    .map_err(|e| format!("Failed to encode Bedrock request: {}", e))?;
+
+    // Model ids such as `anthropic.claude-3-haiku-20240307-v1:0` contain
+    // characters that must be escaped in the path.
+    let url = format!("{}/model/{}/{}", call.base_url, sigv4::uri_encode(&call.model), action);
+    let url = reqwest::Url::parse(&url).map_err(|e| format!("Invalid Bedrock URL {}: {}", url, e))?;
+    let signature = sigv4::sign(&credentials, SERVICE, &sigv4::region(), "POST", &url, &body, chrono::Utc::now());
+
+    let mut request = call
+        .client
+        .post(url)
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .header("content-type", "application/json")
+        .body(body);
+    for (name, value) in signature {
+        request = request.header(name, value);
+    }
+    Ok(request)
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = signed_request(&call, "converse")?
+        .send()
+        .await
+        .map_err(|e| format!("Bedrock request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Bedrock API error: {} - {}", status, error_text));
+    }
+
+    let resp: ConverseResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Bedrock response: {}", e))?;
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in resp.output.message.content {
+        match block {
+            ContentBlock::Text(part) => text.push_str(&part),
+            ContentBlock::ToolUse(tool_use) => tool_calls.push(ToolCall {
+                id: tool_use.tool_use_id,
+                name: tool_use.name,
+                arguments: tool_use.input,
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(Generation {
+        text,
+        tokens: resp.usage.output_tokens,
+        tool_calls,
+        citations: Vec::new(),
+        reasoning: None,
+        finish_reason: resp.stop_reason.as_deref().map(finish_reason).unwrap_or_default(),
+        upstream: None,
+        draft: None,
+    })
+}
+
+/// Streams text deltas as `token` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let request = match signed_request(&call, "converse-stream") {
+            Ok(request) => request,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+        let response = match request.send().await {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Bedrock stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            yield Err(std::io::Error::other(format!("Bedrock API error: {}", response.status())));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Bedrock read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            loop {
+                let frame = match eventstream::next_frame(&mut buffer) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(std::io::Error::other(e));
+                        return;
+                    }
+                };
+                let payload: serde_json::Value = serde_json::from_slice(&frame.payload).unwrap_or_default();
+
+                if frame.message_type != "event" {
+                    let message = payload["message"].as_str().unwrap_or("unknown error");
+                    yield Err(std::io::Error::other(format!(
+                        "Bedrock stream error: {}: {}",
+                        frame.event_type, message
+                    )));
+                    return;
+                }
+
+                let (text, finish) = match frame.event_type.as_str() {
+                    "contentBlockDelta" => (payload["delta"]["text"].as_str().unwrap_or(""), None),
+                    "messageStop" => ("", Some(payload["stopReason"].as_str().map(finish_reason).unwrap_or_default())),
+                    _ => continue,
+                };
+                let complete = finish.is_some();
+
+                if text.is_empty() && !complete {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete,
+                    request_id: call.request_id.clone(),
+                    finish_reason: finish,
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if complete {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use axum::response::sse::Event;
+use futures::stream::Stream;
+use std::path::PathBuf;
+
+#[cfg(not(feature = "candle"))]
+use super::super::config;
+use super::super::v1::inference::{BackendRequest, Generation};
+
+/// Stands in for a URL, since models run inside the engine.
+pub const URL: &str = "local://";
+
+const DEFAULT_MODEL_DIR: &str = "models";
+
+/// The directory holding `model`'s `config.json`, `tokenizer.json` and
+/// safetensors weights: the id itself when it is an absolute path, otherwise
+/// `<id>` under `CANDLE_MODEL_DIR`.
+pub fn model_dir(model: &str) -> PathBuf {
+    if model.starts_with('/') {
+        return PathBuf::from(model);
+    }
+    let dir = config::var("CANDLE_MODEL_DIR").unwrap_or_else(|_| DEFAULT_MODEL_DIR.to_string());
+    PathBuf::from(dir).join(model)
+}
+
+#[cfg(not(feature = "candle"))]
+const UNAVAILABLE: &str = "openllm-server was built without the candle feature";
+
+/// Why the backend cannot run models, if it cannot.
+pub fn unavailable() -> Option<&'static str> {
+    #[cfg(feature = "candle")]
+    return None;
+    #[cfg(not(feature = "candle"))]
+    return Some(UNAVAILABLE);
+}
+
+/// Checks that the model's directory is there, so a wrong path is reported
+/// as such, before failing for want of candle.
+#[cfg(not(feature = "candle"))]
+pub async fn load(model: &str) -> Result<(), String> {
+    let dir = model_dir(model);
+    if !tokio::fs::try_exists(dir.join("config.json")).await.unwrap_or(false) {
+        return Err(format!("No config.json in model directory {}", dir.display()));
+    }
+    Err(UNAVAILABLE.to_string())
+}
+
+#[cfg(not(feature = "candle"))]
+pub fn unload(_model: &str) {}
+
+#[cfg(not(feature = "candle"))]
+pub async fn generate(_call: BackendRequest) -> Result<Generation, String> {
+    Err(UNAVAILABLE.to_string())
+}
+
+#[cfg(not(feature = "candle"))]
+pub fn stream_events(_call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    futures::stream::once(async { Err(std::io::Error::other(UNAVAILABLE)) })
+}
+
+#[cfg(feature = "candle")]
+pub use native::{generate, load, stream_events, unload};
+
+#[cfg(feature = "candle")]
+mod native {
+    use async_stream::stream;
+    use candle_core::{DType, Device, Tensor};
+    use candle_nn::VarBuilder;
+    use candle_transformers::generation::{LogitsProcessor, Sampling};
+    use candle_transformers::models::{llama, mistral, phi, phi3};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, LazyLock, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tokenizers::Tokenizer;
+
+    use super::super::super::v1::inference::{
+        BackendRequest, BiasedToken, FinishReason, Generation, SamplingParams, StreamToken,
+    };
+    use super::*;
+
+    /// Tokens `repetition_penalty` looks back over unless
+    /// `repetition_context_size` says otherwise.
+    const DEFAULT_REPEAT_LAST_N: usize = 64;
+
+    /// End-of-turn tokens of common chat formats, which end generation along
+    /// with the model's own end-of-sequence token.
+    const END_TOKENS: &[&str] = &["</s>", "<|endoftext|>", "<|end|>", "<|eot_id|>", "<|im_end|>"];
+
+    /// Loaded models by registry id. Unloading drops a model's weights once
+    /// the requests still using it have finished.
+    static MODELS: LazyLock<Mutex<HashMap<String, Arc<LocalModel>>>> = LazyLock::new(Default::default);
+
+    /// The supported architectures. Llama keeps its cache outside the model,
+    /// so requests run side by side; the others hold their cache themselves
+    /// and run one request at a time.
+    enum Weights {
+        Llama(llama::Llama, llama::Config),
+        Mistral(Mutex<mistral::Model>),
+        Phi3(Mutex<phi3::Model>),
+        Phi(Mutex<phi::Model>),
+    }
+
+    struct LocalModel {
+        weights: Weights,
+        tokenizer: Tokenizer,
+        end_tokens: Vec<u32>,
+        device: Device,
+    }
+
+    fn read_config<T: serde::de::DeserializeOwned>(config: &serde_json::Value) -> Result<T, String> {
+        serde_json::from_value(config.clone()).map_err(|e| format!("Invalid config.json: {}", e))
+    }
+
+    /// The weight files: those listed in `model.safetensors.index.json` for
+    /// sharded checkpoints, otherwise every `.safetensors` file.
+    fn weight_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+        let index = dir.join("model.safetensors.index.json");
+        if index.exists() {
+            let index: serde_json::Value = std::fs::read(&index)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .ok_or_else(|| format!("Invalid {}", index.display()))?;
+            let mut files: Vec<PathBuf> = index["weight_map"]
+                .as_object()
+                .into_iter()
+                .flat_map(|map| map.values())
+                .filter_map(|file| file.as_str())
+                .map(|file| dir.join(file))
+                .collect();
+            files.sort();
+            files.dedup();
+            return Ok(files);
+        }
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read model directory {}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "safetensors"))
+            .collect();
+        if files.is_empty() {
+            return Err(format!("No safetensors weights in {}", dir.display()));
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn load_model(dir: &Path) -> Result<LocalModel, String> {
+        let config: serde_json::Value = std::fs::read(dir.join("config.json"))
+            .map_err(|e| format!("Failed to read {}: {}", dir.join("config.json").display(), e))
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| format!("Invalid config.json: {}", e)))?;
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| format!("Failed to load tokenizer.json: {}", e))?;
+
+        let device = Device::Cpu;
+        let files = weight_files(dir)?;
+        // The files are only read through the map, and must not change while
+        // the model is loaded.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&files, DType::F32, &device) }
+            .map_err(|e| format!("Failed to map weights: {}", e))?;
+
+        let architecture = config["architectures"][0].as_str().unwrap_or_default();
+        let weights = match architecture {
+            "LlamaForCausalLM" => {
+                let cfg = read_config::<llama::LlamaConfig>(&config)?.into_config(false);
+                Weights::Llama(llama::Llama::load(vb, &cfg).map_err(|e| e.to_string())?, cfg)
+            }
+            "MistralForCausalLM" => {
+                let cfg: mistral::Config = read_config(&config)?;
+                Weights::Mistral(Mutex::new(mistral::Model::new(&cfg, vb).map_err(|e| e.to_string())?))
+            }
+            "Phi3ForCausalLM" => {
+                let cfg: phi3::Config = read_config(&config)?;
+                Weights::Phi3(Mutex::new(phi3::Model::new(&cfg, vb).map_err(|e| e.to_string())?))
+            }
+            "PhiForCausalLM" => {
+                let cfg: phi::Config = read_config(&config)?;
+                Weights::Phi(Mutex::new(phi::Model::new(&cfg, vb).map_err(|e| e.to_string())?))
+            }
+            other => return Err(format!("Unsupported architecture '{}'; candle runs Llama, Mistral and Phi models", other)),
+        };
+
+        let mut end_tokens: Vec<u32> = match &config["eos_token_id"] {
+            serde_json::Value::Number(id) => id.as_u64().into_iter().map(|id| id as u32).collect(),
+            serde_json::Value::Array(ids) => ids.iter().filter_map(|id| id.as_u64()).map(|id| id as u32).collect(),
+            _ => Vec::new(),
+        };
+        end_tokens.extend(END_TOKENS.iter().filter_map(|token| tokenizer.token_to_id(token)));
+
+        Ok(LocalModel {
+            weights,
+            tokenizer,
+            end_tokens,
+            device,
+        })
+    }
+
+    pub async fn load(model: &str) -> Result<(), String> {
+        let dir = model_dir(model);
+        let loaded = tokio::task::spawn_blocking(move || load_model(&dir))
+            .await
+            .map_err(|e| format!("Candle load task failed: {}", e))??;
+        MODELS.lock().unwrap().insert(model.to_string(), Arc::new(loaded));
+        Ok(())
+    }
+
+    pub fn unload(model: &str) {
+        MODELS.lock().unwrap().remove(model);
+    }
+
+    /// What a generation needs from the request, owned so it can move to a
+    /// blocking thread.
+    struct Job {
+        model: Arc<LocalModel>,
+        prompt: String,
+        max_tokens: u32,
+        temperature: f32,
+        sampling: SamplingParams,
+    }
+
+    impl Job {
+        fn new(call: &BackendRequest) -> Result<Self, String> {
+            let model = MODELS
+                .lock()
+                .unwrap()
+                .get(&call.model)
+                .cloned()
+                .ok_or_else(|| format!("Model '{}' is not loaded in candle", call.model))?;
+            Ok(Self {
+                model,
+                prompt: call.prompt.clone(),
+                max_tokens: call.max_tokens,
+                temperature: call.temperature,
+                sampling: call.sampling.clone(),
+            })
+        }
+
+        fn logits_processor(&self) -> LogitsProcessor {
+            let temperature = f64::from(self.temperature);
+            let sampling = match (self.sampling.top_k, self.sampling.top_p) {
+                _ if temperature <= 0.0 => Sampling::ArgMax,
+                (Some(k), Some(p)) => Sampling::TopKThenTopP { k: k as usize, p, temperature },
+                (Some(k), None) => Sampling::TopK { k: k as usize, temperature },
+                (None, Some(p)) => Sampling::TopP { p, temperature },
+                (None, None) => Sampling::All { temperature },
+            };
+            let seed = self.sampling.seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_nanos() as u64)
+                    .unwrap_or_default()
+            });
+            LogitsProcessor::from_sampling(seed, sampling)
+        }
+
+        /// `logit_bias` by token id. Text keys are tokenized, and each of
+        /// their tokens gets the bias.
+        fn logit_bias(&self) -> Result<Vec<(u32, f32)>, String> {
+            let mut biases = Vec::new();
+            for (token, bias) in self.sampling.logit_bias() {
+                match token {
+                    BiasedToken::Id(id) => biases.push((id, bias as f32)),
+                    BiasedToken::Text(text) => {
+                        let encoding = self
+                            .model
+                            .tokenizer
+                            .encode(text, false)
+                            .map_err(|e| format!("Failed to tokenize logit_bias key '{}': {}", text, e))?;
+                        biases.extend(encoding.get_ids().iter().map(|&id| (id, bias as f32)));
+                    }
+                }
+            }
+            Ok(biases)
+        }
+
+        /// Generates up to `max_tokens`, handing each decoded piece of text to
+        /// `emit` until it returns `false`. Returns the number of tokens and
+        /// why generation ended.
+        fn run(&self, mut emit: impl FnMut(&str) -> bool) -> Result<(u32, FinishReason), String> {
+            let model = &self.model;
+            let mut tokens = model
+                .tokenizer
+                .encode(self.prompt.as_str(), true)
+                .map_err(|e| format!("Failed to tokenize prompt: {}", e))?
+                .get_ids()
+                .to_vec();
+            let prompt_len = tokens.len();
+            let mut logits_processor = self.logits_processor();
+            let logit_bias = self.logit_bias()?;
+            let mut decoder = Decoder::default();
+            let mut text = String::new();
+
+            let mut llama_cache = match &model.weights {
+                Weights::Llama(_, cfg) => {
+                    Some(llama::Cache::new(true, DType::F32, cfg, &model.device).map_err(|e| e.to_string())?)
+                }
+                _ => None,
+            };
+            let mut stateful = match &model.weights {
+                Weights::Llama(..) => None,
+                Weights::Mistral(m) => Some(Stateful::Mistral(m.lock().unwrap())),
+                Weights::Phi3(m) => Some(Stateful::Phi3(m.lock().unwrap())),
+                Weights::Phi(m) => Some(Stateful::Phi(m.lock().unwrap())),
+            };
+            if let Some(stateful) = &mut stateful {
+                stateful.clear_kv_cache();
+            }
+
+            let mut finish_reason = FinishReason::Length;
+            for step in 0..self.max_tokens as usize {
+                let start = if step == 0 { 0 } else { tokens.len() - 1 };
+                let input = Tensor::new(&tokens[start..], &model.device)
+                    .and_then(|input| input.unsqueeze(0))
+                    .map_err(|e| e.to_string())?;
+                let logits = match (&model.weights, &mut stateful, &mut llama_cache) {
+                    (Weights::Llama(llama, _), _, Some(cache)) => llama.forward(&input, start, cache),
+                    (_, Some(stateful), _) => stateful.forward(&input, start),
+                    _ => unreachable!("every architecture has a cache"),
+                }
+                .and_then(|logits| logits.flatten_all())
+                .and_then(|logits| logits.to_dtype(DType::F32))
+                .map_err(|e| e.to_string())?;
+
+                let logits = match self.sampling.repetition_penalty {
+                    Some(penalty) if penalty != 1.0 => {
+                        let last_n = self.sampling.repetition_context_size.map_or(DEFAULT_REPEAT_LAST_N, |n| n as usize);
+                        let context = &tokens[tokens.len().saturating_sub(last_n)..];
+                        candle_transformers::utils::apply_repeat_penalty(&logits, penalty as f32, context)
+                            .map_err(|e| e.to_string())?
+                    }
+                    _ => logits,
+                };
+                let logits = if logit_bias.is_empty() {
+                    logits
+                } else {
+                    let mut values = logits.to_vec1::<f32>().map_err(|e| e.to_string())?;
+                    for &(id, bias) in &logit_bias {
+                        if let Some(value) = values.get_mut(id as usize) {
+                            *value += bias;
+                        }
+                    }
+                    Tensor::new(values, &model.device).map_err(|e| e.to_string())?
+                };
+
+                let next = logits_processor.sample(&logits).map_err(|e| e.to_string())?;
+                tokens.push(next);
+                if model.end_tokens.contains(&next) {
+                    finish_reason = FinishReason::Stop;
+                    break;
+                }
+
+                let piece = decoder.next(&model.tokenizer, &tokens[prompt_len..])?;
+                if piece.is_empty() {
+                    continue;
+                }
+                text.push_str(&piece);
+
+                if let Some(stop) = self.sampling.stop.iter().flatten().find_map(|stop| text.find(stop.as_str())) {
+                    let emitted = text.len() - piece.len();
+                    if stop > emitted {
+                        emit(&text[emitted..stop]);
+                    }
+                    finish_reason = FinishReason::Stop;
+                    break;
+                }
+                if !emit(&piece) {
+                    break;
+                }
+            }
+
+            Ok(((tokens.len() - prompt_len) as u32, finish_reason))
+        }
+    }
+
+    /// A locked model that keeps its own cache.
+    enum Stateful<'a> {
+        Mistral(std::sync::MutexGuard<'a, mistral::Model>),
+        Phi3(std::sync::MutexGuard<'a, phi3::Model>),
+        Phi(std::sync::MutexGuard<'a, phi::Model>),
+    }
+
+    impl Stateful<'_> {
+        fn clear_kv_cache(&mut self) {
+            match self {
+                Stateful::Mistral(m) => m.clear_kv_cache(),
+                Stateful::Phi3(m) => m.clear_kv_cache(),
+                Stateful::Phi(m) => m.clear_kv_cache(),
+            }
+        }
+
+        fn forward(&mut self, input: &Tensor, offset: usize) -> candle_core::Result<Tensor> {
+            match self {
+                Stateful::Mistral(m) => m.forward(input, offset),
+                Stateful::Phi3(m) => m.forward(input, offset),
+                // Phi tracks the offset in its cache.
+                Stateful::Phi(m) => m.forward(input),
+            }
+        }
+    }
+
+    /// Turns generated tokens into text piece by piece. Tokens are decoded
+    /// together with the ones before them, since many only form characters
+    /// in combination, and text is held back while it ends in an incomplete
+    /// character.
+    #[derive(Default)]
+    struct Decoder {
+        emitted: usize,
+    }
+
+    impl Decoder {
+        fn next(&mut self, tokenizer: &Tokenizer, generated: &[u32]) -> Result<String, String> {
+            let text = tokenizer
+                .decode(generated, true)
+                .map_err(|e| format!("Failed to decode output: {}", e))?;
+            if text.ends_with('\u{FFFD}') || text.len() <= self.emitted || !text.is_char_boundary(self.emitted) {
+                return Ok(String::new());
+            }
+            let piece = text[self.emitted..].to_string();
+            self.emitted = text.len();
+            Ok(piece)
+        }
+    }
+
+    pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+        let job = Job::new(&call)?;
+        tokio::task::spawn_blocking(move || {
+            let mut text = String::new();
+            let (tokens, finish_reason) = job.run(|piece| {
+                text.push_str(piece);
+                true
+            })?;
+            Ok(Generation {
+                finish_reason,
+                ..Generation::new(text, tokens)
+            })
+        })
+        .await
+        .map_err(|e| format!("Candle generation task failed: {}", e))?
+    }
+
+    /// Runs generation on a blocking thread and relays its text as `token`
+    /// events. Generation stops once the client goes away.
+    pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+        stream! {
+            let job = match Job::new(&call) {
+                Ok(job) => job,
+                Err(e) => {
+                    yield Err(std::io::Error::other(e));
+                    return;
+                }
+            };
+
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+            let task = tokio::task::spawn_blocking(move || job.run(|piece| tx.send(piece.to_string()).is_ok()));
+
+            let mut token_id = 0u32;
+            while let Some(piece) = rx.recv().await {
+                let stream_token = StreamToken {
+                    token: piece,
+                    token_id,
+                    complete: false,
+                    request_id: call.request_id.clone(),
+                    finish_reason: None,
+                };
+                token_id += 1;
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+            }
+
+            match task.await {
+                Ok(Ok((_, finish_reason))) => {
+                    let stream_token = StreamToken {
+                        token: String::new(),
+                        token_id,
+                        complete: true,
+                        request_id: call.request_id.clone(),
+                        finish_reason: Some(finish_reason),
+                    };
+                    call.outcome.record(&stream_token);
+                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                        yield Ok(Event::default().event("token").data(json_data));
+                    }
+                }
+                Ok(Err(e)) => yield Err(std::io::Error::other(e)),
+                Err(e) => yield Err(std::io::Error::other(format!("Candle generation task failed: {}", e))),
+            }
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::embeddings::Embeddings;
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, Citation, FinishReason, Generation, StreamToken, ToolCall, ToolDefinition,
+};
+
+pub const DEFAULT_URL: &str = "https://api.cohere.com";
+
+/// Embed v3 models refuse requests without an input type.
+const DEFAULT_INPUT_TYPE: &str = "search_document";
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chat_history: Vec<HistoryMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preamble: Option<String>,
+    max_tokens: u32,
+    temperature: f32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_results: Vec<ToolResult>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "role", rename_all = "UPPERCASE")]
+enum HistoryMessage {
+    User {
+        message: String,
+    },
+    Chatbot {
+        message: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        tool_calls: Vec<CohereToolCall>,
+    },
+    Tool {
+        tool_results: Vec<ToolResult>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CohereToolCall {
+    name: String,
+    #[serde(default)]
+    parameters: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct ToolResult {
+    call: CohereToolCall,
+    outputs: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    name: String,
+    description: String,
+    parameter_definitions: HashMap<String, ParameterDefinition>,
+}
+
+#[derive(Serialize)]
+struct ParameterDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    r#type: String,
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    citations: Vec<Citation>,
+    #[serde(default)]
+    tool_calls: Vec<CohereToolCall>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    meta: Meta,
+}
+
+/// Reads Cohere's `finish_reason`. `ERROR_TOXIC` means the output was
+/// filtered and `ERROR_LIMIT` that the context ran out.
+fn finish_reason(reason: &str) -> FinishReason {
+    match reason {
+        "MAX_TOKENS" | "ERROR_LIMIT" => FinishReason::Length,
+        "ERROR_TOXIC" => FinishReason::ContentFilter,
+        "ERROR" => FinishReason::Error,
+        _ => FinishReason::Stop,
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct Meta {
+    #[serde(default)]
+    billed_units: BilledUnits,
+}
+
+#[derive(Deserialize, Default)]
+struct BilledUnits {
+    #[serde(default)]
+    input_tokens: f64,
+    #[serde(default)]
+    output_tokens: f64,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    texts: &'a [String],
+    input_type: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankResult>,
+}
+
+#[derive(Deserialize)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f64,
+}
+
+/// Cohere reports errors as `{"message": "..."}`.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| json["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// Tool outputs must be objects, so other results are wrapped.
+fn tool_output(content: &str) -> serde_json::Value {
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(value @ serde_json::Value::Object(_)) => value,
+        Ok(value) => serde_json::json!({ "result": value }),
+        Err(_) => serde_json::json!({ "result": content }),
+    }
+}
+
+/// Splits the unified conversation into Cohere's shape: system messages form
+/// the `preamble`, a trailing user message becomes `message`, trailing tool
+/// messages become `tool_results`, and everything before goes into
+/// `chat_history`. Tool calls carry no ids in this API, so results are
+/// matched back to their call by id here.
+fn to_chat(messages: Vec<ChatMessage>) -> (Option<String>, Vec<HistoryMessage>, String, Vec<ToolResult>) {
+    let mut preamble = Vec::new();
+    let mut history = Vec::new();
+    let mut calls: HashMap<String, CohereToolCall> = HashMap::new();
+    let mut pending_results: Vec<ToolResult> = Vec::new();
+
+    for message in messages {
+        if message.role != "tool" && !pending_results.is_empty() {
+            history.push(HistoryMessage::Tool {
+                tool_results: std::mem::take(&mut pending_results),
+            });
+        }
+        match message.role.as_str() {
+            "system" => preamble.push(message.content),
+            "assistant" => {
+                let tool_calls = message
+                    .tool_calls
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|call| {
+                        let cohere_call = CohereToolCall {
+                            name: call.name,
+                            parameters: call.arguments,
+                        };
+                        calls.insert(call.id, cohere_call.clone());
+                        cohere_call
+                    })
+                    .collect();
+                history.push(HistoryMessage::Chatbot {
+                    message: message.content,
+                    tool_calls,
+                });
+            }
+            "tool" => {
+                let call = message
+                    .tool_call_id
+                    .as_ref()
+                    .and_then(|id| calls.get(id).cloned())
+                    .unwrap_or(CohereToolCall {
+                        name: message.tool_call_id.clone().unwrap_or_default(),
+                        parameters: serde_json::json!({}),
+                    });
+                pending_results.push(ToolResult {
+                    call,
+                    outputs: vec![tool_output(&message.content)],
+                });
+            }
+            _ => history.push(HistoryMessage::User { message: message.content }),
+        }
+    }
+
+    let message = if pending_results.is_empty() {
+        match history.pop() {
+            Some(HistoryMessage::User { message }) => message,
+            Some(other) => {
+                history.push(other);
+                String::new()
+            }
+            None => String::new(),
+        }
+    } else {
+        String::new()
+    };
+
+    let preamble = (!preamble.is_empty()).then(|| preamble.join("\n\n"));
+    (preamble, history, message, pending_results)
+}
+
+/// Cohere's v1 tools take a flat map of parameters with Python type names,
+/// so the top-level properties of the JSON schema are mapped onto it.
+fn to_tools(tools: Vec<ToolDefinition>) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|tool| {
+            let required: Vec<&str> = tool.parameters["required"]
+                .as_array()
+                .map(|names| names.iter().filter_map(|n| n.as_str()).collect())
+                .unwrap_or_default();
+            let parameter_definitions = tool.parameters["properties"]
+                .as_object()
+                .map(|properties| {
+                    properties
+                        .iter()
+                        .map(|(name, schema)| {
+                            let r#type = match schema["type"].as_str() {
+                                Some("string") => "str",
+                                Some("integer") => "int",
+                                Some("number") => "float",
+                                Some("boolean") => "bool",
+                                Some("array") => "list",
+                                Some("object") => "dict",
+                                _ => "str",
+                            };
+                            let definition = ParameterDefinition {
+                                description: schema["description"].as_str().map(str::to_string),
+                                r#type: r#type.to_string(),
+                                required: required.contains(&name.as_str()),
+                            };
+                            (name.clone(), definition)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Tool {
+                name: tool.name,
+                description: tool.description.unwrap_or_default(),
+                parameter_definitions,
+            }
+        })
+        .collect()
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let (preamble, chat_history, message, tool_results) = to_chat(call.messages.clone());
+    ChatRequest {
+        model: call.model.clone(),
+        message,
+        chat_history,
+        preamble,
+        max_tokens: call.max_tokens,
+        temperature: call.temperature,
+        stream,
+        tools: to_tools(call.tools.clone()),
+        tool_results,
+    }
+}
+
+/// Adds the bearer token every Cohere call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("COHERE_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("COHERE_API_KEY").is_err() {
+        return Err("COHERE_API_KEY not set. Set COHERE_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/v1/chat", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("Cohere request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Cohere API error: {} - {}", status, error_message(&error_text)));
+    }
+
+    let resp: ChatResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Cohere response: {}", e))?;
+
+    let tool_calls: Vec<ToolCall> = resp
+        .tool_calls
+        .into_iter()
+        .enumerate()
+        .map(|(i, call)| ToolCall {
+            id: format!("call_{}", i),
+            name: call.name,
+            arguments: call.parameters,
+        })
+        .collect();
+
+    let finish_reason = match resp.finish_reason.as_deref() {
+        _ if !tool_calls.is_empty() => FinishReason::ToolCalls,
+        Some(reason) => finish_reason(reason),
+        None => FinishReason::Stop,
+    };
+
+    Ok(Generation {
+        text: resp.text,
+        tokens: resp.meta.billed_units.output_tokens as u32,
+        tool_calls,
+        citations: resp.citations,
+        reasoning: None,
+        finish_reason,
+        upstream: None,
+        draft: None,
+    })
+}
+
+/// Embeds `texts` with Cohere's embed API. `input_type` defaults to
+/// `search_document`.
+pub async fn embed(
+    client: &reqwest::Client,
+    base_url: &str,
+    model: &str,
+    texts: &[String],
+    input_type: Option<&str>,
+    request_id: &str,
+) -> Result<Embeddings, String> {
+    if config::var("COHERE_API_KEY").is_err() {
+        return Err("COHERE_API_KEY not set. Set COHERE_API_KEY environment variable.".to_string());
+    }
+
+    let body = EmbedRequest {
+        model,
+        texts,
+        input_type: input_type.unwrap_or(DEFAULT_INPUT_TYPE),
+    };
+    let response = authorize(client.post(format!("{}/v1/embed", base_url)))
+        .header(REQUEST_ID_HEADER, request_id)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Cohere request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Cohere API error: {} - {}", status, error_message(&error_text)));
+    }
+
+    let resp: EmbedResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Cohere response: {}", e))?;
+
+    Ok(Embeddings {
+        vectors: resp.embeddings,
+        tokens: resp.meta.billed_units.input_tokens as u32,
+    })
+}
+
+/// Scores `documents` by their relevance to `query` with Cohere's rerank
+/// API, one score per document in order.
+pub async fn rerank(
+    client: &reqwest::Client,
+    base_url: &str,
+    model: &str,
+    query: &str,
+    documents: &[String],
+    request_id: &str,
+) -> Result<Vec<f64>, String> {
+    if config::var("COHERE_API_KEY").is_err() {
+        return Err("COHERE_API_KEY not set. Set COHERE_API_KEY environment variable.".to_string());
+    }
+
+    let body = RerankRequest { model, query, documents };
+    let response = authorize(client.post(format!("{}/v1/rerank", base_url)))
+        .header(REQUEST_ID_HEADER, request_id)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Cohere request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Cohere API error: {} - {}", status, error_message(&error_text)));
+    }
+
+    let resp: RerankResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Cohere response: {}", e))?;
+
+    let mut scores = vec![0.0; documents.len()];
+    for result in resp.results {
+        if let Some(score) = scores.get_mut(result.index) {
+            *score = result.relevance_score;
+        }
+    }
+    Ok(scores)
+}
+
+/// Streams `text-generation` events as `token` events. Cohere streams
+/// newline-delimited JSON rather than SSE. Tool calls and citations are only
+/// returned by non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/v1/chat", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Cohere stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!(
+                "Cohere API error: {} - {}",
+                status,
+                error_message(&error_text)
+            )));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Cohere read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+                    continue;
+                };
+
+                let (text, finish) = match event["event_type"].as_str() {
+                    Some("text-generation") => (event["text"].as_str().unwrap_or(""), None),
+                    Some("stream-end") => {
+                        let reason = event["finish_reason"].as_str().unwrap_or("COMPLETE");
+                        if reason == "ERROR" {
+                            yield Err(std::io::Error::other(format!("Cohere stream error: {}", reason)));
+                            return;
+                        }
+                        ("", Some(finish_reason(reason)))
+                    }
+                    _ => continue,
+                };
+                let complete = finish.is_some();
+
+                if text.is_empty() && !complete {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete,
+                    request_id: call.request_id.clone(),
+                    finish_reason: finish,
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if complete {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::openai_compat::{self, ChatResponse};
+
+pub const DEFAULT_URL: &str = "https://api.deepseek.com";
+
+/// Adds the bearer token every DeepSeek call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("DEEPSEEK_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+/// `deepseek-reasoner` returns its chain of thought in `reasoning_content`,
+/// which ends up in the response's `reasoning`. DeepSeek rejects requests
+/// that send it back, which cannot happen since messages only carry content.
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("DEEPSEEK_API_KEY").is_err() {
+        return Err("DEEPSEEK_API_KEY not set. Set DEEPSEEK_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&openai_compat::request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("DeepSeek request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("DeepSeek API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse DeepSeek response: {}", e))?
+        .into_generation("DeepSeek")
+}
+
+/// Streams content deltas as `token` events and reasoning deltas as
+/// `reasoning` events.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        if config::var("DEEPSEEK_API_KEY").is_err() {
+            yield Err(std::io::Error::other("DEEPSEEK_API_KEY not set. Set DEEPSEEK_API_KEY environment variable."));
+            return;
+        }
+
+        let response = match authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&openai_compat::request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("DeepSeek stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("DeepSeek API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("DeepSeek", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
@@ -0,0 +1,69 @@
+/// One frame of the `application/vnd.amazon.eventstream` encoding AWS uses
+/// for streamed responses, such as Bedrock's ConverseStream.
+pub struct EventFrame {
+    pub message_type: String,
+    pub event_type: String,
+    pub payload: Vec<u8>,
+}
+
+/// Splits the first complete frame off `buffer`. Frames are a 12-byte
+/// prelude (total length, headers length, CRC), the headers, the payload and
+/// a trailing CRC. Checksums are not verified; the connection is already
+/// protected by TLS.
+pub fn next_frame(buffer: &mut Vec<u8>) -> Result<Option<EventFrame>, String> {
+    if buffer.len() < 12 {
+        return Ok(None);
+    }
+    let total = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+    let headers_len = u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]) as usize;
+    if total < 16 + headers_len {
+        return Err(format!("Malformed event stream frame of {} bytes", total));
+    }
+    if buffer.len() < total {
+        return Ok(None);
+    }
+
+    let frame: Vec<u8> = buffer.drain(..total).collect();
+    let mut message_type = String::new();
+    let mut event_type = String::new();
+    let headers = &frame[12..12 + headers_len];
+    let mut pos = 0;
+    while pos < headers.len() {
+        let name_len = headers[pos] as usize;
+        let name = String::from_utf8_lossy(headers.get(pos + 1..pos + 1 + name_len).unwrap_or_default()).into_owned();
+        pos += 1 + name_len;
+        let Some(&value_type) = headers.get(pos) else {
+            break;
+        };
+        pos += 1;
+        let value_len = match value_type {
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 | 7 => {
+                let len = headers.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize);
+                pos += 2;
+                len.unwrap_or(0)
+            }
+            other => return Err(format!("Unknown event stream header type {}", other)),
+        };
+        let value = headers.get(pos..pos + value_len).unwrap_or_default();
+        pos += value_len;
+        if value_type == 7 {
+            match name.as_str() {
+                ":message-type" => message_type = String::from_utf8_lossy(value).into_owned(),
+                ":event-type" | ":exception-type" => event_type = String::from_utf8_lossy(value).into_owned(),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Some(EventFrame {
+        message_type,
+        event_type,
+        payload: frame[12 + headers_len..total - 4].to_vec(),
+    }))
+}
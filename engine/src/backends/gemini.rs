@@ -0,0 +1,469 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, FinishReason, Generation, StreamToken, ToolCall, ToolDefinition,
+};
+
+pub const DEFAULT_URL: &str = "https://generativelanguage.googleapis.com";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest {
+    contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
+    generation_config: GenerationConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<SafetySetting>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tools>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Content {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<InlineData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_call: Option<FunctionCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_response: Option<FunctionResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InlineData {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FunctionCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FunctionResponse {
+    name: String,
+    response: serde_json::Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    max_output_tokens: u32,
+    temperature: f32,
+}
+
+#[derive(Serialize)]
+struct SafetySetting {
+    category: String,
+    threshold: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Tools {
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Serialize)]
+struct FunctionDeclaration {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(default)]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    #[serde(default)]
+    content: Content,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    #[serde(default)]
+    block_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    candidates_token_count: u32,
+}
+
+impl GenerateContentResponse {
+    /// Gemini answers a blocked prompt or response with HTTP 200 and no
+    /// content, so blocks are turned into errors here.
+    fn blocked(&self) -> Option<String> {
+        if let Some(reason) = self.prompt_feedback.as_ref().and_then(|f| f.block_reason.as_ref()) {
+            return Some(format!("Gemini blocked the prompt: {}", reason));
+        }
+        let candidate = self.candidates.first()?;
+        match candidate.finish_reason.as_deref() {
+            Some(reason @ ("SAFETY" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "RECITATION"))
+                if candidate.content.parts.iter().all(|p| p.text.is_none()) =>
+            {
+                Some(format!("Gemini blocked the response: {}", reason))
+            }
+            _ => None,
+        }
+    }
+
+    /// Why the first candidate ended, once it has.
+    fn finish_reason(&self) -> Option<FinishReason> {
+        let reason = self.candidates.first()?.finish_reason.as_deref()?;
+        Some(match reason {
+            "MAX_TOKENS" => FinishReason::Length,
+            "SAFETY" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "RECITATION" | "IMAGE_SAFETY" => {
+                FinishReason::ContentFilter
+            }
+            "MALFORMED_FUNCTION_CALL" => FinishReason::Error,
+            _ => FinishReason::Stop,
+        })
+    }
+
+    fn text(&self) -> String {
+        self.candidates
+            .first()
+            .map(|c| c.content.parts.iter().filter_map(|p| p.text.as_deref()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Safety settings from `GEMINI_SAFETY_SETTINGS`, a comma-separated list of
+/// `category=threshold` pairs such as `harassment=block_none`. Short names
+/// get the `HARM_CATEGORY_` prefix; unset categories keep Google's defaults.
+fn safety_settings() -> Vec<SafetySetting> {
+    let Ok(value) = config::var("GEMINI_SAFETY_SETTINGS") else {
+        return Vec::new();
+    };
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (category, threshold) = pair.split_once('=')?;
+            let category = category.trim().to_uppercase();
+            let category = if category.starts_with("HARM_CATEGORY_") {
+                category
+            } else {
+                format!("HARM_CATEGORY_{}", category)
+            };
+            Some(SafetySetting {
+                category,
+                threshold: threshold.trim().to_uppercase(),
+            })
+        })
+        .collect()
+}
+
+/// Adds the API key header every Gemini call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("GEMINI_API_KEY").unwrap_or_default();
+    request.header("x-goog-api-key", api_key)
+}
+
+/// Function responses must be JSON objects; other tool output is wrapped.
+fn function_response(content: String) -> serde_json::Value {
+    match serde_json::from_str::<serde_json::Value>(&content) {
+        Ok(value @ serde_json::Value::Object(_)) => value,
+        _ => serde_json::json!({ "content": content }),
+    }
+}
+
+/// Maps the unified conversation onto `contents`: system messages become the
+/// `systemInstruction`, assistant turns use the `model` role, and tool results
+/// become `functionResponse` parts named after the call they answer.
+fn to_contents(messages: Vec<ChatMessage>) -> (Option<Content>, Vec<Content>) {
+    let mut system = Vec::new();
+    let mut contents: Vec<Content> = Vec::new();
+    let mut call_names: HashMap<String, String> = HashMap::new();
+
+    for message in messages {
+        let mut parts = Vec::new();
+        let role = match message.role.as_str() {
+            "system" => {
+                system.push(Part {
+                    text: Some(message.content),
+                    ..Default::default()
+                });
+                continue;
+            }
+            "assistant" => {
+                if !message.content.is_empty() {
+                    parts.push(Part {
+                        text: Some(message.content),
+                        ..Default::default()
+                    });
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    call_names.insert(call.id.clone(), call.name.clone());
+                    parts.push(Part {
+                        function_call: Some(FunctionCall {
+                            id: None,
+                            name: call.name,
+                            args: call.arguments,
+                        }),
+                        ..Default::default()
+                    });
+                }
+                "model"
+            }
+            "tool" => {
+                let id = message.tool_call_id.unwrap_or_default();
+                parts.push(Part {
+                    function_response: Some(FunctionResponse {
+                        name: call_names.get(&id).cloned().unwrap_or(id),
+                        response: function_response(message.content),
+                    }),
+                    ..Default::default()
+                });
+                "user"
+            }
+            _ => {
+                if !message.content.is_empty() {
+                    parts.push(Part {
+                        text: Some(message.content),
+                        ..Default::default()
+                    });
+                }
+                "user"
+            }
+        };
+
+        for attachment in message.attachments.unwrap_or_default() {
+            parts.push(Part {
+                inline_data: Some(InlineData {
+                    mime_type: attachment.mime_type,
+                    data: attachment.data,
+                }),
+                ..Default::default()
+            });
+        }
+
+        match contents.last_mut() {
+            Some(last) if last.role.as_deref() == Some(role) => last.parts.extend(parts),
+            _ => contents.push(Content {
+                role: Some(role.to_string()),
+                parts,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then_some(Content { role: None, parts: system });
+    (system, contents)
+}
+
+fn to_tools(tools: Vec<ToolDefinition>) -> Vec<Tools> {
+    if tools.is_empty() {
+        return Vec::new();
+    }
+    vec![Tools {
+        function_declarations: tools
+            .into_iter()
+            .map(|tool| FunctionDeclaration {
+                name: tool.name,
+                description: tool.description,
+                parameters: tool.parameters,
+            })
+            .collect(),
+    }]
+}
+
+fn request_body(call: &BackendRequest) -> GenerateContentRequest {
+    let (system_instruction, contents) = to_contents(call.messages.clone());
+    GenerateContentRequest {
+        contents,
+        system_instruction,
+        generation_config: GenerationConfig {
+            max_output_tokens: call.max_tokens,
+            temperature: call.temperature,
+        },
+        safety_settings: safety_settings(),
+        tools: to_tools(call.tools.clone()),
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("GEMINI_API_KEY").is_err() {
+        return Err("GEMINI_API_KEY not set. Set GEMINI_API_KEY environment variable.".to_string());
+    }
+
+    let url = format!("{}/v1beta/models/{}:generateContent", call.base_url, call.model);
+    let response = authorize(call.client.post(url))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call))
+        .send()
+        .await
+        .map_err(|e| format!("Gemini request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Gemini API error: {} - {}", status, error_text));
+    }
+
+    let resp: GenerateContentResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Gemini response: {}", e))?;
+
+    if let Some(blocked) = resp.blocked() {
+        return Err(blocked);
+    }
+
+    let text = resp.text();
+    let finish_reason = resp.finish_reason().unwrap_or_default();
+    let tokens = resp
+        .usage_metadata
+        .as_ref()
+        .map(|usage| usage.candidates_token_count)
+        .unwrap_or_else(|| text.split_whitespace().count() as u32);
+
+    // Gemini only ids calls on some models; number the rest.
+    let tool_calls: Vec<ToolCall> = resp
+        .candidates
+        .into_iter()
+        .next()
+        .map(|c| c.content.parts)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|part| part.function_call)
+        .enumerate()
+        .map(|(i, call)| ToolCall {
+            id: call.id.unwrap_or_else(|| format!("call_{}", i)),
+            name: call.name,
+            arguments: call.args,
+        })
+        .collect();
+
+    Ok(Generation {
+        text,
+        tokens,
+        citations: Vec::new(),
+        reasoning: None,
+        // Gemini finishes with `STOP` when it calls functions.
+        finish_reason: if tool_calls.is_empty() { finish_reason } else { FinishReason::ToolCalls },
+        tool_calls,
+        upstream: None,
+        draft: None,
+    })
+}
+
+/// Streams text parts as `token` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let url = format!("{}/v1beta/models/{}:streamGenerateContent?alt=sse", call.base_url, call.model);
+        let response = match authorize(call.client.post(url))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Gemini stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            yield Err(std::io::Error::other(format!("Gemini API error: {}", response.status())));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Gemini read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data: ") else {
+                    continue;
+                };
+                let Ok(resp) = serde_json::from_str::<GenerateContentResponse>(data) else {
+                    continue;
+                };
+
+                if let Some(blocked) = resp.blocked() {
+                    yield Err(std::io::Error::other(blocked));
+                    return;
+                }
+
+                let text = resp.text();
+                let finish = resp.finish_reason();
+                if text.is_empty() && finish.is_none() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text,
+                    token_id,
+                    complete: finish.is_some(),
+                    request_id: call.request_id.clone(),
+                    finish_reason: finish,
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if finish.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::super::config;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+/// Tokens are renewed this long before they expire, so none runs out while a
+/// request is in flight.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The access token in use and when it expires.
+static TOKEN: LazyLock<Mutex<Option<(String, Instant)>>> = LazyLock::new(|| Mutex::new(None));
+
+/// An application default credentials file: a service account key, or the
+/// user credentials `gcloud auth application-default login` writes.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Credentials {
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        #[serde(default)]
+        token_uri: Option<String>,
+    },
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// `GOOGLE_APPLICATION_CREDENTIALS`, or where gcloud keeps application
+/// default credentials.
+fn credentials_path() -> Option<PathBuf> {
+    config::var("GOOGLE_APPLICATION_CREDENTIALS")
+        .ok()
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var("HOME").ok().map(|home| {
+                PathBuf::from(home).join(".config/gcloud/application_default_credentials.json")
+            })
+        })
+}
+
+/// Exchanges credentials for an access token at Google's OAuth endpoint.
+async fn fetch_token(client: &reqwest::Client, credentials: Credentials) -> Result<TokenResponse, String> {
+    let request = match credentials {
+        Credentials::ServiceAccount {
+            client_email,
+            private_key,
+            token_uri,
+        } => {
+            let token_uri = token_uri.unwrap_or_else(|| DEFAULT_TOKEN_URI.to_string());
+            let now = chrono::Utc::now().timestamp();
+            let claims = Claims {
+                iss: &client_email,
+                scope: SCOPE,
+                aud: &token_uri,
+                iat: now,
+                exp: now + 3600,
+            };
+            let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+                .map_err(|e| format!("Invalid service account private key: {}", e))?;
+            let assertion = encode(&Header::new(Algorithm::RS256), &claims, &key)
+                .map_err(|e| format!("Failed to sign service account token: {}", e))?;
+            client.post(&token_uri).form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+        }
+        Credentials::AuthorizedUser {
+            client_id,
+            client_secret,
+            refresh_token,
+        } => client.post(DEFAULT_TOKEN_URI).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("refresh_token", refresh_token.as_str()),
+        ]),
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Google token request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Google token error: {} - {}", status, error_text));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Google token response: {}", e))
+}
+
+/// An OAuth access token for Google Cloud APIs: `GOOGLE_ACCESS_TOKEN` when
+/// set, otherwise one obtained from application default credentials and
+/// cached until shortly before it expires.
+pub async fn access_token(client: &reqwest::Client) -> Result<String, String> {
+    if let Ok(token) = config::var("GOOGLE_ACCESS_TOKEN") {
+        return Ok(token);
+    }
+
+    let mut cached = TOKEN.lock().await;
+    if let Some((token, expires_at)) = cached.as_ref()
+        && Instant::now() + EXPIRY_MARGIN < *expires_at
+    {
+        return Ok(token.clone());
+    }
+
+    let path = credentials_path().ok_or(
+        "No Google credentials. Set GOOGLE_ACCESS_TOKEN or GOOGLE_APPLICATION_CREDENTIALS.",
+    )?;
+    let contents = tokio::fs::read(&path).await.map_err(|e| {
+        format!(
+            "Failed to read Google credentials {}: {}. Set GOOGLE_ACCESS_TOKEN or GOOGLE_APPLICATION_CREDENTIALS.",
+            path.display(),
+            e
+        )
+    })?;
+    let credentials: Credentials = serde_json::from_slice(&contents)
+        .map_err(|e| format!("Invalid Google credentials {}: {}", path.display(), e))?;
+
+    let token = fetch_token(client, credentials).await?;
+    *cached = Some((
+        token.access_token.clone(),
+        Instant::now() + Duration::from_secs(token.expires_in),
+    ));
+    Ok(token.access_token)
+}
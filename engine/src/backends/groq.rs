@@ -0,0 +1,119 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use chrono::Utc;
+use futures::stream::{Stream, StreamExt};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::super::InferenceBackend;
+use super::openai_compat::{self, ChatResponse};
+
+pub const DEFAULT_URL: &str = "https://api.groq.com/openai/v1";
+
+const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Waits longer than this fail right away rather than holding the request.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(10);
+
+/// First backoff after a 429 that did not say how long to wait, doubled on
+/// every further retry.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// `GROQ_MAX_RETRIES` bounds how often a request is retried after a 429.
+fn max_retries() -> u32 {
+    config::var("GROQ_MAX_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+}
+
+/// Adds the bearer token every Groq call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("GROQ_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+/// Sends a chat request once Groq's last reported rate limit allows it, and
+/// retries 429s after the wait Groq asks for, or an exponential backoff when
+/// it does not say. Every response's rate limit headers are recorded, so
+/// concurrent requests hold back as soon as one of them sees the limit.
+async fn send(call: &BackendRequest, stream: bool) -> Result<reqwest::Response, String> {
+    let body = openai_compat::request_body(call, stream);
+    let max_retries = max_retries();
+    let mut attempt = 0;
+
+    loop {
+        let now = Utc::now();
+        let blocked_until = call.upstream_limits.lock().await.blocked_until(&InferenceBackend::Groq, now);
+        let mut wait = blocked_until
+            .and_then(|until| (until - now).to_std().ok())
+            .unwrap_or_default();
+        if attempt > 0 {
+            wait = wait.max(BASE_BACKOFF * 2u32.pow(attempt - 1));
+        }
+        if wait > MAX_RETRY_WAIT {
+            return Err(format!("Groq rate limit reached, retry after {}s", wait.as_secs_f64().ceil()));
+        }
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+
+        let response = authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Groq request failed: {}", e))?;
+        call.upstream_limits
+            .lock()
+            .await
+            .observe(&InferenceBackend::Groq, response.headers(), Utc::now());
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS && attempt < max_retries {
+            attempt += 1;
+            tracing::warn!(request_id = %call.request_id, attempt, "Groq rate limited the request, retrying");
+            continue;
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Groq API error: {} - {}", status, error_text));
+        }
+        return Ok(response);
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("GROQ_API_KEY").is_err() {
+        return Err("GROQ_API_KEY not set. Set GROQ_API_KEY environment variable.".to_string());
+    }
+
+    send(&call, false)
+        .await?
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse Groq response: {}", e))?
+        .into_generation("Groq")
+}
+
+/// Streams content deltas as `token` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match send(&call, true).await {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("Groq", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
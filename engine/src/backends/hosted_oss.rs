@@ -0,0 +1,183 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::super::{InferenceBackend, ModelCapability, ModelPricing};
+use super::openai_compat::{self, ChatRequest, ChatResponse};
+
+pub const TOGETHER_DEFAULT_URL: &str = "https://api.together.xyz/v1";
+pub const FIREWORKS_DEFAULT_URL: &str = "https://api.fireworks.ai/inference/v1";
+
+/// Assumed for catalog entries that do not state a context length.
+const DEFAULT_CONTEXT: u32 = 4096;
+
+/// Together AI and Fireworks serve open models behind the same
+/// OpenAI-compatible API, and differ only in name and credentials.
+struct Host {
+    name: &'static str,
+    api_key_var: &'static str,
+}
+
+fn host(backend: &InferenceBackend) -> Host {
+    match backend {
+        InferenceBackend::Fireworks => Host {
+            name: "Fireworks",
+            api_key_var: "FIREWORKS_API_KEY",
+        },
+        _ => Host {
+            name: "Together",
+            api_key_var: "TOGETHER_API_KEY",
+        },
+    }
+}
+
+/// A model offered by the host, mapped onto registry fields.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CatalogModel {
+    pub id: String,
+    pub name: String,
+    pub context: u32,
+    pub capabilities: Vec<ModelCapability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+}
+
+/// Reads one catalog entry. Together lists models as a bare array with a
+/// `type` and prices per million tokens; Fireworks uses OpenAI's `data` list
+/// with `supports_*` flags. Entries that are not text models are skipped.
+fn catalog_model(entry: &serde_json::Value) -> Option<CatalogModel> {
+    let id = entry["id"].as_str()?.to_string();
+
+    let mut capabilities = match entry["type"].as_str() {
+        Some("chat") => vec![ModelCapability::Chat],
+        Some("language" | "code") => vec![ModelCapability::Completion],
+        Some("embedding") => vec![ModelCapability::Embedding],
+        Some(_) => return None,
+        None if entry["supports_chat"].as_bool() == Some(false) => vec![ModelCapability::Completion],
+        None => vec![ModelCapability::Chat],
+    };
+    if entry["supports_image_input"].as_bool() == Some(true) {
+        capabilities.push(ModelCapability::Vision);
+    }
+
+    let pricing = match (entry["pricing"]["input"].as_f64(), entry["pricing"]["output"].as_f64()) {
+        (Some(input), Some(output)) if input > 0.0 || output > 0.0 => Some(ModelPricing {
+            prompt_per_1k: input / 1000.0,
+            completion_per_1k: output / 1000.0,
+        }),
+        _ => None,
+    };
+
+    Some(CatalogModel {
+        name: entry["display_name"].as_str().unwrap_or(&id).to_string(),
+        context: entry["context_length"]
+            .as_u64()
+            .map(|context| context as u32)
+            .unwrap_or(DEFAULT_CONTEXT),
+        capabilities,
+        quant: None,
+        pricing,
+        id,
+    })
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let mut body = openai_compat::request_body(call, stream);
+    openai_compat::add_extended_sampling(&mut body, &call.sampling);
+    body
+}
+
+/// Adds the bearer token for `backend`.
+pub fn authorize(backend: &InferenceBackend, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var(host(backend).api_key_var).unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+/// Lists the models the host serves.
+pub async fn list_models(
+    backend: &InferenceBackend,
+    client: &reqwest::Client,
+    base_url: &str,
+) -> Result<Vec<CatalogModel>, String> {
+    let host = host(backend);
+    let response = authorize(backend, client.get(format!("{}/models", base_url)))
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", host.name, e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("{} API error: {} - {}", host.name, status, error_text));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse {} response: {}", host.name, e))?;
+    let entries = body.as_array().or_else(|| body["data"].as_array()).cloned().unwrap_or_default();
+    Ok(entries.iter().filter_map(catalog_model).collect())
+}
+
+pub async fn generate(backend: &InferenceBackend, call: BackendRequest) -> Result<Generation, String> {
+    let host = host(backend);
+    if config::var(host.api_key_var).is_err() {
+        return Err(format!("{0} not set. Set {0} environment variable.", host.api_key_var));
+    }
+
+    let response = authorize(backend, call.client.post(format!("{}/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", host.name, e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("{} API error: {} - {}", host.name, status, error_text));
+    }
+
+    response
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse {} response: {}", host.name, e))?
+        .into_generation(host.name)
+}
+
+pub fn stream_events(backend: InferenceBackend, call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let host = host(&backend);
+        let response = match authorize(&backend, call.client.post(format!("{}/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("{} stream failed: {}", host.name, e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("{} API error: {} - {}", host.name, status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens(host.name, response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation, StreamToken};
+use super::openai_compat::finish_reason;
+
+pub const DEFAULT_URL: &str = "http://localhost:5001";
+
+/// A KoboldAI generate request, with KoboldCpp's names for the samplers.
+#[derive(Serialize)]
+struct GenerateRequest {
+    prompt: String,
+    max_length: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rep_pen: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequence: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sampler_seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sampler_order: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mirostat: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mirostat_tau: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mirostat_eta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xtc_probability: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xtc_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rep_pen_range: Option<u32>,
+    /// GBNF grammar the output must follow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grammar: Option<String>,
+    /// Leaves the matched stop sequence out of the output.
+    trim_stop: bool,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    results: Vec<GenerateResult>,
+}
+
+#[derive(Deserialize)]
+struct GenerateResult {
+    text: String,
+    #[serde(default)]
+    completion_tokens: Option<u32>,
+    /// `length` or `stop`, on releases recent enough to report it.
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+fn request_body(call: &BackendRequest) -> GenerateRequest {
+    let sampling = &call.sampling;
+    GenerateRequest {
+        prompt: call.prompt.clone(),
+        max_length: call.max_tokens,
+        temperature: call.temperature,
+        top_p: sampling.top_p,
+        top_k: sampling.top_k,
+        min_p: sampling.min_p,
+        rep_pen: sampling.repetition_penalty,
+        presence_penalty: sampling.presence_penalty,
+        stop_sequence: sampling.stop.clone(),
+        sampler_seed: sampling.seed,
+        sampler_order: sampling.sampler_order.clone(),
+        mirostat: sampling.mirostat,
+        mirostat_tau: sampling.mirostat_tau,
+        mirostat_eta: sampling.mirostat_eta,
+        xtc_probability: sampling.xtc_probability,
+        xtc_threshold: sampling.xtc_threshold,
+        rep_pen_range: sampling.repetition_context_size,
+        grammar: sampling.grammar.clone(),
+        trim_stop: true,
+    }
+}
+
+/// Adds `KOBOLDCPP_API_KEY` as a bearer token when set, for servers started
+/// with `--password`.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match config::var("KOBOLDCPP_API_KEY") {
+        Ok(api_key) => request.bearer_auth(api_key),
+        Err(_) => request,
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = authorize(call.client.post(format!("{}/api/v1/generate", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call))
+        .send()
+        .await
+        .map_err(|e| format!("KoboldCpp request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("KoboldCpp API error: {} - {}", status, error_text));
+    }
+
+    let resp: GenerateResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse KoboldCpp response: {}", e))?;
+
+    let result = resp
+        .results
+        .into_iter()
+        .next()
+        .ok_or("KoboldCpp returned no results")?;
+    let tokens = result
+        .completion_tokens
+        .unwrap_or_else(|| result.text.split_whitespace().count() as u32);
+    Ok(Generation {
+        finish_reason: result.finish_reason.as_deref().map(finish_reason).unwrap_or_default(),
+        ..Generation::new(result.text, tokens)
+    })
+}
+
+/// Streams from `/api/extra/generate/stream`, whose events each carry one
+/// `token`. The last has a `finish_reason`.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/api/extra/generate/stream", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("KoboldCpp stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("KoboldCpp API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("KoboldCpp read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data.trim_start()) else {
+                    continue;
+                };
+
+                let text = chunk["token"].as_str().unwrap_or("");
+                // Older releases send the string "null" until the end.
+                let finish = chunk["finish_reason"]
+                    .as_str()
+                    .filter(|reason| *reason != "null")
+                    .map(finish_reason);
+
+                if text.is_empty() && finish.is_none() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete: finish.is_some(),
+                    request_id: call.request_id.clone(),
+                    finish_reason: finish,
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if finish.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use axum::response::sse::Event;
+use futures::stream::{self, Stream};
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+
+use super::super::config;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::super::Placement;
+
+/// Stands in for a URL, since models run inside the engine.
+pub const URL: &str = "local://";
+
+const DEFAULT_MODEL_DIR: &str = "models";
+
+/// Every GGUF file starts with these bytes.
+const GGUF_MAGIC: [u8; 4] = *b"GGUF";
+
+/// Linking llama.cpp needs the `llama-cpp-2` bindings, which this build does
+/// not include yet.
+pub const UNAVAILABLE: &str =
+    "In-process llama.cpp is not available in this build of openllm-server; serve the model with the llama backend instead";
+
+/// The GGUF file for `model`: the id itself when it names a `.gguf` file,
+/// otherwise `<id>.gguf` under `LLAMA_LOCAL_MODEL_DIR`.
+pub fn model_path(model: &str) -> PathBuf {
+    if model.ends_with(".gguf") {
+        return PathBuf::from(model);
+    }
+    model_dir().join(format!("{}.gguf", model))
+}
+
+/// The directory GGUF files are looked up in, `LLAMA_LOCAL_MODEL_DIR`.
+pub fn model_dir() -> PathBuf {
+    PathBuf::from(config::var("LLAMA_LOCAL_MODEL_DIR").unwrap_or_else(|_| DEFAULT_MODEL_DIR.to_string()))
+}
+
+/// Checks that the model's GGUF file is there, so a wrong path is reported as
+/// such, before failing for want of llama.cpp. The placement becomes the
+/// model and context parameters once the bindings are linked.
+pub async fn load(model: &str, _placement: Option<&Placement>) -> Result<(), String> {
+    let path = model_path(model);
+    let mut header = [0u8; 4];
+    tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("Failed to open model file {}: {}", path.display(), e))?
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| format!("Failed to read model file {}: {}", path.display(), e))?;
+    if header != GGUF_MAGIC {
+        return Err(format!("{} is not a GGUF file", path.display()));
+    }
+    Err(UNAVAILABLE.to_string())
+}
+
+pub async fn generate(_call: BackendRequest) -> Result<Generation, String> {
+    Err(UNAVAILABLE.to_string())
+}
+
+pub fn stream_events(_call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream::once(async { Err(std::io::Error::other(UNAVAILABLE)) })
+}
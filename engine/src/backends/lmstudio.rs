@@ -0,0 +1,182 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde_json::json;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::super::ModelCapability;
+use super::hosted_oss::CatalogModel;
+use super::openai_compat::{self, ChatRequest, ChatResponse};
+
+pub const DEFAULT_URL: &str = "http://localhost:1234";
+
+/// Assumed for models that do not state a context length.
+const DEFAULT_CONTEXT: u32 = 4096;
+
+/// Adds `LM_STUDIO_API_KEY` as a bearer token when set. LM Studio only asks
+/// for one when authentication is turned on in its server settings.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match config::var("LM_STUDIO_API_KEY") {
+        Ok(api_key) => request.bearer_auth(api_key),
+        Err(_) => request,
+    }
+}
+
+async fn check(response: reqwest::Response) -> Result<serde_json::Value, String> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("LM Studio API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse LM Studio response: {}", e))
+}
+
+/// Every model LM Studio has downloaded, as listed by its REST API.
+async fn downloaded_models(client: &reqwest::Client, base_url: &str) -> Result<Vec<serde_json::Value>, String> {
+    let response = authorize(client.get(format!("{}/api/v1/models", base_url)))
+        .send()
+        .await
+        .map_err(|e| format!("LM Studio request failed: {}", e))?;
+
+    let body = check(response).await?;
+    Ok(body["models"].as_array().cloned().unwrap_or_default())
+}
+
+fn catalog_model(entry: &serde_json::Value) -> Option<CatalogModel> {
+    let id = entry["key"].as_str()?.to_string();
+
+    let mut capabilities = match entry["type"].as_str() {
+        Some("llm") => vec![ModelCapability::Chat],
+        Some("embedding" | "embeddings") => vec![ModelCapability::Embedding],
+        _ => return None,
+    };
+    if entry["capabilities"]["vision"].as_bool() == Some(true) {
+        capabilities.push(ModelCapability::Vision);
+    }
+
+    Some(CatalogModel {
+        name: entry["display_name"].as_str().unwrap_or(&id).to_string(),
+        context: entry["max_context_length"]
+            .as_u64()
+            .map(|context| context as u32)
+            .unwrap_or(DEFAULT_CONTEXT),
+        capabilities,
+        quant: None,
+        pricing: None,
+        id,
+    })
+}
+
+/// Lists the models LM Studio has downloaded, whether loaded or not.
+pub async fn list_models(client: &reqwest::Client, base_url: &str) -> Result<Vec<CatalogModel>, String> {
+    Ok(downloaded_models(client, base_url)
+        .await?
+        .iter()
+        .filter_map(catalog_model)
+        .collect())
+}
+
+/// Loads `model` into LM Studio's memory with the registered context length.
+pub async fn load(client: &reqwest::Client, base_url: &str, model: &str, context: u32) -> Result<(), String> {
+    let response = authorize(client.post(format!("{}/api/v1/models/load", base_url)))
+        .json(&json!({ "model": model, "context_length": context }))
+        .send()
+        .await
+        .map_err(|e| format!("LM Studio request failed: {}", e))?;
+
+    check(response).await.map(|_| ())
+}
+
+/// Whether LM Studio has an instance of `model` in memory.
+pub async fn is_loaded(client: &reqwest::Client, base_url: &str, model: &str) -> Result<bool, String> {
+    Ok(downloaded_models(client, base_url).await?.iter().any(|entry| {
+        entry["key"].as_str() == Some(model)
+            && entry["loaded_instances"].as_array().is_some_and(|instances| !instances.is_empty())
+    }))
+}
+
+/// Unloads every instance of `model` LM Studio has in memory.
+pub async fn unload(client: &reqwest::Client, base_url: &str, model: &str) -> Result<(), String> {
+    let models = downloaded_models(client, base_url).await?;
+    let instances = models
+        .iter()
+        .filter(|entry| entry["key"].as_str() == Some(model))
+        .flat_map(|entry| entry["loaded_instances"].as_array().cloned().unwrap_or_default())
+        .filter_map(|instance| instance["id"].as_str().map(str::to_string));
+
+    for instance_id in instances {
+        let response = authorize(client.post(format!("{}/api/v1/models/unload", base_url)))
+            .json(&json!({ "instance_id": instance_id }))
+            .send()
+            .await
+            .map_err(|e| format!("LM Studio request failed: {}", e))?;
+        check(response).await?;
+    }
+    Ok(())
+}
+
+/// OpenAI's request, plus the draft model for speculative decoding, which LM
+/// Studio loads alongside the model on first use.
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let mut body = openai_compat::request_body(call, stream);
+    if let Some(speculative) = &call.speculative {
+        body.extra.insert("draft_model".to_string(), speculative.draft_model.clone().into());
+    }
+    body
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = authorize(call.client.post(format!("{}/v1/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("LM Studio request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("LM Studio API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse LM Studio response: {}", e))?
+        .into_generation("LM Studio")
+}
+
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/v1/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("LM Studio stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("LM Studio API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("LM Studio", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
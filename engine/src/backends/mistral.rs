@@ -0,0 +1,397 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, ChatMessage, Generation, StreamToken, ToolCall, ToolDefinition};
+use super::openai_compat::finish_reason;
+
+pub const DEFAULT_URL: &str = "https://api.mistral.ai";
+
+/// Mistral only accepts tool call ids of exactly nine letters and digits.
+const TOOL_CALL_ID_LEN: usize = 9;
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    max_tokens: u32,
+    temperature: f32,
+    stream: bool,
+    safe_prompt: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: String,
+    content: Content,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<RequestToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+/// Plain text, or text and images for vision models.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: String },
+}
+
+#[derive(Serialize)]
+struct RequestToolCall {
+    id: String,
+    r#type: &'static str,
+    function: RequestFunction,
+}
+
+/// Arguments go out as a JSON-encoded string, as in OpenAI's API.
+#[derive(Serialize)]
+struct RequestFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    r#type: &'static str,
+    function: FunctionDefinition,
+}
+
+#[derive(Serialize)]
+struct FunctionDefinition {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ResponseToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct ResponseToolCall {
+    id: String,
+    function: ResponseFunction,
+}
+
+/// Usually a JSON-encoded string, but some models return an object.
+#[derive(Deserialize)]
+struct ResponseFunction {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    completion_tokens: u32,
+}
+
+/// `MISTRAL_SAFE_PROMPT=true` prepends Mistral's safety system prompt.
+fn safe_prompt() -> bool {
+    config::var("MISTRAL_SAFE_PROMPT").is_ok_and(|value| value.trim().eq_ignore_ascii_case("true") || value.trim() == "1")
+}
+
+/// Maps a tool call id from another backend or a client onto the nine
+/// characters Mistral requires. Ids that already fit are kept, so calls made
+/// by Mistral round-trip unchanged.
+fn tool_call_id(id: &str) -> String {
+    if id.len() == TOOL_CALL_ID_LEN && id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return id.to_string();
+    }
+    Sha256::digest(id.as_bytes())
+        .iter()
+        .take(TOOL_CALL_ID_LEN)
+        .map(|b| format!("{:x}", b % 16))
+        .collect()
+}
+
+fn tool_arguments(arguments: serde_json::Value) -> serde_json::Value {
+    match arguments {
+        serde_json::Value::String(encoded) => {
+            serde_json::from_str(&encoded).unwrap_or(serde_json::Value::String(encoded))
+        }
+        other => other,
+    }
+}
+
+/// Mistral reports errors as `{"message": "..."}`, with validation errors as
+/// a `detail` list, rather than OpenAI's `{"error": {...}}`.
+fn error_message(body: &str) -> String {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        return body.to_string();
+    };
+    let message = if json["message"].is_null() { &json } else { &json["message"] };
+    if let Some(text) = message.as_str() {
+        return text.to_string();
+    }
+    match message["detail"].as_array() {
+        Some(details) => details
+            .iter()
+            .map(|d| {
+                let location: Vec<String> = d["loc"]
+                    .as_array()
+                    .map(|loc| loc.iter().map(|l| l.to_string().trim_matches('"').to_string()).collect())
+                    .unwrap_or_default();
+                format!("{}: {}", location.join("."), d["msg"].as_str().unwrap_or_default())
+            })
+            .collect::<Vec<_>>()
+            .join("; "),
+        None => body.to_string(),
+    }
+}
+
+fn to_messages(messages: Vec<ChatMessage>) -> Vec<Message> {
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    messages
+        .into_iter()
+        .map(|message| {
+            let tool_calls: Vec<RequestToolCall> = message
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|call| {
+                    call_names.insert(call.id.clone(), call.name.clone());
+                    RequestToolCall {
+                        id: tool_call_id(&call.id),
+                        r#type: "function",
+                        function: RequestFunction {
+                            name: call.name,
+                            arguments: call.arguments.to_string(),
+                        },
+                    }
+                })
+                .collect();
+            let name = message.tool_call_id.as_ref().and_then(|id| call_names.get(id).cloned());
+
+            let images: Vec<ContentPart> = message
+                .attachments
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|a| a.mime_type.starts_with("image/"))
+                .map(|a| ContentPart::ImageUrl {
+                    image_url: format!("data:{};base64,{}", a.mime_type, a.data),
+                })
+                .collect();
+            let content = if images.is_empty() {
+                Content::Text(message.content)
+            } else {
+                let mut parts = vec![ContentPart::Text { text: message.content }];
+                parts.extend(images);
+                Content::Parts(parts)
+            };
+
+            Message {
+                role: message.role,
+                content,
+                tool_calls,
+                tool_call_id: message.tool_call_id.as_deref().map(tool_call_id),
+                name,
+            }
+        })
+        .collect()
+}
+
+fn to_tools(tools: Vec<ToolDefinition>) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|tool| Tool {
+            r#type: "function",
+            function: FunctionDefinition {
+                name: tool.name,
+                description: tool.description,
+                parameters: tool.parameters,
+            },
+        })
+        .collect()
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    ChatRequest {
+        model: call.model.clone(),
+        messages: to_messages(call.messages.clone()),
+        max_tokens: call.max_tokens,
+        temperature: call.temperature,
+        stream,
+        safe_prompt: safe_prompt(),
+        tools: to_tools(call.tools.clone()),
+    }
+}
+
+/// Adds the bearer token every Mistral call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("MISTRAL_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("MISTRAL_API_KEY").is_err() {
+        return Err("MISTRAL_API_KEY not set. Set MISTRAL_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/v1/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("Mistral request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Mistral API error: {} - {}", status, error_message(&error_text)));
+    }
+
+    let resp: ChatResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Mistral response: {}", e))?;
+    let choice = resp
+        .choices
+        .into_iter()
+        .next()
+        .ok_or("Mistral returned no choices")?;
+    let message = choice.message;
+
+    let tool_calls = message
+        .tool_calls
+        .unwrap_or_default()
+        .into_iter()
+        .map(|call| ToolCall {
+            id: call.id,
+            name: call.function.name,
+            arguments: tool_arguments(call.function.arguments),
+        })
+        .collect();
+
+    Ok(Generation {
+        text: message.content.unwrap_or_default(),
+        tokens: resp.usage.completion_tokens,
+        tool_calls,
+        citations: Vec::new(),
+        reasoning: None,
+        finish_reason: choice.finish_reason.as_deref().map(finish_reason).unwrap_or_default(),
+        upstream: None,
+        draft: None,
+    })
+}
+
+/// Streams content deltas as `token` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/v1/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Mistral stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!(
+                "Mistral API error: {} - {}",
+                status,
+                error_message(&error_text)
+            )));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Mistral read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data: ") else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    return;
+                }
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else {
+                    continue;
+                };
+                let choice = &chunk["choices"][0];
+                let text = choice["delta"]["content"].as_str().unwrap_or("");
+                let finish = choice["finish_reason"].as_str().map(finish_reason);
+
+                if text.is_empty() && finish.is_none() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete: finish.is_some(),
+                    request_id: call.request_id.clone(),
+                    finish_reason: finish,
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if finish.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::super::ModelCapability;
+use super::hosted_oss::CatalogModel;
+use super::openai_compat::{self, ChatRequest, ChatResponse};
+
+/// Where `mlx_lm.server` listens by default.
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+
+/// `/v1/models` does not report context lengths.
+const DEFAULT_CONTEXT: u32 = 4096;
+
+/// Quantization suffixes the mlx-community converters append to repo names.
+const QUANT_SUFFIXES: &[&str] = &["3bit", "4bit", "5bit", "6bit", "8bit", "bf16", "fp16", "mxfp4", "dwq"];
+
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let sampling = &call.sampling;
+    let mut body = openai_compat::request_body(call, stream);
+    openai_compat::add_extended_sampling(&mut body, sampling);
+    openai_compat::insert(&mut body.extra, "xtc_probability", &sampling.xtc_probability);
+    openai_compat::insert(&mut body.extra, "xtc_threshold", &sampling.xtc_threshold);
+    openai_compat::insert(&mut body.extra, "repetition_context_size", &sampling.repetition_context_size);
+    body
+}
+
+/// Reads a model's display name, quantization and capabilities from its
+/// Hugging Face repo id, e.g. `mlx-community/Qwen2.5-7B-Instruct-4bit` is a
+/// 4-bit chat model named `Qwen2.5-7B-Instruct`. Repos without a chat marker
+/// are taken to be base models.
+fn catalog_model(id: &str) -> CatalogModel {
+    let repo = id.rsplit('/').next().unwrap_or(id);
+    let (name, quant) = match repo.rsplit_once('-') {
+        Some((name, suffix)) if QUANT_SUFFIXES.contains(&suffix.to_ascii_lowercase().as_str()) => {
+            (name.to_string(), Some(suffix.to_ascii_lowercase()))
+        }
+        _ => (repo.to_string(), None),
+    };
+
+    let lower = name.to_ascii_lowercase();
+    let chat = ["instruct", "chat", "-it"].iter().any(|marker| lower.contains(marker));
+    let capabilities = if chat {
+        vec![ModelCapability::Chat]
+    } else {
+        vec![ModelCapability::Completion]
+    };
+
+    CatalogModel {
+        id: id.to_string(),
+        name,
+        context: DEFAULT_CONTEXT,
+        capabilities,
+        quant,
+        pricing: None,
+    }
+}
+
+/// Lists the models in the server's Hugging Face cache, which it can load
+/// on demand.
+pub async fn list_models(client: &reqwest::Client, base_url: &str) -> Result<Vec<CatalogModel>, String> {
+    let response = client
+        .get(format!("{}/v1/models", base_url))
+        .send()
+        .await
+        .map_err(|e| format!("MLX request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("MLX API error: {} - {}", status, error_text));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse MLX response: {}", e))?;
+    Ok(body["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry["id"].as_str())
+        .map(catalog_model)
+        .collect())
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = call
+        .client
+        .post(format!("{}/v1/chat/completions", call.base_url))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("MLX request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("MLX API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse MLX response: {}", e))?
+        .into_generation("MLX")
+}
+
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match call
+            .client
+            .post(format!("{}/v1/chat/completions", call.base_url))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("MLX stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("MLX API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("MLX", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
@@ -0,0 +1,25 @@
+pub mod anthropic;
+pub mod bedrock;
+pub mod candle;
+pub mod cohere;
+pub mod deepseek;
+pub mod eventstream;
+pub mod gemini;
+pub mod google_auth;
+pub mod groq;
+pub mod hosted_oss;
+pub mod llama_local;
+pub mod koboldcpp;
+pub mod lmstudio;
+pub mod mistral;
+pub mod mlx;
+pub mod ollama;
+pub mod openai_compat;
+pub mod openrouter;
+pub mod replicate;
+pub mod sagemaker;
+pub mod sigv4;
+pub mod tgi;
+pub mod triton;
+pub mod vertex;
+pub mod xai;
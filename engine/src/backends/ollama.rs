@@ -0,0 +1,515 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::model_metadata::ModelMetadata;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, FinishReason, Generation, StreamToken, ToolCall, ToolDefinition,
+};
+use super::super::{ModelCapability, Placement};
+
+pub const DEFAULT_URL: &str = "http://localhost:11434";
+
+#[derive(Serialize)]
+struct GenerateRequest {
+    model: String,
+    prompt: String,
+    stream: bool,
+    options: Options,
+}
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    options: Options,
+}
+
+#[derive(Serialize)]
+struct Options {
+    num_predict: u32,
+    temperature: f32,
+    #[serde(flatten)]
+    placement: PlacementOptions,
+}
+
+/// Options that decide where the model runs. Ollama reloads the model when
+/// they change, so every request repeats the ones it was loaded with.
+#[derive(Serialize, Default)]
+struct PlacementOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_gpu: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    main_gpu: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_thread: Option<u32>,
+    /// Ollama only turns NUMA support on or off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    numa: Option<bool>,
+}
+
+impl From<&Placement> for PlacementOptions {
+    fn from(placement: &Placement) -> Self {
+        Self {
+            num_gpu: placement.n_gpu_layers,
+            main_gpu: placement.main_gpu,
+            num_thread: placement.threads,
+            numa: placement.numa.map(|_| true),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: String,
+    content: String,
+    /// Base64-encoded, for vision models.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<RequestToolCall>,
+    /// On `tool` messages, the tool whose result this is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RequestToolCall {
+    function: RequestFunction,
+}
+
+#[derive(Serialize)]
+struct RequestFunction {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    r#type: &'static str,
+    function: FunctionDefinition,
+}
+
+#[derive(Serialize)]
+struct FunctionDefinition {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    response: String,
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ResponseMessage,
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    /// Tokens generated, reported once the response is done.
+    #[serde(default)]
+    eval_count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: String,
+    /// Set by thinking models when the request enables `think`.
+    #[serde(default)]
+    thinking: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ResponseToolCall>,
+}
+
+#[derive(Deserialize)]
+struct ResponseToolCall {
+    function: ResponseFunction,
+}
+
+#[derive(Deserialize)]
+struct ResponseFunction {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+/// Reads `done_reason`, which is `length` when `num_predict` ran out. Ollama
+/// reports tool calls as a plain `stop`, so callers check for those.
+fn finish_reason(done_reason: Option<&str>) -> FinishReason {
+    match done_reason {
+        Some("length") => FinishReason::Length,
+        _ => FinishReason::Stop,
+    }
+}
+
+fn options(call: &BackendRequest) -> Options {
+    Options {
+        num_predict: call.max_tokens,
+        temperature: call.temperature,
+        placement: call.placement.as_ref().map(PlacementOptions::from).unwrap_or_default(),
+    }
+}
+
+/// Loads `model` into memory with the given placement. A request without a
+/// prompt only loads the model.
+pub async fn load(client: &reqwest::Client, base_url: &str, model: &str, placement: &Placement) -> Result<(), String> {
+    let response = client
+        .post(format!("{}/api/generate", base_url))
+        .json(&serde_json::json!({ "model": model, "options": PlacementOptions::from(placement) }))
+        .send()
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama API error: {} - {}", status, error_text));
+    }
+    Ok(())
+}
+
+/// The capabilities `/api/show` reports for a model. Versions of Ollama
+/// that do not list them are read from the model's families: a `clip` or
+/// `mllama` projector takes images and `bert` models only embed.
+pub fn capabilities(show: &serde_json::Value) -> Option<Vec<ModelCapability>> {
+    if let Some(listed) = show["capabilities"].as_array() {
+        let mut capabilities = Vec::new();
+        for capability in listed.iter().filter_map(|c| c.as_str()) {
+            match capability {
+                "completion" => capabilities.extend([ModelCapability::Chat, ModelCapability::Completion]),
+                "vision" => capabilities.push(ModelCapability::Vision),
+                "embedding" => capabilities.push(ModelCapability::Embedding),
+                _ => {}
+            }
+        }
+        return Some(capabilities);
+    }
+
+    let families: Vec<&str> = show["details"]["families"].as_array()?.iter().filter_map(|f| f.as_str()).collect();
+    if families.iter().any(|family| family.ends_with("bert")) {
+        return Some(vec![ModelCapability::Embedding]);
+    }
+    let mut capabilities = vec![ModelCapability::Chat, ModelCapability::Completion];
+    if families.iter().any(|family| matches!(*family, "clip" | "mllama")) {
+        capabilities.push(ModelCapability::Vision);
+    }
+    Some(capabilities)
+}
+
+/// Describes `model` from `/api/show`: its architecture, size, context
+/// length and quantization, and from `/api/tags` the size of its weights.
+pub async fn show(client: &reqwest::Client, base_url: &str, model: &str) -> Result<ModelMetadata, String> {
+    let show = client
+        .post(format!("{}/api/show", base_url))
+        .json(&serde_json::json!({ "model": model }));
+    let show = send(show).await?;
+    let tags = tags(client, base_url).await?;
+
+    let info = &show["model_info"];
+    let architecture = info["general.architecture"].as_str();
+    let context = architecture
+        .and_then(|architecture| info[format!("{}.context_length", architecture)].as_u64())
+        .and_then(|context| u32::try_from(context).ok());
+    let latest = format!("{}:latest", model);
+    let size_bytes = tags
+        .into_iter()
+        .find(|(name, _)| *name == model || *name == latest)
+        .map(|(_, size)| size);
+
+    Ok(ModelMetadata {
+        architecture: architecture.map(str::to_string),
+        parameters: info["general.parameter_count"].as_u64(),
+        context,
+        quant: show["details"]["quantization_level"].as_str().map(str::to_string),
+        size_bytes,
+        template: show["template"].as_str().filter(|t| !t.is_empty()).map(str::to_string),
+    })
+}
+
+/// The models Ollama has downloaded, by name, e.g. `llama3:latest`, with
+/// the size of their weights.
+pub async fn tags(client: &reqwest::Client, base_url: &str) -> Result<Vec<(String, u64)>, String> {
+    let body = send(client.get(format!("{}/api/tags", base_url))).await?;
+    Ok(body["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| Some((tag["name"].as_str()?.to_string(), tag["size"].as_u64().unwrap_or_default())))
+        .collect())
+}
+
+/// Deletes `model` and whatever of its layers no other model shares.
+pub async fn delete(client: &reqwest::Client, base_url: &str, model: &str) -> Result<(), String> {
+    send(client.delete(format!("{}/api/delete", base_url)).json(&serde_json::json!({ "model": model })))
+        .await
+        .map(|_| ())
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama API error: {} - {}", status, error_text));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))
+}
+
+/// Whether Ollama has `model` in memory, as listed by `/api/ps`. Models
+/// registered without a tag run as `:latest`.
+pub async fn is_running(client: &reqwest::Client, base_url: &str, model: &str) -> Result<bool, String> {
+    let response = client
+        .get(format!("{}/api/ps", base_url))
+        .send()
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama API error: {} - {}", status, error_text));
+    }
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+    let latest = format!("{}:latest", model);
+    Ok(body["models"].as_array().into_iter().flatten().any(|running| {
+        running["name"]
+            .as_str()
+            .or(running["model"].as_str())
+            .is_some_and(|name| name == model || name == latest)
+    }))
+}
+
+/// Ollama tool calls carry no id, so tool results are matched to the call
+/// they answer by name instead, looked up from the earlier assistant turns.
+fn to_messages(messages: &[ChatMessage]) -> Vec<Message> {
+    messages
+        .iter()
+        .map(|message| Message {
+            role: message.role.clone(),
+            content: message.content.clone(),
+            images: message
+                .attachments
+                .iter()
+                .flatten()
+                .filter(|attachment| attachment.mime_type.starts_with("image/"))
+                .map(|attachment| attachment.data.clone())
+                .collect(),
+            tool_calls: message
+                .tool_calls
+                .iter()
+                .flatten()
+                .map(|call| RequestToolCall {
+                    function: RequestFunction {
+                        name: call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    },
+                })
+                .collect(),
+            tool_name: message.tool_call_id.as_ref().and_then(|id| {
+                messages
+                    .iter()
+                    .flat_map(|message| message.tool_calls.iter().flatten())
+                    .find(|call| &call.id == id)
+                    .map(|call| call.name.clone())
+            }),
+        })
+        .collect()
+}
+
+fn to_tools(tools: &[ToolDefinition]) -> Vec<Tool> {
+    tools
+        .iter()
+        .map(|tool| Tool {
+            r#type: "function",
+            function: FunctionDefinition {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                parameters: tool.parameters.clone(),
+            },
+        })
+        .collect()
+}
+
+/// `/api/chat` for chat models, so Ollama applies the model's own template
+/// and tools can be offered; `/api/generate` for raw completion.
+fn request(call: &BackendRequest, stream: bool) -> reqwest::RequestBuilder {
+    let request = if call.chat {
+        call.client.post(format!("{}/api/chat", call.base_url)).json(&ChatRequest {
+            model: call.model.clone(),
+            messages: to_messages(&call.messages),
+            stream,
+            tools: to_tools(&call.tools),
+            options: options(call),
+        })
+    } else {
+        call.client.post(format!("{}/api/generate", call.base_url)).json(&GenerateRequest {
+            model: call.model.clone(),
+            prompt: call.prompt.clone(),
+            stream,
+            options: options(call),
+        })
+    };
+    request.header(REQUEST_ID_HEADER, &call.request_id)
+}
+
+impl ChatResponse {
+    fn into_generation(self) -> Generation {
+        let tokens = self
+            .eval_count
+            .unwrap_or_else(|| self.message.content.split_whitespace().count() as u32);
+        let tool_calls: Vec<ToolCall> = self
+            .message
+            .tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| ToolCall {
+                id: format!("call_{}", index),
+                name: call.function.name,
+                arguments: call.function.arguments,
+            })
+            .collect();
+
+        let finish_reason = if tool_calls.is_empty() {
+            finish_reason(self.done_reason.as_deref())
+        } else {
+            FinishReason::ToolCalls
+        };
+
+        Generation {
+            tool_calls,
+            reasoning: self.message.thinking.filter(|thinking| !thinking.is_empty()),
+            finish_reason,
+            ..Generation::new(self.message.content, tokens)
+        }
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = request(&call, false)
+        .send()
+        .await
+        .map_err(|e| format!("Ollama request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Ollama API error: {}", response.status()));
+    }
+
+    if call.chat {
+        let ollama_resp: ChatResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+        return Ok(ollama_resp.into_generation());
+    }
+
+    let ollama_resp: GenerateResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+
+    let tokens = ollama_resp.response.split_whitespace().count() as u32;
+    Ok(Generation {
+        finish_reason: finish_reason(ollama_resp.done_reason.as_deref()),
+        ..Generation::new(ollama_resp.response, tokens)
+    })
+}
+
+/// Reads one line of Ollama's newline-delimited stream as the text it adds
+/// and, on the last, why generation ended.
+fn parse_line(line: &str, chat: bool) -> Option<(String, Option<FinishReason>)> {
+    if chat {
+        serde_json::from_str::<ChatResponse>(line).ok().map(|response| {
+            let done = response.done.then(|| finish_reason(response.done_reason.as_deref()));
+            (response.message.content, done)
+        })
+    } else {
+        serde_json::from_str::<GenerateResponse>(line).ok().map(|response| {
+            let done = response.done.then(|| finish_reason(response.done_reason.as_deref()));
+            (response.response, done)
+        })
+    }
+}
+
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match request(&call, true).send().await {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Ollama stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            yield Err(std::io::Error::other(format!("Ollama API error: {}", response.status())));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Ollama read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                if let Some((token, done)) = parse_line(&line, call.chat) {
+                    let stream_token = StreamToken {
+                        token,
+                        token_id,
+                        complete: done.is_some(),
+                        request_id: call.request_id.clone(),
+                        finish_reason: done,
+                    };
+                    call.outcome.record(&stream_token);
+                    token_id += 1;
+
+                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                        yield Ok(Event::default().event("token").data(json_data));
+                    }
+
+                    if done.is_some() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::v1::inference::{
+    BackendRequest, ChatMessage, FinishReason, Generation, SamplingParams, StreamOutcome, StreamToken, ToolCall,
+    ToolDefinition,
+};
+
+/// A chat completions request in OpenAI's format, as accepted by hosted
+/// backends that copy it. Backend-specific fields go in `extra`.
+#[derive(Serialize)]
+pub struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    max_tokens: u32,
+    temperature: f32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<RequestToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RequestToolCall {
+    id: String,
+    r#type: &'static str,
+    function: RequestFunction,
+}
+
+#[derive(Serialize)]
+struct RequestFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    r#type: &'static str,
+    function: FunctionDefinition,
+}
+
+#[derive(Serialize)]
+struct FunctionDefinition {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct ChatResponse {
+    choices: Vec<Choice>,
+    pub usage: Usage,
+    /// The model that actually served the request, which routers may pick.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    /// Reasoning returned apart from the answer, by DeepSeek and xAI.
+    #[serde(default)]
+    reasoning_content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ResponseToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct ResponseToolCall {
+    id: String,
+    function: ResponseFunction,
+}
+
+#[derive(Deserialize)]
+struct ResponseFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Deserialize)]
+pub struct Usage {
+    pub completion_tokens: u32,
+    /// Billed cost in USD, reported by OpenRouter.
+    #[serde(default)]
+    pub cost: Option<f64>,
+}
+
+fn to_messages(messages: Vec<ChatMessage>) -> Vec<Message> {
+    messages
+        .into_iter()
+        .map(|message| Message {
+            role: message.role,
+            content: message.content,
+            tool_calls: message
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|call| RequestToolCall {
+                    id: call.id,
+                    r#type: "function",
+                    function: RequestFunction {
+                        name: call.name,
+                        arguments: call.arguments.to_string(),
+                    },
+                })
+                .collect(),
+            tool_call_id: message.tool_call_id,
+        })
+        .collect()
+}
+
+fn to_tools(tools: Vec<ToolDefinition>) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|tool| Tool {
+            r#type: "function",
+            function: FunctionDefinition {
+                name: tool.name,
+                description: tool.description,
+                parameters: tool.parameters,
+            },
+        })
+        .collect()
+}
+
+/// Sets `key` in a request's `extra` fields when `value` is present.
+pub fn insert<T: Serialize>(extra: &mut serde_json::Map<String, serde_json::Value>, key: &str, value: &Option<T>) {
+    if let Some(value) = value
+        && let Ok(value) = serde_json::to_value(value)
+    {
+        extra.insert(key.to_string(), value);
+    }
+}
+
+/// The request with the sampling parameters OpenAI itself defines.
+pub fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let sampling = &call.sampling;
+    let mut extra = serde_json::Map::new();
+    insert(&mut extra, "top_p", &sampling.top_p);
+    insert(&mut extra, "presence_penalty", &sampling.presence_penalty);
+    insert(&mut extra, "frequency_penalty", &sampling.frequency_penalty);
+    insert(&mut extra, "stop", &sampling.stop);
+    insert(&mut extra, "seed", &sampling.seed);
+    insert(&mut extra, "logit_bias", &sampling.logit_bias);
+
+    ChatRequest {
+        model: call.model.clone(),
+        messages: to_messages(call.messages.clone()),
+        max_tokens: call.max_tokens,
+        temperature: call.temperature,
+        stream,
+        tools: to_tools(call.tools.clone()),
+        extra,
+    }
+}
+
+/// Adds the sampling parameters that open-model hosts accept on top of
+/// OpenAI's: `top_k`, `min_p` and `repetition_penalty`.
+pub fn add_extended_sampling(body: &mut ChatRequest, sampling: &SamplingParams) {
+    insert(&mut body.extra, "top_k", &sampling.top_k);
+    insert(&mut body.extra, "min_p", &sampling.min_p);
+    insert(&mut body.extra, "repetition_penalty", &sampling.repetition_penalty);
+}
+
+/// Reads OpenAI's `finish_reason`. Hosts that copy the API mostly keep its
+/// values; OpenRouter adds `error` and Mistral `model_length`.
+pub fn finish_reason(reason: &str) -> FinishReason {
+    match reason {
+        "length" | "model_length" => FinishReason::Length,
+        "tool_calls" | "function_call" => FinishReason::ToolCalls,
+        "content_filter" => FinishReason::ContentFilter,
+        "error" => FinishReason::Error,
+        _ => FinishReason::Stop,
+    }
+}
+
+impl ChatResponse {
+    /// The first choice's text and tool calls. `name` labels the error when
+    /// there is no choice.
+    pub fn into_generation(self, name: &str) -> Result<Generation, String> {
+        let tokens = self.usage.completion_tokens;
+        let choice = self
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("{} returned no choices", name))?;
+        let message = choice.message;
+
+        let tool_calls = message
+            .tool_calls
+            .unwrap_or_default()
+            .into_iter()
+            .map(|call| ToolCall {
+                id: call.id,
+                name: call.function.name,
+                arguments: serde_json::from_str(&call.function.arguments)
+                    .unwrap_or(serde_json::Value::String(call.function.arguments)),
+            })
+            .collect();
+
+        Ok(Generation {
+            tool_calls,
+            reasoning: message.reasoning_content.filter(|reasoning| !reasoning.is_empty()),
+            finish_reason: choice.finish_reason.as_deref().map(finish_reason).unwrap_or_default(),
+            ..Generation::new(message.content.unwrap_or_default(), tokens)
+        })
+    }
+}
+
+/// Turns a successful streaming response into `token` events, and reasoning
+/// deltas into `reasoning` events. Tool calls are only returned by
+/// non-streaming requests.
+pub fn stream_tokens(
+    name: &'static str,
+    response: reqwest::Response,
+    request_id: String,
+    outcome: StreamOutcome,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream_chunks(name, response.bytes_stream(), request_id, outcome)
+}
+
+/// Like [`stream_tokens`], for server-sent events that arrive wrapped in
+/// another framing, such as SageMaker's response streams.
+pub fn stream_chunks<B: AsRef<[u8]>, E: std::fmt::Display>(
+    name: &'static str,
+    chunks: impl Stream<Item = Result<B, E>>,
+    request_id: String,
+    outcome: StreamOutcome,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let mut byte_stream = Box::pin(chunks);
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("{} read error: {}", name, e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(chunk.as_ref());
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data: ") else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    return;
+                }
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else {
+                    continue;
+                };
+                if let Some(message) = chunk["error"]["message"].as_str() {
+                    yield Err(std::io::Error::other(format!("{} stream error: {}", name, message)));
+                    return;
+                }
+                let choice = &chunk["choices"][0];
+                if let Some(reasoning) = choice["delta"]["reasoning_content"].as_str()
+                    && !reasoning.is_empty()
+                {
+                    let stream_token = StreamToken {
+                        token: reasoning.to_string(),
+                        token_id,
+                        complete: false,
+                        request_id: request_id.clone(),
+                        finish_reason: None,
+                    };
+                    token_id += 1;
+
+                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                        yield Ok(Event::default().event("reasoning").data(json_data));
+                    }
+                }
+
+                let text = choice["delta"]["content"].as_str().unwrap_or("");
+                let finish = choice["finish_reason"].as_str().map(finish_reason);
+
+                if text.is_empty() && finish.is_none() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete: finish.is_some(),
+                    request_id: request_id.clone(),
+                    finish_reason: finish,
+                };
+                outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if finish.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+}
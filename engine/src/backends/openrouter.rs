@@ -0,0 +1,118 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde_json::json;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation, UpstreamMetadata};
+use super::openai_compat::{self, ChatRequest, ChatResponse};
+
+pub const DEFAULT_URL: &str = "https://openrouter.ai/api/v1";
+
+/// OpenRouter's `provider` preferences from `OPENROUTER_PROVIDER_ORDER`
+/// (comma-separated provider names, tried in order) and
+/// `OPENROUTER_ALLOW_FALLBACKS`. `None` leaves routing to OpenRouter.
+fn provider_preferences() -> Option<serde_json::Value> {
+    let order: Vec<String> = config::var("OPENROUTER_PROVIDER_ORDER")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    let allow_fallbacks = config::var("OPENROUTER_ALLOW_FALLBACKS")
+        .ok()
+        .map(|value| value.trim().eq_ignore_ascii_case("true") || value.trim() == "1");
+
+    let mut preferences = serde_json::Map::new();
+    if !order.is_empty() {
+        preferences.insert("order".to_string(), json!(order));
+    }
+    if let Some(allow_fallbacks) = allow_fallbacks {
+        preferences.insert("allow_fallbacks".to_string(), json!(allow_fallbacks));
+    }
+    (!preferences.is_empty()).then_some(serde_json::Value::Object(preferences))
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> ChatRequest {
+    let mut body = openai_compat::request_body(call, stream);
+    openai_compat::add_extended_sampling(&mut body, &call.sampling);
+    if let Some(preferences) = provider_preferences() {
+        body.extra.insert("provider".to_string(), preferences);
+    }
+    // Asks OpenRouter to report the billed cost in `usage`.
+    body.extra.insert("usage".to_string(), json!({ "include": true }));
+    body
+}
+
+/// Adds the bearer token every OpenRouter call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("OPENROUTER_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("OPENROUTER_API_KEY").is_err() {
+        return Err("OPENROUTER_API_KEY not set. Set OPENROUTER_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("OpenRouter request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("OpenRouter API error: {} - {}", status, error_text));
+    }
+
+    let resp: ChatResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse OpenRouter response: {}", e))?;
+
+    let upstream = UpstreamMetadata {
+        model: resp.model.clone(),
+        provider: resp.provider.clone(),
+        cost_usd: resp.usage.cost,
+    };
+    let generation = resp.into_generation("OpenRouter")?;
+    Ok(Generation {
+        upstream: Some(upstream),
+        ..generation
+    })
+}
+
+/// Streams content deltas as `token` events. The upstream model and cost are
+/// only reported for non-streaming requests.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("OpenRouter stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("OpenRouter API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("OpenRouter", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
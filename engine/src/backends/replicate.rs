@@ -0,0 +1,316 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, FinishReason, Generation, StreamToken};
+
+pub const DEFAULT_URL: &str = "https://api.replicate.com";
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Serialize)]
+struct PredictionRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    input: PredictionInput,
+    stream: bool,
+}
+
+/// The input most Replicate language models accept. Models ignore inputs
+/// they do not define.
+#[derive(Serialize)]
+struct PredictionInput {
+    prompt: String,
+    max_tokens: u32,
+    max_new_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Prediction {
+    id: String,
+    status: String,
+    #[serde(default)]
+    output: serde_json::Value,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+    #[serde(default)]
+    metrics: Metrics,
+    urls: PredictionUrls,
+}
+
+#[derive(Deserialize, Default)]
+struct Metrics {
+    #[serde(default)]
+    output_token_count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct PredictionUrls {
+    #[serde(default)]
+    stream: Option<String>,
+}
+
+/// `REPLICATE_POLL_INTERVAL_MS` and `REPLICATE_TIMEOUT_SECS`.
+fn polling() -> (Duration, Duration) {
+    let read = |var: &str| config::var(var).ok().and_then(|value| value.parse::<u64>().ok());
+    (
+        read("REPLICATE_POLL_INTERVAL_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_POLL_INTERVAL),
+        read("REPLICATE_TIMEOUT_SECS")
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TIMEOUT),
+    )
+}
+
+/// Official models are addressed as `owner/name` and run their latest
+/// version; `owner/name:version` pins a version.
+fn predictions_url(base_url: &str, model: &str) -> (String, Option<String>) {
+    match model.split_once(':') {
+        Some((_, version)) => (format!("{}/v1/predictions", base_url), Some(version.to_string())),
+        None => (format!("{}/v1/models/{}/predictions", base_url, model), None),
+    }
+}
+
+/// Language models return their output as a list of chunks.
+fn output_text(output: &serde_json::Value) -> String {
+    match output {
+        serde_json::Value::Array(parts) => parts.iter().filter_map(|p| p.as_str()).collect(),
+        serde_json::Value::String(text) => text.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Adds the bearer token every Replicate call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_token = config::var("REPLICATE_API_TOKEN").unwrap_or_default();
+    request.bearer_auth(api_token)
+}
+
+async fn create_prediction(call: &BackendRequest, stream: bool) -> Result<Prediction, String> {
+    if config::var("REPLICATE_API_TOKEN").is_err() {
+        return Err("REPLICATE_API_TOKEN not set. Set REPLICATE_API_TOKEN environment variable.".to_string());
+    }
+
+    let (url, version) = predictions_url(&call.base_url, &call.model);
+    let body = PredictionRequest {
+        version,
+        input: PredictionInput {
+            prompt: call.prompt.clone(),
+            max_tokens: call.max_tokens,
+            max_new_tokens: call.max_tokens,
+            temperature: call.temperature,
+            top_p: call.sampling.top_p,
+            top_k: call.sampling.top_k,
+            seed: call.sampling.seed,
+        },
+        stream,
+    };
+
+    let response = authorize(call.client.post(url))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Replicate request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Replicate API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Replicate response: {}", e))
+}
+
+/// Best effort, so an abandoned prediction stops running up charges.
+async fn cancel(call: &BackendRequest, prediction: &Prediction) {
+    let url = format!("{}/v1/predictions/{}/cancel", call.base_url, prediction.id);
+    if let Err(e) = authorize(call.client.post(url)).send().await {
+        tracing::warn!("Failed to cancel Replicate prediction: {}", e);
+    }
+}
+
+/// Creates a prediction and polls it until it finishes, so callers see an
+/// ordinary synchronous generation. Predictions still running after
+/// `REPLICATE_TIMEOUT_SECS` are canceled.
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let (interval, timeout) = polling();
+    let started = Instant::now();
+    let mut prediction = create_prediction(&call, false).await?;
+
+    loop {
+        match prediction.status.as_str() {
+            "succeeded" => {
+                let text = output_text(&prediction.output);
+                let tokens = prediction
+                    .metrics
+                    .output_token_count
+                    .unwrap_or_else(|| text.split_whitespace().count() as u32);
+                return Ok(Generation::new(text, tokens));
+            }
+            "failed" | "canceled" => {
+                let reason = prediction
+                    .error
+                    .map(|e| e.as_str().map(str::to_string).unwrap_or_else(|| e.to_string()))
+                    .unwrap_or_else(|| prediction.status.clone());
+                return Err(format!("Replicate prediction {}: {}", prediction.status, reason));
+            }
+            _ => {}
+        }
+
+        if started.elapsed() > timeout {
+            cancel(&call, &prediction).await;
+            return Err(format!("Replicate prediction timed out after {}s", timeout.as_secs()));
+        }
+        tokio::time::sleep(interval).await;
+
+        let url = format!("{}/v1/predictions/{}", call.base_url, prediction.id);
+        let response = authorize(call.client.get(url))
+            .send()
+            .await
+            .map_err(|e| format!("Replicate request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Replicate API error: {} - {}", status, error_text));
+        }
+        prediction = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Replicate response: {}", e))?;
+    }
+}
+
+/// Creates a streaming prediction and relays its `output` events as `token`
+/// events. Replicate sends a `done` event when the prediction ends, with a
+/// `reason` if it was canceled or failed.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let prediction = match create_prediction(&call, true).await {
+            Ok(p) => p,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+        let Some(stream_url) = prediction.urls.stream.clone() else {
+            yield Err(std::io::Error::other("Replicate model does not support streaming"));
+            return;
+        };
+
+        let response = match authorize(call.client.get(&stream_url))
+            .header("accept", "text/event-stream")
+            .header("cache-control", "no-store")
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Replicate stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            yield Err(std::io::Error::other(format!("Replicate API error: {}", response.status())));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+        let mut event_name = String::new();
+        let mut data: Vec<String> = Vec::new();
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Replicate read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).trim_end_matches('\r').to_string();
+                buffer.drain(..=pos);
+
+                // Output chunks can contain newlines, which arrive as several
+                // `data:` lines of one event, so events are only handled at
+                // the blank line that ends them.
+                if let Some(name) = line.strip_prefix("event:") {
+                    event_name = name.trim().to_string();
+                    continue;
+                }
+                if let Some(value) = line.strip_prefix("data:") {
+                    data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+                    continue;
+                }
+                if !line.is_empty() {
+                    continue;
+                }
+
+                let payload = data.join("\n");
+                data.clear();
+                let (text, complete) = match std::mem::take(&mut event_name).as_str() {
+                    "output" => (payload, false),
+                    "done" => {
+                        let reason = serde_json::from_str::<serde_json::Value>(&payload)
+                            .ok()
+                            .and_then(|done| done["reason"].as_str().map(str::to_string));
+                        if let Some(reason) = reason {
+                            yield Err(std::io::Error::other(format!("Replicate prediction {}", reason)));
+                            return;
+                        }
+                        (String::new(), true)
+                    }
+                    "error" => {
+                        yield Err(std::io::Error::other(format!("Replicate stream error: {}", payload)));
+                        return;
+                    }
+                    _ => continue,
+                };
+
+                if text.is_empty() && !complete {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text,
+                    token_id,
+                    complete,
+                    request_id: call.request_id.clone(),
+                    finish_reason: complete.then_some(FinishReason::Stop),
+                };
+                call.outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if complete {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::eventstream;
+use super::openai_compat::{self, ChatResponse};
+use super::sigv4::{self, AwsCredentials};
+use super::tgi;
+
+const SERVICE: &str = "sagemaker";
+
+/// Routes a request on an endpoint hosting several inference components.
+const INFERENCE_COMPONENT_HEADER: &str = "x-amzn-sagemaker-inference-component";
+
+/// The regional SageMaker runtime endpoint, unless `SAGEMAKER_URL` overrides
+/// it (e.g. for a VPC endpoint).
+pub fn default_url() -> String {
+    format!("https://runtime.sagemaker.{}.amazonaws.com", sigv4::region())
+}
+
+/// What the endpoint's container expects, from `SAGEMAKER_PAYLOAD`.
+enum Payload {
+    /// `{"inputs", "parameters"}`, as taken by the Hugging Face TGI and LMI
+    /// containers. The default.
+    HuggingFace,
+    /// OpenAI chat completions, as taken by containers with a Messages API.
+    OpenAI,
+}
+
+impl Payload {
+    fn from_env() -> Result<Self, String> {
+        match config::var("SAGEMAKER_PAYLOAD").as_deref() {
+            Err(_) | Ok("huggingface") => Ok(Self::HuggingFace),
+            Ok("openai") => Ok(Self::OpenAI),
+            Ok(other) => Err(format!(
+                "Unknown SAGEMAKER_PAYLOAD '{}'; expected huggingface or openai",
+                other
+            )),
+        }
+    }
+}
+
+/// A Hugging Face request, which the containers stream when `stream` is set.
+#[derive(Serialize)]
+struct HuggingFaceRequest {
+    #[serde(flatten)]
+    body: tgi::GenerateRequest,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+/// Builds a signed invocation. The model id is the endpoint name, optionally
+/// followed by `/<inference component>`. `action` is `invocations` or
+/// `invocations-response-stream`.
+fn signed_request(call: &BackendRequest, payload: &Payload, action: &str) -> Result<reqwest::RequestBuilder, String> {
+    let credentials = AwsCredentials::from_env()?;
+    let stream = action != "invocations";
+    let body = match payload {
+        Payload::HuggingFace => serde_json::to_vec(&HuggingFaceRequest {
+            body: tgi::request_body(call),
+            stream,
+        }),
+        Payload::OpenAI => serde_json::to_vec(&openai_compat::request_body(call, stream)),
+    }
+    .map_err(|e| format!("Failed to encode SageMaker request: {}", e))?;
+
+    let (endpoint, component) = match call.model.split_once('/') {
+        Some((endpoint, component)) => (endpoint, Some(component)),
+        None => (call.model.as_str(), None),
+    };
+    let url = format!("{}/endpoints/{}/{}", call.base_url, sigv4::uri_encode(endpoint), action);
+    let url = reqwest::Url::parse(&url).map_err(|e| format!("Invalid SageMaker URL {}: {}", url, e))?;
+    let signature = sigv4::sign(&credentials, SERVICE, &sigv4::region(), "POST", &url, &body, chrono::Utc::now());
+
+    let mut request = call
+        .client
+        .post(url)
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .header("content-type", "application/json")
+        .body(body);
+    if let Some(component) = component {
+        request = request.header(INFERENCE_COMPONENT_HEADER, component);
+    }
+    for (name, value) in signature {
+        request = request.header(name, value);
+    }
+    Ok(request)
+}
+
+/// Reads the generated text from a Hugging Face container's response, which
+/// TGI wraps in a list and LMI does not.
+fn huggingface_generation(body: serde_json::Value) -> Result<Generation, String> {
+    let result = match body {
+        serde_json::Value::Array(mut results) if !results.is_empty() => results.swap_remove(0),
+        other => other,
+    };
+    let text = result["generated_text"]
+        .as_str()
+        .ok_or("SageMaker response has no generated_text")?
+        .to_string();
+    let tokens = result["details"]["generated_tokens"]
+        .as_u64()
+        .map(|tokens| tokens as u32)
+        .unwrap_or_else(|| text.split_whitespace().count() as u32);
+    Ok(Generation {
+        finish_reason: result["details"]["finish_reason"]
+            .as_str()
+            .map(tgi::finish_reason)
+            .unwrap_or_default(),
+        ..Generation::new(text, tokens)
+    })
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let payload = Payload::from_env()?;
+    let response = signed_request(&call, &payload, "invocations")?
+        .send()
+        .await
+        .map_err(|e| format!("SageMaker request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("SageMaker API error: {} - {}", status, error_text));
+    }
+
+    match payload {
+        Payload::HuggingFace => huggingface_generation(
+            response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse SageMaker response: {}", e))?,
+        ),
+        Payload::OpenAI => response
+            .json::<ChatResponse>()
+            .await
+            .map_err(|e| format!("Failed to parse SageMaker response: {}", e))?
+            .into_generation("SageMaker"),
+    }
+}
+
+/// Unwraps the `PayloadPart` frames of a response stream into the bytes the
+/// container wrote, ending with an error if SageMaker reports one.
+fn payload_parts(response: reqwest::Response) -> impl Stream<Item = Result<Vec<u8>, String>> {
+    stream! {
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(e.to_string());
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            loop {
+                let frame = match eventstream::next_frame(&mut buffer) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+
+                if frame.message_type != "event" {
+                    let payload: serde_json::Value = serde_json::from_slice(&frame.payload).unwrap_or_default();
+                    let message = payload["Message"]
+                        .as_str()
+                        .or(payload["message"].as_str())
+                        .unwrap_or("unknown error");
+                    yield Err(format!("{}: {}", frame.event_type, message));
+                    return;
+                }
+                if frame.event_type == "PayloadPart" {
+                    yield Ok(frame.payload);
+                }
+            }
+        }
+    }
+}
+
+/// Streams through `InvokeEndpointWithResponseStream`, which carries the
+/// container's own token stream in event stream frames.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let payload = match Payload::from_env() {
+            Ok(payload) => payload,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+        let request = match signed_request(&call, &payload, "invocations-response-stream") {
+            Ok(request) => request,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+        let response = match request.send().await {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("SageMaker stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("SageMaker API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let parts = payload_parts(response);
+        let mut tokens = match payload {
+            Payload::HuggingFace => tgi::stream_chunks("SageMaker", parts, call.request_id.clone(), call.outcome.clone()).boxed(),
+            Payload::OpenAI => openai_compat::stream_chunks("SageMaker", parts, call.request_id.clone(), call.outcome.clone()).boxed(),
+        };
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
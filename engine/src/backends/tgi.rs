@@ -0,0 +1,236 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, FinishReason, Generation, StreamOutcome, StreamToken};
+
+/// The port `text-generation-launcher` listens on by default.
+pub const DEFAULT_URL: &str = "http://localhost:3000";
+
+/// A text-generation-inference request, which the Hugging Face containers
+/// on SageMaker and Vertex AI take too.
+#[derive(Serialize)]
+pub struct GenerateRequest {
+    inputs: String,
+    parameters: Parameters,
+}
+
+#[derive(Serialize)]
+struct Parameters {
+    max_new_tokens: u32,
+    /// TGI rejects a temperature of zero, so greedy decoding is asked for by
+    /// leaving it out and turning sampling off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    do_sample: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repetition_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    return_full_text: bool,
+    details: bool,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    generated_text: String,
+    #[serde(default)]
+    details: Option<Details>,
+}
+
+#[derive(Deserialize)]
+struct Details {
+    generated_tokens: u32,
+    finish_reason: String,
+}
+
+/// Reads TGI's `finish_reason`: `length`, `eos_token` or `stop_sequence`.
+pub fn finish_reason(reason: &str) -> FinishReason {
+    match reason {
+        "length" => FinishReason::Length,
+        _ => FinishReason::Stop,
+    }
+}
+
+pub fn request_body(call: &BackendRequest) -> GenerateRequest {
+    let sampling = &call.sampling;
+    let sample = call.temperature > 0.0;
+    GenerateRequest {
+        inputs: call.prompt.clone(),
+        parameters: Parameters {
+            max_new_tokens: call.max_tokens,
+            temperature: sample.then_some(call.temperature),
+            do_sample: sample,
+            top_p: sampling.top_p,
+            top_k: sampling.top_k,
+            repetition_penalty: sampling.repetition_penalty,
+            frequency_penalty: sampling.frequency_penalty,
+            stop: sampling.stop.clone(),
+            seed: sampling.seed,
+            return_full_text: false,
+            details: true,
+        },
+    }
+}
+
+/// Adds `TGI_API_KEY` as a bearer token when set, as Inference Endpoints
+/// require. Self-hosted servers usually take none.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match config::var("TGI_API_KEY") {
+        Ok(api_key) => request.bearer_auth(api_key),
+        Err(_) => request,
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = authorize(call.client.post(format!("{}/generate", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call))
+        .send()
+        .await
+        .map_err(|e| format!("TGI request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("TGI API error: {} - {}", status, error_text));
+    }
+
+    let resp: GenerateResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse TGI response: {}", e))?;
+
+    let Some(details) = resp.details else {
+        let tokens = resp.generated_text.split_whitespace().count() as u32;
+        return Ok(Generation::new(resp.generated_text, tokens));
+    };
+    Ok(Generation {
+        finish_reason: finish_reason(&details.finish_reason),
+        ..Generation::new(resp.generated_text, details.generated_tokens)
+    })
+}
+
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(format!("{}/generate_stream", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("TGI stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("TGI API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(stream_tokens("TGI", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
+
+/// Turns text-generation-inference's token stream into `token` events.
+/// Special tokens such as end-of-sequence are not passed on. The last event
+/// carries `details`, which marks the token as complete. `name` labels errors,
+/// since the Hugging Face Inference API and SageMaker stream in the same
+/// format.
+pub fn stream_tokens(
+    name: &'static str,
+    response: reqwest::Response,
+    request_id: String,
+    outcome: StreamOutcome,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream_chunks(name, response.bytes_stream(), request_id, outcome)
+}
+
+/// Like [`stream_tokens`], for a token stream that arrives wrapped in another
+/// framing.
+pub fn stream_chunks<B: AsRef<[u8]>, E: std::fmt::Display>(
+    name: &'static str,
+    chunks: impl Stream<Item = Result<B, E>>,
+    request_id: String,
+    outcome: StreamOutcome,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let mut byte_stream = Box::pin(chunks);
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("{} read error: {}", name, e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(chunk.as_ref());
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data.trim_start()) else {
+                    continue;
+                };
+                if let Some(message) = chunk["error"].as_str() {
+                    yield Err(std::io::Error::other(format!("{} stream error: {}", name, message)));
+                    return;
+                }
+
+                let special = chunk["token"]["special"].as_bool().unwrap_or(false);
+                let text = if special { "" } else { chunk["token"]["text"].as_str().unwrap_or("") };
+                let finish = (!chunk["details"].is_null())
+                    .then(|| chunk["details"]["finish_reason"].as_str().map(finish_reason).unwrap_or_default());
+
+                if text.is_empty() && finish.is_none() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete: finish.is_some(),
+                    request_id: request_id.clone(),
+                    finish_reason: finish,
+                };
+                outcome.record(&stream_token);
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+
+                if finish.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+}
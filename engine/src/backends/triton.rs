@@ -0,0 +1,192 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, FinishReason, Generation, StreamToken};
+
+/// Triton's HTTP port.
+pub const DEFAULT_URL: &str = "http://localhost:8000";
+
+/// A request to Triton's generate extension. The fields are the inputs of
+/// the TensorRT-LLM `ensemble` and `tensorrt_llm_bls` models; Triton passes
+/// any a model does not declare as parameters, which it ignores.
+#[derive(Serialize)]
+struct GenerateRequest {
+    text_input: String,
+    max_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repetition_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_words: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    random_seed: Option<u64>,
+    stream: bool,
+    /// Without this TensorRT-LLM echoes the prompt before the output.
+    exclude_input_in_output: bool,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    text_output: String,
+}
+
+fn request_body(call: &BackendRequest, stream: bool) -> GenerateRequest {
+    let sampling = &call.sampling;
+    GenerateRequest {
+        text_input: call.prompt.clone(),
+        max_tokens: call.max_tokens,
+        temperature: call.temperature,
+        top_p: sampling.top_p,
+        // TensorRT-LLM samples greedily when top_k is 1.
+        top_k: if call.temperature > 0.0 { sampling.top_k } else { Some(1) },
+        repetition_penalty: sampling.repetition_penalty,
+        presence_penalty: sampling.presence_penalty,
+        frequency_penalty: sampling.frequency_penalty,
+        stop_words: sampling.stop.clone(),
+        random_seed: sampling.seed,
+        stream,
+        exclude_input_in_output: true,
+    }
+}
+
+/// The URL of one of the model's generate endpoints. The model id is the
+/// Triton model name, optionally followed by `/<version>` to pin a version.
+fn endpoint(call: &BackendRequest, method: &str) -> String {
+    match call.model.split_once('/') {
+        Some((name, version)) => format!("{}/v2/models/{}/versions/{}/{}", call.base_url, name, version, method),
+        None => format!("{}/v2/models/{}/{}", call.base_url, call.model, method),
+    }
+}
+
+/// Adds `TRITON_API_KEY` as a bearer token when set, for servers behind an
+/// authenticating gateway. Triton itself takes none.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match config::var("TRITON_API_KEY") {
+        Ok(api_key) => request.bearer_auth(api_key),
+        Err(_) => request,
+    }
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let response = authorize(call.client.post(endpoint(&call, "generate")))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("Triton request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Triton API error: {} - {}", status, error_text));
+    }
+
+    let resp: GenerateResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Triton response: {}", e))?;
+
+    let tokens = resp.text_output.split_whitespace().count() as u32;
+    Ok(Generation::new(resp.text_output, tokens))
+}
+
+/// Streams from `generate_stream`, where each event carries the text of the
+/// newly generated tokens in `text_output`. Triton closes the stream when the
+/// model is done, so the complete token is sent then.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let response = match authorize(call.client.post(endpoint(&call, "generate_stream")))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("Triton stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("Triton API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut byte_stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut token_id = 0u32;
+
+        while let Some(chunk) = byte_stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    yield Err(std::io::Error::other(format!("Triton read error: {}", e)));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
+                buffer.drain(..=pos);
+
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data.trim_start()) else {
+                    continue;
+                };
+                if let Some(message) = chunk["error"].as_str() {
+                    yield Err(std::io::Error::other(format!("Triton stream error: {}", message)));
+                    return;
+                }
+
+                let text = chunk["text_output"].as_str().unwrap_or("");
+                if text.is_empty() {
+                    continue;
+                }
+
+                let stream_token = StreamToken {
+                    token: text.to_string(),
+                    token_id,
+                    complete: false,
+                    request_id: call.request_id.clone(),
+                    finish_reason: None,
+                };
+                token_id += 1;
+
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+            }
+        }
+
+        let stream_token = StreamToken {
+            token: String::new(),
+            token_id,
+            complete: true,
+            request_id: call.request_id.clone(),
+            finish_reason: Some(FinishReason::Stop),
+        };
+        call.outcome.record(&stream_token);
+        if let Ok(json_data) = serde_json::to_string(&stream_token) {
+            yield Ok(Event::default().event("token").data(json_data));
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::Stream;
+use serde::Serialize;
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation, StreamToken};
+use super::google_auth;
+use super::tgi;
+
+const DEFAULT_LOCATION: &str = "us-central1";
+
+/// `VERTEX_LOCATION`, or `us-central1`.
+fn location() -> String {
+    config::var("VERTEX_LOCATION").unwrap_or_else(|_| DEFAULT_LOCATION.to_string())
+}
+
+/// The regional Vertex AI endpoint, unless `VERTEX_URL` overrides it (e.g.
+/// for Private Service Connect).
+pub fn default_url() -> String {
+    format!("https://{}-aiplatform.googleapis.com", location())
+}
+
+#[derive(Serialize)]
+struct PredictRequest {
+    instances: [Instance; 1],
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Instance {
+    Vllm(VllmInstance),
+    HuggingFace(tgi::GenerateRequest),
+}
+
+/// An instance for the vLLM containers Model Garden deploys.
+#[derive(Serialize)]
+struct VllmInstance {
+    prompt: String,
+    max_tokens: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    /// Without this the prediction repeats the prompt before the output.
+    raw_response: bool,
+}
+
+/// Builds the prediction request. The model id is the endpoint id, and
+/// `VERTEX_PAYLOAD` picks the instance format: `vllm`, the default, or
+/// `huggingface` for the Hugging Face TGI containers.
+fn prediction(call: &BackendRequest) -> Result<PredictRequest, String> {
+    let instance = match config::var("VERTEX_PAYLOAD").as_deref() {
+        Err(_) | Ok("vllm") => Instance::Vllm(VllmInstance {
+            prompt: call.prompt.clone(),
+            max_tokens: call.max_tokens,
+            temperature: call.temperature,
+            top_p: call.sampling.top_p,
+            top_k: call.sampling.top_k,
+            raw_response: true,
+        }),
+        Ok("huggingface") => Instance::HuggingFace(tgi::request_body(call)),
+        Ok(other) => {
+            return Err(format!(
+                "Unknown VERTEX_PAYLOAD '{}'; expected vllm or huggingface",
+                other
+            ))
+        }
+    };
+    Ok(PredictRequest { instances: [instance] })
+}
+
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    let project = config::var("VERTEX_PROJECT").map_err(|_| "VERTEX_PROJECT not set")?;
+    let token = google_auth::access_token(&call.client).await?;
+    let url = format!(
+        "{}/v1/projects/{}/locations/{}/endpoints/{}:predict",
+        call.base_url,
+        project,
+        location(),
+        call.model
+    );
+
+    let response = call
+        .client
+        .post(url)
+        .bearer_auth(token)
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&prediction(&call)?)
+        .send()
+        .await
+        .map_err(|e| format!("Vertex AI request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Vertex AI API error: {} - {}", status, error_text));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Vertex AI response: {}", e))?;
+
+    // vLLM predicts a string, TGI an object like its own responses.
+    let prediction = &body["predictions"][0];
+    let text = prediction
+        .as_str()
+        .or(prediction["generated_text"].as_str())
+        .ok_or("Vertex AI response has no prediction text")?
+        .to_string();
+    let tokens = prediction["details"]["generated_tokens"]
+        .as_u64()
+        .map(|tokens| tokens as u32)
+        .unwrap_or_else(|| text.split_whitespace().count() as u32);
+    Ok(Generation {
+        finish_reason: prediction["details"]["finish_reason"]
+            .as_str()
+            .map(tgi::finish_reason)
+            .unwrap_or_default(),
+        ..Generation::new(text, tokens)
+    })
+}
+
+/// Predictions are not streamed, so the whole output arrives as one token.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let request_id = call.request_id.clone();
+        let outcome = call.outcome.clone();
+        let generation = match generate(call).await {
+            Ok(generation) => generation,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
+
+        for (token_id, (token, complete)) in [(generation.text, false), (String::new(), true)].into_iter().enumerate() {
+            let stream_token = StreamToken {
+                token,
+                token_id: token_id as u32,
+                complete,
+                request_id: request_id.clone(),
+                finish_reason: complete.then_some(generation.finish_reason),
+            };
+            outcome.record(&stream_token);
+            if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                yield Ok(Event::default().event("token").data(json_data));
+            }
+        }
+    }
+}
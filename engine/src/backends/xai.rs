@@ -0,0 +1,79 @@
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+
+use super::super::config;
+use super::super::request_id::REQUEST_ID_HEADER;
+use super::super::v1::inference::{BackendRequest, Generation};
+use super::openai_compat::{self, ChatResponse};
+
+pub const DEFAULT_URL: &str = "https://api.x.ai/v1";
+
+/// Adds the bearer token every xAI call needs.
+pub fn authorize(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let api_key = config::var("XAI_API_KEY").unwrap_or_default();
+    request.bearer_auth(api_key)
+}
+
+/// Grok reasoning models such as `grok-3-mini` return their thinking in
+/// `reasoning_content`, which ends up in the response's `reasoning`.
+pub async fn generate(call: BackendRequest) -> Result<Generation, String> {
+    if config::var("XAI_API_KEY").is_err() {
+        return Err("XAI_API_KEY not set. Set XAI_API_KEY environment variable.".to_string());
+    }
+
+    let response = authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(&openai_compat::request_body(&call, false))
+        .send()
+        .await
+        .map_err(|e| format!("xAI request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("xAI API error: {} - {}", status, error_text));
+    }
+
+    response
+        .json::<ChatResponse>()
+        .await
+        .map_err(|e| format!("Failed to parse xAI response: {}", e))?
+        .into_generation("xAI")
+}
+
+/// Streams content deltas as `token` events and reasoning deltas as
+/// `reasoning` events.
+pub fn stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        if config::var("XAI_API_KEY").is_err() {
+            yield Err(std::io::Error::other("XAI_API_KEY not set. Set XAI_API_KEY environment variable."));
+            return;
+        }
+
+        let response = match authorize(call.client.post(format!("{}/chat/completions", call.base_url)))
+            .header(REQUEST_ID_HEADER, &call.request_id)
+            .json(&openai_compat::request_body(&call, true))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("xAI stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("xAI API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let mut tokens = Box::pin(openai_compat::stream_tokens("xAI", response, call.request_id.clone(), call.outcome.clone()));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::any,
+    Router,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use super::backend_client::BackendClients;
+use super::v1::inference::get_backend_url;
+use super::InferenceBackend;
+
+/// Largest backend request or response the proxy will buffer.
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Headers that are never forwarded: hop-by-hop headers and ones reqwest
+/// sets itself.
+const SKIPPED_HEADERS: [HeaderName; 4] = [
+    header::HOST,
+    header::CONTENT_LENGTH,
+    header::CONNECTION,
+    header::TRANSFER_ENCODING,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CassetteMode {
+    /// Forward backend traffic and save every exchange.
+    Record,
+    /// Answer backend requests from saved exchanges only.
+    Replay,
+}
+
+/// Proxy addresses that replace the configured backend URLs while recording
+/// or replaying.
+static PROXY_URLS: OnceLock<HashMap<InferenceBackend, String>> = OnceLock::new();
+
+/// The proxy URL for `backend`, when cassettes are in use.
+pub fn proxy_url(backend: &InferenceBackend) -> Option<String> {
+    PROXY_URLS.get().and_then(|urls| urls.get(backend).cloned())
+}
+
+/// One backend exchange. Credentials are not stored: only the method, path
+/// and body identify a request.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cassette {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    body: String,
+}
+
+struct Proxy {
+    mode: CassetteMode,
+    dir: PathBuf,
+    upstreams: HashMap<String, (InferenceBackend, String)>,
+    clients: Arc<BackendClients>,
+}
+
+fn backend_name(backend: &InferenceBackend) -> &'static str {
+    match backend {
+        InferenceBackend::Ollama => "ollama",
+        InferenceBackend::Llama => "llama",
+        InferenceBackend::HuggingFace => "huggingface",
+        InferenceBackend::OpenAI => "openai",
+        InferenceBackend::AzureOpenAI => "azure_openai",
+        InferenceBackend::Anthropic => "anthropic",
+        InferenceBackend::Gemini => "gemini",
+        InferenceBackend::Bedrock => "bedrock",
+        InferenceBackend::SageMaker => "sagemaker",
+        InferenceBackend::Vertex => "vertex",
+        InferenceBackend::Mistral => "mistral",
+        InferenceBackend::Cohere => "cohere",
+        InferenceBackend::Groq => "groq",
+        InferenceBackend::OpenRouter => "openrouter",
+        InferenceBackend::DeepSeek => "deepseek",
+        InferenceBackend::Xai => "xai",
+        InferenceBackend::Together => "together",
+        InferenceBackend::Fireworks => "fireworks",
+        InferenceBackend::Replicate => "replicate",
+        InferenceBackend::Tgi => "tgi",
+        InferenceBackend::LmStudio => "lmstudio",
+        InferenceBackend::KoboldCpp => "koboldcpp",
+        InferenceBackend::Mlx => "mlx",
+        InferenceBackend::Triton => "triton",
+        InferenceBackend::LlamaLocal => "llama_local",
+        InferenceBackend::Candle => "candle",
+        InferenceBackend::Mock => "mock",
+    }
+}
+
+/// JSON bodies are compared with their keys sorted, so field order does not
+/// change which cassette a request matches.
+fn normalize_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) => value.to_string(),
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn cassette_file(backend: &str, method: &Method, path: &str, body: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(path);
+    hasher.update(b"\n");
+    hasher.update(body);
+    let digest: String = hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}.json", backend, digest)
+}
+
+/// Starts the recording or replaying proxy on a local port and routes all
+/// HTTP backends through it.
+pub async fn start(mode: CassetteMode, dir: PathBuf, clients: Arc<BackendClients>) -> std::io::Result<()> {
+    if mode == CassetteMode::Record {
+        tokio::fs::create_dir_all(&dir).await?;
+    }
+
+    // Bedrock and SageMaker are left out: their requests are signed for the
+    // exact host and path, which the proxy would change. Replicate streams
+    // from a separate host named in each prediction, which the proxy cannot
+    // redirect.
+    let backends = [
+        InferenceBackend::Ollama,
+        InferenceBackend::Llama,
+        InferenceBackend::HuggingFace,
+        InferenceBackend::OpenAI,
+        InferenceBackend::AzureOpenAI,
+        InferenceBackend::Anthropic,
+        InferenceBackend::Gemini,
+        InferenceBackend::Mistral,
+        InferenceBackend::Cohere,
+        InferenceBackend::Groq,
+        InferenceBackend::OpenRouter,
+        InferenceBackend::Together,
+        InferenceBackend::Fireworks,
+        InferenceBackend::Tgi,
+        InferenceBackend::LmStudio,
+        InferenceBackend::KoboldCpp,
+        InferenceBackend::Mlx,
+        InferenceBackend::Triton,
+        InferenceBackend::Vertex,
+        InferenceBackend::DeepSeek,
+        InferenceBackend::Xai,
+    ];
+    let upstreams = backends
+        .iter()
+        .map(|b| (backend_name(b).to_string(), (b.clone(), get_backend_url(b))))
+        .collect();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let proxy = Arc::new(Proxy {
+        mode,
+        dir: dir.clone(),
+        upstreams,
+        clients,
+    });
+
+    let app = Router::new()
+        .route("/:backend", any(handle_root))
+        .route("/:backend/*path", any(handle))
+        .with_state(proxy);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!("Cassette proxy stopped: {}", e);
+        }
+    });
+
+    let urls = backends
+        .iter()
+        .map(|b| (b.clone(), format!("http://{}/{}", addr, backend_name(b))))
+        .collect();
+    let _ = PROXY_URLS.set(urls);
+
+    match mode {
+        CassetteMode::Record => tracing::info!("Recording backend traffic to {}", dir.display()),
+        CassetteMode::Replay => tracing::info!("Replaying backend traffic from {}", dir.display()),
+    }
+    Ok(())
+}
+
+async fn handle_root(state: State<Arc<Proxy>>, Path(backend): Path<String>, req: Request) -> Response {
+    exchange(state, backend, String::new(), req).await
+}
+
+async fn handle(state: State<Arc<Proxy>>, Path((backend, path)): Path<(String, String)>, req: Request) -> Response {
+    exchange(state, backend, format!("/{}", path), req).await
+}
+
+async fn exchange(State(proxy): State<Arc<Proxy>>, backend: String, mut path: String, req: Request) -> Response {
+    let Some((inference, upstream)) = proxy.upstreams.get(&backend) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown backend '{}'", backend)).into_response();
+    };
+    if let Some(query) = req.uri().query() {
+        path = format!("{}?{}", path, query);
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Failed to read request: {}", e)).into_response(),
+    };
+    let normalized = normalize_body(&body);
+    let file = proxy
+        .dir
+        .join(cassette_file(&backend, &parts.method, &path, &normalized));
+
+    let result = match proxy.mode {
+        CassetteMode::Replay => replay(&file).await,
+        CassetteMode::Record => {
+            let request = proxy
+                .clients
+                .get(inference)
+                .request(parts.method.clone(), format!("{}{}", upstream, path))
+                .headers(forwarded_headers(&parts.headers))
+                .body(body);
+            record(request, &file, parts.method.as_str(), &path, normalized).await
+        }
+    };
+
+    match result {
+        Ok(recorded) => {
+            let mut response = Response::new(Body::from(recorded.body));
+            *response.status_mut() = StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::BAD_GATEWAY);
+            if let Some(content_type) = recorded.content_type.and_then(|v| HeaderValue::from_str(&v).ok()) {
+                response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+            }
+            response
+        }
+        Err((status, message)) => {
+            tracing::warn!(backend = %backend, path = %path, "{}", message);
+            (status, message).into_response()
+        }
+    }
+}
+
+fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    for name in &SKIPPED_HEADERS {
+        forwarded.remove(name);
+    }
+    forwarded
+}
+
+async fn replay(file: &std::path::Path) -> Result<RecordedResponse, (StatusCode, String)> {
+    let bytes = tokio::fs::read(file).await.map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No recorded response in {}", file.display()),
+        )
+    })?;
+    let cassette: Cassette = serde_json::from_slice(&bytes).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid cassette {}: {}", file.display(), e),
+        )
+    })?;
+    Ok(cassette.response)
+}
+
+/// Forwards a request upstream and saves the exchange. Streaming responses
+/// are buffered whole, so they reach the engine in one piece while recording.
+async fn record(
+    request: reqwest::RequestBuilder,
+    file: &std::path::Path,
+    method: &str,
+    path: &str,
+    body: String,
+) -> Result<RecordedResponse, (StatusCode, String)> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Upstream request failed: {}", e)))?;
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let text = response
+        .text()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to read upstream response: {}", e)))?;
+
+    let cassette = Cassette {
+        request: RecordedRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            body,
+        },
+        response: RecordedResponse {
+            status,
+            content_type,
+            body: text,
+        },
+    };
+    let json = serde_json::to_vec_pretty(&cassette)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to encode cassette: {}", e)))?;
+    let tmp = file.with_extension("tmp");
+    let written = async {
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, file).await
+    };
+    if let Err(e) = written.await {
+        tracing::warn!("Failed to write cassette {}: {}", file.display(), e);
+    }
+
+    Ok(cassette.response)
+}
@@ -0,0 +1,17 @@
+use tower_http::compression::predicate::{And, NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+type CompressWhen = And<And<And<SizeAbove, NotForContentType>, NotForContentType>, Option<NotForContentType>>;
+
+/// Compresses responses with gzip, brotli or zstd, whichever the client
+/// prefers in `Accept-Encoding`. Small bodies and images are sent as is.
+/// Event streams are left alone unless `streams` is set; when compressed,
+/// the encoder is flushed whenever the stream waits for the next event, so
+/// tokens still arrive as they are generated.
+pub fn layer(streams: bool) -> CompressionLayer<CompressWhen> {
+    let predicate = SizeAbove::default()
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and((!streams).then_some(NotForContentType::SSE));
+    CompressionLayer::new().no_deflate().compress_when(predicate)
+}
@@ -0,0 +1,263 @@
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env::VarError;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+
+use super::InferenceBackend;
+
+/// The profile used when none is selected. Its settings also apply under
+/// every other profile, which override them.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The table of a profile that holds environment settings, such as
+/// `OLLAMA_URL`, rather than command-line options.
+const ENV_TABLE: &str = "env";
+
+/// Environment variables the engine reads, by prefix, shown by
+/// `/v1/config`. `OPENLLM_` variables are shown with the options they set.
+const VARIABLE_PREFIXES: &[&str] = &[
+    "ANTHROPIC_", "AWS_", "AZURE_OPENAI_", "BEDROCK_", "CANDLE_", "COHERE_", "DEEPSEEK_", "FIREWORKS_", "GEMINI_",
+    "GOOGLE_", "GROQ_", "HUGGINGFACE_", "INJECTION_", "KOBOLDCPP_", "LLAMA_CPP_", "LLAMA_LOCAL_", "LM_STUDIO_",
+    "MISTRAL_", "MLX_", "MOCK_", "MODERATION_", "OLLAMA_", "OPENAI_", "OPENROUTER_", "PII_", "QDRANT_", "REPLICATE_",
+    "SAGEMAKER_", "TGI_", "TOGETHER_", "TRITON_", "VERTEX_", "XAI_",
+];
+
+/// The prefixes of the environment settings `backend` reads, such as its
+/// URL and API key.
+pub fn backend_prefixes(backend: &InferenceBackend) -> &'static [&'static str] {
+    match backend {
+        InferenceBackend::Ollama => &["OLLAMA_"],
+        InferenceBackend::Llama => &["LLAMA_CPP_"],
+        InferenceBackend::HuggingFace => &["HUGGINGFACE_"],
+        InferenceBackend::OpenAI => &["OPENAI_"],
+        InferenceBackend::AzureOpenAI => &["AZURE_OPENAI_"],
+        InferenceBackend::Anthropic => &["ANTHROPIC_"],
+        InferenceBackend::Gemini => &["GEMINI_", "GOOGLE_"],
+        InferenceBackend::Bedrock => &["BEDROCK_", "AWS_"],
+        InferenceBackend::SageMaker => &["SAGEMAKER_", "AWS_"],
+        InferenceBackend::Vertex => &["VERTEX_", "GOOGLE_"],
+        InferenceBackend::Mistral => &["MISTRAL_"],
+        InferenceBackend::Cohere => &["COHERE_"],
+        InferenceBackend::Groq => &["GROQ_"],
+        InferenceBackend::OpenRouter => &["OPENROUTER_"],
+        InferenceBackend::DeepSeek => &["DEEPSEEK_"],
+        InferenceBackend::Xai => &["XAI_"],
+        InferenceBackend::Together => &["TOGETHER_"],
+        InferenceBackend::Fireworks => &["FIREWORKS_"],
+        InferenceBackend::Replicate => &["REPLICATE_"],
+        InferenceBackend::Tgi => &["TGI_"],
+        InferenceBackend::LmStudio => &["LM_STUDIO_"],
+        InferenceBackend::KoboldCpp => &["KOBOLDCPP_"],
+        InferenceBackend::Mlx => &["MLX_"],
+        InferenceBackend::Triton => &["TRITON_"],
+        InferenceBackend::LlamaLocal => &["LLAMA_LOCAL_"],
+        InferenceBackend::Candle => &["CANDLE_"],
+        InferenceBackend::Mock => &["MOCK_"],
+    }
+}
+
+/// Shown in place of secrets.
+const REDACTED: &str = "[redacted]";
+
+/// The active profile's `env` table, read by `var` for variables the
+/// environment does not set.
+static FILE_VARIABLES: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Reads a setting such as `OLLAMA_URL` from the environment, or else from
+/// the config file, so either can configure the engine.
+pub fn var(name: &str) -> Result<String, VarError> {
+    std::env::var(name).or_else(|e| {
+        FILE_VARIABLES
+            .get()
+            .and_then(|variables| variables.get(name))
+            .cloned()
+            .ok_or(e)
+    })
+}
+
+/// Where an effective setting came from, highest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingSource {
+    Cli,
+    Env,
+    File,
+    Default,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Setting {
+    /// A string, a list for repeatable options, or `null` when unset.
+    /// Secrets are redacted.
+    #[schema(value_type = Object)]
+    pub value: Value,
+    pub source: SettingSource,
+}
+
+/// The settings the engine started with, as reported by `/v1/config`.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct EffectiveConfig {
+    pub profile: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub file: Option<PathBuf>,
+    /// Command-line options, by name, e.g. `max_concurrent_requests`.
+    pub options: BTreeMap<String, Setting>,
+    /// Environment settings, e.g. `OLLAMA_URL`.
+    pub variables: BTreeMap<String, Setting>,
+}
+
+/// Parses the command line over the config file's options. Settings are
+/// layered: built-in defaults, then the config file's `default` profile,
+/// then the selected profile, then environment variables, then the command
+/// line.
+pub fn load<T: CommandFactory + FromArgMatches>() -> Result<(T, EffectiveConfig), String> {
+    let argv: Vec<String> = std::env::args().collect();
+    let file = flag(&argv, "config")
+        .or_else(|| std::env::var("OPENLLM_CONFIG").ok())
+        .map(PathBuf::from);
+    let profile = flag(&argv, "profile")
+        .or_else(|| std::env::var("OPENLLM_PROFILE").ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+    let mut options = match &file {
+        Some(path) => read(path, &profile)?,
+        None if profile == DEFAULT_PROFILE => BTreeMap::new(),
+        None => return Err(format!("--profile {} needs a --config file", profile)),
+    };
+    let file_variables: BTreeMap<String, String> = match options.remove(ENV_TABLE) {
+        None => BTreeMap::new(),
+        Some(Value::Object(table)) => table
+            .into_iter()
+            .map(|(name, value)| Ok((name.clone(), scalar(&value).ok_or_else(|| invalid(&name))?)))
+            .collect::<Result<_, String>>()?,
+        Some(_) => return Err(format!("'{}' must be a table", ENV_TABLE)),
+    };
+
+    let mut command = T::command();
+    let mut from_file = Vec::new();
+    for (name, value) in options {
+        let id = name.replace('-', "_");
+        if !command.get_arguments().any(|arg| arg.get_id() == id.as_str()) || id == "config" || id == "profile" {
+            return Err(format!("Unknown option '{}' in profile '{}'", name, profile));
+        }
+        let values: Vec<String> = match &value {
+            Value::Array(values) => values.iter().map(|v| scalar(v).ok_or_else(|| invalid(&name))).collect::<Result<_, _>>()?,
+            value => vec![scalar(value).ok_or_else(|| invalid(&name))?],
+        };
+        command = command.mut_arg(&id, |arg| arg.default_values(values));
+        from_file.push(id);
+    }
+
+    // Every option, and whether it may be given more than once.
+    let ids: Vec<(String, bool)> = command
+        .get_arguments()
+        .filter(|arg| !matches!(arg.get_action(), ArgAction::Help | ArgAction::Version))
+        .map(|arg| (arg.get_id().to_string(), matches!(arg.get_action(), ArgAction::Append)))
+        .collect();
+    let matches = command.get_matches();
+    let args = T::from_arg_matches(&matches).map_err(|e| e.to_string())?;
+
+    let options = ids
+        .into_iter()
+        .map(|(name, repeatable)| {
+            let source = match matches.value_source(&name) {
+                Some(ValueSource::CommandLine) => SettingSource::Cli,
+                Some(ValueSource::EnvVariable) => SettingSource::Env,
+                _ if from_file.contains(&name) => SettingSource::File,
+                _ => SettingSource::Default,
+            };
+            let values: Vec<String> = matches
+                .get_raw(&name)
+                .into_iter()
+                .flatten()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            let value = match values.len() {
+                0 => Value::Null,
+                _ if secret(&name) => Value::from(REDACTED),
+                1 if !repeatable => Value::from(values[0].clone()),
+                _ => Value::from(values),
+            };
+            (name, Setting { value, source })
+        })
+        .collect();
+
+    let mut variables: BTreeMap<String, Setting> = file_variables
+        .iter()
+        .map(|(name, value)| (name.clone(), setting(name, value, SettingSource::File)))
+        .collect();
+    for (name, value) in std::env::vars() {
+        if VARIABLE_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) || file_variables.contains_key(&name) {
+            variables.insert(name.clone(), setting(&name, &value, SettingSource::Env));
+        }
+    }
+    let _ = FILE_VARIABLES.set(file_variables);
+
+    Ok((args, EffectiveConfig { profile, file, options, variables }))
+}
+
+/// Reads the selected profile of a TOML config file, over its `default`
+/// profile.
+fn read(path: &Path, profile: &str) -> Result<BTreeMap<String, Value>, String> {
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    let figment = Figment::from(Toml::file(path).nested()).select(profile);
+    if profile != DEFAULT_PROFILE && !figment.profiles().any(|p| p == profile) {
+        return Err(format!("{} has no profile '{}'", path.display(), profile));
+    }
+    figment
+        .extract::<BTreeMap<String, Value>>()
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The value of `--name value` or `--name=value`, read before the command
+/// line is parsed so the config file can supply the parser's defaults.
+fn flag(argv: &[String], name: &str) -> Option<String> {
+    let long = format!("--{}", name);
+    let mut argv = argv.iter();
+    while let Some(arg) = argv.next() {
+        if *arg == long {
+            return argv.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&long).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn invalid(name: &str) -> String {
+    format!("'{}' must be a string, number or boolean, or a list of them", name)
+}
+
+/// Whether a setting holds a key, token or password rather than where to
+/// find one.
+fn secret(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    name.ends_with("_KEY") || name.ends_with("_TOKEN") || name.contains("SECRET") || name.contains("PASSWORD")
+}
+
+fn setting(name: &str, value: &str, source: SettingSource) -> Setting {
+    let value = if secret(name) { REDACTED } else { value };
+    Setting {
+        value: Value::from(value),
+        source,
+    }
+}
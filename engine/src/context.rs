@@ -0,0 +1,279 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::auth::Caller;
+use super::error::ApiError;
+use super::templates;
+use super::v1::inference::{count_tokens, generate_internal, ChatMessage, InferenceRequest};
+use super::{AppState, ModelRegistryEntry};
+
+const TRUNCATION_MARKER: &str = "[...]";
+const SUMMARY_MAX_TOKENS: u32 = 256;
+
+/// How to shrink a prompt that does not fit the model's context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationStrategy {
+    /// Drop whole messages, oldest first, keeping system messages.
+    DropOldest,
+    /// Keep the most recent tokens, cutting into the oldest kept message.
+    SlidingWindow,
+    /// Keep the start and end of the conversation and drop the middle.
+    Middle,
+    /// Replace the oldest messages with a summary generated by the model.
+    Summarize,
+}
+
+/// Plain `role: content` rendering of a conversation, used for token
+/// budgeting and for backends without a chat template.
+pub fn render_messages(messages: &[ChatMessage]) -> String {
+    let mut prompt: String = messages
+        .iter()
+        .map(|m| format!("{}: {}\n", m.role, m.content))
+        .collect();
+    prompt.push_str("assistant:");
+    prompt
+}
+
+fn tokens(messages: &[ChatMessage]) -> u32 {
+    count_tokens(&render_messages(messages))
+}
+
+fn is_system(message: &ChatMessage) -> bool {
+    message.role == "system"
+}
+
+fn keep_last_words(text: &str, count: usize) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let start = words.len().saturating_sub(count);
+    words[start..].join(" ")
+}
+
+fn keep_head_and_tail(text: &str, count: usize) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() <= count {
+        return text.to_string();
+    }
+    let head = count / 2;
+    let tail = count - head;
+    format!(
+        "{} {} {}",
+        words[..head].join(" "),
+        TRUNCATION_MARKER,
+        words[words.len() - tail..].join(" ")
+    )
+}
+
+/// Index of the oldest message that may be dropped: never a system message
+/// and never the final message, which carries the current turn.
+fn oldest_droppable(messages: &[ChatMessage]) -> Option<usize> {
+    let last = messages.len().checked_sub(1)?;
+    (0..last).find(|&i| !is_system(&messages[i]))
+}
+
+/// Shortens the last message so the whole conversation fits `budget`.
+fn fit_last_message(messages: &mut [ChatMessage], budget: u32, keep: fn(&str, usize) -> String) {
+    let overflow = tokens(messages).saturating_sub(budget) as usize;
+    if let Some(last) = messages.last_mut()
+        && overflow > 0
+    {
+        let words = last.content.split_whitespace().count();
+        last.content = keep(&last.content, words.saturating_sub(overflow + 1));
+    }
+}
+
+fn drop_oldest(mut messages: Vec<ChatMessage>, budget: u32) -> Vec<ChatMessage> {
+    while tokens(&messages) > budget {
+        match oldest_droppable(&messages) {
+            Some(index) => {
+                messages.remove(index);
+            }
+            None => break,
+        }
+    }
+    fit_last_message(&mut messages, budget, keep_last_words);
+    messages
+}
+
+fn sliding_window(messages: Vec<ChatMessage>, budget: u32) -> Vec<ChatMessage> {
+    let (system, conversation): (Vec<_>, Vec<_>) = messages.into_iter().partition(is_system);
+
+    let mut kept: Vec<ChatMessage> = Vec::new();
+    for message in conversation.into_iter().rev() {
+        let mut candidate = system.clone();
+        candidate.push(message.clone());
+        candidate.extend(kept.iter().cloned());
+
+        let overflow = tokens(&candidate).saturating_sub(budget) as usize;
+        if overflow == 0 {
+            kept.insert(0, message);
+            continue;
+        }
+
+        // Fill whatever room is left with the tail of this message.
+        let words = message.content.split_whitespace().count();
+        if words > overflow {
+            kept.insert(
+                0,
+                ChatMessage {
+                    content: keep_last_words(&message.content, words - overflow),
+                    ..message
+                },
+            );
+        }
+        break;
+    }
+
+    let mut result = system;
+    result.extend(kept);
+    fit_last_message(&mut result, budget, keep_last_words);
+    result
+}
+
+fn middle(mut messages: Vec<ChatMessage>, budget: u32) -> Vec<ChatMessage> {
+    while tokens(&messages) > budget {
+        // Everything between the first non-system message and the last one.
+        let droppable: Vec<usize> = (0..messages.len().saturating_sub(1))
+            .filter(|&i| !is_system(&messages[i]))
+            .skip(1)
+            .collect();
+        if droppable.is_empty() {
+            break;
+        }
+        messages.remove(droppable[droppable.len() / 2]);
+    }
+    fit_last_message(&mut messages, budget, keep_head_and_tail);
+    messages
+}
+
+async fn summarize(
+    state: &AppState,
+    model: &ModelRegistryEntry,
+    mut messages: Vec<ChatMessage>,
+    budget: u32,
+    request_id: &str,
+) -> Result<Vec<ChatMessage>, String> {
+    let summary_room = SUMMARY_MAX_TOKENS.min(budget / 4);
+    let mut dropped: Vec<ChatMessage> = Vec::new();
+    let mut insert_at = None;
+    while tokens(&messages) + summary_room > budget {
+        let Some(index) = oldest_droppable(&messages) else {
+            break;
+        };
+        insert_at.get_or_insert(index);
+        dropped.push(messages.remove(index));
+    }
+
+    if let Some(index) = insert_at {
+        let prompt = format!(
+            "Summarize the following conversation in a few sentences, keeping names, facts and decisions.\n\n{}",
+            render_messages(&dropped)
+        );
+        let summary = generate_internal(state, model, prompt, summary_room, request_id).await?;
+        messages.insert(
+            index,
+            ChatMessage {
+                role: "system".to_string(),
+                content: format!("Summary of the earlier conversation: {}", summary.trim()),
+                ..Default::default()
+            },
+        );
+    }
+
+    // A single oversized message, or a summary that came back too long.
+    Ok(drop_oldest(messages, budget))
+}
+
+/// Renders `messages` into `prompt` and, when the prompt does not fit the
+/// model's context (minus room for `max_tokens`), shrinks it with the
+/// request's or the server's truncation strategy. Without a strategy the
+/// request is left as is and rejected by the limits check. Chat requests
+/// keep the fitted messages for chat-native backends.
+pub async fn prepare(
+    state: &AppState,
+    caller: &Caller,
+    request_id: &str,
+    mut req: InferenceRequest,
+) -> Result<InferenceRequest, ApiError> {
+    let chat = req.messages.is_some();
+    let messages = match req.messages.take() {
+        Some(messages) if !messages.is_empty() => messages,
+        Some(_) => {
+            return Err(ApiError::Unprocessable {
+                message: "messages must not be empty".to_string(),
+                param: Some("messages"),
+            });
+        }
+        None if req.prompt.is_empty() => {
+            return Err(ApiError::Unprocessable {
+                message: "Either prompt or messages is required".to_string(),
+                param: Some("prompt"),
+            });
+        }
+        None => vec![ChatMessage::user(&req.prompt)],
+    };
+
+    let model = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == req.model_id && m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.clone());
+
+    let render = |messages: &[ChatMessage]| {
+        templates::render_prompt(model.as_ref(), messages).map_err(ApiError::unprocessable)
+    };
+
+    let strategy = req.truncation.or(state.truncation);
+    let (Some(model), Some(strategy)) = (model.as_ref(), strategy) else {
+        if chat {
+            req.prompt = render(&messages)?;
+            req.messages = Some(messages);
+        }
+        return Ok(req);
+    };
+
+    let mut budget = model.context.saturating_sub(req.max_tokens()).max(1);
+    if let Some(max) = state.limits.max_prompt_tokens {
+        budget = budget.min(max);
+    }
+
+    let measured = if chat { tokens(&messages) } else { count_tokens(&req.prompt) };
+    if measured <= budget {
+        if chat {
+            req.prompt = render(&messages)?;
+            req.messages = Some(messages);
+        }
+        return Ok(req);
+    }
+
+    // A plain prompt is truncated as a lone user message; allow for the chat
+    // framing, which is stripped again below.
+    let budget = if chat { budget } else { budget + tokens(&messages) - measured };
+
+    let fitted = match strategy {
+        TruncationStrategy::DropOldest => drop_oldest(messages, budget),
+        TruncationStrategy::SlidingWindow => sliding_window(messages, budget),
+        TruncationStrategy::Middle => middle(messages, budget),
+        TruncationStrategy::Summarize => summarize(state, model, messages, budget, request_id)
+            .await
+            .map_err(|e| ApiError::Backend(format!("Failed to summarize context: {}", e)))?,
+    };
+
+    tracing::debug!(
+        model_id = %req.model_id,
+        strategy = ?strategy,
+        before = measured,
+        "Truncated prompt to fit context window"
+    );
+
+    if chat {
+        req.prompt = render(&fitted)?;
+        req.messages = Some(fitted);
+    } else {
+        req.prompt = fitted.into_iter().map(|m| m.content).collect::<Vec<_>>().join("\n");
+    }
+    Ok(req)
+}
@@ -0,0 +1,60 @@
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::rate_limit::RATE_LIMIT_HEADERS;
+use super::request_id::REQUEST_ID_HEADER;
+
+const DEFAULT_HEADERS: [&str; 3] = ["authorization", "content-type", "x-request-id"];
+const DEFAULT_METHODS: [&str; 3] = ["GET", "POST", "DELETE"];
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Builds the CORS layer from the `--cors-*` flags. `*` as an origin allows
+/// any origin; empty header and method lists fall back to what the API
+/// needs (bearer auth, JSON bodies, the request id header).
+pub fn layer(origins: &[String], headers: &[String], methods: &[String]) -> Result<CorsLayer, String> {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| HeaderValue::from_str(o).map_err(|e| format!("invalid CORS origin '{}': {}", o, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let headers = if headers.is_empty() {
+        DEFAULT_HEADERS.iter().map(|h| h.to_string()).collect()
+    } else {
+        headers.to_vec()
+    };
+    let allow_headers = headers
+        .iter()
+        .map(|h| HeaderName::from_bytes(h.as_bytes()).map_err(|e| format!("invalid CORS header '{}': {}", h, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let methods = if methods.is_empty() {
+        DEFAULT_METHODS.iter().map(|m| m.to_string()).collect()
+    } else {
+        methods.to_vec()
+    };
+    let allow_methods = methods
+        .iter()
+        .map(|m| {
+            Method::from_bytes(m.to_uppercase().as_bytes())
+                .map_err(|e| format!("invalid CORS method '{}': {}", m, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expose_headers: Vec<HeaderName> = [REQUEST_ID_HEADER, header::RETRY_AFTER]
+        .into_iter()
+        .chain(RATE_LIMIT_HEADERS.map(HeaderName::from_static))
+        .collect();
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_headers(allow_headers)
+        .allow_methods(allow_methods)
+        .expose_headers(expose_headers)
+        .max_age(PREFLIGHT_MAX_AGE))
+}
@@ -0,0 +1,22 @@
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+};
+
+/// The admin dashboard, compiled into the binary. The page itself is public;
+/// it calls the API with a key the user enters.
+const INDEX: &str = include_str!("../assets/dashboard/index.html");
+const SCRIPT: &str = include_str!("../assets/dashboard/app.js");
+const STYLE: &str = include_str!("../assets/dashboard/style.css");
+
+pub async fn index() -> impl IntoResponse {
+    Html(INDEX)
+}
+
+pub async fn script() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/javascript; charset=utf-8")], SCRIPT)
+}
+
+pub async fn style() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/css; charset=utf-8")], STYLE)
+}
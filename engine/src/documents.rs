@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+use super::auth::{Caller, Role};
+use super::v1::inference::count_tokens;
+
+/// How long a finished ingestion job can still be looked up.
+const JOB_RETENTION: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Text,
+    Markdown,
+    Pdf,
+}
+
+impl DocumentFormat {
+    /// The format of a `Content-Type`, ignoring parameters like `charset`.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime.to_ascii_lowercase().as_str() {
+            "text/plain" => Some(Self::Text),
+            "text/markdown" | "text/x-markdown" => Some(Self::Markdown),
+            "application/pdf" => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// The document's text. PDF parsing is CPU-bound; call this off the runtime.
+pub fn extract_text(format: DocumentFormat, bytes: &[u8]) -> Result<String, String> {
+    match format {
+        DocumentFormat::Text | DocumentFormat::Markdown => {
+            String::from_utf8(bytes.to_vec()).map_err(|_| "The document is not valid UTF-8".to_string())
+        }
+        DocumentFormat::Pdf => {
+            pdf_extract::extract_text_from_mem(bytes).map_err(|e| format!("Failed to read the PDF: {}", e))
+        }
+    }
+}
+
+/// How a document is cut into chunks. Sizes are in tokens as the engine
+/// counts them, i.e. whitespace-separated words.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// Windows of `chunk_size` tokens, each repeating the last
+    /// `chunk_overlap` tokens of the one before.
+    Fixed,
+    /// Whole paragraphs packed into chunks of up to `chunk_size` tokens.
+    /// Longer paragraphs are split into fixed windows.
+    #[default]
+    Paragraph,
+    /// Like `paragraph`, but never across a Markdown heading. Chunks carry
+    /// the heading of their section.
+    Markdown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    pub heading: Option<String>,
+}
+
+pub fn chunk(text: &str, strategy: ChunkStrategy, size: usize, overlap: usize) -> Vec<Chunk> {
+    let plain = |texts: Vec<String>| texts.into_iter().map(|text| Chunk { text, heading: None }).collect();
+    match strategy {
+        ChunkStrategy::Fixed => plain(windows(text, size, overlap)),
+        ChunkStrategy::Paragraph => plain(pack(&paragraphs(text), size, overlap)),
+        ChunkStrategy::Markdown => sections(text)
+            .into_iter()
+            .flat_map(|(heading, body)| {
+                pack(&paragraphs(&body), size, overlap)
+                    .into_iter()
+                    .map(move |text| Chunk { text, heading: heading.clone() })
+            })
+            .collect(),
+    }
+}
+
+fn windows(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start = end - overlap;
+    }
+    chunks
+}
+
+/// Blank-line separated paragraphs, trimmed.
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+        } else {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line.trim_end());
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs
+}
+
+fn pack(paragraphs: &[String], size: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut tokens = 0;
+
+    for paragraph in paragraphs {
+        let count = count_tokens(paragraph) as usize;
+        if count > size {
+            if !current.is_empty() {
+                chunks.push(current.join("\n\n"));
+            }
+            chunks.extend(windows(paragraph, size, overlap));
+            current.clear();
+            tokens = 0;
+            continue;
+        }
+        if tokens + count > size && !current.is_empty() {
+            let chunk = current.join("\n\n");
+            current.clear();
+            tokens = 0;
+            // Start the next chunk with the end of this one, when it fits.
+            let words: Vec<&str> = chunk.split_whitespace().collect();
+            let tail = overlap.min(words.len());
+            if tail > 0 && tail + count <= size {
+                current.push(words[words.len() - tail..].join(" "));
+                tokens = tail;
+            }
+            chunks.push(chunk);
+        }
+        current.push(paragraph.clone());
+        tokens += count;
+    }
+    if !current.is_empty() {
+        chunks.push(current.join("\n\n"));
+    }
+    chunks
+}
+
+/// Splits Markdown at ATX headings (`#` to `######`) outside code fences.
+/// Text before the first heading is a section without one.
+fn sections(text: &str) -> Vec<(Option<String>, String)> {
+    let mut sections = Vec::new();
+    let mut heading = None;
+    let mut body = String::new();
+    let mut fenced = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fenced = !fenced;
+        }
+        if !fenced && let Some(title) = heading_text(trimmed) {
+            if !body.trim().is_empty() {
+                sections.push((heading.take(), std::mem::take(&mut body)));
+            }
+            body.clear();
+            heading = Some(title);
+        }
+        body.push_str(line);
+        body.push('\n');
+    }
+    if !body.trim().is_empty() {
+        sections.push((heading, body));
+    }
+    sections
+}
+
+fn heading_text(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') && !rest.starts_with('\t') {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim_end().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Extracting,
+    Indexing,
+    Completed,
+    Failed,
+}
+
+/// An ingestion job, as `/v1/documents/jobs/{job_id}` reports it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DocumentJob {
+    pub id: String,
+    pub document_id: String,
+    pub collection: String,
+    pub status: JobStatus,
+    /// Size of the uploaded document.
+    pub bytes: usize,
+    /// Chunks the document was cut into, known once its text is extracted.
+    pub chunks: usize,
+    /// Chunks embedded and stored so far.
+    pub indexed: usize,
+    pub tokens: u32,
+    pub cached: usize,
+    pub error: Option<String>,
+    /// Unix timestamps in seconds.
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+    #[serde(skip)]
+    caller: String,
+    #[serde(skip)]
+    tenant: Option<String>,
+    #[serde(skip)]
+    finished: Option<Instant>,
+}
+
+impl DocumentJob {
+    pub fn new(caller: &Caller, document_id: String, collection: String, bytes: usize) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id,
+            collection,
+            status: JobStatus::Queued,
+            bytes,
+            chunks: 0,
+            indexed: 0,
+            tokens: 0,
+            cached: 0,
+            error: None,
+            created_at: unix_now(),
+            finished_at: None,
+            caller: caller.id.clone(),
+            tenant: caller.tenant.clone(),
+            finished: None,
+        }
+    }
+
+    /// Jobs are visible to whoever started them and to admins who can see
+    /// their tenant.
+    fn visible_to(&self, caller: &Caller) -> bool {
+        self.caller == caller.id || (caller.role == Role::Admin && caller.can_see(self.tenant.as_deref()))
+    }
+
+    pub fn complete(&mut self) {
+        self.status = JobStatus::Completed;
+        self.finish();
+    }
+
+    pub fn fail(&mut self, error: String) {
+        self.status = JobStatus::Failed;
+        self.error = Some(error);
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.finished_at = Some(unix_now());
+        self.finished = Some(Instant::now());
+    }
+}
+
+/// Ingestion jobs in memory. Finished jobs are dropped after an hour.
+#[derive(Default)]
+pub struct DocumentJobs {
+    jobs: HashMap<String, DocumentJob>,
+}
+
+impl DocumentJobs {
+    pub fn insert(&mut self, job: DocumentJob) {
+        self.jobs
+            .retain(|_, job| job.finished.is_none_or(|at| at.elapsed() < JOB_RETENTION));
+        self.jobs.insert(job.id.clone(), job);
+    }
+
+    pub fn get(&self, id: &str, caller: &Caller) -> Option<DocumentJob> {
+        self.jobs.get(id).filter(|job| job.visible_to(caller)).cloned()
+    }
+
+    pub fn update(&mut self, id: &str, update: impl FnOnce(&mut DocumentJob)) {
+        if let Some(job) = self.jobs.get_mut(id) {
+            update(job);
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,152 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// What an embedding depends on: the model and the backend model it was
+/// computed with, the input type it was requested for, and the text, by its
+/// SHA-256.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    model_id: String,
+    backend_model: String,
+    input_type: Option<String>,
+    text: [u8; 32],
+}
+
+impl CacheKey {
+    pub fn new(model_id: &str, backend_model: &str, input_type: Option<&str>, text: &str) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            backend_model: backend_model.to_string(),
+            input_type: input_type.map(str::to_string),
+            text: Sha256::digest(text.as_bytes()).into(),
+        }
+    }
+}
+
+struct Entry {
+    vector: Vec<f32>,
+    stored_at: Instant,
+    /// The entry's key in `EmbeddingCache::recency`.
+    used: u64,
+}
+
+/// Embedding vectors already computed, so texts that are embedded again
+/// (chunks of a document that is re-indexed, say) skip the backend. Entries
+/// expire after `ttl`; once `capacity` is reached, the least recently used
+/// is evicted.
+pub struct EmbeddingCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<CacheKey, Entry>,
+    /// Keys by when they were last used, oldest first.
+    recency: BTreeMap<u64, CacheKey>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EmbeddingCacheSnapshot {
+    pub entries: usize,
+    pub capacity: usize,
+    pub ttl_secs: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Hits over lookups, or 0 before the first lookup.
+    pub hit_rate: f64,
+    pub evictions: u64,
+}
+
+impl EmbeddingCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<Vec<f32>> {
+        let ttl = self.ttl;
+        match self.entries.get(key) {
+            Some(entry) if entry.stored_at.elapsed() < ttl => {}
+            Some(_) => {
+                self.remove(key);
+                self.misses += 1;
+                return None;
+            }
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used);
+        entry.used = self.clock;
+        self.recency.insert(self.clock, key.clone());
+        self.hits += 1;
+        Some(entry.vector.clone())
+    }
+
+    pub fn insert(&mut self, key: CacheKey, vector: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.evictions += 1;
+        }
+
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                vector,
+                stored_at: Instant::now(),
+                used: self.clock,
+            },
+        );
+    }
+
+    /// Drops a model's vectors once it is removed from the registry, so a
+    /// model registered later under the same id starts afresh.
+    pub fn forget(&mut self, model_id: &str) {
+        self.entries.retain(|key, _| key.model_id != model_id);
+        self.recency.retain(|_, key| key.model_id != model_id);
+    }
+
+    pub fn snapshot(&self) -> EmbeddingCacheSnapshot {
+        let lookups = self.hits + self.misses;
+        EmbeddingCacheSnapshot {
+            entries: self.entries.len(),
+            capacity: self.capacity,
+            ttl_secs: self.ttl.as_secs(),
+            hits: self.hits,
+            misses: self.misses,
+            hit_rate: if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 },
+            evictions: self.evictions,
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+}
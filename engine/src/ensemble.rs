@@ -0,0 +1,64 @@
+use minijinja::{context, Environment};
+use serde::Serialize;
+
+/// What the judge ranks by unless the request says otherwise.
+pub const DEFAULT_CRITERIA: &str = "accuracy, helpfulness and clarity";
+
+/// The prompt the judge model gets. It must put its ranking on the first
+/// line, which is all that is parsed.
+const JUDGE_TEMPLATE: &str = "\
+Several assistants answered the same request. Rank their responses from best \
+to worst by {{ criteria }}.
+
+Request:
+{{ question }}
+
+{% for response in responses -%}
+Response {{ response.number }}:
+{{ response.text }}
+
+{% endfor -%}
+On the first line, list the response numbers from best to worst, separated \
+by commas (for example: {{ example }}). Then briefly explain the ranking.";
+
+/// A response as the judge sees it, numbered from 1.
+#[derive(Serialize)]
+pub struct Candidate<'a> {
+    pub number: usize,
+    pub text: &'a str,
+}
+
+pub fn judge_prompt(question: &str, criteria: &str, candidates: &[Candidate]) -> String {
+    let example: Vec<String> = (1..=candidates.len()).rev().map(|n| n.to_string()).collect();
+    Environment::new()
+        .render_str(
+            JUDGE_TEMPLATE,
+            context! {
+                question => question,
+                criteria => criteria,
+                responses => candidates,
+                example => example.join(", "),
+            },
+        )
+        .expect("the judge template renders")
+}
+
+/// The candidate numbers on the first line of the judge's answer that has
+/// any, best first. Numbers out of range or repeated are skipped, so a
+/// judge that ranks only some responses leaves the rest unranked.
+pub fn parse_ranking(answer: &str, candidates: usize) -> Vec<usize> {
+    let line = answer
+        .lines()
+        .find(|line| line.chars().any(|c| c.is_ascii_digit()))
+        .unwrap_or_default();
+    let mut ranking = Vec::new();
+    for number in line
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|digits| digits.parse::<usize>().ok())
+    {
+        if (1..=candidates).contains(&number) && !ranking.contains(&number) {
+            ranking.push(number);
+        }
+    }
+    ranking
+}
@@ -0,0 +1,273 @@
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::admission::SaturationError;
+use super::guardrails::ModerationError;
+use super::injection::InjectionError;
+use super::limits::LimitError;
+use super::plugins::PluginRejection;
+use super::request_id::RequestId;
+use super::routing::RoutingRejection;
+
+/// Plain-text error bodies longer than this are cut off when wrapped.
+const MAX_WRAPPED_BODY_BYTES: usize = 64 * 1024;
+
+/// An error returned by any route. All of them render as the same body,
+/// shaped like OpenAI's so its clients can read it:
+///
+/// `{"error": {"type", "code", "message", "param", "request_id"}}`
+///
+/// The request id is added by [`render_errors`].
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{message}")]
+    InvalidRequest { message: String, param: Option<&'static str> },
+    #[error("{message}")]
+    Unprocessable { message: String, param: Option<&'static str> },
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("Model '{0}' not found")]
+    ModelNotFound(String),
+    #[error("Model '{0}' is not loaded. Load it first.")]
+    ModelNotLoaded(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    RateLimited(String),
+    #[error("{0}")]
+    QuotaExceeded(String),
+    #[error("{0}")]
+    Backend(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    Internal(String),
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    #[error(transparent)]
+    Moderation(#[from] ModerationError),
+    #[error(transparent)]
+    Injection(#[from] InjectionError),
+    #[error(transparent)]
+    Saturated(#[from] SaturationError),
+    #[error(transparent)]
+    Plugin(#[from] PluginRejection),
+    #[error(transparent)]
+    Routing(#[from] RoutingRejection),
+}
+
+impl ApiError {
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::InvalidRequest { message: message.into(), param: None }
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::Unprocessable { message: message.into(), param: None }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest { .. } => StatusCode::BAD_REQUEST,
+            Self::Unprocessable { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::ModelNotFound(_) | Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::ModelNotLoaded(_) => StatusCode::PRECONDITION_FAILED,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::RateLimited(_) | Self::QuotaExceeded(_) | Self::Saturated(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Backend(_) => StatusCode::BAD_GATEWAY,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Limit(e) => e.status,
+            Self::Moderation(e) => e.status,
+            Self::Injection(e) => e.status,
+            Self::Plugin(e) => e.status,
+            Self::Routing(e) => e.status,
+        }
+    }
+
+    /// A stable, machine-readable name for the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest { .. } => "invalid_request",
+            Self::Unprocessable { .. } => "invalid_value",
+            Self::Unauthorized(_) => "invalid_api_key",
+            Self::Forbidden(_) => "forbidden",
+            Self::ModelNotFound(_) => "model_not_found",
+            Self::ModelNotLoaded(_) => "model_not_loaded",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::RateLimited(_) => "rate_limit_exceeded",
+            Self::QuotaExceeded(_) => "quota_exceeded",
+            Self::Saturated(_) => "server_saturated",
+            Self::Backend(_) => "backend_error",
+            Self::Unavailable(_) => "service_unavailable",
+            Self::Internal(_) => "internal_error",
+            Self::Limit(e) => e.code,
+            Self::Moderation(e) => e.code,
+            Self::Injection(e) => e.code,
+            Self::Plugin(_) => "plugin_rejected",
+            Self::Routing(_) => "routing_rejected",
+        }
+    }
+
+    /// The request field the error is about, if any.
+    pub fn param(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidRequest { param, .. } | Self::Unprocessable { param, .. } => *param,
+            Self::ModelNotFound(_) | Self::ModelNotLoaded(_) => Some("model_id"),
+            Self::Limit(e) => e.param,
+            _ => None,
+        }
+    }
+
+    /// Fields that only some errors carry, such as the limit that was hit.
+    fn details(&self) -> serde_json::Map<String, serde_json::Value> {
+        let details = match self {
+            Self::Limit(e) => serde_json::to_value(e),
+            Self::Moderation(e) => serde_json::to_value(e),
+            Self::Injection(e) => serde_json::to_value(e),
+            Self::Saturated(e) => serde_json::to_value(e),
+            Self::Plugin(e) => serde_json::to_value(e),
+            _ => return serde_json::Map::new(),
+        };
+        match details {
+            Ok(serde_json::Value::Object(details)) => details,
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+/// The `type` of an error, by status, following OpenAI's names where it has
+/// one.
+fn error_type(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+        StatusCode::BAD_GATEWAY => "backend_error",
+        status if status.is_server_error() => "server_error",
+        _ => "invalid_request_error",
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct ErrorObject {
+    r#type: &'static str,
+    code: String,
+    message: String,
+    param: Option<&'static str>,
+    request_id: Option<String>,
+    #[serde(flatten)]
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorBody {
+    error: ErrorObject,
+}
+
+impl ErrorObject {
+    fn into_body(self) -> Json<ErrorBody> {
+        Json(ErrorBody { error: self })
+    }
+}
+
+impl From<&ApiError> for ErrorObject {
+    fn from(e: &ApiError) -> Self {
+        Self {
+            r#type: error_type(e.status()),
+            code: e.code().to_string(),
+            message: e.to_string(),
+            param: e.param(),
+            request_id: None,
+            details: e.details(),
+        }
+    }
+}
+
+impl ErrorObject {
+    /// The error as it appears inside a successful response, e.g. for one
+    /// model of an ensemble.
+    pub(crate) fn for_request(e: &ApiError, request_id: &RequestId) -> Self {
+        Self {
+            request_id: Some(request_id.0.clone()),
+            ..Self::from(e)
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let error = ErrorObject::from(&self);
+
+        let mut response = (status, error.clone().into_body()).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        if let Self::Saturated(e) = &self {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(e.retry_after_secs));
+        }
+        response.extensions_mut().insert(error);
+        response
+    }
+}
+
+/// Adds the request id to error bodies, and wraps the plain-text errors axum
+/// produces itself, such as for malformed JSON or unknown routes, in the
+/// same body. Must run inside `assign_request_id`.
+pub async fn render_errors(req: Request, next: Next) -> Response {
+    let request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+    let response = next.run(req).await;
+    let status = response.status();
+
+    if let Some(error) = response.extensions().get::<ErrorObject>().cloned() {
+        let (mut parts, _) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let body = ErrorObject { request_id, ..error }.into_body();
+        return (parts, body).into_response();
+    }
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !(status.is_client_error() || status.is_server_error()) || is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let text = to_bytes(body, MAX_WRAPPED_BODY_BYTES)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+    let reason = status.canonical_reason().unwrap_or("Error");
+    let error = ErrorObject {
+        r#type: error_type(status),
+        code: reason.to_lowercase().replace([' ', '-'], "_"),
+        message: if text.is_empty() { reason.to_string() } else { text },
+        param: None,
+        request_id,
+        details: serde_json::Map::new(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    (parts, error.into_body()).into_response()
+}
@@ -0,0 +1,61 @@
+use axum::http::{header, HeaderMap};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Counts changes to the model registry, so `GET /v1/models` can answer
+/// `304 Not Modified` while nothing has changed. Bumped wherever the
+/// registry is modified, while its lock is still held.
+#[derive(Debug)]
+pub struct RegistryRevision {
+    /// Keeps tags handed out by an earlier run from matching after a restart.
+    epoch: u128,
+    revision: AtomicU64,
+}
+
+impl Default for RegistryRevision {
+    fn default() -> Self {
+        Self {
+            epoch: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default(),
+            revision: AtomicU64::new(0),
+        }
+    }
+}
+
+impl RegistryRevision {
+    pub fn bump(&self) {
+        self.revision.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Changes whenever the registry does.
+    pub fn current(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    /// The entity tag of the model listing as seen from `tenant`. Tenants
+    /// see different models, so each gets its own tag.
+    pub fn etag(&self, tenant: Option<&str>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.epoch.to_le_bytes());
+        hasher.update(self.current().to_le_bytes());
+        if let Some(tenant) = tenant {
+            hasher.update(tenant);
+        }
+        let digest: String = hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        format!("\"{}\"", digest)
+    }
+}
+
+/// Whether the request's `If-None-Match` names `etag`, or is `*`.
+pub fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use super::model_metadata::ModelMetadata;
+
+/// Every GGUF file starts with these bytes.
+const MAGIC: [u8; 4] = *b"GGUF";
+
+/// Longest string read from a header, so a corrupt length fails instead of
+/// allocating gigabytes.
+const MAX_STRING: u64 = 16 * 1024 * 1024;
+
+/// A metadata value. Only integers and strings are kept; floats, booleans
+/// and arrays, such as the vocabulary, are skipped.
+#[derive(Debug)]
+enum Value {
+    Uint(u64),
+    Int(i64),
+    String(String),
+    Skipped,
+}
+
+impl Value {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Uint(value) => Some(*value),
+            Self::Int(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the metadata in a GGUF file's header: its architecture, context
+/// length, parameter count, quantization and chat template. Only the header
+/// is read, not the tensors.
+pub fn read_metadata(path: &Path) -> Result<ModelMetadata, String> {
+    let describe = |e: io::Error| format!("Failed to read {}: {}", path.display(), e);
+    let file = File::open(path).map_err(describe)?;
+    let size_bytes = file.metadata().map_err(describe)?.len();
+    let values = read_header(&mut BufReader::new(file)).map_err(describe)?;
+
+    let architecture = values.get("general.architecture").and_then(Value::as_str);
+    let context = architecture
+        .and_then(|architecture| values.get(&format!("{}.context_length", architecture)))
+        .and_then(Value::as_u64)
+        .and_then(|context| u32::try_from(context).ok());
+
+    Ok(ModelMetadata {
+        architecture: architecture.map(str::to_string),
+        parameters: values.get("general.parameter_count").and_then(Value::as_u64),
+        context,
+        quant: values
+            .get("general.file_type")
+            .and_then(Value::as_u64)
+            .and_then(file_type)
+            .map(str::to_string),
+        size_bytes: Some(size_bytes),
+        template: values
+            .get("tokenizer.chat_template")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+fn read_header(reader: &mut impl Read) -> io::Result<BTreeMap<String, Value>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a GGUF file".to_string()));
+    }
+    // Version 1 used 32-bit lengths and is no longer written.
+    let version = read_u32(reader)?;
+    if !(2..=3).contains(&version) {
+        return Err(invalid(format!("unsupported GGUF version {}", version)));
+    }
+    let _tensors = read_u64(reader)?;
+    let count = read_u64(reader)?;
+
+    let mut values = BTreeMap::new();
+    for _ in 0..count {
+        let key = read_string(reader)?;
+        let kind = read_u32(reader)?;
+        values.insert(key, read_value(reader, kind)?);
+    }
+    Ok(values)
+}
+
+fn read_value(reader: &mut impl Read, kind: u32) -> io::Result<Value> {
+    Ok(match kind {
+        0 => Value::Uint(read_bytes::<1>(reader)?[0] as u64),
+        1 => Value::Int(i8::from_le_bytes(read_bytes(reader)?) as i64),
+        2 => Value::Uint(u16::from_le_bytes(read_bytes(reader)?) as u64),
+        3 => Value::Int(i16::from_le_bytes(read_bytes(reader)?) as i64),
+        4 => Value::Uint(read_u32(reader)? as u64),
+        5 => Value::Int(i32::from_le_bytes(read_bytes(reader)?) as i64),
+        6 => skip(reader, 4).map(|_| Value::Skipped)?,
+        7 => skip(reader, 1).map(|_| Value::Skipped)?,
+        8 => Value::String(read_string(reader)?),
+        9 => {
+            let item = read_u32(reader)?;
+            let count = read_u64(reader)?;
+            skip_array(reader, item, count)?;
+            Value::Skipped
+        }
+        10 => Value::Uint(read_u64(reader)?),
+        11 => Value::Int(i64::from_le_bytes(read_bytes(reader)?)),
+        12 => skip(reader, 8).map(|_| Value::Skipped)?,
+        kind => return Err(invalid(format!("unknown metadata type {}", kind))),
+    })
+}
+
+fn skip_array(reader: &mut impl Read, item: u32, count: u64) -> io::Result<()> {
+    let width = match item {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        8 => {
+            for _ in 0..count {
+                let len = read_u64(reader)?;
+                skip(reader, len)?;
+            }
+            return Ok(());
+        }
+        9 => {
+            for _ in 0..count {
+                let item = read_u32(reader)?;
+                let count = read_u64(reader)?;
+                skip_array(reader, item, count)?;
+            }
+            return Ok(());
+        }
+        kind => return Err(invalid(format!("unknown metadata type {}", kind))),
+    };
+    let len = count
+        .checked_mul(width)
+        .ok_or_else(|| invalid("array too long".to_string()))?;
+    skip(reader, len)
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    read_bytes(reader).map(u32::from_le_bytes)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    read_bytes(reader).map(u64::from_le_bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_STRING {
+        return Err(invalid(format!("string of {} bytes", len)));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The quantization named by llama.cpp's `general.file_type`.
+fn file_type(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    })
+}
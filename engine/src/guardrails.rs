@@ -0,0 +1,226 @@
+use axum::http::StatusCode;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+const REDACTED_OUTPUT: &str = "[content removed by moderation]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ModerationStage {
+    Input,
+    Output,
+    Both,
+}
+
+/// What to do when a category is flagged, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Let the content through and report the categories in the response.
+    Annotate,
+    /// Replace generated output with a placeholder. Prompts cannot be
+    /// partially redacted, so this blocks on input.
+    Redact,
+    /// Reject the request.
+    Block,
+}
+
+/// Parses a `--moderation-rule` value of the form `category=action`, e.g.
+/// `violence=annotate` or `*=block`.
+pub fn parse_rule(value: &str) -> Result<(String, ModerationAction), String> {
+    let (category, action) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected CATEGORY=ACTION, got '{}'", value))?;
+    let action = ModerationAction::from_str(action, true)
+        .map_err(|_| format!("unknown action '{}' (annotate, redact, block)", action))?;
+    Ok((category.to_string(), action))
+}
+
+#[derive(Debug, Clone)]
+pub struct GuardrailsConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub stage: ModerationStage,
+    pub rules: HashMap<String, ModerationAction>,
+    pub threshold: Option<f64>,
+    pub fail_open: bool,
+}
+
+/// Moderation responses follow the OpenAI `/moderations` format, which
+/// local classifiers are expected to mimic.
+#[derive(Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+#[derive(Deserialize)]
+struct ModerationResult {
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+    #[serde(default)]
+    category_scores: HashMap<String, f64>,
+}
+
+/// Outcome of moderating one piece of text, reported to clients under
+/// `moderation` when content is let through.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ModerationVerdict {
+    pub stage: &'static str,
+    pub categories: Vec<String>,
+    pub action: ModerationAction,
+}
+
+#[derive(Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct ModerationError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    #[serde(skip)]
+    pub code: &'static str,
+    #[serde(skip)]
+    pub message: String,
+    pub stage: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+}
+
+/// Runs prompts and/or outputs through a moderation model and applies the
+/// configured action to flagged categories.
+pub struct Guardrails {
+    config: GuardrailsConfig,
+    client: reqwest::Client,
+}
+
+impl Guardrails {
+    pub fn new(config: GuardrailsConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn moderates_input(&self) -> bool {
+        matches!(self.config.stage, ModerationStage::Input | ModerationStage::Both)
+    }
+
+    pub fn moderates_output(&self) -> bool {
+        matches!(self.config.stage, ModerationStage::Output | ModerationStage::Both)
+    }
+
+    /// With rules configured only the listed categories (or `*`) act;
+    /// without any, every flagged category blocks.
+    fn action_for(&self, category: &str) -> Option<ModerationAction> {
+        if self.config.rules.is_empty() {
+            return Some(ModerationAction::Block);
+        }
+        self.config
+            .rules
+            .get(category)
+            .or_else(|| self.config.rules.get("*"))
+            .copied()
+    }
+
+    async fn moderate(&self, text: &str, stage: &'static str) -> Result<Option<ModerationVerdict>, String> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .json(&serde_json::json!({ "input": text }));
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Moderation request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Moderation API error: {}", response.status()));
+        }
+        let response: ModerationResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse moderation response: {}", e))?;
+
+        let mut flagged: Vec<(String, ModerationAction)> = Vec::new();
+        for result in response.results {
+            let mut categories: Vec<String> = match self.config.threshold {
+                Some(threshold) => result
+                    .category_scores
+                    .into_iter()
+                    .filter(|(_, score)| *score >= threshold)
+                    .map(|(category, _)| category)
+                    .collect(),
+                None => result
+                    .categories
+                    .into_iter()
+                    .filter(|(_, hit)| *hit)
+                    .map(|(category, _)| category)
+                    .collect(),
+            };
+            categories.sort();
+            for category in categories {
+                if let Some(action) = self.action_for(&category) {
+                    flagged.push((category, action));
+                }
+            }
+        }
+
+        Ok(flagged.iter().map(|(_, action)| *action).max().map(|action| ModerationVerdict {
+            stage,
+            categories: flagged.into_iter().map(|(category, _)| category).collect(),
+            action,
+        }))
+    }
+
+    /// Moderates a prompt. Returns an annotation to attach to the response
+    /// when the prompt may proceed.
+    pub async fn check_input(&self, prompt: &str) -> Result<Option<ModerationVerdict>, ModerationError> {
+        match self.moderate(prompt, "input").await {
+            Ok(Some(verdict)) if verdict.action != ModerationAction::Annotate => Err(ModerationError {
+                status: StatusCode::BAD_REQUEST,
+                code: "content_policy_violation",
+                message: format!("Prompt flagged by moderation: {}", verdict.categories.join(", ")),
+                stage: "input",
+                categories: verdict.categories,
+            }),
+            Ok(verdict) => Ok(verdict),
+            Err(e) => self.unavailable(e, "input"),
+        }
+    }
+
+    /// Moderates generated text, redacting it in place when required.
+    pub async fn check_output(&self, text: &mut String) -> Result<Option<ModerationVerdict>, ModerationError> {
+        match self.moderate(text, "output").await {
+            Ok(Some(verdict)) if verdict.action == ModerationAction::Block => Err(ModerationError {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                code: "content_policy_violation",
+                message: format!("Response flagged by moderation: {}", verdict.categories.join(", ")),
+                stage: "output",
+                categories: verdict.categories,
+            }),
+            Ok(Some(verdict)) => {
+                if verdict.action == ModerationAction::Redact {
+                    *text = REDACTED_OUTPUT.to_string();
+                }
+                Ok(Some(verdict))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => self.unavailable(e, "output"),
+        }
+    }
+
+    fn unavailable(&self, error: String, stage: &'static str) -> Result<Option<ModerationVerdict>, ModerationError> {
+        if self.config.fail_open {
+            tracing::warn!("Moderation unavailable, letting {} through: {}", stage, error);
+            return Ok(None);
+        }
+        Err(ModerationError {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            code: "moderation_unavailable",
+            message: error,
+            stage,
+            categories: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,265 @@
+use axum::http::StatusCode;
+use clap::ValueEnum;
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::auth::Caller;
+use super::v1::inference::InferenceRequest;
+use super::AppState;
+
+/// Phrasings common in prompt injection and jailbreak attempts, with how
+/// strongly each one suggests an attack.
+const HEURISTICS: &[(&str, &str, f64)] = &[
+    (
+        "ignore_instructions",
+        r"(ignore|disregard|forget|override)\s+(all\s+|any\s+)?(the\s+|your\s+)?(previous|prior|above|earlier|system)\s+(instructions|prompts?|rules|directions)",
+        0.9,
+    ),
+    ("do_anything_now", r"(\b(?-i:DAN)\b|\bdo anything now\b)", 0.8),
+    ("developer_mode", r"(developer|god|jailbreak|unrestricted)\s+mode", 0.7),
+    (
+        "reveal_system_prompt",
+        r"(reveal|print|show|repeat|output|leak)\s+(me\s+)?(your|the)\s+(system\s+prompt|hidden\s+instructions|initial\s+instructions)",
+        0.7,
+    ),
+    (
+        "role_markers",
+        r"(<\|im_start\|>\s*system|<\|start_header_id\|>\s*system|\[/?INST\]|^\s*#{2,}\s*system\s*:)",
+        0.6,
+    ),
+    ("no_restrictions", r"(without|no|ignore)\s+(any\s+)?(restrictions|filters|guidelines|safety|limitations)", 0.5),
+    ("new_persona", r"(you\s+are\s+now|from\s+now\s+on\s+you\s+are|pretend\s+(to\s+be|you\s+are))", 0.4),
+    ("jailbreak", r"\bjailbr(ea|o)k", 0.5),
+];
+
+/// What to do with a prompt whose injection score reaches the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectionAction {
+    /// Do not screen prompts.
+    #[default]
+    Off,
+    /// Let the prompt through and report the score in the response.
+    Flag,
+    /// Reject the request.
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct InjectionPolicy {
+    pub action: InjectionAction,
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+}
+
+pub fn default_threshold() -> f64 {
+    0.7
+}
+
+impl Default for InjectionPolicy {
+    fn default() -> Self {
+        Self {
+            action: InjectionAction::Off,
+            threshold: default_threshold(),
+        }
+    }
+}
+
+/// Screening result attached to responses whose prompt was flagged.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InjectionReport {
+    pub score: f64,
+    pub signals: Vec<String>,
+}
+
+#[derive(Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct InjectionError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    #[serde(skip)]
+    pub code: &'static str,
+    #[serde(skip)]
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signals: Vec<String>,
+}
+
+/// Output of a Hugging Face style text-classification endpoint, which
+/// returns either a flat list of labels or one list per input.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ClassifierResponse {
+    Nested(Vec<Vec<ClassifierLabel>>),
+    Flat(Vec<ClassifierLabel>),
+}
+
+#[derive(Deserialize)]
+struct ClassifierLabel {
+    label: String,
+    score: f64,
+}
+
+fn is_injection_label(label: &str) -> bool {
+    matches!(
+        label.to_ascii_uppercase().as_str(),
+        "INJECTION" | "JAILBREAK" | "MALICIOUS" | "UNSAFE" | "LABEL_1"
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassifierConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+/// Scores prompts for injection attempts with built-in heuristics and, when
+/// configured, a classifier model.
+pub struct InjectionScreener {
+    default_policy: InjectionPolicy,
+    heuristics: RegexSet,
+    classifier: Option<ClassifierConfig>,
+    client: reqwest::Client,
+}
+
+impl Default for InjectionScreener {
+    fn default() -> Self {
+        Self::new(InjectionPolicy::default(), None)
+    }
+}
+
+impl InjectionScreener {
+    pub fn new(default_policy: InjectionPolicy, classifier: Option<ClassifierConfig>) -> Self {
+        let patterns = HEURISTICS.iter().map(|(_, pattern, _)| format!("(?im){}", pattern));
+        Self {
+            default_policy,
+            heuristics: RegexSet::new(patterns).expect("built-in injection patterns"),
+            classifier,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Combines matched heuristics as independent signals, so several weak
+    /// ones add up to a strong one.
+    fn heuristic_score(&self, text: &str) -> (f64, Vec<String>) {
+        let matched = self.heuristics.matches(text);
+        let mut clean = 1.0;
+        let mut signals = Vec::new();
+        for index in matched.iter() {
+            let (name, _, weight) = HEURISTICS[index];
+            clean *= 1.0 - weight;
+            signals.push(name.to_string());
+        }
+        (1.0 - clean, signals)
+    }
+
+    async fn classifier_score(&self, classifier: &ClassifierConfig, text: &str) -> Result<f64, String> {
+        let mut request = self.client.post(&classifier.url).json(&serde_json::json!({ "inputs": text }));
+        if let Some(api_key) = &classifier.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Injection classifier request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Injection classifier error: {}", response.status()));
+        }
+        let labels = match response
+            .json::<ClassifierResponse>()
+            .await
+            .map_err(|e| format!("Failed to parse injection classifier response: {}", e))?
+        {
+            ClassifierResponse::Nested(lists) => lists.into_iter().flatten().collect(),
+            ClassifierResponse::Flat(labels) => labels,
+        };
+
+        Ok(labels
+            .iter()
+            .filter(|l| is_injection_label(&l.label))
+            .map(|l| l.score)
+            .fold(0.0, f64::max))
+    }
+
+    async fn screen(&self, text: &str) -> Result<InjectionReport, String> {
+        let (mut score, mut signals) = self.heuristic_score(text);
+        if let Some(classifier) = &self.classifier {
+            let classified = self.classifier_score(classifier, text).await?;
+            if classified > score {
+                score = classified;
+            }
+            signals.push(format!("classifier:{:.2}", classified));
+        }
+        Ok(InjectionReport { score, signals })
+    }
+}
+
+/// The caller-supplied text of a request. System messages come from the
+/// application rather than its users and are not screened.
+fn screened_text(req: &InferenceRequest) -> String {
+    match &req.messages {
+        Some(messages) => messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => req.prompt.clone(),
+    }
+}
+
+/// Screens a request under its tenant's policy, falling back to the
+/// server-wide one. Returns a report to attach to the response when the
+/// prompt was flagged but let through.
+pub async fn check(
+    state: &AppState,
+    caller: &Caller,
+    req: &InferenceRequest,
+) -> Result<Option<InjectionReport>, InjectionError> {
+    let screener = &state.injection;
+    let tenant_policy = match &caller.tenant {
+        Some(tenant) => state.tenants.lock().await.injection_policy(tenant),
+        None => None,
+    };
+    let policy = tenant_policy.unwrap_or(screener.default_policy);
+    if policy.action == InjectionAction::Off {
+        return Ok(None);
+    }
+
+    let report = screener.screen(&screened_text(req)).await.map_err(|e| InjectionError {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "injection_screening_unavailable",
+        message: e,
+        score: None,
+        signals: Vec::new(),
+    })?;
+
+    if report.score < policy.threshold {
+        return Ok(None);
+    }
+
+    tracing::warn!(
+        caller = %caller.id,
+        score = report.score,
+        signals = ?report.signals,
+        "Prompt flagged as possible injection"
+    );
+
+    match policy.action {
+        InjectionAction::Reject => Err(InjectionError {
+            status: StatusCode::BAD_REQUEST,
+            code: "prompt_injection",
+            message: format!(
+                "Prompt rejected as a likely injection attempt (score {:.2}, threshold {:.2})",
+                report.score, policy.threshold
+            ),
+            score: Some(report.score),
+            signals: report.signals,
+        }),
+        _ => Ok(Some(report)),
+    }
+}
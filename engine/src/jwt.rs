@@ -0,0 +1,157 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+const JWKS_TTL: Duration = Duration::from_secs(600);
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    pub issuer: String,
+    pub audience: Option<String>,
+    pub jwks_url: Option<String>,
+    pub tenant_claim: String,
+    pub roles_claim: String,
+    pub admin_role: String,
+}
+
+/// Identity extracted from a validated token.
+#[derive(Debug, Clone)]
+pub struct JwtIdentity {
+    pub subject: String,
+    pub tenant: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OidcDiscovery {
+    jwks_uri: String,
+}
+
+struct JwksCache {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// Validates bearer JWTs against the issuer's JWKS. Keys are cached for
+/// `JWKS_TTL` and refetched early when a token names an unknown `kid`, at
+/// most once every `JWKS_MIN_REFRESH`.
+pub struct JwtValidator {
+    config: JwtConfig,
+    client: reqwest::Client,
+    cache: Mutex<Option<JwksCache>>,
+}
+
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+impl JwtValidator {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn admin_role(&self) -> &str {
+        &self.config.admin_role
+    }
+
+    async fn jwks_url(&self) -> Result<String, String> {
+        if let Some(url) = &self.config.jwks_url {
+            return Ok(url.clone());
+        }
+
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let discovery: OidcDiscovery = self
+            .client
+            .get(&discovery_url)
+            .send()
+            .await
+            .map_err(|e| format!("OIDC discovery failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid OIDC discovery document: {}", e))?;
+        Ok(discovery.jwks_uri)
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, String> {
+        let url = self.jwks_url().await?;
+        self.client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("JWKS fetch failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid JWKS document: {}", e))
+    }
+
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey, String> {
+        let mut cache = self.cache.lock().await;
+
+        let stale = cache.as_ref().is_none_or(|c| c.fetched_at.elapsed() > JWKS_TTL);
+        let missing_kid = cache.as_ref().is_some_and(|c| {
+            kid.is_some_and(|kid| c.keys.find(kid).is_none()) && c.fetched_at.elapsed() > JWKS_MIN_REFRESH
+        });
+        if stale || missing_kid {
+            let keys = self.fetch_jwks().await?;
+            *cache = Some(JwksCache {
+                keys,
+                fetched_at: Instant::now(),
+            });
+        }
+
+        let keys = &cache.as_ref().ok_or("JWKS unavailable")?.keys;
+        let jwk = match kid {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or("No matching signing key in JWKS")?;
+
+        DecodingKey::from_jwk(jwk).map_err(|e| format!("Unusable signing key: {}", e))
+    }
+
+    pub async fn validate(&self, token: &str) -> Result<JwtIdentity, String> {
+        let header = decode_header(token).map_err(|e| format!("Malformed token: {}", e))?;
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = decode::<serde_json::Value>(token, &key, &validation)
+            .map_err(|e| format!("Invalid token: {}", e))?
+            .claims;
+
+        let subject = claims["sub"]
+            .as_str()
+            .ok_or("Token has no 'sub' claim")?
+            .to_string();
+        let tenant = claims[&self.config.tenant_claim].as_str().map(str::to_string);
+        let roles = match &claims[&self.config.roles_claim] {
+            serde_json::Value::Array(values) => values
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            serde_json::Value::String(value) => value.split_whitespace().map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(JwtIdentity {
+            subject,
+            tenant,
+            roles,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+use super::LatencyProfile;
+
+/// Requests older than this no longer count toward a model's profile.
+const WINDOW_SECS: i64 = 300;
+
+/// Samples kept per model; the oldest go first.
+const MAX_SAMPLES: usize = 200;
+
+/// A model is only classified once this many requests have succeeded
+/// within the window.
+const MIN_SAMPLES: usize = 5;
+
+/// Tokens per second and streamed time to first token each profile needs.
+const EXTREME: (f64, f64) = (60.0, 300.0);
+const FAST: (f64, f64) = (20.0, 1500.0);
+
+/// A successful request, as far as latency goes.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// Only streamed requests have one; the others return all at once.
+    pub ttft_ms: Option<u64>,
+    pub completion_tokens: u32,
+    pub generation_ms: u64,
+}
+
+/// A model's latency over its recent requests, and the profile it earns.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MeasuredLatency {
+    pub profile: LatencyProfile,
+    pub tokens_per_second: f64,
+    /// Averaged over streamed requests, if there were any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_to_first_token_ms: Option<f64>,
+    pub requests: usize,
+    pub measured_at: DateTime<Utc>,
+}
+
+/// Recent requests per model, from which latency profiles are measured.
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    models: HashMap<String, VecDeque<(DateTime<Utc>, Sample)>>,
+}
+
+impl LatencyTracker {
+    /// Adds a request and measures the model again. `None` until the model
+    /// has enough recent requests.
+    pub fn record(&mut self, model_id: &str, sample: Sample, now: DateTime<Utc>) -> Option<MeasuredLatency> {
+        let samples = self.models.entry(model_id.to_string()).or_default();
+        samples.push_back((now, sample));
+        let cutoff = now - TimeDelta::seconds(WINDOW_SECS);
+        while samples.len() > MAX_SAMPLES || samples.front().is_some_and(|(at, _)| *at < cutoff) {
+            samples.pop_front();
+        }
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let tokens: u64 = samples.iter().map(|(_, sample)| sample.completion_tokens as u64).sum();
+        let generation_ms: u64 = samples.iter().map(|(_, sample)| sample.generation_ms).sum();
+        let tokens_per_second = super::stats::tokens_per_second(tokens, generation_ms);
+        let ttfts: Vec<u64> = samples.iter().filter_map(|(_, sample)| sample.ttft_ms).collect();
+        let time_to_first_token_ms =
+            (!ttfts.is_empty()).then(|| ttfts.iter().sum::<u64>() as f64 / ttfts.len() as f64);
+
+        Some(MeasuredLatency {
+            profile: classify(tokens_per_second, time_to_first_token_ms),
+            tokens_per_second,
+            time_to_first_token_ms,
+            requests: samples.len(),
+            measured_at: now,
+        })
+    }
+
+    /// Drops a model's samples, e.g. once it is loaded with another quant or
+    /// placement, which may run at another speed.
+    pub fn forget(&mut self, model_id: &str) {
+        self.models.remove(model_id);
+    }
+}
+
+fn classify(tokens_per_second: f64, time_to_first_token_ms: Option<f64>) -> LatencyProfile {
+    let meets = |(min_tps, max_ttft): (f64, f64)| {
+        tokens_per_second >= min_tps && time_to_first_token_ms.is_none_or(|ttft| ttft <= max_ttft)
+    };
+    if meets(EXTREME) {
+        LatencyProfile::Extreme
+    } else if meets(FAST) {
+        LatencyProfile::Fast
+    } else {
+        LatencyProfile::Slow
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Datelike, Local, TimeDelta, Timelike};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+use super::auth::{Caller, Role};
+use super::v1::models;
+use super::{AppState, Placement};
+
+/// How far back the scheduler looks on startup for the last load or unload
+/// each model missed.
+const CATCH_UP_DAYS: i64 = 7;
+
+/// When a model is loaded and unloaded, from `--schedule-file`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelSchedule {
+    pub model_id: String,
+    #[serde(default)]
+    pub load: Option<Cron>,
+    #[serde(default)]
+    pub unload: Option<Cron>,
+    /// The quant variant to load; picked by the memory available when unset.
+    #[serde(default)]
+    pub quant: Option<String>,
+    #[serde(flatten)]
+    pub placement: Placement,
+}
+
+impl ModelSchedule {
+    /// Whether the model should be loaded after the last of its times in
+    /// `(after, until]`. `None` when none of them fall in that span.
+    fn last_event(&self, after: DateTime<Local>, until: DateTime<Local>) -> Option<bool> {
+        let mut minute = until;
+        while minute > after {
+            if self.unload.as_ref().is_some_and(|cron| cron.matches(&minute)) {
+                return Some(false);
+            }
+            if self.load.as_ref().is_some_and(|cron| cron.matches(&minute)) {
+                return Some(true);
+            }
+            minute -= TimeDelta::minutes(1);
+        }
+        None
+    }
+}
+
+/// A five-field cron expression: minute, hour, day of month, month and day
+/// of week. Each field is `*`, a number, a range `a-b`, a step `*/n` or
+/// `a-b/n`, or a comma-separated list of these. Sunday is 0 or 7.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// As in cron, when both the day of month and day of week are
+    /// restricted, a time matches if either does.
+    either_day: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("'{}' does not have five fields", expression));
+        };
+        let mut weekday_bits = field(weekdays, 0, 7)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Ok(Self {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            either_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, time: &DateTime<Local>) -> bool {
+        let bit = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = bit(self.days, time.day());
+        let weekday = bit(self.weekdays, time.weekday().num_days_from_sunday());
+        bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.months, time.month())
+            && if self.either_day { day || weekday } else { day && weekday }
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        Self::parse(&expression)
+    }
+}
+
+/// The values one cron field allows, as bits.
+fn field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |value: &str| -> Result<u32, String> {
+        match value.parse() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' is not between {} and {}", value, min, max)),
+        }
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("'{}' is not a valid step", step)),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `a/n` runs from `a` to the end of the field.
+            None if step > 1 => (number(range)?, max),
+            None => {
+                let value = number(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("'{}' is an empty range", range));
+        }
+        for value in (start..=end).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// Reads `--schedule-file`: a JSON list of model schedules.
+pub async fn open(path: &Path) -> Result<Vec<ModelSchedule>, String> {
+    let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let schedules: Vec<ModelSchedule> = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    if let Some(schedule) = schedules.iter().find(|s| s.load.is_none() && s.unload.is_none()) {
+        return Err(format!("The schedule for '{}' has neither a load nor an unload time", schedule.model_id));
+    }
+    Ok(schedules)
+}
+
+/// Loads and unloads models on their schedules, in the server's local time.
+/// On startup each model is brought to the state its last scheduled time
+/// left it in; after that models are only touched at their scheduled times,
+/// so loads and unloads made through the API in between are kept.
+pub fn spawn(state: AppState, schedules: Vec<ModelSchedule>) {
+    let caller = Caller {
+        id: "scheduler".to_string(),
+        role: Role::Admin,
+        tier: None,
+        tenant: None,
+    };
+    tokio::spawn(async move {
+        let mut checked = Local::now() - TimeDelta::days(CATCH_UP_DAYS);
+        loop {
+            let now = Local::now();
+            let minute = now.with_second(0).and_then(|now| now.with_nanosecond(0)).unwrap_or(now);
+            for schedule in &schedules {
+                if let Some(load) = schedule.last_event(checked, minute) {
+                    apply(&state, &caller, schedule, load).await;
+                }
+            }
+            checked = minute;
+
+            let next = minute + TimeDelta::minutes(1);
+            tokio::time::sleep((next - Local::now()).to_std().unwrap_or(Duration::ZERO)).await;
+        }
+    });
+}
+
+async fn apply(state: &AppState, caller: &Caller, schedule: &ModelSchedule, load: bool) {
+    let loaded = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == schedule.model_id)
+        .map(|m| m.registry_entry.loaded);
+    let model_id = &schedule.model_id;
+    match loaded {
+        None => tracing::warn!("Scheduled model '{}' is not registered", model_id),
+        Some(loaded) if loaded == load => {}
+        Some(_) if load => {
+            let placement = schedule.placement.clone();
+            match models::load(state, caller, model_id, schedule.quant.as_deref(), placement).await {
+                Ok(_) => tracing::info!("Loaded model '{}' on schedule", model_id),
+                Err(e) => tracing::warn!("Failed to load model '{}' on schedule: {}", model_id, e),
+            }
+        }
+        Some(_) => match models::unload(state, caller, model_id).await {
+            Ok(()) => tracing::info!("Unloaded model '{}' on schedule", model_id),
+            Err(e) => tracing::warn!("Failed to unload model '{}' on schedule: {}", model_id, e),
+        },
+    }
+}
@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use super::auth::Caller;
+use super::error::ApiError;
+use super::v1::inference::{count_tokens, InferenceRequest};
+use super::AppState;
+
+pub const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+pub const DEFAULT_MAX_DOCUMENT_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    pub max_body_bytes: usize,
+    /// `--max-document-bytes`, which replaces `max_body_bytes` for uploads
+    /// to `/v1/documents`.
+    pub max_document_bytes: usize,
+    pub max_prompt_tokens: Option<u32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_document_bytes: DEFAULT_MAX_DOCUMENT_BYTES,
+            max_prompt_tokens: None,
+            max_tokens: None,
+        }
+    }
+}
+
+/// A request rejected for exceeding a configured limit. The error body
+/// carries `limit` and `actual` so the client can tell which limit was hit.
+#[derive(Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct LimitError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    #[serde(skip)]
+    pub code: &'static str,
+    #[serde(skip)]
+    pub message: String,
+    #[serde(skip)]
+    pub param: Option<&'static str>,
+    pub limit: u64,
+    pub actual: u64,
+}
+
+/// Rejects bodies whose declared `Content-Length` exceeds `--max-body-bytes`
+/// (`--max-document-bytes` for document uploads) before they are read.
+/// Bodies without a length are cut off by the `DefaultBodyLimit` layer instead.
+pub async fn limit_body_size(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let limit = if req.uri().path() == "/v1/documents" {
+        state.limits.max_document_bytes
+    } else {
+        state.limits.max_body_bytes
+    } as u64;
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    match length {
+        Some(length) if length > limit => ApiError::from(LimitError {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            code: "body_too_large",
+            message: format!("Request body of {} bytes exceeds the {} byte limit", length, limit),
+            param: None,
+            limit,
+            actual: length,
+        })
+        .into_response(),
+        _ => next.run(req).await,
+    }
+}
+
+/// Checks `max_tokens` and the prompt length against the configured limits
+/// and the model's context window. Unknown models pass through so the
+/// handler can report them as not found.
+pub async fn check_inference(state: &AppState, caller: &Caller, req: &InferenceRequest) -> Result<(), LimitError> {
+    let limits = &state.limits;
+
+    if let Some(limit) = limits.max_tokens
+        && req.max_tokens() > limit
+    {
+        return Err(LimitError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: "max_tokens_too_large",
+            message: format!("max_tokens of {} exceeds the limit of {}", req.max_tokens(), limit),
+            param: Some("max_tokens"),
+            limit: limit as u64,
+            actual: req.max_tokens() as u64,
+        });
+    }
+
+    let context = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == req.model_id && m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.context);
+
+    let limit = match (limits.max_prompt_tokens, context) {
+        (Some(max), Some(context)) => max.min(context),
+        (Some(max), None) => max,
+        (None, Some(context)) => context,
+        (None, None) => return Ok(()),
+    };
+
+    let prompt_tokens = count_tokens(&req.prompt);
+    if prompt_tokens > limit {
+        return Err(LimitError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: "prompt_too_long",
+            message: format!("Prompt of {} tokens exceeds the limit of {}", prompt_tokens, limit),
+            param: Some("prompt"),
+            limit: limit as u64,
+            actual: prompt_tokens as u64,
+        });
+    }
+
+    Ok(())
+}
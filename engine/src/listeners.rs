@@ -0,0 +1,79 @@
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::net::UnixListener;
+use tokio::sync::watch;
+
+/// Binds `--uds`. A socket left behind by an earlier run is replaced; any
+/// other file at the path is an error.
+pub fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    UnixListener::bind(path)
+}
+
+/// Resolves once shutdown has begun.
+pub async fn drained(mut drain: watch::Receiver<bool>) {
+    let _ = drain.wait_for(|draining| *draining).await;
+}
+
+/// Serves `app` on a Unix socket until shutdown begins, then waits for open
+/// connections to finish and removes the socket. Clients on the socket have
+/// no peer address, so anonymous callers are all `anonymous@unknown`.
+pub async fn serve_unix(
+    listener: UnixListener,
+    path: PathBuf,
+    app: Router,
+    drain: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    let shutdown = drained(drain);
+    tokio::pin!(shutdown);
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept a connection on {}: {}", path.display(), e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        let connection = builder
+            .serve_connection_with_upgrades(TokioIo::new(stream), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::debug!("Connection on a Unix socket failed: {}", e);
+            }
+        });
+    }
+
+    drop(listener);
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::warn!("Failed to remove {}: {}", path.display(), e);
+    }
+    graceful.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use super::request_id::REQUEST_ID_HEADER;
+
+/// A session gives up its slot after this long without a request.
+const SESSION_IDLE_SECS: i64 = 600;
+
+/// A slot as llama-server's `/slots` endpoint reports it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerSlot {
+    pub id: u32,
+    #[serde(default)]
+    pub n_ctx: u32,
+    #[serde(default)]
+    pub is_processing: bool,
+}
+
+/// Asks the llama-server at `base_url` for its slots. Servers started with
+/// `--no-slots` refuse.
+pub async fn fetch(client: &reqwest::Client, base_url: &str, request_id: &str) -> Result<Vec<ServerSlot>, String> {
+    let response = client
+        .get(format!("{}/slots", base_url))
+        .header(REQUEST_ID_HEADER, request_id)
+        .send()
+        .await
+        .map_err(|e| format!("llama.cpp request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("llama.cpp API error: {}", response.status()));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse llama.cpp slots: {}", e))
+}
+
+#[derive(Debug)]
+struct Slot {
+    id: u32,
+    /// The session whose conversation the slot's KV cache holds.
+    session: Option<String>,
+    last_used: Option<DateTime<Utc>>,
+    in_flight: Arc<AtomicU32>,
+}
+
+impl Slot {
+    fn new(slot: &ServerSlot) -> Self {
+        Self {
+            id: slot.id,
+            session: None,
+            last_used: None,
+            in_flight: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Carries a reservation and in-flight count over to a refreshed slot.
+    fn keep(self, known: &Slot) -> Self {
+        Self {
+            session: known.session.clone(),
+            last_used: known.last_used,
+            in_flight: known.in_flight.clone(),
+            ..self
+        }
+    }
+
+    /// Whether a session has used the slot recently enough to keep it.
+    fn reserved(&self, now: DateTime<Utc>) -> bool {
+        self.session.is_some()
+            && self
+                .last_used
+                .is_some_and(|at| now - at < TimeDelta::seconds(SESSION_IDLE_SECS))
+    }
+}
+
+/// A slot picked for a request. The slot counts the request as in flight
+/// until this is dropped.
+#[derive(Debug)]
+pub struct SlotLease {
+    pub id: u32,
+    in_flight: Arc<AtomicU32>,
+}
+
+impl Drop for SlotLease {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A slot as shown by `/v1/backends/llama/slots`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SlotStatus {
+    pub id: u32,
+    pub n_ctx: u32,
+    pub is_processing: bool,
+    /// Requests the engine has running on the slot.
+    pub in_flight: u32,
+    /// The session holding the slot, until `reserved_until`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved_until: Option<DateTime<Utc>>,
+}
+
+/// The slots of each llama-server the engine sends requests to, keyed by
+/// URL, and the session each slot is reserved for. Requests are sent to a
+/// slot of the engine's choosing rather than the server's, so a session
+/// keeps reusing the KV cache of its earlier turns.
+#[derive(Debug, Default)]
+pub struct LlamaSlots {
+    servers: HashMap<String, Vec<Slot>>,
+}
+
+impl LlamaSlots {
+    pub fn knows(&self, base_url: &str) -> bool {
+        self.servers.contains_key(base_url)
+    }
+
+    /// Takes a server's slots as reported, keeping the reservations of slots
+    /// it still has. An empty list leaves the server to pick slots itself.
+    pub fn sync(&mut self, base_url: &str, reported: &[ServerSlot]) {
+        let known = self.servers.remove(base_url).unwrap_or_default();
+        let slots = reported
+            .iter()
+            .map(|slot| match known.iter().find(|known| known.id == slot.id) {
+                Some(known) => Slot::new(slot).keep(known),
+                None => Slot::new(slot),
+            })
+            .collect();
+        self.servers.insert(base_url.to_string(), slots);
+    }
+
+    /// Forgets a server's slots so they are fetched again, e.g. once it has
+    /// failed a request and may have restarted with other slots.
+    pub fn forget(&mut self, base_url: &str) {
+        self.servers.remove(base_url);
+    }
+
+    /// Picks the slot for a request. A session gets back the slot it holds;
+    /// otherwise the request takes the least busy, least recently used slot
+    /// no session holds. When sessions hold every slot, the one idle longest
+    /// loses its slot. `None` when the server's slots are unknown.
+    pub fn assign(&mut self, base_url: &str, session: Option<&str>, now: DateTime<Utc>) -> Option<SlotLease> {
+        let slots = self.servers.get_mut(base_url)?;
+        let index = session
+            .and_then(|session| slots.iter().position(|slot| slot.session.as_deref() == Some(session)))
+            .or_else(|| {
+                slots
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, slot)| {
+                        (slot.reserved(now), slot.in_flight.load(Ordering::SeqCst), slot.last_used)
+                    })
+                    .map(|(index, _)| index)
+            })?;
+
+        let slot = &mut slots[index];
+        slot.session = session.map(str::to_string);
+        slot.last_used = Some(now);
+        slot.in_flight.fetch_add(1, Ordering::SeqCst);
+        Some(SlotLease {
+            id: slot.id,
+            in_flight: slot.in_flight.clone(),
+        })
+    }
+
+    /// The server's slots as reported, with the engine's reservations.
+    pub fn report(&self, base_url: &str, reported: &[ServerSlot], now: DateTime<Utc>) -> Vec<SlotStatus> {
+        let known = self.servers.get(base_url);
+        reported
+            .iter()
+            .map(|slot| {
+                let known = known.and_then(|slots| slots.iter().find(|known| known.id == slot.id));
+                let reserved = known.filter(|known| known.reserved(now));
+                SlotStatus {
+                    id: slot.id,
+                    n_ctx: slot.n_ctx,
+                    is_processing: slot.is_processing,
+                    in_flight: known.map_or(0, |known| known.in_flight.load(Ordering::SeqCst)),
+                    session: reserved.and_then(|known| known.session.clone()),
+                    reserved_until: reserved
+                        .and_then(|known| known.last_used)
+                        .map(|at| at + TimeDelta::seconds(SESSION_IDLE_SECS)),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,61 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use clap::ValueEnum;
+use std::time::Instant;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+
+use super::request_id::RequestId;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable single-line output
+    Text,
+    /// One JSON object per line, including request-scoped span fields
+    Json,
+}
+
+/// Installs the global tracing subscriber. `RUST_LOG` takes precedence over
+/// the level passed on the command line so individual modules can still be
+/// tuned without a rebuild.
+pub fn init(level: &str, format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(filter)
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
+
+/// Wraps every request in a span carrying the request id, method and path,
+/// and logs the response status and latency when the handler returns.
+pub async fn trace_requests(req: Request, next: Next) -> Response {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+
+    async move {
+        let started = Instant::now();
+        let response = next.run(req).await;
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "request completed"
+        );
+        response
+    }
+    .instrument(span)
+    .await
+}
@@ -1,26 +1,193 @@
-use axum::{routing::{get, post}, Router};
+use axum::{extract::DefaultBodyLimit, middleware, routing::{delete, get, post, put}, Router};
 use chrono::{DateTime, Utc};
 use clap::{Parser, ValueEnum};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
+use utoipa::ToSchema;
+use utoipa_swagger_ui::SwaggerUi;
 
+mod accounting;
+mod admission;
+mod audit;
+mod auth;
+mod backend_client;
+mod backend_health;
+mod backends;
+mod cassettes;
+mod compression;
+mod config;
+mod context;
+mod cors;
+mod dashboard;
+mod documents;
+mod embedding_cache;
+mod ensemble;
+mod etag;
+mod error;
+mod gguf;
+mod guardrails;
+mod injection;
+mod jwt;
+mod latency;
+mod lifecycle;
+mod limits;
+mod listeners;
+mod llama_slots;
+mod logging;
+mod mock_backend;
+mod model_metadata;
+mod openapi;
+mod persistence;
+mod pipeline;
+mod plugins;
+mod pools;
+mod prompts;
+mod pii;
+mod postprocess;
+mod quota;
+mod rag;
+mod rate_limit;
+mod request_id;
+mod routing;
+mod shutdown;
+mod sse;
+mod stats;
+mod storage;
+mod templates;
+mod tenants;
+mod tls;
+mod upstream_limits;
+mod vector_store;
 mod v1;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use accounting::Accounting;
+use admission::{Admission, ModelConcurrency};
+use audit::{AuditConfig, AuditLog, PromptRedaction};
+use auth::{ApiKeyStore, AuthSettings, Caller};
+use backend_client::BackendClients;
+use backend_health::BackendHealthCache;
+use cassettes::CassetteMode;
+use config::EffectiveConfig;
+use context::TruncationStrategy;
+use documents::DocumentJobs;
+use embedding_cache::EmbeddingCache;
+use etag::RegistryRevision;
+use guardrails::{Guardrails, GuardrailsConfig, ModerationAction, ModerationStage};
+use injection::{ClassifierConfig, InjectionAction, InjectionPolicy, InjectionScreener};
+use jwt::{JwtConfig, JwtValidator};
+use latency::{LatencyTracker, MeasuredLatency};
+use limits::RequestLimits;
+use llama_slots::LlamaSlots;
+use rate_limit::{RateLimitTier, RateLimiter};
+use logging::LogFormat;
+use pii::{PiiConfig, PiiEntity, PiiRedactor};
+use postprocess::PostProcessor;
+use plugins::Plugins;
+use pools::PoolStore;
+use prompts::PromptStore;
+use routing::RoutingPolicy;
+use sse::{Replays, SseSettings};
+use stats::UsageStats;
+use tenants::TenantStore;
+use upstream_limits::UpstreamLimits;
+use vector_store::VectorStore;
+use v1::benchmark::BenchmarkReport;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum, ToSchema)]
 pub enum InferenceBackend {
     #[serde(rename = "ollama")]
+    #[value(name = "ollama")]
     Ollama,
     #[serde(rename = "llama")]
+    #[value(name = "llama")]
     Llama,
     #[serde(rename = "huggingface")]
+    #[value(name = "huggingface")]
     HuggingFace,
     #[serde(rename = "openai")]
+    #[value(name = "openai")]
     OpenAI,
+    #[serde(rename = "azure_openai")]
+    #[value(name = "azure_openai")]
+    AzureOpenAI,
+    #[serde(rename = "anthropic")]
+    #[value(name = "anthropic")]
+    Anthropic,
+    #[serde(rename = "gemini")]
+    #[value(name = "gemini")]
+    Gemini,
+    #[serde(rename = "bedrock")]
+    #[value(name = "bedrock")]
+    Bedrock,
+    #[serde(rename = "sagemaker")]
+    #[value(name = "sagemaker")]
+    SageMaker,
+    #[serde(rename = "vertex")]
+    #[value(name = "vertex")]
+    Vertex,
+    #[serde(rename = "mistral")]
+    #[value(name = "mistral")]
+    Mistral,
+    #[serde(rename = "cohere")]
+    #[value(name = "cohere")]
+    Cohere,
+    #[serde(rename = "groq")]
+    #[value(name = "groq")]
+    Groq,
+    #[serde(rename = "openrouter")]
+    #[value(name = "openrouter")]
+    OpenRouter,
+    #[serde(rename = "deepseek")]
+    #[value(name = "deepseek")]
+    DeepSeek,
+    #[serde(rename = "xai")]
+    #[value(name = "xai")]
+    Xai,
+    #[serde(rename = "together")]
+    #[value(name = "together")]
+    Together,
+    #[serde(rename = "fireworks")]
+    #[value(name = "fireworks")]
+    Fireworks,
+    #[serde(rename = "replicate")]
+    #[value(name = "replicate")]
+    Replicate,
+    #[serde(rename = "tgi")]
+    #[value(name = "tgi")]
+    Tgi,
+    #[serde(rename = "lmstudio")]
+    #[value(name = "lmstudio")]
+    LmStudio,
+    #[serde(rename = "koboldcpp")]
+    #[value(name = "koboldcpp")]
+    KoboldCpp,
+    #[serde(rename = "mlx")]
+    #[value(name = "mlx")]
+    Mlx,
+    #[serde(rename = "triton")]
+    #[value(name = "triton")]
+    Triton,
+    /// Runs GGUF models inside the engine with llama.cpp.
+    #[serde(rename = "llama_local")]
+    #[value(name = "llama_local")]
+    LlamaLocal,
+    /// Runs Llama, Mistral and Phi safetensors models inside the engine with
+    /// candle. Needs the `candle` feature.
+    #[serde(rename = "candle")]
+    #[value(name = "candle")]
+    Candle,
+    /// Built-in backend that echoes prompts or returns canned text, for
+    /// tests and local development.
+    #[serde(rename = "mock")]
+    #[value(name = "mock")]
+    Mock,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum ModelCapability {
     #[serde(rename = "chat")]
     Chat,
@@ -30,9 +197,11 @@ pub enum ModelCapability {
     Embedding,
     #[serde(rename = "completion")]
     Completion,
+    #[serde(rename = "rerank")]
+    Rerank,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum LatencyProfile {
     #[serde(rename = "extreme")]
     Extreme,
@@ -42,20 +211,143 @@ pub enum LatencyProfile {
     Slow,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Prices in USD per 1,000 tokens, used for per-request cost estimates.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ModelPricing {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+/// One quantization of a model, loaded in place of the others.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuantVariant {
+    /// E.g. `Q4_K_M`, `Q8_0` or `FP16`.
+    pub quant: String,
+    /// The backend's name for the variant, such as an Ollama tag or a GGUF
+    /// file. The model's `id` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub size_bytes: u64,
+}
+
+/// Where a model runs, as hinted when it is loaded, for backends that place
+/// models themselves.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Placement {
+    /// The GPU that holds the model, or its scratch buffers when the model
+    /// is split across GPUs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_gpu: Option<u32>,
+    /// Layers offloaded to the GPU. `0` keeps the model on the CPU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n_gpu_layers: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numa: Option<NumaStrategy>,
+}
+
+impl Placement {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How llama.cpp spreads work over NUMA nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NumaStrategy {
+    /// Spread threads evenly across nodes.
+    Distribute,
+    /// Keep threads on the node the engine started on.
+    Isolate,
+    /// Follow the CPU map `numactl` set.
+    Numactl,
+}
+
+/// A smaller model of the same family that drafts tokens for the registered
+/// model to verify, several at a time.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SpeculativeDecoding {
+    /// Id of the draft model, which must be registered on the same backend.
+    pub draft_model: String,
+    /// Most tokens drafted per step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft_max: Option<u32>,
+    /// Fewest tokens drafted per step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft_min: Option<u32>,
+    /// Drafting stops at a token the draft model is less sure of than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft_p_min: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ModelRegistryEntry {
     pub id: String,
     pub name: String,
     pub inference: InferenceBackend,
     pub context: u32,
+    /// With `variants`, the one loaded last.
     #[serde(default)]
     pub quant: Option<String>,
+    /// Quantizations to choose from when loading the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<QuantVariant>,
     pub capabilities: Vec<ModelCapability>,
+    /// A routing hint. Measured from the model's recent requests unless it
+    /// was given at registration.
     #[serde(default)]
     pub latency: Option<LatencyProfile>,
+    /// Whether `latency` was given at registration and is kept as is.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub latency_pinned: bool,
+    /// The recent latency the model was last classified by.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measured_latency: Option<MeasuredLatency>,
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+    /// Chat template for backends that take pre-formatted prompts: `chatml`,
+    /// `llama3`, `mistral`, or a custom Jinja template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    /// Applied to generated text unless a request brings its own pipeline.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_process: Vec<PostProcessor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speculative: Option<SpeculativeDecoding>,
+    /// Generations the model runs at once; further requests wait their
+    /// turn. Unbounded when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
     pub size_bytes: u64,
     pub loaded: bool,
     pub loaded_at: Option<DateTime<Utc>>,
+    /// The placement the model was loaded with, while it is loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<Placement>,
+    /// The results of the model's latest benchmark.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<BenchmarkReport>,
+    /// Tenant that registered the model; only that tenant can see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl ModelRegistryEntry {
+    pub fn visible_to(&self, caller: &Caller) -> bool {
+        caller.can_see(self.tenant.as_deref())
+    }
+
+    /// The backend's name for the model: that of the current quant variant,
+    /// if it names one, or the model's `id`.
+    pub fn backend_model(&self) -> &str {
+        self.variants
+            .iter()
+            .find(|variant| Some(&variant.quant) == self.quant.as_ref())
+            .and_then(|variant| variant.model.as_deref())
+            .unwrap_or(&self.id)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -67,12 +359,84 @@ pub struct LoadedModel {
 #[derive(Clone)]
 pub struct AppState {
     pub models: Arc<Mutex<Vec<LoadedModel>>>,
+    pub registry_revision: Arc<RegistryRevision>,
+    pub stats: Arc<Mutex<UsageStats>>,
+    pub audit: Option<AuditLog>,
+    pub accounting: Arc<Mutex<Accounting>>,
+    pub backend_health: Arc<Mutex<BackendHealthCache>>,
+    pub startup_complete: Arc<AtomicBool>,
+    pub shutting_down: Arc<AtomicBool>,
+    pub required_backends: Arc<Vec<InferenceBackend>>,
+    pub auth: Arc<AuthSettings>,
+    pub api_keys: Arc<Mutex<ApiKeyStore>>,
+    pub tenants: Arc<Mutex<TenantStore>>,
+    pub prompts: Arc<Mutex<PromptStore>>,
+    pub pools: Arc<Mutex<PoolStore>>,
+    pub rate_limiter: Arc<Mutex<RateLimiter>>,
+    pub jwt: Option<Arc<JwtValidator>>,
+    pub backend_clients: Arc<BackendClients>,
+    pub upstream_limits: Arc<Mutex<UpstreamLimits>>,
+    pub llama_slots: Arc<Mutex<LlamaSlots>>,
+    pub latency: Arc<Mutex<LatencyTracker>>,
+    pub limits: Arc<RequestLimits>,
+    pub truncation: Option<TruncationStrategy>,
+    pub sse: SseSettings,
+    pub admission: Arc<Admission>,
+    pub model_concurrency: Arc<Mutex<ModelConcurrency>>,
+    pub replays: Arc<Mutex<Replays>>,
+    /// `--memory-budget`.
+    pub memory_budget: Option<u64>,
+    pub embedding_cache: Option<Arc<Mutex<EmbeddingCache>>>,
+    pub vector_store: Option<Arc<VectorStore>>,
+    pub document_jobs: Arc<Mutex<DocumentJobs>>,
+    pub guardrails: Option<Arc<Guardrails>>,
+    pub pii: Option<Arc<PiiRedactor>>,
+    pub injection: Arc<InjectionScreener>,
+    pub plugins: Option<Arc<Plugins>>,
+    pub routing: Option<Arc<RoutingPolicy>>,
+    /// The settings the engine started with, for `/v1/config`.
+    pub config: Arc<EffectiveConfig>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             models: Arc::new(Mutex::new(Vec::new())),
+            registry_revision: Arc::new(RegistryRevision::default()),
+            stats: Arc::new(Mutex::new(UsageStats::default())),
+            audit: None,
+            accounting: Arc::new(Mutex::new(Accounting::default())),
+            backend_health: Arc::new(Mutex::new(BackendHealthCache::default())),
+            startup_complete: Arc::new(AtomicBool::new(false)),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            required_backends: Arc::new(Vec::new()),
+            auth: Arc::new(AuthSettings::default()),
+            api_keys: Arc::new(Mutex::new(ApiKeyStore::default())),
+            tenants: Arc::new(Mutex::new(TenantStore::default())),
+            prompts: Arc::new(Mutex::new(PromptStore::default())),
+            pools: Arc::new(Mutex::new(PoolStore::default())),
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::default())),
+            jwt: None,
+            backend_clients: Arc::new(BackendClients::default()),
+            upstream_limits: Arc::new(Mutex::new(UpstreamLimits::default())),
+            llama_slots: Arc::new(Mutex::new(LlamaSlots::default())),
+            latency: Arc::new(Mutex::new(LatencyTracker::default())),
+            limits: Arc::new(RequestLimits::default()),
+            truncation: None,
+            sse: SseSettings::default(),
+            admission: Arc::new(Admission::default()),
+            model_concurrency: Arc::new(Mutex::new(ModelConcurrency::default())),
+            replays: Arc::new(Mutex::new(Replays::default())),
+            memory_budget: None,
+            embedding_cache: None,
+            vector_store: None,
+            document_jobs: Arc::new(Mutex::new(DocumentJobs::default())),
+            guardrails: None,
+            pii: None,
+            injection: Arc::new(InjectionScreener::default()),
+            plugins: None,
+            routing: None,
+            config: Arc::new(EffectiveConfig::default()),
         }
     }
 }
@@ -88,20 +452,296 @@ enum LogLevel {
 #[command(name = "openllm-server")]
 #[command(author = "Solace Contributors")]
 #[command(version = "1.0.0")]
-#[command(about = "OpenLLM inference engine - optimizes interactions with Ollama, HuggingFace, llama.cpp, and OpenAI-compatible APIs", long_about = None)]
+#[command(about = "OpenLLM inference engine - optimizes interactions with Ollama, HuggingFace, llama.cpp, Anthropic, Gemini, Bedrock, and OpenAI-compatible APIs", long_about = None)]
 struct Args {
+    #[arg(long, env = "OPENLLM_CONFIG", value_name = "PATH")]
+    #[arg(help = "TOML config file whose profiles set these options and backend environment variables")]
+    config: Option<PathBuf>,
+
+    #[arg(long, env = "OPENLLM_PROFILE", default_value = config::DEFAULT_PROFILE)]
+    #[arg(help = "Config file profile to apply over its default profile, e.g. dev, staging or prod")]
+    profile: String,
+
     #[arg(short, long, default_value = "8080")]
     #[arg(help = "Port to run the server on")]
     port: u16,
 
+    #[arg(long, visible_alias = "bind", value_name = "IP", default_value = "0.0.0.0", conflicts_with = "listen")]
+    #[arg(help = "IP address to serve --port on, e.g. 127.0.0.1, :: or ::1; 0.0.0.0 listens on every IPv4 interface")]
+    host: std::net::IpAddr,
+
+    #[arg(long = "listen", value_name = "ADDR")]
+    #[arg(help = "TCP address to listen on, e.g. 127.0.0.1:8080 or [::1]:8080 (repeatable); replaces --host and --port")]
+    listen: Vec<std::net::SocketAddr>,
+
+    #[arg(long = "admin-listen", value_name = "ADDR")]
+    #[arg(help = "TCP address for a separate admin listener (repeatable); management, stats and the dashboard are then served only there")]
+    admin_listen: Vec<std::net::SocketAddr>,
+
+    #[arg(long, value_name = "PATH")]
+    #[arg(help = "Unix socket to listen on; without --listen, the engine listens on no TCP port")]
+    uds: Option<PathBuf>,
+
     #[arg(short, long, value_enum)]
     #[arg(help = "Log level (info, debug, trace)")]
     log: Option<LogLevel>,
+
+    #[arg(long, value_enum, default_value = "text")]
+    #[arg(help = "Log output format (text, json)")]
+    log_format: LogFormat,
+
+    #[arg(long = "required-backend", value_enum)]
+    #[arg(help = "Backend that must be reachable for /readyz to report ready (repeatable)")]
+    required_backends: Vec<InferenceBackend>,
+
+    #[arg(long)]
+    #[arg(help = "Allow requests without an API key (intended for local development)")]
+    allow_anonymous: bool,
+
+    #[arg(long)]
+    #[arg(help = "Do not compress responses, even when the client accepts gzip, brotli or zstd")]
+    no_compression: bool,
+
+    #[arg(long, conflicts_with = "no_compression")]
+    #[arg(help = "Also compress streaming (SSE) responses")]
+    compress_streams: bool,
+
+    #[arg(long)]
+    #[arg(help = "Serve Swagger UI for the OpenAPI spec at /docs")]
+    swagger_ui: bool,
+
+    #[arg(long, env = "OPENLLM_ADMIN_KEY", hide_env_values = true)]
+    #[arg(help = "Bootstrap admin key used to manage API keys")]
+    admin_key: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "File issued API keys are stored in (hashed); keys are kept in memory only when unset")]
+    api_keys_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "File tenant limits and usage are stored in; kept in memory only when unset")]
+    tenants_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "File named prompt templates are stored in; kept in memory only when unset")]
+    prompts_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "File model pools are stored in; kept in memory only when unset")]
+    pools_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "OIDC issuer whose JWTs are accepted as bearer credentials")]
+    jwt_issuer: Option<String>,
+
+    #[arg(long, requires = "jwt_issuer")]
+    #[arg(help = "Required 'aud' claim for JWTs")]
+    jwt_audience: Option<String>,
+
+    #[arg(long, requires = "jwt_issuer")]
+    #[arg(help = "JWKS URL (discovered from the issuer when unset)")]
+    jwks_url: Option<String>,
+
+    #[arg(long, default_value = "tenant")]
+    #[arg(help = "JWT claim holding the caller's tenant")]
+    jwt_tenant_claim: String,
+
+    #[arg(long, default_value = "roles")]
+    #[arg(help = "JWT claim holding the caller's roles (array or space-separated string)")]
+    jwt_roles_claim: String,
+
+    #[arg(long, default_value = "admin")]
+    #[arg(help = "Role that grants admin access")]
+    jwt_admin_role: String,
+
+    #[arg(long = "rate-limit-tier", value_parser = rate_limit::parse_tier)]
+    #[arg(help = "Rate limit tier as NAME=REQUESTS_PER_MIN:TOKENS_PER_MIN; keys without a matching tier use 'default' (repeatable)")]
+    rate_limit_tiers: Vec<(String, RateLimitTier)>,
+
+    #[arg(long)]
+    #[arg(help = "Directory to write rotating JSONL audit logs to (disabled when unset)")]
+    audit_log: Option<PathBuf>,
+
+    #[arg(long, default_value = "10485760")]
+    #[arg(help = "Maximum size in bytes of an audit log file before it is rotated")]
+    audit_max_bytes: u64,
+
+    #[arg(long, default_value = "5")]
+    #[arg(help = "Number of rotated audit log files to keep")]
+    audit_max_files: usize,
+
+    #[arg(long, value_enum, default_value = "hash")]
+    #[arg(help = "How prompt content is recorded in the audit log (hash, truncate, full)")]
+    audit_redaction: PromptRedaction,
+
+    #[arg(long)]
+    #[arg(help = "File the model registry is saved to as it changes and restored from on startup, reloading the models that were loaded")]
+    state_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "JSON file of cron schedules that models are loaded and unloaded on")]
+    schedule_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "JSON file of WebAssembly plugins that hook into requests and responses (needs the plugins feature)")]
+    plugins_file: Option<PathBuf>,
+
+    #[arg(long)]
+    #[arg(help = "Rhai script that routes or rejects inference requests (needs the scripting feature)")]
+    routing_script: Option<PathBuf>,
+
+    #[arg(long, default_value = "5", requires = "routing_script")]
+    #[arg(help = "Seconds between checks for a changed --routing-script")]
+    routing_reload_interval: u64,
+
+    #[arg(long, default_value = "30")]
+    #[arg(help = "Seconds to wait for in-flight requests and streams to finish on shutdown")]
+    drain_timeout: u64,
+
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(help = "Generation requests run at once; further requests queue (unbounded when unset)")]
+    max_concurrent_requests: Option<u64>,
+
+    #[arg(long, default_value = "32")]
+    #[arg(help = "Requests that may wait for --max-concurrent-requests; more are rejected with 429")]
+    max_queued_requests: usize,
+
+    #[arg(long, default_value = "15", value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(help = "Seconds without an event before a keep-alive comment is sent on a stream")]
+    sse_keep_alive: u64,
+
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(help = "Seconds without an event before a `ping` event with the elapsed time is sent on a stream (disabled when unset)")]
+    sse_ping: Option<u64>,
+
+    #[arg(long)]
+    #[arg(help = "Seconds a finished stream can still be resumed with Last-Event-ID; streams also run to the end when their client disconnects (disabled when unset)")]
+    sse_resume_window: Option<u64>,
+
+    #[arg(long)]
+    #[arg(help = "Memory in bytes loaded models may take, by their registered size; picking a quant variant automatically uses the system's available memory when unset")]
+    memory_budget: Option<u64>,
+
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    #[arg(help = "Embedding vectors to keep, so identical texts are not embedded again (disabled when unset)")]
+    embedding_cache_size: Option<usize>,
+
+    #[arg(long, default_value = "3600", requires = "embedding_cache_size")]
+    #[arg(help = "Seconds a cached embedding vector is reused for")]
+    embedding_cache_ttl: u64,
+
+    #[arg(long, value_name = "URL")]
+    #[arg(help = "Vector store for /v1/vectors: a Qdrant URL (http://localhost:6333) or a PostgreSQL connection string with pgvector (postgres://...)")]
+    vector_store: Option<String>,
+
+    #[arg(long, default_value_t = limits::DEFAULT_MAX_BODY_BYTES)]
+    #[arg(help = "Maximum request body size in bytes")]
+    max_body_bytes: usize,
+
+    #[arg(long, default_value_t = limits::DEFAULT_MAX_DOCUMENT_BYTES)]
+    #[arg(help = "Maximum size in bytes of a document uploaded to /v1/documents")]
+    max_document_bytes: usize,
+
+    #[arg(long)]
+    #[arg(help = "Maximum prompt length in tokens; prompts are also limited to the model's context")]
+    max_prompt_tokens: Option<u32>,
+
+    #[arg(long)]
+    #[arg(help = "Maximum max_tokens a request may ask for")]
+    max_tokens: Option<u32>,
+
+    #[arg(long, value_enum)]
+    #[arg(help = "How to shrink prompts that exceed the model's context (drop-oldest, sliding-window, middle, summarize); oversized prompts are rejected when unset")]
+    truncation: Option<TruncationStrategy>,
+
+    #[arg(long, value_enum)]
+    #[arg(help = "Run prompts and/or responses through a moderation model (input, output, both); disabled when unset")]
+    moderation: Option<ModerationStage>,
+
+    #[arg(long, requires = "moderation")]
+    #[arg(help = "OpenAI-compatible moderation endpoint; defaults to the OpenAI backend's /moderations")]
+    moderation_url: Option<String>,
+
+    #[arg(long = "moderation-rule", value_parser = guardrails::parse_rule, requires = "moderation")]
+    #[arg(help = "Action for a flagged category as CATEGORY=ACTION (annotate, redact, block), '*' matching any category (repeatable; every flagged category blocks when unset)")]
+    moderation_rules: Vec<(String, ModerationAction)>,
+
+    #[arg(long, requires = "moderation")]
+    #[arg(help = "Flag categories whose score reaches this value instead of using the moderation model's own flags")]
+    moderation_threshold: Option<f64>,
+
+    #[arg(long, requires = "moderation")]
+    #[arg(help = "Let requests through when the moderation endpoint fails instead of rejecting them")]
+    moderation_fail_open: bool,
+
+    #[arg(long)]
+    #[arg(help = "Mask personal data (emails, phone numbers, card numbers, ...) in prompts sent to hosted backends")]
+    redact_pii: bool,
+
+    #[arg(long = "pii-backend", value_enum, requires = "redact_pii")]
+    #[arg(help = "Backend whose prompts are redacted (repeatable; defaults to openai and huggingface)")]
+    pii_backends: Vec<InferenceBackend>,
+
+    #[arg(long = "pii-entity", value_enum, requires = "redact_pii")]
+    #[arg(help = "Kind of personal data to mask (email, phone, credit-card, ssn, ip-address) (repeatable; defaults to all)")]
+    pii_entities: Vec<PiiEntity>,
+
+    #[arg(long, requires = "redact_pii")]
+    #[arg(help = "Hugging Face style token-classification endpoint used to also mask names, places and organizations")]
+    pii_ner_url: Option<String>,
+
+    #[arg(long, default_value = "0.5", requires = "pii_ner_url")]
+    #[arg(help = "Minimum score for an entity from --pii-ner-url to be masked")]
+    pii_ner_min_score: f64,
+
+    #[arg(long, value_enum, default_value = "off")]
+    #[arg(help = "What to do with prompts scored as likely injection attempts (off, flag, reject); tenants can override this")]
+    injection_action: InjectionAction,
+
+    #[arg(long, default_value_t = injection::default_threshold())]
+    #[arg(help = "Injection score from 0 to 1 at which --injection-action applies")]
+    injection_threshold: f64,
+
+    #[arg(long)]
+    #[arg(help = "Hugging Face style text-classification endpoint scoring prompts for injection, combined with the built-in heuristics")]
+    injection_classifier_url: Option<String>,
+
+    #[arg(long, value_enum)]
+    #[arg(help = "Record backend traffic to --cassette-dir, or replay it from there without contacting any backend")]
+    cassettes: Option<CassetteMode>,
+
+    #[arg(long, default_value = "cassettes")]
+    #[arg(help = "Directory holding recorded backend exchanges")]
+    cassette_dir: PathBuf,
+
+    #[arg(long = "cors-origin")]
+    #[arg(help = "Origin allowed to call the API from a browser, or '*' for any (repeatable; CORS is disabled when unset)")]
+    cors_origins: Vec<String>,
+
+    #[arg(long = "cors-header", requires = "cors_origins")]
+    #[arg(help = "Request header browsers may send (repeatable; defaults to authorization, content-type, x-request-id)")]
+    cors_headers: Vec<String>,
+
+    #[arg(long = "cors-method", requires = "cors_origins")]
+    #[arg(help = "HTTP method browsers may use (repeatable; defaults to GET, POST, DELETE)")]
+    cors_methods: Vec<String>,
+
+    #[arg(long, requires = "tls_key")]
+    #[arg(help = "PEM certificate chain; serves HTTPS when set together with --tls-key")]
+    tls_cert: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_cert")]
+    #[arg(help = "PEM private key for --tls-cert")]
+    tls_key: Option<PathBuf>,
+
+    #[arg(long, requires = "tls_cert")]
+    #[arg(help = "Seconds between checks for a rotated certificate; reloading is disabled when unset")]
+    tls_reload_interval: Option<u64>,
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let (args, effective_config) = config::load::<Args>().unwrap_or_else(|e| panic!("Failed to load config: {}", e));
 
     let log_level = match args.log {
         Some(LogLevel::Debug) => "debug",
@@ -109,41 +749,565 @@ async fn main() {
         None | Some(LogLevel::Info) => "info",
     };
 
-    tracing_subscriber::fmt::init();
+    logging::init(log_level, args.log_format);
 
     tracing::info!("OpenLLM Inference Engine v1.0.0");
-    tracing::info!("Optimized for Ollama, HuggingFace, llama.cpp, and OpenAI-compatible APIs");
+    tracing::info!("Optimized for Ollama, HuggingFace, llama.cpp, Anthropic, Gemini, Bedrock, and OpenAI-compatible APIs");
+    if let Some(file) = &effective_config.file {
+        tracing::info!("Using the '{}' profile of {}", effective_config.profile, file.display());
+    }
 
-    let state = AppState::default();
+    let api_keys = ApiKeyStore::open(args.api_keys_file.clone())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load API keys: {}", e));
+    let tenants = TenantStore::open(args.tenants_file.clone())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load tenants: {}", e));
+    let prompts = PromptStore::open(args.prompts_file.clone())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load prompts: {}", e));
+    let pools = PoolStore::open(args.pools_file.clone())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load pools: {}", e));
 
-    let app = Router::new()
-        .route("/health", get(v1::health_check))
-        .route("/v1/models", get(v1::list_models))
+    let backend_clients = Arc::new(
+        BackendClients::from_env().unwrap_or_else(|e| panic!("Invalid backend TLS configuration: {}", e)),
+    );
+
+    // Must run before anything resolves a backend URL.
+    if let Some(mode) = args.cassettes {
+        cassettes::start(mode, args.cassette_dir.clone(), backend_clients.clone())
+            .await
+            .unwrap_or_else(|e| panic!("Failed to start cassette proxy: {}", e));
+    }
+
+    let mut state = AppState {
+        required_backends: Arc::new(args.required_backends.clone()),
+        auth: Arc::new(AuthSettings {
+            allow_anonymous: args.allow_anonymous,
+            admin_key_hash: args.admin_key.as_deref().map(auth::hash_key),
+        }),
+        api_keys: Arc::new(Mutex::new(api_keys)),
+        tenants: Arc::new(Mutex::new(tenants)),
+        prompts: Arc::new(Mutex::new(prompts)),
+        pools: Arc::new(Mutex::new(pools)),
+        rate_limiter: Arc::new(Mutex::new(RateLimiter::new(
+            args.rate_limit_tiers.iter().cloned().collect(),
+        ))),
+        jwt: args.jwt_issuer.clone().map(|issuer| {
+            Arc::new(JwtValidator::new(JwtConfig {
+                issuer,
+                audience: args.jwt_audience.clone(),
+                jwks_url: args.jwks_url.clone(),
+                tenant_claim: args.jwt_tenant_claim.clone(),
+                roles_claim: args.jwt_roles_claim.clone(),
+                admin_role: args.jwt_admin_role.clone(),
+            }))
+        }),
+        backend_clients,
+        limits: Arc::new(RequestLimits {
+            max_body_bytes: args.max_body_bytes,
+            max_document_bytes: args.max_document_bytes,
+            max_prompt_tokens: args.max_prompt_tokens,
+            max_tokens: args.max_tokens,
+        }),
+        truncation: args.truncation,
+        sse: SseSettings {
+            keep_alive: Duration::from_secs(args.sse_keep_alive),
+            ping: args.sse_ping.map(Duration::from_secs),
+            resume_window: args.sse_resume_window.map(Duration::from_secs),
+        },
+        admission: Arc::new(Admission::new(
+            args.max_concurrent_requests.map(|max| max as usize),
+            args.max_queued_requests,
+        )),
+        memory_budget: args.memory_budget,
+        embedding_cache: args.embedding_cache_size.map(|capacity| {
+            Arc::new(Mutex::new(EmbeddingCache::new(
+                capacity,
+                Duration::from_secs(args.embedding_cache_ttl),
+            )))
+        }),
+        vector_store: args.vector_store.as_deref().map(|url| {
+            let store = VectorStore::new(url).unwrap_or_else(|e| panic!("Invalid vector store: {}", e));
+            tracing::info!("Vector store: {}", store.name());
+            Arc::new(store)
+        }),
+        guardrails: args.moderation.map(|stage| {
+            // Only send the OpenAI key to OpenAI's own endpoint.
+            let (url, api_key) = match args.moderation_url.clone() {
+                Some(url) => (url, config::var("MODERATION_API_KEY").ok()),
+                None => (
+                    format!("{}/moderations", v1::inference::get_backend_url(&InferenceBackend::OpenAI)),
+                    config::var("OPENAI_API_KEY").ok(),
+                ),
+            };
+            Arc::new(Guardrails::new(GuardrailsConfig {
+                url,
+                api_key,
+                stage,
+                rules: args.moderation_rules.iter().cloned().collect(),
+                threshold: args.moderation_threshold,
+                fail_open: args.moderation_fail_open,
+            }))
+        }),
+        pii: args.redact_pii.then(|| {
+            let backends = if args.pii_backends.is_empty() {
+                vec![InferenceBackend::OpenAI, InferenceBackend::HuggingFace]
+            } else {
+                args.pii_backends.clone()
+            };
+            let entities = if args.pii_entities.is_empty() {
+                PiiEntity::ALL.to_vec()
+            } else {
+                args.pii_entities.clone()
+            };
+            Arc::new(PiiRedactor::new(PiiConfig {
+                backends,
+                entities,
+                ner_url: args.pii_ner_url.clone(),
+                ner_api_key: config::var("PII_NER_API_KEY").ok(),
+                ner_min_score: args.pii_ner_min_score,
+            }))
+        }),
+        injection: Arc::new(InjectionScreener::new(
+            InjectionPolicy {
+                action: args.injection_action,
+                threshold: args.injection_threshold,
+            },
+            args.injection_classifier_url.clone().map(|url| ClassifierConfig {
+                url,
+                api_key: config::var("INJECTION_CLASSIFIER_API_KEY").ok(),
+            }),
+        )),
+        config: Arc::new(effective_config),
+        ..AppState::default()
+    };
+
+    if args.allow_anonymous {
+        tracing::warn!("Anonymous access enabled; /v1 routes accept requests without an API key");
+    }
+
+    if let Some(dir) = args.audit_log.clone() {
+        let config = AuditConfig {
+            dir: dir.clone(),
+            max_bytes: args.audit_max_bytes,
+            max_files: args.audit_max_files,
+            redaction: args.audit_redaction,
+        };
+        let audit = AuditLog::start(config)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to open audit log in {}: {}", dir.display(), e));
+        tracing::info!("Audit logging enabled in {}", dir.display());
+        state.audit = Some(audit);
+    }
+
+    let mut previously_loaded = Vec::new();
+    if let Some(path) = &args.state_file {
+        match persistence::load_registry(path).await {
+            Ok(entries) => {
+                tracing::info!("Restored {} model(s) from {}", entries.len(), path.display());
+                let mut models = state.models.lock().await;
+                for mut entry in entries {
+                    // Loaded again once the server is up, if they still can be.
+                    if entry.loaded {
+                        previously_loaded.push(entry.clone());
+                    }
+                    entry.loaded = false;
+                    entry.loaded_at = None;
+                    entry.placement = None;
+                    models.push(LoadedModel {
+                        registry_entry: entry,
+                        last_accessed: SystemTime::now(),
+                    });
+                }
+            }
+            Err(e) => tracing::warn!("Failed to restore registry from {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(path) = &args.schedule_file {
+        let schedules = lifecycle::open(path)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to load schedules from {}: {}", path.display(), e));
+        tracing::info!("Scheduling the lifecycle of {} model(s)", schedules.len());
+        lifecycle::spawn(state.clone(), schedules);
+    }
+
+    if let Some(path) = &args.plugins_file {
+        let plugins = Plugins::open(path)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to load plugins from {}: {}", path.display(), e));
+        tracing::info!("Plugins enabled: {}", plugins.names().collect::<Vec<_>>().join(", "));
+        state.plugins = Some(Arc::new(plugins));
+    }
+
+    if let Some(path) = &args.routing_script {
+        let policy = RoutingPolicy::open(path)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to load routing script: {}", e));
+        let policy = Arc::new(policy);
+        tokio::spawn(policy.clone().watch(Duration::from_secs(args.routing_reload_interval.max(1))));
+        tracing::info!("Routing inference requests with {}", path.display());
+        state.routing = Some(policy);
+    }
+
+    let admin_routes = Router::new()
         .route("/v1/models/register", post(v1::register_model))
+        .route("/v1/models/register/bulk", post(v1::register_models))
         .route("/v1/models/load", post(v1::load_model))
         .route("/v1/models/unload/:model_id", post(v1::unload_model))
+        .route("/v1/models/:model_id", delete(v1::unregister_model))
+        .route("/v1/models/:model_id/benchmark", post(v1::benchmark_model))
+        .route("/v1/accounting", get(v1::accounting_summary))
+        .route("/v1/backends/:backend/models", get(v1::list_catalog))
+        .route("/v1/backends/:backend/models/import", post(v1::import_catalog))
+        .route("/v1/backends/llama/slots", get(v1::list_llama_slots))
+        .route("/v1/keys", get(v1::list_keys).post(v1::create_key))
+        .route("/v1/keys/:key_id", delete(v1::revoke_key))
+        .route("/v1/prompts", get(v1::list_prompts).post(v1::create_prompt))
+        .route(
+            "/v1/prompts/:prompt_id",
+            get(v1::get_prompt).put(v1::update_prompt).delete(v1::delete_prompt),
+        )
+        .route("/v1/pools", get(v1::list_pools).post(v1::create_pool))
+        .route(
+            "/v1/pools/:pool_id",
+            get(v1::get_pool).put(v1::update_pool).delete(v1::delete_pool),
+        )
+        .route("/v1/pools/:pool_id/members", post(v1::add_pool_member))
+        .route(
+            "/v1/pools/:pool_id/members/:model_id",
+            put(v1::set_pool_member_weight).delete(v1::remove_pool_member),
+        )
+        .route_layer(middleware::from_fn(auth::require_admin));
+
+    let global_admin_routes = Router::new()
+        .route("/v1/tenants", get(v1::list_tenants))
+        .route("/v1/tenants/:tenant_id", put(v1::update_tenant))
+        .route("/v1/config", get(v1::get_config))
+        .route("/v1/models/storage", get(v1::storage_usage))
+        .route("/v1/models/storage/prune", post(v1::prune_storage))
+        .route("/v1/models/export", get(v1::export_registry))
+        .route("/v1/models/import", post(v1::import_registry))
+        .route_layer(middleware::from_fn(auth::require_global_admin));
+
+    let inference_routes = Router::new()
         .route("/v1/inference", post(v1::inference_complete))
         .route("/v1/inference/stream", post(v1::inference_stream))
-        .with_state(state);
+        .route("/v1/inference/ensemble", post(v1::inference_ensemble))
+        .route("/v1/pipelines/run", post(v1::run_pipeline))
+        .route("/v1/embeddings", post(v1::create_embeddings))
+        .route("/v1/vectors/upsert", post(v1::upsert_vectors))
+        .route("/v1/vectors/search", post(v1::search_vectors))
+        .route("/v1/rag/query", post(v1::rag_query))
+        .route(
+            "/v1/documents",
+            post(v1::ingest_document).route_layer(DefaultBodyLimit::max(args.max_document_bytes)),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), admission::admit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), quota::enforce_quota));
 
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], args.port));
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .expect(&format!("Failed to bind to port {}", args.port));
+    let authenticated = |routes: Router<AppState>| {
+        routes
+            .route_layer(middleware::from_fn_with_state(state.clone(), plugins::run_hooks))
+            .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::enforce_rate_limit))
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_api_key))
+    };
+
+    let public_routes = Router::new()
+        .route("/health", get(v1::health_check))
+        .route("/health/backends", get(v1::backend_health))
+        .route("/livez", get(v1::liveness))
+        .route("/readyz", get(v1::readiness))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .merge(authenticated(
+            Router::new()
+                .route("/v1/models", get(v1::list_models))
+                .route("/v1/usage", get(v1::usage))
+                .route("/v1/documents/jobs/:job_id", get(v1::document_job))
+                .merge(inference_routes),
+        ));
+
+    // Model and key management, tenants, stats and the dashboard. With
+    // --admin-listen these are only served on the admin listeners.
+    let management_routes = Router::new()
+        .route("/dashboard", get(dashboard::index))
+        .route("/dashboard/app.js", get(dashboard::script))
+        .route("/dashboard/style.css", get(dashboard::style))
+        .merge(authenticated(
+            Router::new()
+                .route("/v1/models/:model_id/stats", get(v1::model_stats))
+                .route("/v1/stats", get(v1::global_stats))
+                .merge(admin_routes)
+                .merge(global_admin_routes),
+        ));
+
+    let cors = if args.cors_origins.is_empty() {
+        None
+    } else {
+        let cors = cors::layer(&args.cors_origins, &args.cors_headers, &args.cors_methods)
+            .unwrap_or_else(|e| panic!("Invalid CORS configuration: {}", e));
+        tracing::info!("CORS enabled for origins: {}", args.cors_origins.join(", "));
+        Some(cors)
+    };
 
-    tracing::info!("Server started on http://{}", addr);
+    let finish = |routes: Router<AppState>| -> Router {
+        let app = routes
+            .layer(DefaultBodyLimit::max(args.max_body_bytes))
+            .layer(middleware::from_fn_with_state(state.clone(), limits::limit_body_size))
+            .layer(middleware::from_fn(error::render_errors))
+            .layer(middleware::from_fn(logging::trace_requests))
+            .layer(middleware::from_fn(request_id::assign_request_id))
+            .with_state(state.clone());
+
+        let app = if args.swagger_ui {
+            app.merge(SwaggerUi::new("/docs").config(utoipa_swagger_ui::Config::from("/openapi.json")))
+        } else {
+            app
+        };
+
+        let app = if args.no_compression {
+            app
+        } else {
+            app.layer(compression::layer(args.compress_streams))
+        };
+
+        match &cors {
+            Some(cors) => app.layer(cors.clone()),
+            None => app,
+        }
+    };
+
+    // The admin listeners serve the whole API, so the dashboard's playground
+    // keeps working there; the public listeners leave management out.
+    let (app, admin_app) = if args.admin_listen.is_empty() {
+        (finish(public_routes.merge(management_routes)), None)
+    } else {
+        (
+            finish(public_routes.clone()),
+            Some(finish(public_routes.merge(management_routes))),
+        )
+    };
+
+    let addrs = match (&args.listen[..], &args.uds) {
+        ([], None) => vec![std::net::SocketAddr::new(args.host, args.port)],
+        (addrs, _) => addrs.to_vec(),
+    };
+    let mut tcp_listeners = Vec::with_capacity(addrs.len() + args.admin_listen.len());
+    let served = addrs
+        .into_iter()
+        .map(|addr| (addr, app.clone(), false))
+        .chain(args.admin_listen.iter().map(|&addr| (addr, admin_app.clone().unwrap_or_else(|| app.clone()), true)));
+    for (addr, app, admin) in served {
+        if addr.ip().is_unspecified() && args.allow_anonymous {
+            tracing::warn!(
+                "Listening on every interface ({}) with anonymous access; anyone who can reach this host can use the API",
+                addr
+            );
+        }
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", addr, e));
+        tcp_listeners.push((addr, listener, app, admin));
+    }
+    let unix_listener = args.uds.as_ref().map(|path| {
+        listeners::bind_unix(path).unwrap_or_else(|e| panic!("Failed to bind to {}: {}", path.display(), e))
+    });
+
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(
+            tls::load(cert, key)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to load TLS certificate: {}", e)),
+        ),
+        _ => None,
+    };
+
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    for (addr, _, _, admin) in &tcp_listeners {
+        if *admin {
+            tracing::info!("Admin listener started on {}://{}", scheme, addr);
+        } else {
+            tracing::info!("Server started on {}://{}", scheme, addr);
+        }
+    }
+    if let Some(path) = &args.uds {
+        tracing::info!("Server started on unix:{}", path.display());
+    }
     tracing::info!("Available endpoints:");
     tracing::info!("  - GET  /health                 - Health check");
+    tracing::info!("  - GET  /health/backends        - Probe configured backends");
+    tracing::info!("  - GET  /livez                  - Liveness probe");
+    tracing::info!("  - GET  /readyz                 - Readiness probe");
+    tracing::info!("  - GET  /openapi.json           - OpenAPI specification");
+    tracing::info!("  - GET  /dashboard              - Admin dashboard");
     tracing::info!("  - GET  /v1/models              - List registered models");
-    tracing::info!("  - POST /v1/models/register     - Register a model in the registry");
-    tracing::info!("  - POST /v1/models/load         - Load a registered model");
-    tracing::info!("  - POST /v1/models/unload/:id   - Unload a model");
+    tracing::info!("  - POST /v1/models/register     - Register a model in the registry; POST .../bulk registers several (admin)");
+    tracing::info!("  - POST /v1/models/load         - Load a registered model (admin)");
+    tracing::info!("  - POST /v1/models/unload/:id   - Unload a model (admin)");
+    tracing::info!("  - DEL  /v1/models/:id          - Remove a model from the registry (admin)");
+    tracing::info!("  - GET  /v1/models/:id/stats    - Usage statistics for a model");
+    tracing::info!("  - GET  /v1/models/storage      - Disk used by model files; POST .../prune deletes unused ones (global admin)");
+    tracing::info!("  - GET  /v1/models/export       - Dump the registry, credential names and routing script (global admin)");
+    tracing::info!("  - POST /v1/models/import       - Register the models in an export (global admin)");
+    tracing::info!("  - GET  /v1/stats               - Aggregated usage statistics");
+    tracing::info!("  - GET  /v1/accounting          - Cumulative cost per caller (admin)");
     tracing::info!("  - POST /v1/inference           - Non-streaming inference");
     tracing::info!("  - POST /v1/inference/stream    - Streaming inference (SSE)");
+    tracing::info!("  - POST /v1/inference/ensemble  - Send one prompt to several models, optionally ranked by a judge");
+    tracing::info!("  - POST /v1/pipelines/run       - Run prompt steps in order, each fed the outputs before it");
+    tracing::info!("  - POST /v1/vectors/upsert      - Embed and store texts in a vector store");
+    tracing::info!("  - POST /v1/vectors/search      - Search a vector store by text");
+    tracing::info!("  - POST /v1/rag/query           - Answer a question from a vector store, with sources");
+    tracing::info!("  - POST /v1/documents           - Chunk, embed and store a text, Markdown or PDF document");
+    tracing::info!("  - GET  /v1/documents/jobs/:id  - Progress of a document ingestion job");
+    tracing::info!("  - GET  /v1/usage               - Remaining token quota for the caller");
+    tracing::info!("  - GET  /v1/keys                - List API keys (admin)");
+    tracing::info!("  - POST /v1/keys                - Create an API key (admin)");
+    tracing::info!("  - DEL  /v1/keys/:id            - Revoke an API key (admin)");
+    tracing::info!("  - GET  /v1/prompts             - List stored prompt templates (admin)");
+    tracing::info!("  - POST /v1/prompts             - Store a prompt template (admin)");
+    tracing::info!("  - GET  /v1/prompts/:id         - A stored prompt and its versions (admin)");
+    tracing::info!("  - PUT  /v1/prompts/:id         - Save a new version of a prompt (admin)");
+    tracing::info!("  - DEL  /v1/prompts/:id         - Delete a prompt and its versions (admin)");
+    tracing::info!("  - GET  /v1/pools               - List model pools (admin)");
+    tracing::info!("  - POST /v1/pools               - Create a pool requests can target as a model (admin)");
+    tracing::info!("  - GET  /v1/pools/:id           - A pool and its members (admin)");
+    tracing::info!("  - PUT  /v1/pools/:id           - Replace a pool's description or members (admin)");
+    tracing::info!("  - DEL  /v1/pools/:id           - Delete a pool (admin)");
+    tracing::info!("  - POST /v1/pools/:id/members   - Add a model to a pool; PUT/DEL .../:model_id sets its weight or removes it (admin)");
+    tracing::info!("  - GET  /v1/tenants             - List tenant limits and usage (global admin)");
+    tracing::info!("  - PUT  /v1/tenants/:id         - Set a tenant's tier and quota (global admin)");
+    tracing::info!("  - GET  /v1/config              - Effective settings, secrets redacted (global admin)");
 
     tracing::info!("Running with log level: {}", log_level);
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server failed to start");
+    tokio::spawn({
+        let api_keys = state.api_keys.clone();
+        let tenants = state.tenants.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Err(e) = api_keys.lock().await.flush().await {
+                    tracing::warn!("Failed to save API key usage: {}", e);
+                }
+                if let Err(e) = tenants.lock().await.flush().await {
+                    tracing::warn!("Failed to save tenant usage: {}", e);
+                }
+            }
+        }
+    });
+
+    if let Some(path) = &args.state_file {
+        tokio::spawn(persistence::watch(state.clone(), path.clone()));
+    }
+    if previously_loaded.is_empty() {
+        state.startup_complete.store(true, Ordering::SeqCst);
+    } else {
+        tracing::info!("Reloading {} model(s) that were loaded before the restart", previously_loaded.len());
+        tokio::spawn(persistence::restore_loaded(state.clone(), previously_loaded));
+    }
+
+    let (drain, draining) = tokio::sync::watch::channel(false);
+    if let (Some(config), Some(interval), Some(cert), Some(key)) =
+        (&tls_config, args.tls_reload_interval, &args.tls_cert, &args.tls_key)
+    {
+        tokio::spawn(tls::watch(
+            config.clone(),
+            cert.clone(),
+            key.clone(),
+            Duration::from_secs(interval),
+        ));
+    }
+
+    let mut servers = Vec::new();
+    for (_, listener, app, _) in tcp_listeners {
+        let service = app.clone().into_make_service_with_connect_info::<std::net::SocketAddr>();
+        servers.push(match &tls_config {
+            Some(config) => {
+                let handle = axum_server::Handle::new();
+                tokio::spawn({
+                    let draining = draining.clone();
+                    let handle = handle.clone();
+                    async move {
+                        listeners::drained(draining).await;
+                        handle.graceful_shutdown(None);
+                    }
+                });
+
+                let listener = listener
+                    .into_std()
+                    .unwrap_or_else(|e| panic!("Failed to prepare TLS listener: {}", e));
+                tokio::spawn(
+                    axum_server::from_tcp_rustls(listener, config.clone())
+                        .handle(handle)
+                        .serve(service),
+                )
+            }
+            None => tokio::spawn(
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(listeners::drained(draining.clone()))
+                    .into_future(),
+            ),
+        });
+    }
+    if let (Some(listener), Some(path)) = (unix_listener, &args.uds) {
+        servers.push(tokio::spawn(listeners::serve_unix(
+            listener,
+            path.clone(),
+            app.clone(),
+            draining.clone(),
+        )));
+    }
+    // Ends when every listener has drained, or as soon as one fails.
+    let mut server = tokio::spawn(futures::future::try_join_all(
+        servers
+            .into_iter()
+            .map(|server| async move { server.await.map_err(std::io::Error::other)? }),
+    ));
+
+    tokio::select! {
+        result = &mut server => {
+            if let Ok(Err(e)) = result {
+                tracing::error!("Server failed: {}", e);
+            }
+            return;
+        }
+        _ = shutdown::shutdown_signal() => {}
+    }
+
+    tracing::info!(
+        "Shutdown requested; draining in-flight requests for up to {}s",
+        args.drain_timeout
+    );
+    state.shutting_down.store(true, Ordering::SeqCst);
+    let _ = drain.send(true);
+
+    match tokio::time::timeout(Duration::from_secs(args.drain_timeout), server).await {
+        Ok(_) => tracing::info!("All connections drained"),
+        Err(_) => tracing::warn!("Drain timeout elapsed; closing remaining connections"),
+    }
+
+    if let Err(e) = state.api_keys.lock().await.flush().await {
+        tracing::error!("Failed to save API key usage: {}", e);
+    }
+    if let Err(e) = state.tenants.lock().await.flush().await {
+        tracing::error!("Failed to save tenant usage: {}", e);
+    }
+
+    if let Some(path) = &args.state_file {
+        let entries: Vec<ModelRegistryEntry> = state
+            .models
+            .lock()
+            .await
+            .iter()
+            .map(|m| m.registry_entry.clone())
+            .collect();
+        match persistence::save_registry(path, &entries).await {
+            Ok(()) => tracing::info!("Saved {} model(s) to {}", entries.len(), path.display()),
+            Err(e) => tracing::error!("Failed to save registry to {}: {}", path.display(), e),
+        }
+    }
 }
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use super::config;
+
+/// Behaviour of the built-in `mock` backend. Like the other backends'
+/// settings it is read from the environment on every call:
+///
+/// - `MOCK_RESPONSE`: canned response text; the prompt is echoed when unset.
+/// - `MOCK_TOKEN_DELAY_MS`: delay before each token.
+/// - `MOCK_FAILURE_RATE`: probability from 0 to 1 that a call fails outright.
+/// - `MOCK_FAIL_AFTER_TOKENS`: fail once this many tokens have been produced.
+#[derive(Debug, Clone, Default)]
+pub struct MockSettings {
+    pub response: Option<String>,
+    pub token_delay: Duration,
+    pub failure_rate: f64,
+    pub fail_after_tokens: Option<u32>,
+}
+
+fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    config::var(name).ok().and_then(|value| value.trim().parse().ok())
+}
+
+impl MockSettings {
+    pub fn from_env() -> Self {
+        Self {
+            response: config::var("MOCK_RESPONSE").ok(),
+            token_delay: Duration::from_millis(env("MOCK_TOKEN_DELAY_MS").unwrap_or(0)),
+            failure_rate: env("MOCK_FAILURE_RATE").unwrap_or(0.0),
+            fail_after_tokens: env("MOCK_FAIL_AFTER_TOKENS"),
+        }
+    }
+
+    /// Rolls the dice for `MOCK_FAILURE_RATE`.
+    pub fn should_fail(&self) -> bool {
+        if self.failure_rate <= 0.0 {
+            return false;
+        }
+        let roll = uuid::Uuid::new_v4().as_u128() as f64 / u128::MAX as f64;
+        roll < self.failure_rate
+    }
+
+    /// The response split into whitespace-separated tokens, each keeping its
+    /// trailing space, and capped at `max_tokens`.
+    pub fn tokens(&self, prompt: &str, max_tokens: u32) -> Vec<String> {
+        let text = self.response.as_deref().unwrap_or(prompt);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let count = words.len().min(max_tokens as usize);
+        words[..count]
+            .iter()
+            .enumerate()
+            .map(|(i, word)| if i + 1 < count { format!("{} ", word) } else { word.to_string() })
+            .collect()
+    }
+
+    /// Whether `max_tokens` cut the response short.
+    pub fn truncated(&self, prompt: &str, max_tokens: u32) -> bool {
+        let text = self.response.as_deref().unwrap_or(prompt);
+        text.split_whitespace().count() > max_tokens as usize
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::backends::{llama_local, ollama};
+use super::gguf;
+use super::v1::inference::get_backend_url;
+use super::{AppState, InferenceBackend, ModelRegistryEntry};
+
+/// What a backend or a model's file says about the model, used at
+/// registration in place of what the caller declared.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ModelMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    /// Parameter count, e.g. `8030261248`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<u64>,
+    /// The context length the model was trained for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    /// The model's own prompt template, in the backend's language: Jinja in
+    /// GGUF files, Go templates in Ollama.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Reads a model's metadata from Ollama, or from the GGUF header of an
+/// in-process llama.cpp model. `None` for backends that do not describe
+/// their models.
+pub async fn discover(state: &AppState, entry: &ModelRegistryEntry) -> Result<Option<ModelMetadata>, String> {
+    let model = entry.backend_model().to_string();
+    match entry.inference {
+        InferenceBackend::Ollama => {
+            let client = state.backend_clients.get(&entry.inference);
+            ollama::show(&client, &get_backend_url(&entry.inference), &model)
+                .await
+                .map(Some)
+        }
+        InferenceBackend::LlamaLocal => {
+            let path = llama_local::model_path(&model);
+            tokio::task::spawn_blocking(move || gguf::read_metadata(&path))
+                .await
+                .map_err(|e| e.to_string())?
+                .map(Some)
+        }
+        _ => Ok(None),
+    }
+}
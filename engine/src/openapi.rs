@@ -0,0 +1,99 @@
+use axum::Json;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use super::v1;
+
+/// The engine's HTTP API. Schemas are collected from the request and
+/// response types the paths name.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "OpenLLM Engine API"),
+    paths(
+        v1::models::list_models,
+        v1::models::register_model,
+        v1::models::register_models,
+        v1::models::load_model,
+        v1::models::unload_model,
+        v1::models::unregister_model,
+        v1::storage::storage_usage,
+        v1::storage::prune_storage,
+        v1::registry::export_registry,
+        v1::registry::import_registry,
+        v1::benchmark::benchmark_model,
+        v1::stats::model_stats,
+        v1::stats::global_stats,
+        v1::accounting::accounting_summary,
+        v1::inference::inference_complete,
+        v1::inference::inference_stream,
+        v1::ensemble::inference_ensemble,
+        v1::pipelines::run_pipeline,
+        v1::embeddings::create_embeddings,
+        v1::vectors::upsert_vectors,
+        v1::vectors::search_vectors,
+        v1::rag::rag_query,
+        v1::documents::ingest_document,
+        v1::documents::document_job,
+        v1::catalog::list_catalog,
+        v1::catalog::import_catalog,
+        v1::catalog::list_llama_slots,
+        v1::keys::list_keys,
+        v1::keys::create_key,
+        v1::keys::revoke_key,
+        v1::keys::usage,
+        v1::prompts::list_prompts,
+        v1::prompts::create_prompt,
+        v1::prompts::get_prompt,
+        v1::prompts::update_prompt,
+        v1::prompts::delete_prompt,
+        v1::pools::list_pools,
+        v1::pools::create_pool,
+        v1::pools::get_pool,
+        v1::pools::update_pool,
+        v1::pools::delete_pool,
+        v1::pools::add_pool_member,
+        v1::pools::remove_pool_member,
+        v1::pools::set_pool_member_weight,
+        v1::tenants::list_tenants,
+        v1::tenants::update_tenant,
+        v1::config::get_config,
+        v1::health::health_check,
+        v1::health::backend_health,
+        v1::health::liveness,
+        v1::health::readiness,
+    ),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "models", description = "Registering, loading and benchmarking models"),
+        (name = "inference", description = "Completions, streams and embeddings"),
+        (name = "vectors", description = "Storing and searching embeddings, ingesting documents and answering questions from them"),
+        (name = "stats", description = "Usage statistics and cost accounting"),
+        (name = "backends", description = "Backend model catalogs and llama.cpp slots"),
+        (name = "keys", description = "API keys and quota usage"),
+        (name = "prompts", description = "Named, versioned prompt templates"),
+        (name = "pools", description = "Groups of models requests can target as one"),
+        (name = "tenants", description = "Tenant limits"),
+        (name = "config", description = "The settings the engine is running with"),
+        (name = "health", description = "Liveness, readiness and backend health"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Every route but the health checks takes an admin key, JWT or issued API
+/// key as a bearer token.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
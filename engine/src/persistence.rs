@@ -0,0 +1,113 @@
+use futures::future::join_all;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::fs;
+
+use super::auth::{Caller, Role};
+use super::error::ApiError;
+use super::v1::models;
+use super::{AppState, ModelRegistryEntry};
+
+/// Times a model is tried when restoring it, for backends that start
+/// after the engine.
+const RESTORE_ATTEMPTS: u32 = 3;
+
+/// Wait before the second attempt, doubled before each one after.
+const RESTORE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How often the registry is checked for changes to save.
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Writes the registry to `path` via a temporary file and rename, so a crash
+/// mid-write never leaves a truncated state file behind.
+pub async fn save_registry(path: &Path, entries: &[ModelRegistryEntry]) -> std::io::Result<()> {
+    let json = serde_json::to_vec_pretty(entries)?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, json).await?;
+    fs::rename(&tmp, path).await
+}
+
+/// Reads a registry previously written by `save_registry`. A missing file is
+/// treated as an empty registry.
+pub async fn load_registry(path: &Path) -> std::io::Result<Vec<ModelRegistryEntry>> {
+    match fs::read(path).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the registry shortly after it changes, so which models are
+/// registered and loaded survives the engine being killed, not only a clean
+/// shutdown.
+pub async fn watch(state: AppState, path: PathBuf) {
+    let mut saved = state.registry_revision.current();
+    let mut ticker = tokio::time::interval(SAVE_INTERVAL);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let revision = state.registry_revision.current();
+        if revision == saved {
+            continue;
+        }
+        let entries: Vec<ModelRegistryEntry> = state
+            .models
+            .lock()
+            .await
+            .iter()
+            .map(|m| m.registry_entry.clone())
+            .collect();
+        match save_registry(&path, &entries).await {
+            Ok(()) => saved = revision,
+            Err(e) => tracing::error!("Failed to save registry to {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Loads the models that were loaded when the registry was saved, each with
+/// the quant and placement it had, then marks startup complete so `/readyz`
+/// only reports ready once they are back. A model that still fails after
+/// `RESTORE_ATTEMPTS` is left unloaded.
+pub async fn restore_loaded(state: AppState, saved: Vec<ModelRegistryEntry>) {
+    let caller = Caller {
+        id: "restore".to_string(),
+        role: Role::Admin,
+        tier: None,
+        tenant: None,
+    };
+    join_all(saved.iter().map(|entry| restore(&state, &caller, entry))).await;
+    state.startup_complete.store(true, Ordering::SeqCst);
+}
+
+async fn restore(state: &AppState, caller: &Caller, saved: &ModelRegistryEntry) {
+    let mut delay = RESTORE_RETRY_DELAY;
+    for attempt in 1..=RESTORE_ATTEMPTS {
+        match models::restore(state, caller, saved).await {
+            Ok(true) => {
+                tracing::info!("Model '{}' is still loaded on {:?}", saved.id, saved.inference);
+                return;
+            }
+            Ok(false) => {
+                tracing::info!("Reloaded model '{}'", saved.id);
+                return;
+            }
+            // Loaded meanwhile, e.g. by its schedule.
+            Err(ApiError::Conflict(_)) => return,
+            Err(e) if attempt < RESTORE_ATTEMPTS => {
+                tracing::warn!(
+                    "Failed to reload model '{}' (attempt {} of {}), retrying in {}s: {}",
+                    saved.id,
+                    attempt,
+                    RESTORE_ATTEMPTS,
+                    delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(e) => tracing::warn!("Failed to reload model '{}', leaving it unloaded: {}", saved.id, e),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::InferenceBackend;
+
+/// Kinds of personal data recognised by the built-in patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PiiEntity {
+    Email,
+    Phone,
+    CreditCard,
+    Ssn,
+    IpAddress,
+}
+
+impl PiiEntity {
+    pub const ALL: [PiiEntity; 5] = [
+        PiiEntity::Email,
+        PiiEntity::CreditCard,
+        PiiEntity::Ssn,
+        PiiEntity::Phone,
+        PiiEntity::IpAddress,
+    ];
+
+    fn pattern(self) -> &'static str {
+        match self {
+            PiiEntity::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            PiiEntity::Phone => r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)|\b\d{2,4})[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b",
+            PiiEntity::CreditCard => r"\b\d(?:[ -]?\d){12,18}\b",
+            PiiEntity::Ssn => r"\b\d{3}-\d{2}-\d{4}\b",
+            PiiEntity::IpAddress => r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b",
+        }
+    }
+
+    fn mask(self) -> &'static str {
+        match self {
+            PiiEntity::Email => "[EMAIL]",
+            PiiEntity::Phone => "[PHONE]",
+            PiiEntity::CreditCard => "[CREDIT_CARD]",
+            PiiEntity::Ssn => "[SSN]",
+            PiiEntity::IpAddress => "[IP_ADDRESS]",
+        }
+    }
+
+    /// Filters out pattern matches that cannot be the entity, such as digit
+    /// runs failing the card checksum.
+    fn accepts(self, text: &str) -> bool {
+        match self {
+            PiiEntity::CreditCard => luhn_valid(text),
+            _ => true,
+        }
+    }
+}
+
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[derive(Debug, Clone)]
+pub struct PiiConfig {
+    pub backends: Vec<InferenceBackend>,
+    pub entities: Vec<PiiEntity>,
+    pub ner_url: Option<String>,
+    pub ner_api_key: Option<String>,
+    pub ner_min_score: f64,
+}
+
+/// An entity from a Hugging Face style token-classification endpoint.
+/// Offsets are in characters.
+#[derive(Deserialize)]
+struct NerEntity {
+    #[serde(alias = "entity")]
+    entity_group: String,
+    #[serde(default = "default_score")]
+    score: f64,
+    start: usize,
+    end: usize,
+}
+
+fn default_score() -> f64 {
+    1.0
+}
+
+fn ner_mask(group: &str) -> String {
+    let group = group.trim_start_matches("B-").trim_start_matches("I-");
+    match group {
+        "PER" | "PERSON" => "[PERSON]".to_string(),
+        "LOC" | "GPE" | "LOCATION" => "[LOCATION]".to_string(),
+        "ORG" | "ORGANIZATION" => "[ORGANIZATION]".to_string(),
+        other => format!("[{}]", other.to_uppercase()),
+    }
+}
+
+/// Masks personal data in prompts before they are sent to hosted backends.
+pub struct PiiRedactor {
+    config: PiiConfig,
+    patterns: Vec<(PiiEntity, Regex)>,
+    client: reqwest::Client,
+}
+
+impl PiiRedactor {
+    pub fn new(config: PiiConfig) -> Self {
+        let patterns = config
+            .entities
+            .iter()
+            .map(|&entity| (entity, Regex::new(entity.pattern()).expect("built-in PII pattern")))
+            .collect();
+        Self {
+            config,
+            patterns,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn applies_to(&self, backend: &InferenceBackend) -> bool {
+        self.config.backends.contains(backend)
+    }
+
+    /// Returns `text` with every detected entity replaced by a placeholder
+    /// such as `[EMAIL]`. Fails if the NER endpoint is configured but cannot
+    /// be reached, so unredacted prompts are never sent on.
+    pub async fn redact(&self, text: &str) -> Result<String, String> {
+        let mut spans: Vec<(usize, usize, String)> = Vec::new();
+
+        for (entity, regex) in &self.patterns {
+            for found in regex.find_iter(text) {
+                if entity.accepts(found.as_str()) {
+                    spans.push((found.start(), found.end(), entity.mask().to_string()));
+                }
+            }
+        }
+
+        if let Some(url) = &self.config.ner_url {
+            spans.extend(self.recognize(url, text).await?);
+        }
+
+        // Earlier patterns win ties, e.g. a card number over a phone number,
+        // and later spans overlapping a kept one are dropped.
+        spans.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+        let mut redacted = String::with_capacity(text.len());
+        let mut cursor = 0;
+        for (start, end, mask) in spans {
+            if start < cursor {
+                continue;
+            }
+            redacted.push_str(&text[cursor..start]);
+            redacted.push_str(&mask);
+            cursor = end;
+        }
+        redacted.push_str(&text[cursor..]);
+        Ok(redacted)
+    }
+
+    async fn recognize(&self, url: &str, text: &str) -> Result<Vec<(usize, usize, String)>, String> {
+        let mut request = self.client.post(url).json(&serde_json::json!({
+            "inputs": text,
+            "parameters": { "aggregation_strategy": "simple" },
+        }));
+        if let Some(api_key) = &self.config.ner_api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("PII recognizer request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("PII recognizer error: {}", response.status()));
+        }
+        let entities: Vec<NerEntity> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse PII recognizer response: {}", e))?;
+
+        // Map character offsets to byte offsets.
+        let mut offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        offsets.push(text.len());
+
+        Ok(entities
+            .into_iter()
+            .filter(|e| e.score >= self.config.ner_min_score && e.start < e.end && e.end < offsets.len())
+            .map(|e| (offsets[e.start], offsets[e.end], ner_mask(&e.entity_group)))
+            .collect())
+    }
+}
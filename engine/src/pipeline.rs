@@ -0,0 +1,26 @@
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use super::templates::{self, VariableEscape};
+
+/// Steps a pipeline may have.
+pub const MAX_STEPS: usize = 16;
+
+/// Whether `name` can be used as a variable in templates.
+pub fn valid_variable(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= 64
+}
+
+/// Compiles a step's template, so a broken pipeline fails before its first
+/// step runs, and returns the variables it reads.
+pub fn compile(template: &str) -> Result<HashSet<String>, String> {
+    templates::template_variables(template)
+}
+
+/// A step's prompt, rendered with the inputs and earlier steps' outputs.
+pub fn render(template: &str, variables: &Map<String, Value>) -> Result<String, String> {
+    templates::render_variables(template, variables, VariableEscape::None)
+}
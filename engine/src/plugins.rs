@@ -0,0 +1,410 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::auth::Caller;
+use super::error::ApiError;
+use super::AppState;
+
+/// Fuel a hook call may burn before it is stopped, roughly one unit per
+/// WebAssembly instruction.
+const DEFAULT_FUEL: u64 = 100_000_000;
+
+/// Linear memory a plugin instance may grow to.
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// A WebAssembly plugin from `--plugins-file`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    /// The `.wasm` (or `.wat`) module, relative to the plugins file.
+    pub path: PathBuf,
+    /// Routes the plugin runs on, as requested (`/v1/inference`) or as
+    /// routed (`/v1/models/:model_id`). Every route when empty.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// Models the plugin runs for, by the `model_id` of the request body.
+    /// Every request when empty.
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+}
+
+fn default_fuel() -> u64 {
+    DEFAULT_FUEL
+}
+
+struct Plugin {
+    config: PluginConfig,
+    module: runtime::Module,
+    on_request: bool,
+    on_response: bool,
+}
+
+impl Plugin {
+    fn applies(&self, route: &str, path: &str, model_id: Option<&str>) -> bool {
+        let config = &self.config;
+        (config.routes.is_empty() || config.routes.iter().any(|r| r == route || r == path))
+            && (config.models.is_empty() || model_id.is_some_and(|id| config.models.iter().any(|m| m == id)))
+    }
+
+    /// Runs one of the plugin's hooks off the async runtime.
+    async fn call(self: &Arc<Self>, hook: &'static str, input: Value) -> Result<HookResult, ApiError> {
+        let plugin = self.clone();
+        let input = serde_json::to_vec(&input).map_err(|e| ApiError::Internal(e.to_string()))?;
+        let output = tokio::task::spawn_blocking(move || {
+            runtime::call(&plugin.module, hook, &input, plugin.config.fuel, MAX_MEMORY_BYTES)
+        })
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .map_err(|e| ApiError::Internal(format!("Plugin '{}' failed in {}: {}", self.config.name, hook, e)))?;
+
+        match output {
+            None => Ok(HookResult::default()),
+            Some(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                ApiError::Internal(format!(
+                    "Plugin '{}' returned an invalid result from {}: {}",
+                    self.config.name, hook, e
+                ))
+            }),
+        }
+    }
+}
+
+/// What a hook returns. An empty result, or a hook returning 0, lets the
+/// request through unchanged.
+#[derive(Debug, Default, Deserialize)]
+struct HookResult {
+    /// Replaces the request or response body.
+    #[serde(default)]
+    body: Option<Value>,
+    #[serde(default)]
+    reject: Option<Rejection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rejection {
+    /// 403 unless given; must be a 4xx or 5xx status.
+    #[serde(default)]
+    status: Option<u16>,
+    message: String,
+}
+
+/// A request a plugin turned away.
+#[derive(Debug, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct PluginRejection {
+    #[serde(skip)]
+    pub status: StatusCode,
+    #[serde(skip)]
+    pub message: String,
+    pub plugin: String,
+}
+
+/// The plugins loaded from `--plugins-file`, in the order they run.
+pub struct Plugins {
+    plugins: Vec<Arc<Plugin>>,
+}
+
+impl Plugins {
+    /// Reads `--plugins-file` and compiles every plugin it lists.
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+        let configs: Vec<PluginConfig> = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let engine = runtime::Engine::new()?;
+
+        let mut plugins = Vec::with_capacity(configs.len());
+        for config in configs {
+            let wasm = tokio::fs::read(dir.join(&config.path))
+                .await
+                .map_err(|e| format!("Plugin '{}': {}: {}", config.name, config.path.display(), e))?;
+            let module = engine
+                .compile(&wasm)
+                .map_err(|e| format!("Plugin '{}': {}", config.name, e))?;
+            let on_request = runtime::exports(&module, "on_request");
+            let on_response = runtime::exports(&module, "on_response");
+            if !on_request && !on_response {
+                return Err(format!(
+                    "Plugin '{}' exports neither on_request nor on_response",
+                    config.name
+                ));
+            }
+            plugins.push(Arc::new(Plugin {
+                config,
+                module,
+                on_request,
+                on_response,
+            }));
+        }
+        Ok(Self { plugins })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.plugins.iter().map(|p| p.config.name.as_str())
+    }
+}
+
+/// Runs the plugins that apply to a request: their `on_request` hooks
+/// before the handler, in order, and their `on_response` hooks on a
+/// successful JSON response, in reverse order. Hooks can rewrite the JSON
+/// body either way, or reject the request. Streamed responses and non-JSON
+/// bodies are passed through, though `on_request` still sees the request.
+pub async fn run_hooks(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(plugins) = state.plugins.clone() else {
+        return next.run(req).await;
+    };
+    let path = req.uri().path().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    if !plugins.plugins.iter().any(|p| {
+        p.config.routes.is_empty() || p.config.routes.iter().any(|r| *r == route || *r == path)
+    }) {
+        return next.run(req).await;
+    }
+
+    match hooked(&state, &plugins, &route, &path, req, next).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn hooked(
+    state: &AppState,
+    plugins: &Plugins,
+    route: &str,
+    path: &str,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let caller = req.extensions().get::<Caller>().cloned();
+    let (mut parts, body) = req.into_parts();
+    let json = is_json(&parts.headers);
+    let (mut body_json, mut body) = if json {
+        let bytes = to_bytes(body, state.limits.max_body_bytes)
+            .await
+            .map_err(|e| ApiError::invalid(format!("Failed to read request body: {}", e)))?;
+        // Bodies that are not valid JSON are left for the handler to reject.
+        (serde_json::from_slice::<Value>(&bytes).ok(), Body::from(bytes))
+    } else {
+        (None, body)
+    };
+    let model_id = body_json
+        .as_ref()
+        .and_then(|b| b.get("model_id"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let applied: Vec<&Arc<Plugin>> = plugins
+        .plugins
+        .iter()
+        .filter(|p| p.applies(route, path, model_id.as_deref()))
+        .collect();
+
+    let caller_json = caller.as_ref().map(|c| {
+        json!({
+            "id": c.id,
+            "role": c.role,
+            "tenant": c.tenant,
+        })
+    });
+    let mut rewritten = false;
+    for plugin in applied.iter().filter(|p| p.on_request) {
+        let headers: serde_json::Map<String, Value> = parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), Value::from(value.to_str().ok()?))))
+            .collect();
+        let result = plugin
+            .call(
+                "on_request",
+                json!({
+                    "route": route,
+                    "path": path,
+                    "method": parts.method.as_str(),
+                    "caller": caller_json,
+                    "headers": headers,
+                    "body": body_json,
+                }),
+            )
+            .await?;
+        reject(plugin, result.reject)?;
+        if let Some(new_body) = result.body {
+            if body_json.is_none() {
+                tracing::warn!("Plugin '{}' cannot rewrite a non-JSON body on {}", plugin.config.name, path);
+                continue;
+            }
+            body_json = Some(new_body);
+            rewritten = true;
+        }
+    }
+    if rewritten && let Some(new_body) = &body_json {
+        let bytes = serde_json::to_vec(new_body).map_err(|e| ApiError::Internal(e.to_string()))?;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        body = Body::from(bytes);
+    }
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let response_hooks: Vec<&Arc<Plugin>> = applied.iter().rev().filter(|p| p.on_response).copied().collect();
+    if response_hooks.is_empty() || !response.status().is_success() || !is_json(response.headers()) {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to read response body: {}", e)))?;
+    let Ok(mut response_json) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    };
+    for plugin in response_hooks {
+        let result = plugin
+            .call(
+                "on_response",
+                json!({
+                    "route": route,
+                    "path": path,
+                    "status": parts.status.as_u16(),
+                    "caller": caller_json,
+                    "model_id": model_id,
+                    "body": response_json,
+                }),
+            )
+            .await?;
+        reject(plugin, result.reject)?;
+        if let Some(new_body) = result.body {
+            response_json = new_body;
+        }
+    }
+    let bytes = serde_json::to_vec(&response_json).map_err(|e| ApiError::Internal(e.to_string()))?;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+fn reject(plugin: &Plugin, rejection: Option<Rejection>) -> Result<(), PluginRejection> {
+    let Some(rejection) = rejection else {
+        return Ok(());
+    };
+    let status = rejection
+        .status
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .filter(|status| status.is_client_error() || status.is_server_error())
+        .unwrap_or(StatusCode::FORBIDDEN);
+    Err(PluginRejection {
+        status,
+        message: rejection.message,
+        plugin: plugin.config.name.clone(),
+    })
+}
+
+/// Runs modules with wasmtime. A module exports its `memory`, an
+/// `alloc(len: i32) -> i32` the engine writes the hook's JSON input into,
+/// and the hooks as `(ptr: i32, len: i32) -> i64`. A hook returns 0 to let
+/// the request through, or the pointer and length of its JSON result as
+/// `ptr << 32 | len`. Each call gets a fresh instance, so no state carries
+/// over between requests.
+#[cfg(feature = "plugins")]
+mod runtime {
+    use wasmtime::{Config, Instance, Linker, Store, StoreLimits, StoreLimitsBuilder};
+
+    pub use wasmtime::Module;
+
+    pub struct Engine(wasmtime::Engine);
+
+    impl Engine {
+        pub fn new() -> Result<Self, String> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            wasmtime::Engine::new(&config).map(Self).map_err(|e| e.to_string())
+        }
+
+        /// Compiles a binary module, or one in the text format.
+        pub fn compile(&self, wasm: &[u8]) -> Result<Module, String> {
+            Module::new(&self.0, wasm).map_err(|e| e.to_string())
+        }
+    }
+
+    pub fn exports(module: &Module, name: &str) -> bool {
+        module.get_export(name).is_some_and(|export| export.func().is_some())
+    }
+
+    pub fn call(module: &Module, hook: &str, input: &[u8], fuel: u64, max_memory: usize) -> Result<Option<Vec<u8>>, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(max_memory).build();
+        let mut store: Store<StoreLimits> = Store::new(module.engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(fuel).map_err(|e| e.to_string())?;
+
+        let instance: Instance = Linker::new(module.engine())
+            .instantiate(&mut store, module)
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("the module does not export its memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| format!("alloc: {}", e))?;
+        let hook = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, hook)
+            .map_err(|e| e.to_string())?;
+
+        let len = i32::try_from(input.len()).map_err(|_| "the input is too large")?;
+        let ptr = alloc.call(&mut store, len).map_err(|e| e.root_cause().to_string())?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| e.to_string())?;
+        let result = hook.call(&mut store, (ptr, len)).map_err(|e| e.root_cause().to_string())?;
+        if result == 0 {
+            return Ok(None);
+        }
+
+        let (ptr, len) = ((result >> 32) as u32 as usize, result as u32 as usize);
+        let mut output = vec![0; len];
+        memory.read(&store, ptr, &mut output).map_err(|e| e.to_string())?;
+        Ok(Some(output))
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+mod runtime {
+    const UNAVAILABLE: &str = "openllm-server was built without the plugins feature";
+
+    pub struct Module;
+
+    pub struct Engine;
+
+    impl Engine {
+        pub fn new() -> Result<Self, String> {
+            Err(UNAVAILABLE.to_string())
+        }
+
+        pub fn compile(&self, _wasm: &[u8]) -> Result<Module, String> {
+            Err(UNAVAILABLE.to_string())
+        }
+    }
+
+    pub fn exports(_module: &Module, _name: &str) -> bool {
+        false
+    }
+
+    pub fn call(_module: &Module, _hook: &str, _input: &[u8], _fuel: u64, _max_memory: usize) -> Result<Option<Vec<u8>>, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+}
@@ -0,0 +1,324 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use super::auth::Caller;
+use super::backend_health::ProbeStatus;
+use super::error::ApiError;
+use super::v1::inference::InferenceRequest;
+use super::AppState;
+
+/// How far back a member's errors count against it.
+const HEALTH_WINDOW_MINUTES: i64 = 5;
+/// Requests a member must have served in the window before its error rate
+/// is trusted.
+const MIN_HEALTH_REQUESTS: u64 = 5;
+/// Members failing at least this share of their recent requests are only
+/// picked when no healthier member is available.
+const UNHEALTHY_ERROR_RATE: f64 = 0.5;
+
+/// A named group of models that requests can target as one: a request whose
+/// `model_id` is the pool's id goes to one of its members.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ModelPool {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Registered model ids, in the order ties between them are broken.
+    pub members: Vec<String>,
+    /// Shares of the pool's requests by member, e.g. `{"llama3": 4,
+    /// "gpt-4o": 1}` for 80% and 20%. Members not listed weigh 1, and 0
+    /// takes a member out of rotation while others can serve. Without
+    /// weights requests go to the least busy member.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub weights: BTreeMap<String, u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ModelPool {
+    pub fn weight(&self, member: &str) -> u32 {
+        self.weights.get(member).copied().unwrap_or(1)
+    }
+}
+
+/// Model pools, stored in `--pools-file` when given, and the requests each
+/// member is serving for them.
+#[derive(Debug, Default)]
+pub struct PoolStore {
+    pools: Vec<ModelPool>,
+    path: Option<PathBuf>,
+    in_flight: HashMap<String, Arc<AtomicU32>>,
+    /// Smooth weighted round-robin state, by pool and member.
+    current_weights: HashMap<(String, String), i64>,
+    /// Rotates which of equally good members goes first.
+    turn: usize,
+}
+
+/// A pool request running on a member, counted in its load until dropped.
+pub struct PoolLease(Arc<AtomicU32>);
+
+impl Drop for PoolLease {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl PoolStore {
+    /// Opens the pool store, loading existing pools from `path` when given.
+    pub async fn open(path: Option<PathBuf>) -> std::io::Result<Self> {
+        let pools = match &path {
+            Some(path) => match tokio::fs::read(path).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e),
+            },
+            None => Vec::new(),
+        };
+        Ok(Self {
+            pools,
+            path,
+            ..Self::default()
+        })
+    }
+
+    async fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&self.pools)?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+
+    pub fn list(&self) -> &[ModelPool] {
+        &self.pools
+    }
+
+    pub fn get(&self, id: &str, caller: &Caller) -> Option<&ModelPool> {
+        self.pools
+            .iter()
+            .find(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+    }
+
+    /// Whether any tenant has a pool with this id. Pool ids share the
+    /// namespace of model ids, so they are unique across tenants too.
+    pub fn contains(&self, id: &str) -> bool {
+        self.pools.iter().any(|p| p.id == id)
+    }
+
+    pub async fn insert(&mut self, pool: ModelPool) -> std::io::Result<()> {
+        self.pools.push(pool);
+        self.save().await
+    }
+
+    /// Applies `change` to a pool the caller can see and returns the updated
+    /// pool.
+    pub async fn update(
+        &mut self,
+        id: &str,
+        caller: &Caller,
+        change: impl FnOnce(&mut ModelPool),
+    ) -> std::io::Result<Option<ModelPool>> {
+        let Some(pool) = self
+            .pools
+            .iter_mut()
+            .find(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+        else {
+            return Ok(None);
+        };
+        change(pool);
+        pool.updated_at = Utc::now();
+        let pool = pool.clone();
+        self.current_weights.retain(|(pool_id, _), _| *pool_id != pool.id);
+        self.save().await?;
+        Ok(Some(pool))
+    }
+
+    pub async fn remove(&mut self, id: &str, caller: &Caller) -> std::io::Result<Option<ModelPool>> {
+        let Some(position) = self
+            .pools
+            .iter()
+            .position(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+        else {
+            return Ok(None);
+        };
+        let pool = self.pools.remove(position);
+        self.current_weights.retain(|(pool_id, _), _| *pool_id != pool.id);
+        self.save().await?;
+        Ok(Some(pool))
+    }
+
+    /// Pool requests a model is serving.
+    pub fn in_flight(&self, model_id: &str) -> u32 {
+        self.in_flight
+            .get(model_id)
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+
+    /// Picks one of `members` by smooth weighted round-robin, as nginx
+    /// does: each gains its weight, the one furthest ahead is picked and
+    /// drops back by the total. Every member gets its share of any run of
+    /// requests, interleaved rather than in bursts. `None` when all weigh 0.
+    fn pick_weighted(&mut self, pool_id: &str, members: &[(&str, u32)]) -> Option<usize> {
+        let total: i64 = members.iter().map(|(_, weight)| i64::from(*weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut best: Option<(usize, i64)> = None;
+        for (i, (member, weight)) in members.iter().enumerate() {
+            let current = self
+                .current_weights
+                .entry((pool_id.to_string(), member.to_string()))
+                .or_default();
+            *current += i64::from(*weight);
+            if best.is_none_or(|(_, ahead)| *current > ahead) {
+                best = Some((i, *current));
+            }
+        }
+        let (chosen, _) = best?;
+        if let Some(current) = self
+            .current_weights
+            .get_mut(&(pool_id.to_string(), members[chosen].0.to_string()))
+        {
+            *current -= total;
+        }
+        Some(chosen)
+    }
+
+    fn lease(&mut self, model_id: &str) -> PoolLease {
+        let count = self.in_flight.entry(model_id.to_string()).or_default().clone();
+        count.fetch_add(1, Ordering::Relaxed);
+        PoolLease(count)
+    }
+}
+
+/// A member that could take the request, and what counts against it.
+struct Candidate {
+    id: String,
+    /// At its `max_concurrency`, so the request would queue.
+    saturated: bool,
+    unhealthy: bool,
+    in_flight: u32,
+    position: usize,
+}
+
+/// Sends a request whose `model_id` names a pool to one of the pool's
+/// members, and returns the lease that counts it against that member until
+/// the response is done. Requests for models are left alone.
+///
+/// Members that are not loaded, or whose backend the last health check
+/// found down, are passed over. Of the rest, members with a free turn come
+/// before those at their `max_concurrency`, and healthy ones before those
+/// failing most of their recent requests. Among the best of those, pools
+/// with weights share requests by weight; otherwise the least busy member
+/// is picked, and ties go round the pool.
+pub async fn resolve(state: &AppState, caller: &Caller, req: &mut InferenceRequest) -> Result<Option<PoolLease>, ApiError> {
+    let (pool, turn) = {
+        let mut pools = state.pools.lock().await;
+        let Some(pool) = pools.get(&req.model_id, caller).cloned() else {
+            return Ok(None);
+        };
+        pools.turn = pools.turn.wrapping_add(1);
+        (pool, pools.turn)
+    };
+
+    let entries: Vec<_> = {
+        let models = state.models.lock().await;
+        pool.members
+            .iter()
+            .filter_map(|id| {
+                models
+                    .iter()
+                    .map(|m| &m.registry_entry)
+                    .find(|entry| &entry.id == id && entry.visible_to(caller) && entry.loaded)
+                    .cloned()
+            })
+            .collect()
+    };
+    if entries.is_empty() {
+        return Err(ApiError::Unavailable(format!(
+            "No model in pool '{}' is loaded",
+            pool.id
+        )));
+    }
+
+    let down: Vec<_> = state
+        .backend_health
+        .lock()
+        .await
+        .latest()
+        .map(|report| {
+            report
+                .backends
+                .iter()
+                .filter(|b| b.status == ProbeStatus::Down)
+                .map(|b| b.backend.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let since = Utc::now() - TimeDelta::minutes(HEALTH_WINDOW_MINUTES);
+
+    // Chosen and leased under one lock, so concurrent requests see each
+    // other's leases.
+    let mut pools = state.pools.lock().await;
+    let candidates: Vec<Candidate> = {
+        let concurrency = state.model_concurrency.lock().await;
+        let stats = state.stats.lock().await;
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !down.contains(&entry.inference))
+            .map(|(position, entry)| {
+                let recent = stats.recent(&entry.id, since);
+                Candidate {
+                    id: entry.id.clone(),
+                    saturated: entry
+                        .max_concurrency
+                        .is_some_and(|max| concurrency.in_flight(&entry.id) >= max),
+                    unhealthy: recent.requests >= MIN_HEALTH_REQUESTS
+                        && recent.errors as f64 / recent.requests as f64 >= UNHEALTHY_ERROR_RATE,
+                    in_flight: pools.in_flight(&entry.id),
+                    position: (position + entries.len() - turn % entries.len()) % entries.len(),
+                }
+            })
+            .collect()
+    };
+    let Some(best) = candidates.iter().map(|c| (c.saturated, c.unhealthy)).min() else {
+        return Err(ApiError::Unavailable(format!(
+            "The backends of every loaded model in pool '{}' are down",
+            pool.id
+        )));
+    };
+    let mut tier: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|c| (c.saturated, c.unhealthy) == best)
+        .collect();
+
+    let weighted = match pool.weights.is_empty() {
+        true => None,
+        false => {
+            let weights: Vec<(&str, u32)> = tier.iter().map(|c| (c.id.as_str(), pool.weight(&c.id))).collect();
+            pools.pick_weighted(&pool.id, &weights)
+        }
+    };
+    let chosen = match weighted {
+        Some(i) => tier.swap_remove(i),
+        None => tier
+            .into_iter()
+            .min_by_key(|c| (c.in_flight, c.position))
+            .expect("the tier has the best candidate"),
+    };
+
+    tracing::debug!("Pool '{}' sent a request to '{}'", pool.id, chosen.id);
+    let lease = pools.lease(&chosen.id);
+    req.model_id = chosen.id;
+    Ok(Some(lease))
+}
@@ -0,0 +1,140 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A step applied to generated text before it is returned. Models can carry
+/// a default pipeline, which a request's own `post_process` replaces.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostProcessor {
+    /// Cut the text at the first occurrence of any of the sequences.
+    StripStop { sequences: Vec<String> },
+    /// Remove leading and trailing whitespace.
+    Trim,
+    /// Keep only the first JSON object or array in the text. Text without
+    /// one is left unchanged.
+    ExtractJson,
+    /// Remove chain-of-thought blocks such as `<think>...</think>`.
+    StripReasoning {
+        #[serde(default = "default_reasoning_tags")]
+        tags: Vec<String>,
+    },
+    /// Replace every match of a regular expression. `$1` and `${name}` refer
+    /// to capture groups.
+    Replace { pattern: String, replacement: String },
+}
+
+fn default_reasoning_tags() -> Vec<String> {
+    ["think", "thinking", "reasoning"].map(String::from).to_vec()
+}
+
+/// Checks that every `replace` pattern compiles, so mistakes are reported
+/// before any generation happens.
+pub fn validate(processors: &[PostProcessor]) -> Result<(), String> {
+    for processor in processors {
+        if let PostProcessor::Replace { pattern, .. } = processor {
+            Regex::new(pattern).map_err(|e| format!("Invalid post_process pattern '{}': {}", pattern, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `text` through `processors` in order. Processors are expected to
+/// have passed `validate`; invalid patterns are skipped.
+pub fn apply(processors: &[PostProcessor], mut text: String) -> String {
+    for processor in processors {
+        text = match processor {
+            PostProcessor::StripStop { sequences } => strip_stop(text, sequences),
+            PostProcessor::Trim => text.trim().to_string(),
+            PostProcessor::ExtractJson => extract_json(&text).map(str::to_string).unwrap_or(text),
+            PostProcessor::StripReasoning { tags } => strip_reasoning(text, tags),
+            PostProcessor::Replace { pattern, replacement } => match Regex::new(pattern) {
+                Ok(regex) => regex.replace_all(&text, replacement.as_str()).into_owned(),
+                Err(_) => text,
+            },
+        };
+    }
+    text
+}
+
+fn strip_stop(mut text: String, sequences: &[String]) -> String {
+    let cut = sequences
+        .iter()
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| text.find(s.as_str()))
+        .min();
+    if let Some(cut) = cut {
+        text.truncate(cut);
+    }
+    text
+}
+
+/// Finds the first balanced `{...}` or `[...]` block, skipping brackets
+/// inside strings. Code fences around it are dropped along the way.
+fn extract_json(text: &str) -> Option<&str> {
+    let mut search_from = 0;
+    while let Some(offset) = text[search_from..].find(['{', '[']) {
+        let start = search_from + offset;
+        if let Some(end) = balanced_end(&text[start..]) {
+            let candidate = &text[start..start + end];
+            if serde_json::from_str::<serde_json::Value>(candidate).is_ok() {
+                return Some(candidate);
+            }
+        }
+        search_from = start + 1;
+    }
+    None
+}
+
+fn balanced_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Removes `<tag>...</tag>` blocks. An unclosed block, as left by a
+/// generation that ran out of tokens mid-thought, is removed to the end. A
+/// lone closing tag, from models whose template opens the block in the
+/// prompt, removes everything before it.
+fn strip_reasoning(mut text: String, tags: &[String]) -> String {
+    for tag in tags {
+        let open = format!("<{}>", tag);
+        let close = format!("</{}>", tag);
+        if let Some(end) = text.find(&close)
+            && !text[..end].contains(&open)
+        {
+            text.replace_range(..end + close.len(), "");
+        }
+        while let Some(start) = text.find(&open) {
+            let end = text[start..]
+                .find(&close)
+                .map(|i| start + i + close.len())
+                .unwrap_or(text.len());
+            text.replace_range(start..end, "");
+        }
+    }
+    text
+}
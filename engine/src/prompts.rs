@@ -0,0 +1,170 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use utoipa::ToSchema;
+
+use super::auth::Caller;
+use super::postprocess::PostProcessor;
+use super::templates::VariableEscape;
+use super::v1::inference::ChatMessage;
+
+/// Settings a request that references a prompt gets unless it sets them
+/// itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PromptDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Values for variables the request does not send.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub variables: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variable_escape: Option<VariableEscape>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_process: Option<Vec<PostProcessor>>,
+}
+
+/// One version of a stored prompt. Versions are never changed once saved,
+/// so requests pinned to one keep getting the same prompt.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PromptVersion {
+    pub version: u32,
+    /// A prompt template, e.g. `Summarize: {{ document }}`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prompt: String,
+    /// Chat messages whose contents are templates, instead of `prompt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<ChatMessage>>,
+    /// The model requests go to unless they name one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub defaults: PromptDefaults,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StoredPrompt {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Oldest first.
+    pub versions: Vec<PromptVersion>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StoredPrompt {
+    pub fn latest(&self) -> &PromptVersion {
+        self.versions.last().expect("a prompt has at least one version")
+    }
+
+    /// The given version, or the latest.
+    pub fn version(&self, version: Option<u32>) -> Option<&PromptVersion> {
+        match version {
+            Some(version) => self.versions.iter().find(|v| v.version == version),
+            None => Some(self.latest()),
+        }
+    }
+}
+
+/// Named prompt templates, stored in `--prompts-file` when given.
+#[derive(Debug, Default)]
+pub struct PromptStore {
+    prompts: Vec<StoredPrompt>,
+    path: Option<PathBuf>,
+}
+
+impl PromptStore {
+    /// Opens the prompt store, loading existing prompts from `path` when given.
+    pub async fn open(path: Option<PathBuf>) -> std::io::Result<Self> {
+        let prompts = match &path {
+            Some(path) => match tokio::fs::read(path).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e),
+            },
+            None => Vec::new(),
+        };
+        Ok(Self { prompts, path })
+    }
+
+    async fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&self.prompts)?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+
+    pub fn list(&self) -> &[StoredPrompt] {
+        &self.prompts
+    }
+
+    /// A prompt the caller can see. Prompts without a tenant are shared by
+    /// every tenant.
+    pub fn get(&self, id: &str, caller: &Caller) -> Option<&StoredPrompt> {
+        self.prompts
+            .iter()
+            .find(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+    }
+
+    /// Whether any tenant has a prompt with this id. Ids are unique across
+    /// tenants, like model ids.
+    pub fn contains(&self, id: &str) -> bool {
+        self.prompts.iter().any(|p| p.id == id)
+    }
+
+    pub async fn insert(&mut self, prompt: StoredPrompt) -> std::io::Result<()> {
+        self.prompts.push(prompt);
+        self.save().await
+    }
+
+    /// Adds a version to a prompt the caller can see, numbered after its
+    /// latest, and returns the updated prompt.
+    pub async fn add_version(
+        &mut self,
+        id: &str,
+        caller: &Caller,
+        description: Option<String>,
+        mut version: PromptVersion,
+    ) -> std::io::Result<Option<StoredPrompt>> {
+        let Some(prompt) = self
+            .prompts
+            .iter_mut()
+            .find(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+        else {
+            return Ok(None);
+        };
+        version.version = prompt.latest().version + 1;
+        prompt.updated_at = version.created_at;
+        prompt.versions.push(version);
+        if description.is_some() {
+            prompt.description = description;
+        }
+        let prompt = prompt.clone();
+        self.save().await?;
+        Ok(Some(prompt))
+    }
+
+    /// Removes a prompt the caller can see, with all its versions.
+    pub async fn remove(&mut self, id: &str, caller: &Caller) -> std::io::Result<Option<StoredPrompt>> {
+        let Some(position) = self
+            .prompts
+            .iter()
+            .position(|p| p.id == id && caller.can_see(p.tenant.as_deref()))
+        else {
+            return Ok(None);
+        };
+        let prompt = self.prompts.remove(position);
+        self.save().await?;
+        Ok(Some(prompt))
+    }
+}
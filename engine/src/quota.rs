@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::auth::Caller;
+use super::error::ApiError;
+use super::AppState;
+
+/// Token limits for a key. `None` leaves that window unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct TokenQuota {
+    #[serde(default)]
+    pub daily: Option<u64>,
+    #[serde(default)]
+    pub monthly: Option<u64>,
+}
+
+/// Tokens used in the current day and month (UTC).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct QuotaUsage {
+    pub day: Option<NaiveDate>,
+    pub day_tokens: u64,
+    pub month: Option<NaiveDate>,
+    pub month_tokens: u64,
+}
+
+fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+impl QuotaUsage {
+    /// Resets counters whose window has ended.
+    fn roll(&mut self, today: NaiveDate) {
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.day_tokens = 0;
+        }
+        if self.month != Some(month_start(today)) {
+            self.month = Some(month_start(today));
+            self.month_tokens = 0;
+        }
+    }
+
+    pub fn add(&mut self, tokens: u64, now: DateTime<Utc>) {
+        self.roll(now.date_naive());
+        self.day_tokens += tokens;
+        self.month_tokens += tokens;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuotaWindow {
+    pub limit: Option<u64>,
+    pub used: u64,
+    pub remaining: Option<u64>,
+    pub resets_at: DateTime<Utc>,
+}
+
+impl QuotaWindow {
+    fn new(limit: Option<u64>, used: u64, resets_at: DateTime<Utc>) -> Self {
+        Self {
+            limit,
+            used,
+            remaining: limit.map(|limit| limit.saturating_sub(used)),
+            resets_at,
+        }
+    }
+
+    fn exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuotaStatus {
+    pub daily: QuotaWindow,
+    pub monthly: QuotaWindow,
+}
+
+impl QuotaStatus {
+    pub fn compute(quota: &TokenQuota, usage: &QuotaUsage, now: DateTime<Utc>) -> Self {
+        let mut usage = usage.clone();
+        let today = now.date_naive();
+        usage.roll(today);
+
+        let next_day = today.succ_opt().unwrap_or(today);
+        let next_month = month_start(today)
+            .checked_add_months(Months::new(1))
+            .unwrap_or(today);
+        let midnight = |date: NaiveDate| Utc.from_utc_datetime(&date.and_time(Default::default()));
+
+        Self {
+            daily: QuotaWindow::new(quota.daily, usage.day_tokens, midnight(next_day)),
+            monthly: QuotaWindow::new(quota.monthly, usage.month_tokens, midnight(next_month)),
+        }
+    }
+
+    /// When the caller may spend tokens again, if a window is exhausted.
+    pub fn blocked_until(&self) -> Option<DateTime<Utc>> {
+        match (self.daily.exhausted(), self.monthly.exhausted()) {
+            (_, true) => Some(self.monthly.resets_at),
+            (true, false) => Some(self.daily.resets_at),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Rejects generation requests from keys or tenants whose daily or monthly
+/// token quota is used up. Must run after `require_api_key`.
+pub async fn enforce_quota(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(caller) = req.extensions().get::<Caller>() else {
+        return next.run(req).await;
+    };
+
+    let key_status = state.api_keys.lock().await.quota_status(&caller.id);
+    let tenant_status = match &caller.tenant {
+        Some(tenant) => state.tenants.lock().await.quota_status(tenant),
+        None => None,
+    };
+    let blocked_until = [key_status, tenant_status]
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.blocked_until())
+        .max();
+    let Some(blocked_until) = blocked_until else {
+        return next.run(req).await;
+    };
+
+    let retry_after = (blocked_until - Utc::now()).num_seconds().max(1);
+    (
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        ApiError::QuotaExceeded(format!("Token quota exhausted until {}", blocked_until.to_rfc3339())),
+    )
+        .into_response()
+}
@@ -0,0 +1,57 @@
+use minijinja::{context, Environment};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// The prompt `/v1/rag/query` sends unless the request brings a template.
+const DEFAULT_TEMPLATE: &str = "\
+Answer the question using only the numbered sources below. Cite the sources \
+each statement is based on by their number in square brackets, like [1]. If \
+the sources do not contain the answer, say that you do not know.
+
+{% for source in sources -%}
+[{{ source.number }}] {{ source.text }}
+
+{% endfor -%}
+Question: {{ question }}
+Answer:";
+
+/// `[1]`, or several sources in one pair of brackets: `[1, 3]`.
+static CITATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").unwrap());
+
+/// A retrieved chunk as templates see it, numbered from 1.
+#[derive(Serialize)]
+pub struct Source<'a> {
+    pub number: usize,
+    pub id: &'a str,
+    pub score: f64,
+    pub text: &'a str,
+    pub metadata: &'a Map<String, Value>,
+}
+
+/// Renders the grounded prompt. Templates get the `question` and the
+/// `sources`, each with its `number`, `id`, `score`, `text` and `metadata`.
+pub fn render_prompt(template: Option<&str>, question: &str, sources: &[Source]) -> Result<String, String> {
+    Environment::new()
+        .render_str(
+            template.unwrap_or(DEFAULT_TEMPLATE),
+            context! { question => question, sources => sources },
+        )
+        .map_err(|e| format!("invalid RAG template: {}", e))
+}
+
+/// The source numbers an answer cites, ignoring numbers no source has.
+pub fn cited(answer: &str, sources: usize) -> HashSet<usize> {
+    CITATION
+        .captures_iter(answer)
+        .flat_map(|captures| {
+            captures[1]
+                .split(',')
+                .filter_map(|number| number.trim().parse::<usize>().ok())
+                .collect::<Vec<_>>()
+        })
+        .filter(|number| (1..=sources).contains(number))
+        .collect()
+}
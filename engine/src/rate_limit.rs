@@ -0,0 +1,232 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::auth::Caller;
+use super::error::ApiError;
+use super::AppState;
+
+pub const DEFAULT_TIER: &str = "default";
+
+/// Headers set on every rate-limited response.
+pub const RATE_LIMIT_HEADERS: [&str; 6] = [
+    "x-ratelimit-limit-requests",
+    "x-ratelimit-remaining-requests",
+    "x-ratelimit-reset-requests",
+    "x-ratelimit-limit-tokens",
+    "x-ratelimit-remaining-tokens",
+    "x-ratelimit-reset-tokens",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitTier {
+    pub requests_per_minute: u32,
+    pub tokens_per_minute: u64,
+}
+
+/// Parses a `--rate-limit-tier` value of the form `name=requests:tokens`,
+/// e.g. `default=60:100000`.
+pub fn parse_tier(value: &str) -> Result<(String, RateLimitTier), String> {
+    let (name, limits) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=REQUESTS:TOKENS, got '{}'", value))?;
+    let (requests, tokens) = limits
+        .split_once(':')
+        .ok_or_else(|| format!("expected REQUESTS:TOKENS, got '{}'", limits))?;
+
+    let tier = RateLimitTier {
+        requests_per_minute: requests
+            .parse()
+            .map_err(|e| format!("invalid requests per minute '{}': {}", requests, e))?,
+        tokens_per_minute: tokens
+            .parse()
+            .map_err(|e| format!("invalid tokens per minute '{}': {}", tokens, e))?,
+    };
+    Ok((name.to_string(), tier))
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(capacity: f64) -> Self {
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec: capacity / 60.0,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` units are available again.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount - self.available;
+        if missing <= 0.0 || self.refill_per_sec <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.refill_per_sec)
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        self.available.max(0.0).floor() as u64
+    }
+}
+
+#[derive(Debug)]
+struct CallerBuckets {
+    tier: RateLimitTier,
+    requests: TokenBucket,
+    tokens: TokenBucket,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitStatus {
+    pub tier: RateLimitTier,
+    pub remaining_requests: u64,
+    pub remaining_tokens: u64,
+    pub reset_requests: Duration,
+    pub reset_tokens: Duration,
+}
+
+pub enum RateLimitDecision {
+    Unlimited,
+    Allowed(RateLimitStatus),
+    Limited {
+        status: RateLimitStatus,
+        retry_after: Duration,
+    },
+}
+
+/// Token-bucket limits per caller: one bucket for requests per minute and
+/// one for tokens per minute. Token usage is only known once a generation
+/// finishes, so it is deducted afterwards and may push the bucket negative,
+/// which then delays the caller's next request.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    tiers: HashMap<String, RateLimitTier>,
+    callers: HashMap<String, CallerBuckets>,
+}
+
+impl RateLimiter {
+    pub fn new(tiers: HashMap<String, RateLimitTier>) -> Self {
+        Self {
+            tiers,
+            callers: HashMap::new(),
+        }
+    }
+
+    fn tier(&self, name: &str) -> Option<RateLimitTier> {
+        self.tiers
+            .get(name)
+            .or_else(|| self.tiers.get(DEFAULT_TIER))
+            .copied()
+    }
+
+    pub fn check(&mut self, caller_id: &str, tier_name: &str) -> RateLimitDecision {
+        let Some(tier) = self.tier(tier_name) else {
+            return RateLimitDecision::Unlimited;
+        };
+
+        let now = Instant::now();
+        let buckets = self
+            .callers
+            .entry(caller_id.to_string())
+            .or_insert_with(|| CallerBuckets {
+                tier,
+                requests: TokenBucket::per_minute(tier.requests_per_minute as f64),
+                tokens: TokenBucket::per_minute(tier.tokens_per_minute as f64),
+            });
+        buckets.requests.refill(now);
+        buckets.tokens.refill(now);
+
+        let request_wait = buckets.requests.wait_for(1.0);
+        let token_wait = buckets.tokens.wait_for(1.0);
+        if request_wait.is_zero() && token_wait.is_zero() {
+            buckets.requests.available -= 1.0;
+            return RateLimitDecision::Allowed(status_of(buckets));
+        }
+
+        RateLimitDecision::Limited {
+            status: status_of(buckets),
+            retry_after: request_wait.max(token_wait),
+        }
+    }
+
+    pub fn consume_tokens(&mut self, caller_id: &str, tokens: u64) {
+        if let Some(buckets) = self.callers.get_mut(caller_id) {
+            buckets.tokens.refill(Instant::now());
+            buckets.tokens.available -= tokens as f64;
+        }
+    }
+}
+
+fn status_of(buckets: &CallerBuckets) -> RateLimitStatus {
+    RateLimitStatus {
+        tier: buckets.tier,
+        remaining_requests: buckets.requests.remaining(),
+        remaining_tokens: buckets.tokens.remaining(),
+        reset_requests: buckets.requests.wait_for(buckets.requests.capacity),
+        reset_tokens: buckets.tokens.wait_for(buckets.tokens.capacity),
+    }
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &'static str, value: String) {
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(HeaderName::from_static(name), value);
+    }
+}
+
+fn apply_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    insert_header(headers, "x-ratelimit-limit-requests", status.tier.requests_per_minute.to_string());
+    insert_header(headers, "x-ratelimit-remaining-requests", status.remaining_requests.to_string());
+    insert_header(headers, "x-ratelimit-reset-requests", format!("{}s", status.reset_requests.as_secs()));
+    insert_header(headers, "x-ratelimit-limit-tokens", status.tier.tokens_per_minute.to_string());
+    insert_header(headers, "x-ratelimit-remaining-tokens", status.remaining_tokens.to_string());
+    insert_header(headers, "x-ratelimit-reset-tokens", format!("{}s", status.reset_tokens.as_secs()));
+}
+
+/// Enforces the caller's rate limit tier. Must run after `require_api_key`.
+pub async fn enforce_rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(caller) = req.extensions().get::<Caller>().cloned() else {
+        return next.run(req).await;
+    };
+    let Some(tier) = caller.tier.as_deref() else {
+        return next.run(req).await;
+    };
+
+    let decision = state.rate_limiter.lock().await.check(&caller.rate_limit_key(), tier);
+    match decision {
+        RateLimitDecision::Unlimited => next.run(req).await,
+        RateLimitDecision::Allowed(status) => {
+            let mut response = next.run(req).await;
+            apply_headers(response.headers_mut(), &status);
+            response
+        }
+        RateLimitDecision::Limited { status, retry_after } => {
+            let retry_secs = retry_after.as_secs_f64().ceil() as u64;
+            let mut response =
+                ApiError::RateLimited(format!("Rate limit exceeded. Retry after {} seconds.", retry_secs))
+                    .into_response();
+            apply_headers(response.headers_mut(), &status);
+            insert_header(response.headers_mut(), "retry-after", retry_secs.to_string());
+            response
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use axum::http::StatusCode;
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use super::auth::Caller;
+use super::backend_health::ProbeStatus;
+use super::error::ApiError;
+use super::v1::inference::InferenceRequest;
+use super::AppState;
+
+/// Operations a script may run per request before it is stopped, so a
+/// runaway loop fails the request instead of stalling the engine.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a routing script decided for a request.
+#[derive(Debug)]
+enum Decision {
+    /// Send the request to the model it names.
+    Keep,
+    Route(String),
+    Reject { status: StatusCode, message: String },
+}
+
+impl Decision {
+    /// Reads a script's result: `()` or `true` keeps the request as is, a
+    /// string routes it to that model, `false` rejects it, and a map can do
+    /// either with `model_id`, or `reject` and an optional `status`.
+    fn parse(result: Value) -> Result<Self, String> {
+        match result {
+            Value::Null | Value::Bool(true) => Ok(Self::Keep),
+            Value::Bool(false) => Ok(Self::reject(None, "Rejected by the routing policy".to_string())),
+            Value::String(model_id) => Ok(Self::Route(model_id)),
+            Value::Object(mut map) => {
+                if let Some(message) = map.remove("reject") {
+                    let message = match message {
+                        Value::String(message) => message,
+                        _ => "Rejected by the routing policy".to_string(),
+                    };
+                    let status = map.get("status").and_then(Value::as_u64);
+                    return Ok(Self::reject(status, message));
+                }
+                match map.remove("model_id") {
+                    None | Some(Value::Null) => Ok(Self::Keep),
+                    Some(Value::String(model_id)) => Ok(Self::Route(model_id)),
+                    Some(other) => Err(format!("model_id must be a string, not {}", other)),
+                }
+            }
+            other => Err(format!(
+                "a script must return (), a bool, a model id or a map, not {}",
+                other
+            )),
+        }
+    }
+
+    /// A 403 unless the script gave another 4xx or 5xx status.
+    fn reject(status: Option<u64>, message: String) -> Self {
+        let status = status
+            .and_then(|status| u16::try_from(status).ok())
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .filter(|status| status.is_client_error() || status.is_server_error())
+            .unwrap_or(StatusCode::FORBIDDEN);
+        Self::Reject { status, message }
+    }
+}
+
+/// A request the routing policy turned away.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct RoutingRejection {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+/// The Rhai script from `--routing-script`, recompiled when the file
+/// changes.
+pub struct RoutingPolicy {
+    path: PathBuf,
+    script: RwLock<Arc<runtime::Script>>,
+}
+
+impl RoutingPolicy {
+    /// Reads and compiles the script.
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        let script = compile(path).await?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            script: RwLock::new(Arc::new(script)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn script(&self) -> Arc<runtime::Script> {
+        self.script.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Polls the script every `interval` and swaps in the new version when
+    /// it changes. A script that no longer compiles is logged and the
+    /// previous one kept, so a typo does not take routing down.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut last = modified(&self.path).await;
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            let current = modified(&self.path).await;
+            if current == last {
+                continue;
+            }
+            last = current;
+
+            match compile(&self.path).await {
+                Ok(script) => {
+                    *self.script.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(script);
+                    tracing::info!("Reloaded routing script from {}", self.path.display());
+                }
+                Err(e) => tracing::warn!("Keeping the previous routing script: {}", e),
+            }
+        }
+    }
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+async fn compile(path: &Path) -> Result<runtime::Script, String> {
+    let source = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    runtime::Script::compile(&source, MAX_OPERATIONS).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Runs the routing script, if there is one, over the request and the
+/// models the caller can see, and applies its decision: the request is
+/// kept, sent to another model, or rejected.
+pub async fn route(state: &AppState, caller: &Caller, path: &str, req: &mut InferenceRequest) -> Result<(), ApiError> {
+    let Some(policy) = state.routing.clone() else {
+        return Ok(());
+    };
+    let input = json!({
+        "request": {
+            "path": path,
+            "model_id": req.model_id,
+            "stream": path.ends_with("/stream"),
+            "prompt_chars": req.prompt.chars().count(),
+            "messages": req.messages.as_ref().map_or(0, Vec::len),
+            "max_tokens": req.max_tokens(),
+            "temperature": req.temperature,
+            "tools": req.tools.as_ref().map_or(0, Vec::len),
+            "session_id": req.session_id,
+            "prompt_id": req.prompt_id,
+            "caller": {
+                "id": caller.id,
+                "role": caller.role,
+                "tier": caller.tier,
+                "tenant": caller.tenant,
+            },
+        },
+        "models": models(state, caller).await,
+        "engine": state.admission.status(),
+    });
+
+    let script = policy.script();
+    let decision = tokio::task::spawn_blocking(move || script.run(input))
+        .await
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .and_then(Decision::parse)
+        .map_err(|e| {
+            tracing::warn!("Routing script failed for model '{}': {}", req.model_id, e);
+            ApiError::Internal(format!("Routing script failed: {}", e))
+        })?;
+
+    match decision {
+        Decision::Keep => Ok(()),
+        Decision::Route(model_id) if model_id == req.model_id => Ok(()),
+        Decision::Route(model_id) => {
+            tracing::debug!("Routing script sent a request for '{}' to '{}'", req.model_id, model_id);
+            req.model_id = model_id;
+            Ok(())
+        }
+        Decision::Reject { status, message } => {
+            tracing::debug!("Routing script rejected a request for '{}': {}", req.model_id, message);
+            Err(RoutingRejection { status, message }.into())
+        }
+    }
+}
+
+/// Live metrics of the models the caller can see, keyed by model id.
+async fn models(state: &AppState, caller: &Caller) -> Map<String, Value> {
+    let entries: Vec<_> = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .filter(|m| m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.clone())
+        .collect();
+    let backends = state.backend_health.lock().await.latest().map(|report| report.backends.clone());
+    let concurrency = state.model_concurrency.lock().await;
+    let stats = state.stats.lock().await;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let backend_up = backends.as_ref().and_then(|backends| {
+                backends
+                    .iter()
+                    .find(|b| b.backend == entry.inference)
+                    .map(|b| b.status == ProbeStatus::Up)
+            });
+            let in_flight = entry.max_concurrency.map(|_| concurrency.in_flight(&entry.id));
+            let totals = stats.model_snapshot(entry.tenant.as_deref(), &entry.id).totals;
+            let measured = entry.measured_latency.as_ref();
+            let metrics = json!({
+                "id": entry.id,
+                "backend": entry.inference,
+                "backend_up": backend_up,
+                "loaded": entry.loaded,
+                "context": entry.context,
+                "capabilities": entry.capabilities,
+                "tenant": entry.tenant,
+                "latency": entry.latency,
+                "tokens_per_second": measured.map(|m| m.tokens_per_second),
+                "time_to_first_token_ms": measured.and_then(|m| m.time_to_first_token_ms),
+                "max_concurrency": entry.max_concurrency,
+                "in_flight": in_flight,
+                "requests": totals.requests,
+                "errors": totals.errors,
+                "average_latency_ms": totals.average_latency_ms,
+            });
+            (entry.id, metrics)
+        })
+        .collect()
+}
+
+/// Runs scripts with Rhai. A script sees `request`, `models` and `engine`
+/// as constants and its last expression is its decision. Printing from a
+/// script logs at debug level.
+#[cfg(feature = "scripting")]
+mod runtime {
+    use rhai::{Dynamic, Engine, Scope, AST};
+    use serde_json::Value;
+
+    pub struct Script {
+        engine: Engine,
+        ast: AST,
+    }
+
+    impl Script {
+        pub fn compile(source: &str, max_operations: u64) -> Result<Self, String> {
+            let mut engine = Engine::new();
+            engine
+                .set_max_operations(max_operations)
+                .set_max_call_levels(32)
+                .set_max_string_size(64 * 1024)
+                .set_max_array_size(10_000)
+                .set_max_map_size(10_000)
+                .on_print(|text| tracing::debug!("Routing script: {}", text))
+                .on_debug(|text, _, position| tracing::debug!("Routing script at {}: {}", position, text));
+            engine.disable_symbol("eval");
+            let ast = engine.compile(source).map_err(|e| e.to_string())?;
+            Ok(Self { engine, ast })
+        }
+
+        pub fn run(&self, input: Value) -> Result<Value, String> {
+            let mut scope = Scope::new();
+            if let Value::Object(input) = input {
+                for (name, value) in input {
+                    let value: Dynamic = rhai::serde::to_dynamic(value).map_err(|e| e.to_string())?;
+                    scope.push_constant_dynamic(name, value);
+                }
+            }
+            let result: Dynamic = self
+                .engine
+                .eval_ast_with_scope(&mut scope, &self.ast)
+                .map_err(|e| e.to_string())?;
+            rhai::serde::from_dynamic(&result).map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+mod runtime {
+    use serde_json::Value;
+
+    const UNAVAILABLE: &str = "openllm-server was built without the scripting feature";
+
+    pub struct Script;
+
+    impl Script {
+        pub fn compile(_source: &str, _max_operations: u64) -> Result<Self, String> {
+            Err(UNAVAILABLE.to_string())
+        }
+
+        pub fn run(&self, _input: Value) -> Result<Value, String> {
+            Err(UNAVAILABLE.to_string())
+        }
+    }
+}
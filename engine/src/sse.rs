@@ -0,0 +1,190 @@
+use async_stream::stream;
+use axum::response::sse::{Event, KeepAlive};
+use futures::stream::{Stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use super::v1::inference::EventStream;
+
+/// Sent by clients reconnecting to a stream, with the id of the last event
+/// they received.
+pub const LAST_EVENT_ID: &str = "last-event-id";
+
+/// How streamed responses are kept open while nothing is sent.
+#[derive(Debug, Clone, Copy)]
+pub struct SseSettings {
+    /// Comment lines are sent after this long without an event, so proxies
+    /// do not close the connection.
+    pub keep_alive: Duration,
+    /// `ping` events are sent after this long without an event, so clients
+    /// see the request is still going.
+    pub ping: Option<Duration>,
+    /// How long a stream's events are kept after it ends, for clients that
+    /// reconnect with `Last-Event-ID`. Streams are not resumable when unset.
+    pub resume_window: Option<Duration>,
+}
+
+impl Default for SseSettings {
+    fn default() -> Self {
+        Self {
+            keep_alive: Duration::from_secs(15),
+            ping: None,
+            resume_window: None,
+        }
+    }
+}
+
+impl SseSettings {
+    pub fn keep_alive(&self) -> KeepAlive {
+        KeepAlive::new().interval(self.keep_alive)
+    }
+}
+
+#[derive(Serialize)]
+struct Ping<'a> {
+    request_id: &'a str,
+    elapsed_ms: u64,
+}
+
+/// Passes a stream's events through, adding a `ping` event whenever `every`
+/// passes without one.
+pub fn with_pings(
+    mut inner: EventStream,
+    every: Option<Duration>,
+    request_id: String,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let started = Instant::now();
+        loop {
+            let next = match every {
+                Some(every) => tokio::select! {
+                    item = inner.next() => Some(item),
+                    _ = tokio::time::sleep(every) => None,
+                },
+                None => Some(inner.next().await),
+            };
+
+            match next {
+                Some(Some(item)) => yield item,
+                Some(None) => return,
+                None => {
+                    let ping = Ping {
+                        request_id: &request_id,
+                        elapsed_ms: started.elapsed().as_millis() as u64,
+                    };
+                    if let Ok(json_data) = serde_json::to_string(&ping) {
+                        yield Ok(Event::default().event("ping").data(json_data));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The events a stream has produced so far, shared by the task that runs it
+/// and the connections that follow it.
+#[derive(Debug)]
+pub struct Replay {
+    caller: String,
+    state: std::sync::Mutex<ReplayState>,
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    events: Vec<Event>,
+    /// Why the stream failed, once it has.
+    error: Option<String>,
+    ended_at: Option<Instant>,
+}
+
+/// Resumable streams by request id. Each stream runs to the end whether or
+/// not a client is still connected, and its events are kept for the resume
+/// window after that.
+#[derive(Debug, Default)]
+pub struct Replays {
+    streams: HashMap<String, Arc<Replay>>,
+}
+
+impl Replays {
+    /// Runs `inner` in the background, numbering its events `request_id:n`
+    /// so a client can tell where it left off. Streams past their window
+    /// are dropped.
+    pub fn start(&mut self, request_id: &str, caller: &str, window: Duration, mut inner: EventStream) -> Arc<Replay> {
+        self.streams.retain(|_, replay| {
+            let ended_at = replay.state.lock().unwrap().ended_at;
+            ended_at.is_none_or(|at| at.elapsed() < window)
+        });
+
+        let replay = Arc::new(Replay {
+            caller: caller.to_string(),
+            state: std::sync::Mutex::default(),
+            changed: Notify::new(),
+        });
+        self.streams.insert(request_id.to_string(), replay.clone());
+
+        let request_id = request_id.to_string();
+        tokio::spawn({
+            let replay = replay.clone();
+            async move {
+                while let Some(item) = inner.next().await {
+                    let mut state = replay.state.lock().unwrap();
+                    match item {
+                        Ok(event) => {
+                            let id = format!("{}:{}", request_id, state.events.len());
+                            state.events.push(event.id(id));
+                        }
+                        Err(e) => {
+                            state.error = Some(e.to_string());
+                            break;
+                        }
+                    }
+                    drop(state);
+                    replay.changed.notify_waiters();
+                }
+                replay.state.lock().unwrap().ended_at = Some(Instant::now());
+                replay.changed.notify_waiters();
+            }
+        });
+        replay
+    }
+
+    /// The stream a `Last-Event-ID` belongs to, if the caller started it and
+    /// it is still kept, and the index of the event after that one.
+    pub fn resume(&self, last_event_id: &str, caller: &str) -> Option<(Arc<Replay>, usize)> {
+        let (request_id, index) = last_event_id.rsplit_once(':')?;
+        let index: usize = index.parse().ok()?;
+        let replay = self.streams.get(request_id).filter(|replay| replay.caller == caller)?;
+        Some((replay.clone(), index + 1))
+    }
+}
+
+/// A stream's events from `from` on, as they are produced.
+pub fn follow(replay: Arc<Replay>, from: usize) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let mut next = from;
+        loop {
+            // Registered before the state is read, so no event is missed.
+            let changed = replay.changed.notified();
+            let (events, error, ended) = {
+                let state = replay.state.lock().unwrap();
+                let events = state.events.get(next..).unwrap_or_default().to_vec();
+                (events, state.error.clone(), state.ended_at.is_some())
+            };
+            next += events.len();
+            for event in events {
+                yield Ok(event);
+            }
+            if ended {
+                if let Some(error) = error {
+                    yield Err(std::io::Error::other(error));
+                }
+                return;
+            }
+            changed.await;
+        }
+    }
+}
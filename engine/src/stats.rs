@@ -0,0 +1,301 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+use super::embedding_cache::EmbeddingCacheSnapshot;
+
+const BUCKET_WIDTH_SECS: i64 = 60;
+const MAX_BUCKETS: usize = 60;
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct UsageCounters {
+    pub requests: u64,
+    pub errors: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_latency_ms: u64,
+    pub total_ttft_ms: u64,
+    pub total_generation_ms: u64,
+    pub draft_tokens: u64,
+    pub accepted_draft_tokens: u64,
+}
+
+impl UsageCounters {
+    fn record(&mut self, outcome: &RequestOutcome) {
+        self.requests += 1;
+        if !outcome.success {
+            self.errors += 1;
+        }
+        self.prompt_tokens += outcome.prompt_tokens as u64;
+        self.completion_tokens += outcome.completion_tokens as u64;
+        self.total_latency_ms += outcome.latency_ms;
+        self.total_ttft_ms += outcome.ttft_ms;
+        self.total_generation_ms += outcome.generation_ms;
+        if let Some(draft) = outcome.draft {
+            self.draft_tokens += draft.drafted as u64;
+            self.accepted_draft_tokens += draft.accepted as u64;
+        }
+    }
+
+    fn merge(&mut self, other: &UsageCounters) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_latency_ms += other.total_latency_ms;
+        self.total_ttft_ms += other.total_ttft_ms;
+        self.total_generation_ms += other.total_generation_ms;
+        self.draft_tokens += other.draft_tokens;
+        self.accepted_draft_tokens += other.accepted_draft_tokens;
+    }
+
+    pub fn average_latency_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_latency_ms as f64 / self.requests as f64
+        }
+    }
+
+    pub fn average_ttft_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_ttft_ms as f64 / self.requests as f64
+        }
+    }
+
+    pub fn tokens_per_second(&self) -> f64 {
+        tokens_per_second(self.completion_tokens, self.total_generation_ms)
+    }
+}
+
+pub fn tokens_per_second(tokens: u64, generation_ms: u64) -> f64 {
+    if generation_ms == 0 {
+        0.0
+    } else {
+        tokens as f64 * 1000.0 / generation_ms as f64
+    }
+}
+
+/// Tokens a draft model proposed during speculative decoding, and how many
+/// of them the model kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DraftTokens {
+    pub drafted: u32,
+    pub accepted: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestOutcome {
+    pub success: bool,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub latency_ms: u64,
+    pub ttft_ms: u64,
+    pub generation_ms: u64,
+    /// Set for requests a backend decoded speculatively.
+    pub draft: Option<DraftTokens>,
+}
+
+#[derive(Debug, Clone)]
+struct StatsBucket {
+    start: DateTime<Utc>,
+    counters: UsageCounters,
+}
+
+#[derive(Debug, Default)]
+struct ModelStats {
+    totals: UsageCounters,
+    buckets: VecDeque<StatsBucket>,
+}
+
+impl ModelStats {
+    fn record(&mut self, outcome: &RequestOutcome, now: DateTime<Utc>) {
+        self.totals.record(outcome);
+
+        let start = bucket_start(now);
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.start == start => bucket.counters.record(outcome),
+            _ => {
+                let mut counters = UsageCounters::default();
+                counters.record(outcome);
+                self.buckets.push_back(StatsBucket { start, counters });
+                while self.buckets.len() > MAX_BUCKETS {
+                    self.buckets.pop_front();
+                }
+            }
+        }
+    }
+}
+
+fn bucket_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.duration_trunc(TimeDelta::seconds(BUCKET_WIDTH_SECS))
+        .unwrap_or(at)
+}
+
+/// Stats per model, partitioned by the tenant that made the requests.
+#[derive(Debug, Default)]
+pub struct UsageStats {
+    models: HashMap<(Option<String>, String), ModelStats>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CountersSnapshot {
+    pub requests: u64,
+    pub errors: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub average_latency_ms: f64,
+    pub average_ttft_ms: f64,
+    pub tokens_per_second: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speculative: Option<SpeculativeSnapshot>,
+}
+
+/// How well draft models guessed, over requests decoded speculatively.
+#[derive(Serialize, ToSchema)]
+pub struct SpeculativeSnapshot {
+    pub draft_tokens: u64,
+    pub accepted_draft_tokens: u64,
+    pub acceptance_rate: f64,
+}
+
+impl From<&UsageCounters> for CountersSnapshot {
+    fn from(counters: &UsageCounters) -> Self {
+        Self {
+            requests: counters.requests,
+            errors: counters.errors,
+            prompt_tokens: counters.prompt_tokens,
+            completion_tokens: counters.completion_tokens,
+            average_latency_ms: counters.average_latency_ms(),
+            average_ttft_ms: counters.average_ttft_ms(),
+            tokens_per_second: counters.tokens_per_second(),
+            speculative: (counters.draft_tokens > 0).then(|| SpeculativeSnapshot {
+                draft_tokens: counters.draft_tokens,
+                accepted_draft_tokens: counters.accepted_draft_tokens,
+                acceptance_rate: counters.accepted_draft_tokens as f64 / counters.draft_tokens as f64,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BucketSnapshot {
+    pub start: DateTime<Utc>,
+    #[serde(flatten)]
+    pub counters: CountersSnapshot,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModelStatsSnapshot {
+    pub model_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(flatten)]
+    pub totals: CountersSnapshot,
+    pub buckets: Vec<BucketSnapshot>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatsSnapshot {
+    pub bucket_width_secs: i64,
+    pub totals: CountersSnapshot,
+    pub buckets: Vec<BucketSnapshot>,
+    pub models: Vec<ModelStatsSnapshot>,
+    /// With `--embedding-cache-size`, for callers not scoped to a tenant.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding_cache: Option<EmbeddingCacheSnapshot>,
+}
+
+impl UsageStats {
+    pub fn record(&mut self, tenant: Option<&str>, model_id: &str, outcome: RequestOutcome) {
+        self.models
+            .entry((tenant.map(str::to_string), model_id.to_string()))
+            .or_default()
+            .record(&outcome, Utc::now());
+    }
+
+    pub fn model_snapshot(&self, tenant: Option<&str>, model_id: &str) -> ModelStatsSnapshot {
+        let key = (tenant.map(str::to_string), model_id.to_string());
+        match self.models.get(&key) {
+            Some(stats) => snapshot_model(&key, stats),
+            None => ModelStatsSnapshot {
+                model_id: model_id.to_string(),
+                tenant: key.0,
+                totals: CountersSnapshot::from(&UsageCounters::default()),
+                buckets: Vec::new(),
+            },
+        }
+    }
+
+    /// A model's counters since `since`, across every tenant's requests,
+    /// to the minute.
+    pub fn recent(&self, model_id: &str, since: DateTime<Utc>) -> UsageCounters {
+        let mut counters = UsageCounters::default();
+        for ((_, id), stats) in &self.models {
+            if id != model_id {
+                continue;
+            }
+            for bucket in stats.buckets.iter().filter(|bucket| bucket.start >= bucket_start(since)) {
+                counters.merge(&bucket.counters);
+            }
+        }
+        counters
+    }
+
+    /// Aggregated stats across all models, or only those of `tenant` when
+    /// given.
+    pub fn snapshot(&self, tenant: Option<&str>) -> StatsSnapshot {
+        let selected: Vec<_> = self
+            .models
+            .iter()
+            .filter(|((model_tenant, _), _)| tenant.is_none() || model_tenant.as_deref() == tenant)
+            .collect();
+
+        let mut totals = UsageCounters::default();
+        let mut buckets: Vec<StatsBucket> = Vec::new();
+
+        for (_, stats) in &selected {
+            totals.merge(&stats.totals);
+            for bucket in &stats.buckets {
+                match buckets.iter_mut().find(|b| b.start == bucket.start) {
+                    Some(existing) => existing.counters.merge(&bucket.counters),
+                    None => buckets.push(bucket.clone()),
+                }
+            }
+        }
+        buckets.sort_by_key(|b| b.start);
+
+        let mut models: Vec<ModelStatsSnapshot> = selected
+            .iter()
+            .map(|(key, stats)| snapshot_model(key, stats))
+            .collect();
+        models.sort_by(|a, b| (&a.model_id, &a.tenant).cmp(&(&b.model_id, &b.tenant)));
+
+        StatsSnapshot {
+            bucket_width_secs: BUCKET_WIDTH_SECS,
+            totals: CountersSnapshot::from(&totals),
+            buckets: buckets.iter().map(snapshot_bucket).collect(),
+            models,
+            embedding_cache: None,
+        }
+    }
+}
+
+fn snapshot_bucket(bucket: &StatsBucket) -> BucketSnapshot {
+    BucketSnapshot {
+        start: bucket.start,
+        counters: CountersSnapshot::from(&bucket.counters),
+    }
+}
+
+fn snapshot_model((tenant, model_id): &(Option<String>, String), stats: &ModelStats) -> ModelStatsSnapshot {
+    ModelStatsSnapshot {
+        model_id: model_id.clone(),
+        tenant: tenant.clone(),
+        totals: CountersSnapshot::from(&stats.totals),
+        buckets: stats.buckets.iter().map(snapshot_bucket).collect(),
+    }
+}
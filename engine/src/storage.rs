@@ -0,0 +1,212 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use super::backend_health::configured_backends;
+use super::backends::{llama_local, ollama};
+use super::v1::inference::get_backend_url;
+use super::{AppState, InferenceBackend, ModelRegistryEntry};
+
+/// A model's weights on disk: a GGUF file, or a model Ollama has pulled.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StoredModel {
+    pub backend: InferenceBackend,
+    /// Ollama's name for the model, e.g. `llama3:latest`, or the GGUF file's
+    /// path.
+    pub name: String,
+    pub size_bytes: u64,
+    /// Registered models that use it, across every tenant. Empty when
+    /// nothing does, so pruning would delete it.
+    pub used_by: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StorageReport {
+    pub models: Vec<StoredModel>,
+    pub total_bytes: u64,
+    /// What the models no registered model uses take up.
+    pub unreferenced_bytes: u64,
+    /// Storage that could not be read, such as Ollama being down.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    /// The models deleted, or that would be with `dry_run`.
+    pub pruned: Vec<StoredModel>,
+    pub freed_bytes: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Lists the GGUF files in `LLAMA_LOCAL_MODEL_DIR`, and those registered
+/// from elsewhere, and the models Ollama has pulled when Ollama is
+/// configured, with the registered models that use each.
+pub async fn scan(state: &AppState) -> StorageReport {
+    let entries: Vec<ModelRegistryEntry> = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .map(|m| m.registry_entry.clone())
+        .collect();
+    let mut models = Vec::new();
+    let mut errors = Vec::new();
+
+    match gguf_files(&entries).await {
+        Ok(files) => models.extend(files),
+        Err(e) => errors.push(e),
+    }
+
+    let registered: Vec<InferenceBackend> = entries.iter().map(|entry| entry.inference.clone()).collect();
+    if configured_backends(&registered).contains(&InferenceBackend::Ollama) {
+        let client = state.backend_clients.get(&InferenceBackend::Ollama);
+        match ollama::tags(&client, &get_backend_url(&InferenceBackend::Ollama)).await {
+            Ok(tags) => models.extend(tags.into_iter().map(|(name, size_bytes)| {
+                let untagged = name.strip_suffix(":latest");
+                let used_by = users(&entries, InferenceBackend::Ollama, |model| {
+                    model == name || Some(model) == untagged
+                });
+                StoredModel {
+                    backend: InferenceBackend::Ollama,
+                    name,
+                    size_bytes,
+                    used_by,
+                }
+            })),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    StorageReport {
+        total_bytes: models.iter().map(|m| m.size_bytes).sum(),
+        unreferenced_bytes: models.iter().filter(|m| m.used_by.is_empty()).map(|m| m.size_bytes).sum(),
+        models,
+        errors,
+    }
+}
+
+/// Deletes the stored models no registered model uses: GGUF files, and
+/// with `ollama` also Ollama's models, which other applications may share.
+pub async fn prune(state: &AppState, dry_run: bool, ollama: bool) -> PruneReport {
+    let report = scan(state).await;
+    let mut errors = report.errors;
+    let unused = report
+        .models
+        .into_iter()
+        .filter(|m| m.used_by.is_empty() && (ollama || m.backend != InferenceBackend::Ollama));
+
+    let mut pruned = Vec::new();
+    for model in unused {
+        if !dry_run {
+            let deleted = match model.backend {
+                InferenceBackend::Ollama => {
+                    let client = state.backend_clients.get(&model.backend);
+                    ollama::delete(&client, &get_backend_url(&model.backend), &model.name).await
+                }
+                _ => tokio::fs::remove_file(&model.name)
+                    .await
+                    .map_err(|e| format!("Failed to delete {}: {}", model.name, e)),
+            };
+            if let Err(e) = deleted {
+                errors.push(e);
+                continue;
+            }
+            tracing::info!("Pruned {:?} model {}", model.backend, model.name);
+        }
+        pruned.push(model);
+    }
+
+    PruneReport {
+        dry_run,
+        freed_bytes: pruned.iter().map(|m| m.size_bytes).sum(),
+        pruned,
+        errors,
+    }
+}
+
+/// The GGUF files in the model directory, plus registered files outside it.
+async fn gguf_files(entries: &[ModelRegistryEntry]) -> Result<Vec<StoredModel>, String> {
+    let dir = llama_local::model_dir();
+    let mut paths = Vec::new();
+    match tokio::fs::read_dir(&dir).await {
+        Ok(mut listing) => {
+            while let Some(file) = listing
+                .next_entry()
+                .await
+                .map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?
+            {
+                let path = file.path();
+                if path.extension().is_some_and(|extension| extension == "gguf") {
+                    paths.push(canonical(&path).await);
+                }
+            }
+        }
+        // No directory just means no local models.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to list {}: {}", dir.display(), e)),
+    }
+
+    let mut registered = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.inference == InferenceBackend::LlamaLocal) {
+        for model in backend_models(entry) {
+            registered.push((canonical(&llama_local::model_path(model)).await, entry.id.clone()));
+        }
+    }
+    for (path, _) in &registered {
+        if !paths.contains(path) && tokio::fs::try_exists(path).await.unwrap_or(false) {
+            paths.push(path.clone());
+        }
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let mut used_by: Vec<String> = registered
+            .iter()
+            .filter(|(registered, _)| *registered == path)
+            .map(|(_, id)| id.clone())
+            .collect();
+        used_by.dedup();
+        files.push(StoredModel {
+            backend: InferenceBackend::LlamaLocal,
+            name: path.display().to_string(),
+            size_bytes: metadata.len(),
+            used_by,
+        });
+    }
+    Ok(files)
+}
+
+/// The path with links and `..` resolved, so the same file is recognised
+/// however it was named; the path as given when it does not exist.
+async fn canonical(path: &Path) -> PathBuf {
+    tokio::fs::canonicalize(path).await.unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Every name the backend knows a model by: its id and its variants'
+/// models.
+fn backend_models(entry: &ModelRegistryEntry) -> Vec<&str> {
+    let mut models = vec![entry.id.as_str()];
+    models.extend(
+        entry
+            .variants
+            .iter()
+            .map(|variant| variant.model.as_deref().unwrap_or(&entry.id)),
+    );
+    models
+}
+
+/// The ids of the models registered on `backend` under a name `matches`
+/// accepts.
+fn users(entries: &[ModelRegistryEntry], backend: InferenceBackend, matches: impl Fn(&str) -> bool) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| entry.inference == backend && backend_models(entry).into_iter().any(&matches))
+        .map(|entry| entry.id.clone())
+        .collect()
+}
@@ -0,0 +1,174 @@
+use minijinja::{context, AutoEscape, Environment, Error, ErrorKind, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+use super::context::render_messages;
+use super::v1::inference::ChatMessage;
+use super::{InferenceBackend, ModelRegistryEntry};
+
+const CHATML: &str = "\
+{%- for message in messages -%}
+<|im_start|>{{ message.role }}
+{{ message.content }}<|im_end|>
+{% endfor -%}
+<|im_start|>assistant
+";
+
+const LLAMA3: &str = "\
+<|begin_of_text|>
+{%- for message in messages -%}
+<|start_header_id|>{{ message.role }}<|end_header_id|>
+
+{{ message.content | trim }}<|eot_id|>
+{%- endfor -%}
+<|start_header_id|>assistant<|end_header_id|>
+
+";
+
+// Mistral has no system role; system messages are prepended to the first
+// user turn.
+const MISTRAL: &str = "\
+{%- set ns = namespace(system='', first=true) -%}
+{%- for message in messages if message.role == 'system' -%}
+{%- set ns.system = ns.system ~ message.content ~ '\n\n' -%}
+{%- endfor -%}
+<s>
+{%- for message in messages if message.role != 'system' -%}
+{%- if message.role == 'user' -%}
+[INST] {% if ns.first %}{{ ns.system }}{% set ns.first = false %}{% endif %}{{ message.content }} [/INST]
+{%- else %} {{ message.content }}</s>
+{%- endif -%}
+{%- endfor -%}
+";
+
+/// Resolves a model's `chat_template` setting: one of the built-in format
+/// names, or otherwise the source of a custom Jinja template.
+fn source(template: &str) -> &str {
+    match template {
+        "chatml" => CHATML,
+        "llama3" => LLAMA3,
+        "mistral" => MISTRAL,
+        custom => custom,
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    // Templates copied from Hugging Face tokenizer configs call this to
+    // reject unsupported conversations.
+    env.add_function("raise_exception", |message: String| -> Result<String, Error> {
+        Err(Error::new(ErrorKind::InvalidOperation, message))
+    });
+    env
+}
+
+fn render(template: &str, messages: &[ChatMessage]) -> Result<String, Error> {
+    environment().render_str(
+        source(template),
+        context! {
+            messages => messages,
+            add_generation_prompt => true,
+            bos_token => "",
+            eos_token => "",
+        },
+    )
+}
+
+/// Checks that a template compiles and renders a minimal conversation, so
+/// mistakes surface at registration rather than on the first request.
+pub fn validate(template: &str) -> Result<(), String> {
+    let sample = [ChatMessage::user("Hello")];
+    render(template, &sample)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid chat template: {}", e))
+}
+
+/// Converts chat messages into the raw prompt sent to the model's backend.
+/// llama.cpp (served or in-process), Hugging Face, TGI, KoboldCpp and
+/// Replicate take pre-formatted prompts, so their models' templates apply, as
+/// do mock models' so templates can be tried out. Other backends format chats
+/// themselves and get the plain `role: content` rendering.
+pub fn render_prompt(model: Option<&ModelRegistryEntry>, messages: &[ChatMessage]) -> Result<String, String> {
+    let template = model
+        .filter(|m| {
+            matches!(
+                m.inference,
+                InferenceBackend::Llama
+                    | InferenceBackend::LlamaLocal
+                    | InferenceBackend::Candle
+                    | InferenceBackend::HuggingFace
+                    | InferenceBackend::Tgi
+                    | InferenceBackend::KoboldCpp
+                    | InferenceBackend::Replicate
+                    | InferenceBackend::Triton
+                    | InferenceBackend::SageMaker
+                    | InferenceBackend::Vertex
+                    | InferenceBackend::Mock
+            )
+        })
+        .and_then(|m| m.chat_template.as_deref());
+
+    match template {
+        Some(template) => render(template, messages).map_err(|e| format!("Failed to apply chat template: {}", e)),
+        None => Ok(render_messages(messages)),
+    }
+}
+
+/// How `variables` are escaped when rendered into a prompt. Values marked
+/// `| safe` in the template are inserted as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableEscape {
+    /// Inserted as they are.
+    #[default]
+    None,
+    /// `<`, `>`, `&` and quotes are escaped, so a value cannot close the
+    /// XML-style tags a prompt wraps it in.
+    Html,
+    /// Inserted as JSON, strings quoted, for prompts laid out as JSON.
+    Json,
+}
+
+/// The environment prompts with variables are rendered in. Unlike chat
+/// templates, referring to a variable the caller did not send is an error
+/// rather than an empty string.
+pub fn variables_environment(escape: VariableEscape) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_auto_escape_callback(move |_| match escape {
+        VariableEscape::None => AutoEscape::None,
+        VariableEscape::Html => AutoEscape::Html,
+        VariableEscape::Json => AutoEscape::Json,
+    });
+    env
+}
+
+/// Compiles a prompt template and returns the variables it reads.
+pub fn template_variables(template: &str) -> Result<HashSet<String>, String> {
+    let env = variables_environment(VariableEscape::None);
+    let template = env.template_from_str(template).map_err(|e| e.to_string())?;
+    let globals: HashSet<&str> = env.globals().map(|(name, _)| name).collect();
+    Ok(template
+        .undeclared_variables(false)
+        .into_iter()
+        .filter(|name| !globals.contains(name.as_str()))
+        .collect())
+}
+
+/// Renders a prompt template such as `Summarize: {{ document }}`, naming
+/// the first variable it reads that is missing from `variables`.
+pub fn render_variables(template: &str, variables: &Map<String, Value>, escape: VariableEscape) -> Result<String, String> {
+    let mut missing: Vec<String> = template_variables(template)?
+        .into_iter()
+        .filter(|name| !variables.contains_key(name))
+        .collect();
+    missing.sort();
+    if let Some(name) = missing.first() {
+        return Err(format!("'{}' is not in variables", name));
+    }
+    variables_environment(escape)
+        .render_str(template, variables)
+        .map_err(|e| e.to_string())
+}
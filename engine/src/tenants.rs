@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::injection::InjectionPolicy;
+use super::quota::{QuotaStatus, QuotaUsage, TokenQuota};
+
+/// Limits and usage shared by every caller of a tenant. Tenants without a
+/// record have no quota and use their callers' own rate limit tiers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantRecord {
+    pub id: String,
+    #[serde(default)]
+    pub tier: Option<String>,
+    #[serde(default)]
+    pub quota: TokenQuota,
+    /// Overrides the server-wide prompt injection policy.
+    #[serde(default)]
+    pub injection: Option<InjectionPolicy>,
+    #[serde(default)]
+    pub usage: QuotaUsage,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct TenantStore {
+    tenants: Vec<TenantRecord>,
+    path: Option<PathBuf>,
+    /// Set when usage counters changed since the last save.
+    dirty: bool,
+}
+
+impl TenantStore {
+    /// Opens the tenant store, loading existing tenants from `path` when given.
+    pub async fn open(path: Option<PathBuf>) -> std::io::Result<Self> {
+        let tenants = match &path {
+            Some(path) => match tokio::fs::read(path).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e),
+            },
+            None => Vec::new(),
+        };
+        Ok(Self {
+            tenants,
+            path,
+            dirty: false,
+        })
+    }
+
+    async fn save(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(&self.tenants)?;
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Saves pending usage counters, if any changed since the last save.
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        if self.dirty {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    pub fn list(&self) -> &[TenantRecord] {
+        &self.tenants
+    }
+
+    /// Creates or replaces the settings of a tenant, keeping its usage.
+    pub async fn upsert(
+        &mut self,
+        tenant_id: &str,
+        tier: Option<String>,
+        quota: TokenQuota,
+        injection: Option<InjectionPolicy>,
+    ) -> std::io::Result<TenantRecord> {
+        let record = match self.tenants.iter_mut().find(|t| t.id == tenant_id) {
+            Some(record) => {
+                record.tier = tier;
+                record.quota = quota;
+                record.injection = injection;
+                record.updated_at = Utc::now();
+                record.clone()
+            }
+            None => {
+                let record = TenantRecord {
+                    id: tenant_id.to_string(),
+                    tier,
+                    quota,
+                    injection,
+                    usage: QuotaUsage::default(),
+                    updated_at: Utc::now(),
+                };
+                self.tenants.push(record.clone());
+                record
+            }
+        };
+        self.save().await?;
+        Ok(record)
+    }
+
+    pub fn tier(&self, tenant_id: &str) -> Option<String> {
+        self.tenants
+            .iter()
+            .find(|t| t.id == tenant_id)
+            .and_then(|t| t.tier.clone())
+    }
+
+    pub fn injection_policy(&self, tenant_id: &str) -> Option<InjectionPolicy> {
+        self.tenants
+            .iter()
+            .find(|t| t.id == tenant_id)
+            .and_then(|t| t.injection)
+    }
+
+    pub fn record_usage(&mut self, tenant_id: &str, tokens: u64) {
+        if let Some(record) = self.tenants.iter_mut().find(|t| t.id == tenant_id) {
+            record.usage.add(tokens, Utc::now());
+            self.dirty = true;
+        }
+    }
+
+    pub fn quota_status(&self, tenant_id: &str) -> Option<QuotaStatus> {
+        self.tenants
+            .iter()
+            .find(|t| t.id == tenant_id)
+            .map(|t| QuotaStatus::compute(&t.quota, &t.usage, Utc::now()))
+    }
+}
@@ -0,0 +1,42 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Loads the PEM certificate chain and private key used to serve HTTPS.
+pub async fn load(cert: &Path, key: &Path) -> std::io::Result<RustlsConfig> {
+    // Several rustls providers can end up in the dependency tree; pin ring so
+    // rustls does not have to guess. Fails harmlessly if already installed.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert, key).await
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// Polls the certificate and key files every `interval` and reloads the TLS
+/// config when either changes, so rotated certificates are picked up without
+/// a restart. Existing connections keep the certificate they started with.
+pub async fn watch(config: RustlsConfig, cert: PathBuf, key: PathBuf, interval: Duration) {
+    let mut last = (modified(&cert).await, modified(&key).await);
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let current = (modified(&cert).await, modified(&key).await);
+        if current == last {
+            continue;
+        }
+
+        match config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => {
+                tracing::info!("Reloaded TLS certificate from {}", cert.display());
+                last = current;
+            }
+            // A rotation may write the cert and key separately; retry on the
+            // next tick rather than giving up.
+            Err(e) => tracing::warn!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::InferenceBackend;
+
+/// A backend's rate limit as last reported through the OpenAI-style
+/// `x-ratelimit-*` and `retry-after` response headers.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct UpstreamRateLimit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_reset_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_reset_at: Option<DateTime<Utc>>,
+    /// Set from `retry-after` on a 429.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<DateTime<Utc>>,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl UpstreamRateLimit {
+    /// When the backend will accept requests again, if it is out of requests
+    /// or tokens right now.
+    pub fn blocked_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let exhausted = |remaining: Option<u64>, reset_at: Option<DateTime<Utc>>| match (remaining, reset_at) {
+            (Some(0), Some(reset_at)) => Some(reset_at),
+            _ => None,
+        };
+        [
+            self.retry_after,
+            exhausted(self.remaining_requests, self.requests_reset_at),
+            exhausted(self.remaining_tokens, self.tokens_reset_at),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|until| *until > now)
+        .max()
+    }
+}
+
+/// A backend's last reported limit, as shown by `/health/backends`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateLimitStatus {
+    pub backend: InferenceBackend,
+    /// Requests to this backend are held back or refused until then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_until: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub limit: UpstreamRateLimit,
+}
+
+/// Parses Groq and OpenAI reset durations such as `2m59.56s`, `7.66s`,
+/// `1h2m` or `120ms`.
+fn parse_reset(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.trim().chars().peekable();
+    let mut parsed_any = false;
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        let unit_secs = match c {
+            'h' => 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                0.001
+            }
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return None,
+        };
+        total += amount * unit_secs;
+        parsed_any = true;
+    }
+    if !number.is_empty() {
+        // A bare number is seconds.
+        total += number.parse::<f64>().ok()?;
+        parsed_any = true;
+    }
+
+    parsed_any.then(|| Duration::from_secs_f64(total))
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn reset_at(headers: &HeaderMap, name: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let duration = parse_reset(header(headers, name)?)?;
+    TimeDelta::from_std(duration).ok().map(|delta| now + delta)
+}
+
+/// Rate limits reported by backends, shared by adapters so that a limit hit
+/// by one request holds back the next ones instead of each of them running
+/// into a 429.
+#[derive(Debug, Default)]
+pub struct UpstreamLimits {
+    by_backend: HashMap<InferenceBackend, UpstreamRateLimit>,
+}
+
+impl UpstreamLimits {
+    /// Records the rate limit headers of a backend response. Responses
+    /// without any are ignored.
+    pub fn observe(&mut self, backend: &InferenceBackend, headers: &HeaderMap, now: DateTime<Utc>) {
+        let limit = UpstreamRateLimit {
+            remaining_requests: header(headers, "x-ratelimit-remaining-requests").and_then(|v| v.parse().ok()),
+            remaining_tokens: header(headers, "x-ratelimit-remaining-tokens").and_then(|v| v.parse().ok()),
+            requests_reset_at: reset_at(headers, "x-ratelimit-reset-requests", now),
+            tokens_reset_at: reset_at(headers, "x-ratelimit-reset-tokens", now),
+            retry_after: reset_at(headers, "retry-after", now),
+            observed_at: now,
+        };
+        let reported = limit.remaining_requests.is_some() || limit.remaining_tokens.is_some() || limit.retry_after.is_some();
+        if reported {
+            self.by_backend.insert(backend.clone(), limit);
+        }
+    }
+
+    pub fn blocked_until(&self, backend: &InferenceBackend, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.by_backend.get(backend).and_then(|limit| limit.blocked_until(now))
+    }
+
+    pub fn report(&self, now: DateTime<Utc>) -> Vec<RateLimitStatus> {
+        let mut report: Vec<RateLimitStatus> = self
+            .by_backend
+            .iter()
+            .map(|(backend, limit)| RateLimitStatus {
+                backend: backend.clone(),
+                blocked_until: limit.blocked_until(now),
+                limit: limit.clone(),
+            })
+            .collect();
+        report.sort_by_key(|status| status.limit.observed_at);
+        report
+    }
+}
@@ -0,0 +1,19 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+
+use super::super::accounting::AccountingSnapshot;
+use super::super::auth::Caller;
+use super::super::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/v1/accounting",
+    tag = "stats",
+    responses((status = 200, body = AccountingSnapshot))
+)]
+pub async fn accounting_summary(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let accounting = state.accounting.lock().await;
+    (StatusCode::OK, Json(accounting.snapshot(caller.tenant.as_deref())))
+}
@@ -0,0 +1,223 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::request_id::RequestId;
+use super::super::{AppState, ModelRegistryEntry};
+use super::inference::{count_tokens, internal_call, stream_events};
+
+/// Prompt lengths, in tokens, measured unless a request picks its own. Those
+/// that do not fit the model's context are skipped.
+const DEFAULT_CONTEXT_LENGTHS: [u32; 4] = [128, 512, 2048, 8192];
+
+const DEFAULT_MAX_TOKENS: u32 = 128;
+
+/// The text every prompt is cut from, so runs are comparable across models
+/// and machines.
+const PASSAGE: &str = "The lighthouse keeper climbed the spiral stairs each evening before sunset, \
+    counting the steps as his father had taught him. At the top he trimmed the wick, polished the \
+    great lens until it caught the last of the daylight, and wrote the weather in a leather logbook: \
+    wind from the south west, a long swell, gulls sheltering on the rocks below. Ships passed far \
+    out on the horizon, their lamps appearing one by one as the sky darkened, and he watched them \
+    until they slipped beyond the headland.";
+
+const INSTRUCTION: &str = "Continue the story above in the same style.";
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BenchmarkRequest {
+    #[serde(default)]
+    pub context_lengths: Option<Vec<u32>>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+/// A model's latest benchmark, kept on its registry entry.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BenchmarkReport {
+    pub ran_at: DateTime<Utc>,
+    /// The quant variant loaded at the time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quant: Option<String>,
+    pub max_tokens: u32,
+    pub results: Vec<BenchmarkResult>,
+}
+
+/// What one prompt length measured.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BenchmarkResult {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub time_to_first_token_ms: u64,
+    /// Prompt processing speed: prompt tokens over the time to first token.
+    pub prompt_tokens_per_second: f64,
+    /// Generation speed: the tokens after the first over the time they took.
+    pub tokens_per_second: f64,
+    pub total_ms: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BenchmarkResponse {
+    pub model_id: String,
+    #[serde(flatten)]
+    pub report: BenchmarkReport,
+}
+
+/// Runs the standard prompts through a loaded model, one at a time, after a
+/// short warm-up. The runs are not recorded in stats, quotas or the audit
+/// log.
+#[utoipa::path(
+    post,
+    path = "/v1/models/{model_id}/benchmark",
+    tag = "models",
+    params(("model_id" = String, Path)),
+    request_body(content = Option<BenchmarkRequest>, description = "Optional; an empty body runs the defaults"),
+    responses(
+        (status = 200, body = BenchmarkResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn benchmark_model(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Path(model_id): Path<String>,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let req: BenchmarkRequest = if body.is_empty() {
+        BenchmarkRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ApiError::invalid(format!("Invalid benchmark request: {}", e)))?
+    };
+
+    let model = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(&caller))
+        .map(|m| m.registry_entry.clone())
+        .ok_or_else(|| ApiError::ModelNotFound(model_id.clone()))?;
+    if !model.loaded {
+        return Err(ApiError::ModelNotLoaded(model_id));
+    }
+
+    let max_tokens = req.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    if max_tokens == 0 {
+        return Err(ApiError::Unprocessable {
+            message: "max_tokens must be at least 1".to_string(),
+            param: Some("max_tokens"),
+        });
+    }
+    let fits = |length: &u32| length.saturating_add(max_tokens) <= model.context;
+    let context_lengths = match req.context_lengths {
+        Some(lengths) => {
+            if let Some(length) = lengths.iter().find(|length| !fits(length)) {
+                return Err(ApiError::Unprocessable {
+                    message: format!(
+                        "A {} token prompt and {} tokens of output exceed the model's context of {}",
+                        length, max_tokens, model.context
+                    ),
+                    param: Some("context_lengths"),
+                });
+            }
+            lengths
+        }
+        None => DEFAULT_CONTEXT_LENGTHS.into_iter().filter(fits).collect(),
+    };
+    if context_lengths.is_empty() {
+        return Err(ApiError::Unprocessable {
+            message: "No prompt lengths to benchmark".to_string(),
+            param: Some("context_lengths"),
+        });
+    }
+
+    // The first request after a load can pay for it, so it is not counted.
+    run(&state, &model, prompt(0), 1, &request_id.0).await?;
+
+    let mut results = Vec::with_capacity(context_lengths.len());
+    for length in context_lengths {
+        results.push(run(&state, &model, prompt(length), max_tokens, &request_id.0).await?);
+    }
+
+    let report = BenchmarkReport {
+        ran_at: Utc::now(),
+        quant: model.quant.clone(),
+        max_tokens,
+        results,
+    };
+    if let Some(entry) = state
+        .models
+        .lock()
+        .await
+        .iter_mut()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(&caller))
+    {
+        entry.registry_entry.benchmark = Some(report.clone());
+        state.registry_revision.bump();
+    }
+
+    Ok((StatusCode::OK, Json(BenchmarkResponse { model_id, report })))
+}
+
+/// A prompt of `tokens` tokens by the engine's count: as much of the passage
+/// as fits, repeated if need be, then the instruction.
+fn prompt(tokens: u32) -> String {
+    let room = tokens.saturating_sub(count_tokens(INSTRUCTION)) as usize;
+    let mut words: Vec<&str> = PASSAGE.split_whitespace().cycle().take(room).collect();
+    words.push(INSTRUCTION);
+    words.join(" ")
+}
+
+/// Streams one prompt and times its tokens as they arrive.
+async fn run(
+    state: &AppState,
+    model: &ModelRegistryEntry,
+    prompt: String,
+    max_tokens: u32,
+    request_id: &str,
+) -> Result<BenchmarkResult, ApiError> {
+    let prompt_tokens = count_tokens(&prompt);
+    let call = internal_call(state, model, prompt, max_tokens, request_id)
+        .await
+        .map_err(ApiError::Unavailable)?;
+
+    let started = Instant::now();
+    let mut events = stream_events(&model.inference, call);
+    let mut completion_tokens = 0u32;
+    let mut first_token_at = None;
+    while let Some(event) = events.next().await {
+        if let Err(e) = event {
+            return Err(ApiError::Backend(e.to_string()));
+        }
+        completion_tokens += 1;
+        first_token_at.get_or_insert_with(Instant::now);
+    }
+
+    let total = started.elapsed();
+    let to_first_token = first_token_at.map_or(total, |at| at - started);
+    Ok(BenchmarkResult {
+        prompt_tokens,
+        completion_tokens,
+        time_to_first_token_ms: to_first_token.as_millis() as u64,
+        prompt_tokens_per_second: per_second(prompt_tokens, to_first_token),
+        tokens_per_second: per_second(completion_tokens.saturating_sub(1), total - to_first_token),
+        total_ms: total.as_millis() as u64,
+    })
+}
+
+fn per_second(tokens: u32, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 { tokens as f64 / seconds } else { 0.0 }
+}
@@ -0,0 +1,220 @@
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::backends::cohere;
+use super::super::context::render_messages;
+use super::super::ensemble::{self, Candidate};
+use super::super::error::{ApiError, ErrorObject};
+use super::super::request_id::RequestId;
+use super::super::{AppState, InferenceBackend, ModelCapability};
+use super::inference::{complete_request, get_backend_url, FinishReason, InferenceRequest, InferenceResponse};
+
+pub const MAX_BEST_OF: u32 = 8;
+
+/// Tokens the judge may use to rank the candidates.
+const JUDGE_MAX_TOKENS: u32 = 256;
+
+/// How `best_of` candidates are compared.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CandidateScorer {
+    /// A model ranks the candidates from a judge prompt; the request's own
+    /// model unless `model_id` is given.
+    Judge {
+        #[serde(default)]
+        model_id: Option<String>,
+        /// What makes a candidate better. Defaults to accuracy, helpfulness
+        /// and clarity.
+        #[serde(default)]
+        criteria: Option<String>,
+    },
+    /// A model with the `rerank` capability scores each candidate's
+    /// relevance to the prompt.
+    Reranker { model_id: String },
+}
+
+/// A `best_of` candidate, as returned with `return_candidates`.
+#[derive(Serialize, ToSchema)]
+pub struct BestOfCandidate {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_generated: Option<u32>,
+    /// Higher is better. Judged candidates score by rank, from 1 for the
+    /// best down to 0 for any the judge left unranked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Why the candidate could not be generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+}
+
+/// Generates `best_of` candidates for the request in parallel, each through
+/// the same checks as a single completion, and returns the one the scorer
+/// rates highest.
+pub(super) async fn complete_best_of(
+    state: AppState,
+    caller: Caller,
+    request_id: RequestId,
+    mut req: InferenceRequest,
+) -> Result<InferenceResponse, ApiError> {
+    let n = req.best_of.take().unwrap_or(1);
+    if n == 0 || n > MAX_BEST_OF {
+        return Err(ApiError::InvalidRequest {
+            message: format!("best_of must be between 1 and {}", MAX_BEST_OF),
+            param: Some("best_of"),
+        });
+    }
+    let scorer = req.scorer.take().unwrap_or(CandidateScorer::Judge {
+        model_id: None,
+        criteria: None,
+    });
+    let return_candidates = req.return_candidates;
+    if n == 1 {
+        return complete_request(state, caller, request_id, req).await;
+    }
+
+    let question = match &req.messages {
+        Some(messages) if req.prompt.is_empty() => render_messages(messages),
+        _ => req.prompt.clone(),
+    };
+    let results = join_all((0..n as u64).map(|i| {
+        let mut candidate = req.clone();
+        // The same seed would give the same candidate every time.
+        candidate.sampling.seed = req.sampling.seed.map(|seed| seed.wrapping_add(i));
+        complete_request(state.clone(), caller.clone(), request_id.clone(), candidate)
+    }))
+    .await;
+
+    let answered: Vec<usize> = (0..results.len()).filter(|&i| results[i].is_ok()).collect();
+    if answered.is_empty() {
+        return Err(results.into_iter().find_map(Result::err).expect("at least one candidate"));
+    }
+    let texts: Vec<String> = answered
+        .iter()
+        .map(|&i| results[i].as_ref().map(|r| r.text.clone()).unwrap_or_default())
+        .collect();
+
+    let scores = match answered.len() {
+        1 => vec![1.0],
+        _ => score(&state, &caller, &request_id, &req.model_id, &scorer, &question, &texts).await?,
+    };
+    let best = (0..scores.len())
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(b.cmp(&a)))
+        .map(|position| answered[position])
+        .unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    let mut best_response = None;
+    for (index, result) in results.into_iter().enumerate() {
+        let score = answered.iter().position(|&i| i == index).map(|position| scores[position]);
+        let candidate = match &result {
+            Ok(response) => BestOfCandidate {
+                index,
+                text: Some(response.text.clone()),
+                finish_reason: Some(response.finish_reason),
+                tokens_generated: Some(response.tokens_generated),
+                score,
+                error: None,
+            },
+            Err(e) => BestOfCandidate {
+                index,
+                text: None,
+                finish_reason: None,
+                tokens_generated: None,
+                score: None,
+                error: Some(ErrorObject::for_request(e, &request_id)),
+            },
+        };
+        candidates.push(candidate);
+        if index == best {
+            best_response = result.ok();
+        }
+    }
+
+    let mut response = best_response.expect("the best candidate was answered");
+    if return_candidates {
+        response.candidates = candidates;
+    }
+    Ok(response)
+}
+
+/// One score per candidate text, higher being better.
+async fn score(
+    state: &AppState,
+    caller: &Caller,
+    request_id: &RequestId,
+    model_id: &str,
+    scorer: &CandidateScorer,
+    question: &str,
+    texts: &[String],
+) -> Result<Vec<f64>, ApiError> {
+    match scorer {
+        CandidateScorer::Judge { model_id: judge, criteria } => {
+            let candidates: Vec<Candidate> = texts
+                .iter()
+                .enumerate()
+                .map(|(i, text)| Candidate { number: i + 1, text })
+                .collect();
+            let prompt = ensemble::judge_prompt(
+                question,
+                criteria.as_deref().unwrap_or(ensemble::DEFAULT_CRITERIA),
+                &candidates,
+            );
+            let verdict = complete_request(
+                state.clone(),
+                caller.clone(),
+                request_id.clone(),
+                InferenceRequest {
+                    temperature: Some(0.0),
+                    ..InferenceRequest::prompt(judge.as_deref().unwrap_or(model_id), prompt, JUDGE_MAX_TOKENS)
+                },
+            )
+            .await?;
+
+            let ranking = ensemble::parse_ranking(&verdict.text, texts.len());
+            let mut scores = vec![0.0; texts.len()];
+            for (position, number) in ranking.iter().enumerate() {
+                scores[number - 1] = 1.0 - position as f64 / texts.len() as f64;
+            }
+            Ok(scores)
+        }
+        CandidateScorer::Reranker { model_id } => {
+            let models = state.models.lock().await;
+            let entry = models
+                .iter()
+                .find(|m| m.registry_entry.id == *model_id && m.registry_entry.visible_to(caller))
+                .map(|m| m.registry_entry.clone())
+                .ok_or_else(|| ApiError::ModelNotFound(model_id.clone()))?;
+            drop(models);
+
+            if !entry.loaded {
+                return Err(ApiError::ModelNotLoaded(model_id.clone()));
+            }
+            if !entry.capabilities.contains(&ModelCapability::Rerank) {
+                return Err(ApiError::InvalidRequest {
+                    message: format!("Model '{}' does not have the rerank capability", model_id),
+                    param: Some("scorer"),
+                });
+            }
+            let client = state.backend_clients.get(&entry.inference);
+            let base_url = get_backend_url(&entry.inference);
+            match entry.inference {
+                InferenceBackend::Cohere => {
+                    cohere::rerank(&client, &base_url, entry.backend_model(), question, texts, &request_id.0)
+                        .await
+                        .map_err(ApiError::Backend)
+                }
+                ref other => Err(ApiError::invalid(format!(
+                    "Reranking is not supported on the {:?} backend",
+                    other
+                ))),
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::backends::hosted_oss::{self, CatalogModel};
+use super::super::backends::{lmstudio, mlx};
+use super::super::error::{ApiError, ErrorBody};
+use super::super::llama_slots::{self, SlotStatus};
+use super::super::request_id::RequestId;
+use super::super::{AppState, InferenceBackend, LoadedModel, ModelRegistryEntry};
+use super::inference::get_backend_url;
+use super::models::sync_backend;
+
+#[derive(Serialize, ToSchema)]
+pub struct CatalogResponse {
+    pub backend: InferenceBackend,
+    pub models: Vec<CatalogModel>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ImportCatalogRequest {
+    /// Catalog ids to register. Everything in the catalog when omitted.
+    #[serde(default)]
+    pub models: Option<Vec<String>>,
+    /// Loads the registered models right away.
+    #[serde(default)]
+    pub load: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportCatalogResponse {
+    pub registered: Vec<String>,
+    /// Ids that were already registered.
+    pub skipped: Vec<String>,
+    /// Requested ids the catalog does not have.
+    pub not_found: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LlamaSlotsResponse {
+    pub url: String,
+    pub slots: Vec<SlotStatus>,
+}
+
+async fn fetch_catalog(state: &AppState, backend: &InferenceBackend) -> Result<Vec<CatalogModel>, ApiError> {
+    let client = state.backend_clients.get(backend);
+    let url = get_backend_url(backend);
+    let models = match backend {
+        InferenceBackend::Together | InferenceBackend::Fireworks => hosted_oss::list_models(backend, &client, &url).await,
+        InferenceBackend::LmStudio => lmstudio::list_models(&client, &url).await,
+        InferenceBackend::Mlx => mlx::list_models(&client, &url).await,
+        _ => {
+            return Err(ApiError::InvalidRequest {
+                message: format!(
+                    "Model catalogs are only available for together, fireworks, lmstudio and mlx, not {:?}",
+                    backend
+                ),
+                param: Some("backend"),
+            });
+        }
+    };
+    models.map_err(ApiError::Backend)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/backends/{backend}/models",
+    tag = "backends",
+    params(("backend" = InferenceBackend, Path)),
+    responses(
+        (status = 200, body = CatalogResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn list_catalog(
+    State(state): State<AppState>,
+    Path(backend): Path<InferenceBackend>,
+) -> Result<impl IntoResponse, ApiError> {
+    let models = fetch_catalog(&state, &backend).await?;
+    Ok((StatusCode::OK, Json(CatalogResponse { backend, models })))
+}
+
+/// Registers catalog models under their catalog id, with the context length,
+/// capabilities and prices the host reports. With `load`, backends that manage
+/// model memory load each new model first.
+#[utoipa::path(
+    post,
+    path = "/v1/backends/{backend}/models/import",
+    tag = "backends",
+    params(("backend" = InferenceBackend, Path)),
+    request_body = ImportCatalogRequest,
+    responses(
+        (status = 200, body = ImportCatalogResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn import_catalog(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(backend): Path<InferenceBackend>,
+    Json(req): Json<ImportCatalogRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let catalog = fetch_catalog(&state, &backend).await?;
+
+    let not_found = req
+        .models
+        .iter()
+        .flatten()
+        .filter(|id| !catalog.iter().any(|m| &m.id == *id))
+        .cloned()
+        .collect();
+    let wanted = catalog
+        .into_iter()
+        .filter(|m| req.models.as_ref().is_none_or(|ids| ids.contains(&m.id)));
+
+    let known: Vec<String> = state.models.lock().await.iter().map(|m| m.registry_entry.id.clone()).collect();
+    let (skipped, new): (Vec<CatalogModel>, Vec<CatalogModel>) = wanted.partition(|m| known.contains(&m.id));
+    let entries: Vec<ModelRegistryEntry> = new
+        .into_iter()
+        .map(|model| ModelRegistryEntry {
+            id: model.id,
+            name: model.name,
+            inference: backend.clone(),
+            context: model.context,
+            quant: model.quant,
+            variants: Vec::new(),
+            capabilities: model.capabilities,
+            latency: None,
+            latency_pinned: false,
+            measured_latency: None,
+            pricing: model.pricing,
+            chat_template: None,
+            post_process: Vec::new(),
+            speculative: None,
+            max_concurrency: None,
+            // Catalog models run in the backend and take no memory here.
+            size_bytes: 0,
+            loaded: req.load,
+            loaded_at: req.load.then(Utc::now),
+            placement: None,
+            benchmark: None,
+            tenant: caller.tenant.clone(),
+        })
+        .collect();
+
+    if req.load {
+        for entry in &entries {
+            sync_backend(&state, entry, true).await.map_err(ApiError::Backend)?;
+        }
+    }
+
+    let mut registered = Vec::new();
+    let mut models = state.models.lock().await;
+    for entry in entries {
+        if models.iter().any(|m| m.registry_entry.id == entry.id) {
+            continue;
+        }
+        registered.push(entry.id.clone());
+        models.push(LoadedModel {
+            registry_entry: entry,
+            last_accessed: SystemTime::now(),
+        });
+    }
+    if !registered.is_empty() {
+        state.registry_revision.bump();
+    }
+    let skipped = skipped.into_iter().map(|m| m.id).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(ImportCatalogResponse {
+            registered,
+            skipped,
+            not_found,
+        }),
+    ))
+}
+
+/// llama-server's slots as it reports them, with the sessions the engine has
+/// reserved them for.
+#[utoipa::path(
+    get,
+    path = "/v1/backends/llama/slots",
+    tag = "backends",
+    responses(
+        (status = 200, body = LlamaSlotsResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn list_llama_slots(
+    State(state): State<AppState>,
+    Extension(request_id): Extension<RequestId>,
+) -> Result<impl IntoResponse, ApiError> {
+    let backend = InferenceBackend::Llama;
+    let url = get_backend_url(&backend);
+    let reported = llama_slots::fetch(&state.backend_clients.get(&backend), &url, &request_id.0)
+        .await
+        .map_err(ApiError::Backend)?;
+
+    let mut slots = state.llama_slots.lock().await;
+    slots.sync(&url, &reported);
+    let slots = slots.report(&url, &reported, Utc::now());
+    Ok((StatusCode::OK, Json(LlamaSlotsResponse { url, slots })))
+}
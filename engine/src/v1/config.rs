@@ -0,0 +1,14 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use super::super::config::EffectiveConfig;
+use super::super::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/v1/config",
+    tag = "config",
+    responses((status = 200, body = EffectiveConfig))
+)]
+pub async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.config.as_ref().clone()))
+}
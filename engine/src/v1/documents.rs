@@ -0,0 +1,251 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use utoipa::IntoParams;
+
+use super::super::auth::Caller;
+use super::super::documents::{self, ChunkStrategy, DocumentFormat, DocumentJob, JobStatus};
+use super::super::error::{ApiError, ErrorBody};
+use super::super::request_id::RequestId;
+use super::super::vector_store::StoredPoint;
+use super::super::AppState;
+use super::embeddings::{embed_texts, embedding_model};
+use super::vectors::{collection_name, vector_store, DOCUMENT_INPUT_TYPE};
+
+const MAX_CHUNK_SIZE: usize = 8192;
+
+/// Chunks embedded and stored per call to the backend and the store.
+const BATCH_SIZE: usize = 64;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IngestDocumentParams {
+    /// The embedding model; search the collection with the same one.
+    pub model_id: String,
+    pub collection: String,
+    /// Chunks are stored as `{document_id}:{n}`, so uploading a document
+    /// again under its id replaces them. Generated when omitted.
+    #[serde(default)]
+    pub document_id: Option<String>,
+    /// A file name or title, kept in every chunk's metadata.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    #[param(inline)]
+    pub strategy: ChunkStrategy,
+    /// Tokens per chunk.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Tokens repeated from the end of one chunk at the start of the next.
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: usize,
+}
+
+fn default_chunk_size() -> usize {
+    256
+}
+
+fn default_chunk_overlap() -> usize {
+    32
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/documents",
+    tag = "vectors",
+    params(IngestDocumentParams),
+    request_body(
+        description = "The document, as text/plain, text/markdown or application/pdf",
+        content(
+            (String = "text/plain"),
+            (String = "text/markdown"),
+            (Vec<u8> = "application/pdf"),
+        ),
+    ),
+    responses(
+        (status = 202, description = "Ingestion started; poll the job for progress", body = DocumentJob),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn ingest_document(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Query(mut params): Query<IngestDocumentParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    vector_store(&state)?;
+    let collection = collection_name(&caller, &params.collection)?;
+    embedding_model(&state, &caller, &params.model_id).await?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let format = DocumentFormat::from_content_type(content_type).ok_or_else(|| {
+        ApiError::invalid(format!(
+            "Unsupported Content-Type '{}'; send text/plain, text/markdown or application/pdf",
+            content_type
+        ))
+    })?;
+    if body.is_empty() {
+        return Err(ApiError::invalid("The document is empty"));
+    }
+    if params.chunk_size == 0 || params.chunk_size > MAX_CHUNK_SIZE {
+        return Err(ApiError::InvalidRequest {
+            message: format!("chunk_size must be between 1 and {}", MAX_CHUNK_SIZE),
+            param: Some("chunk_size"),
+        });
+    }
+    if params.chunk_overlap >= params.chunk_size {
+        return Err(ApiError::InvalidRequest {
+            message: "chunk_overlap must be smaller than chunk_size".to_string(),
+            param: Some("chunk_overlap"),
+        });
+    }
+
+    let document_id = params
+        .document_id
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+        .clone();
+    let job = DocumentJob::new(&caller, document_id, params.collection.clone(), body.len());
+    let job_id = job.id.clone();
+    state.document_jobs.lock().await.insert(job.clone());
+    tracing::info!(
+        "Ingesting document '{}' ({} bytes) into collection '{}' as job {}",
+        job.document_id,
+        body.len(),
+        params.collection,
+        job_id
+    );
+
+    tokio::spawn(async move {
+        let result = ingest(&state, &caller, &request_id, &job_id, &params, &collection, format, body).await;
+        let mut jobs = state.document_jobs.lock().await;
+        match result {
+            Ok(()) => jobs.update(&job_id, DocumentJob::complete),
+            Err(error) => {
+                tracing::warn!("Document ingestion job {} failed: {}", job_id, error);
+                jobs.update(&job_id, |job| job.fail(error));
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/v1/documents/jobs/{}", job.id))],
+        Json(job),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/documents/jobs/{job_id}",
+    tag = "vectors",
+    params(("job_id" = String, Path, description = "The id /v1/documents returned")),
+    responses(
+        (status = 200, body = DocumentJob),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn document_job(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = state
+        .document_jobs
+        .lock()
+        .await
+        .get(&job_id, &caller)
+        .ok_or_else(|| ApiError::NotFound(format!("Document job '{}' not found", job_id)))?;
+    Ok((StatusCode::OK, Json(job)))
+}
+
+/// Extracts, chunks, embeds and stores a document, reporting progress on
+/// its job. Errors are the job's `error`.
+#[allow(clippy::too_many_arguments)]
+async fn ingest(
+    state: &AppState,
+    caller: &Caller,
+    request_id: &RequestId,
+    job_id: &str,
+    params: &IngestDocumentParams,
+    collection: &str,
+    format: DocumentFormat,
+    body: Bytes,
+) -> Result<(), String> {
+    let set_status = |status: JobStatus, chunks: usize| async move {
+        state.document_jobs.lock().await.update(job_id, |job| {
+            job.status = status;
+            job.chunks = chunks;
+        });
+    };
+
+    set_status(JobStatus::Extracting, 0).await;
+    let text = tokio::task::spawn_blocking(move || documents::extract_text(format, &body))
+        .await
+        .unwrap_or_else(|_| Err("Failed to read the document".to_string()))?;
+    let chunks = documents::chunk(&text, params.strategy, params.chunk_size, params.chunk_overlap);
+    if chunks.is_empty() {
+        return Err("The document contains no text".to_string());
+    }
+
+    set_status(JobStatus::Indexing, chunks.len()).await;
+    let store = vector_store(state).map_err(|e| e.to_string())?;
+    let document_id = params.document_id.as_deref().unwrap_or_default();
+
+    for (batch, chunks) in chunks.chunks(BATCH_SIZE).enumerate() {
+        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        let embedded = embed_texts(
+            state,
+            caller,
+            &params.model_id,
+            &texts,
+            Some(DOCUMENT_INPUT_TYPE),
+            &request_id.0,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let points = chunks
+            .iter()
+            .zip(embedded.vectors)
+            .enumerate()
+            .map(|(i, (chunk, vector))| {
+                let index = batch * BATCH_SIZE + i;
+                let mut metadata = Map::new();
+                metadata.insert("document_id".to_string(), Value::from(document_id));
+                metadata.insert("chunk".to_string(), Value::from(index));
+                if let Some(name) = &params.name {
+                    metadata.insert("name".to_string(), Value::from(name.as_str()));
+                }
+                if let Some(heading) = &chunk.heading {
+                    metadata.insert("heading".to_string(), Value::from(heading.as_str()));
+                }
+                StoredPoint {
+                    id: format!("{}:{}", document_id, index),
+                    vector,
+                    text: chunk.text.clone(),
+                    metadata,
+                }
+            })
+            .collect();
+        store.upsert(collection, points).await?;
+
+        state.document_jobs.lock().await.update(job_id, |job| {
+            job.indexed += chunks.len();
+            job.tokens += embedded.tokens;
+            job.cached += embedded.cached;
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::backends::cohere;
+use super::super::embedding_cache::CacheKey;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::request_id::RequestId;
+use super::super::{AppState, InferenceBackend, ModelCapability, ModelRegistryEntry};
+use super::inference::get_backend_url;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EmbeddingRequest {
+    pub model_id: String,
+    pub input: Vec<String>,
+    /// What the texts will be used for, on backends that distinguish, e.g.
+    /// Cohere's `search_document` or `search_query`.
+    #[serde(default)]
+    pub input_type: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EmbeddingResponse {
+    pub model_id: String,
+    pub embeddings: Vec<Vec<f32>>,
+    pub tokens: u32,
+    /// Inputs answered from the embedding cache. They count no tokens.
+    pub cached: usize,
+}
+
+/// What a backend adapter returns for an embedding call, one vector per input.
+pub(crate) struct Embeddings {
+    pub vectors: Vec<Vec<f32>>,
+    pub tokens: u32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/embeddings",
+    tag = "inference",
+    request_body = EmbeddingRequest,
+    responses(
+        (status = 200, body = EmbeddingResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn create_embeddings(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<EmbeddingRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.input.is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "input must not be empty".to_string(),
+            param: Some("input"),
+        });
+    }
+
+    let embedded = embed_texts(
+        &state,
+        &caller,
+        &req.model_id,
+        &req.input,
+        req.input_type.as_deref(),
+        &request_id.0,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(EmbeddingResponse {
+            model_id: req.model_id,
+            embeddings: embedded.vectors,
+            tokens: embedded.tokens,
+            cached: embedded.cached,
+        }),
+    ))
+}
+
+/// Embedding vectors for a request's texts, in order.
+pub(crate) struct EmbeddedTexts {
+    pub vectors: Vec<Vec<f32>>,
+    /// Tokens the backend counted for the texts that were not cached.
+    pub tokens: u32,
+    pub cached: usize,
+}
+
+/// Embeds `texts` with a loaded embedding model, taking what it can from the
+/// embedding cache. Used by `/v1/embeddings` and the vector store.
+pub(crate) async fn embed_texts(
+    state: &AppState,
+    caller: &Caller,
+    model_id: &str,
+    texts: &[String],
+    input_type: Option<&str>,
+    request_id: &str,
+) -> Result<EmbeddedTexts, ApiError> {
+    let entry = embedding_model(state, caller, model_id).await?;
+
+    let keys: Vec<CacheKey> = texts
+        .iter()
+        .map(|text| CacheKey::new(&entry.id, entry.backend_model(), input_type, text))
+        .collect();
+    let mut vectors: Vec<Option<Vec<f32>>> = match &state.embedding_cache {
+        Some(cache) => {
+            let mut cache = cache.lock().await;
+            keys.iter().map(|key| cache.get(key)).collect()
+        }
+        None => vec![None; keys.len()],
+    };
+
+    let missing: Vec<usize> = (0..vectors.len()).filter(|&i| vectors[i].is_none()).collect();
+    let mut tokens = 0;
+    if !missing.is_empty() {
+        let texts: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+        let embeddings = embed(state, &entry, &texts, input_type, request_id).await?;
+        if embeddings.vectors.len() != texts.len() {
+            return Err(ApiError::Backend(format!(
+                "Backend returned {} embeddings for {} inputs",
+                embeddings.vectors.len(),
+                texts.len()
+            )));
+        }
+        tokens = embeddings.tokens;
+
+        if let Some(cache) = &state.embedding_cache {
+            let mut cache = cache.lock().await;
+            for (&i, vector) in missing.iter().zip(&embeddings.vectors) {
+                cache.insert(keys[i].clone(), vector.clone());
+            }
+        }
+        for (&i, vector) in missing.iter().zip(embeddings.vectors) {
+            vectors[i] = Some(vector);
+        }
+    }
+
+    Ok(EmbeddedTexts {
+        vectors: vectors.into_iter().flatten().collect(),
+        tokens,
+        cached: keys.len() - missing.len(),
+    })
+}
+
+/// A loaded model with the embedding capability, checked before any text is
+/// embedded with it.
+pub(crate) async fn embedding_model(
+    state: &AppState,
+    caller: &Caller,
+    model_id: &str,
+) -> Result<ModelRegistryEntry, ApiError> {
+    let models = state.models.lock().await;
+    let entry = models
+        .iter()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.clone())
+        .ok_or_else(|| ApiError::ModelNotFound(model_id.to_string()))?;
+    drop(models);
+
+    if !entry.loaded {
+        return Err(ApiError::ModelNotLoaded(model_id.to_string()));
+    }
+    if !entry.capabilities.iter().any(|c| matches!(c, ModelCapability::Embedding)) {
+        return Err(ApiError::InvalidRequest {
+            message: format!("Model '{}' does not have the embedding capability", model_id),
+            param: Some("model_id"),
+        });
+    }
+    Ok(entry)
+}
+
+async fn embed(
+    state: &AppState,
+    entry: &ModelRegistryEntry,
+    texts: &[String],
+    input_type: Option<&str>,
+    request_id: &str,
+) -> Result<Embeddings, ApiError> {
+    let client = state.backend_clients.get(&entry.inference);
+    let base_url = get_backend_url(&entry.inference);
+    let result = match entry.inference {
+        InferenceBackend::Cohere => {
+            cohere::embed(&client, &base_url, entry.backend_model(), texts, input_type, request_id).await
+        }
+        ref other => {
+            return Err(ApiError::invalid(format!("Embeddings are not supported on the {:?} backend", other)));
+        }
+    };
+    result.map_err(ApiError::Backend)
+}
@@ -0,0 +1,239 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::context::render_messages;
+use super::super::ensemble::{self, Candidate};
+use super::super::error::{ApiError, ErrorBody, ErrorObject};
+use super::super::request_id::RequestId;
+use super::super::AppState;
+use super::inference::{complete_request, ChatMessage, InferenceRequest, InferenceResponse, SamplingParams};
+
+const MAX_ENSEMBLE_MODELS: usize = 8;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EnsembleRequest {
+    /// The models to send the prompt to, 2 to 8 of them.
+    pub model_ids: Vec<String>,
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub messages: Option<Vec<ChatMessage>>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// A model that ranks the responses once they are all in.
+    #[serde(default)]
+    pub judge: Option<EnsembleJudge>,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+fn default_max_tokens() -> u32 {
+    512
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EnsembleJudge {
+    pub model_id: String,
+    /// What makes a response better, e.g. "factual accuracy". Defaults to
+    /// accuracy, helpfulness and clarity.
+    #[serde(default)]
+    pub criteria: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EnsembleMember {
+    pub model_id: String,
+    /// The model's response, unless it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<InferenceResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+    /// The judge's rank for the response, 1 being the best.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EnsembleJudgement {
+    pub model_id: String,
+    /// Model ids, best response first. Responses the judge did not rank are
+    /// left out.
+    pub ranking: Vec<String>,
+    /// The judge's answer, with its reasons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_generated: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EnsembleResponse {
+    /// One entry per requested model, in request order.
+    pub responses: Vec<EnsembleMember>,
+    /// Present when a judge was requested and at least two models answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub judge: Option<EnsembleJudgement>,
+    pub total_ms: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/inference/ensemble",
+    tag = "inference",
+    request_body = EnsembleRequest,
+    responses(
+        (status = 200, body = EnsembleResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn inference_ensemble(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<EnsembleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.model_ids.len() < 2 || req.model_ids.len() > MAX_ENSEMBLE_MODELS {
+        return Err(ApiError::InvalidRequest {
+            message: format!("model_ids must name between 2 and {} models", MAX_ENSEMBLE_MODELS),
+            param: Some("model_ids"),
+        });
+    }
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = req.model_ids.iter().find(|id| !seen.insert(*id)) {
+        return Err(ApiError::InvalidRequest {
+            message: format!("model_ids names '{}' more than once", duplicate),
+            param: Some("model_ids"),
+        });
+    }
+
+    let started = Instant::now();
+    let results = join_all(req.model_ids.iter().map(|model_id| {
+        complete_request(
+            state.clone(),
+            caller.clone(),
+            request_id.clone(),
+            InferenceRequest {
+                messages: req.messages.clone(),
+                temperature: req.temperature,
+                sampling: req.sampling.clone(),
+                ..InferenceRequest::prompt(model_id, req.prompt.clone(), req.max_tokens)
+            },
+        )
+    }))
+    .await;
+
+    // With nothing to compare, fail like /v1/inference would.
+    if results.iter().all(Result::is_err) {
+        return Err(results.into_iter().find_map(Result::err).expect("at least two models"));
+    }
+
+    let mut responses: Vec<EnsembleMember> = req
+        .model_ids
+        .iter()
+        .zip(results)
+        .map(|(model_id, result)| match result {
+            Ok(response) => EnsembleMember {
+                model_id: model_id.clone(),
+                response: Some(response),
+                error: None,
+                rank: None,
+            },
+            Err(e) => {
+                tracing::warn!("Ensemble model '{}' failed: {}", model_id, e);
+                EnsembleMember {
+                    model_id: model_id.clone(),
+                    response: None,
+                    error: Some(ErrorObject::for_request(&e, &request_id)),
+                    rank: None,
+                }
+            }
+        })
+        .collect();
+
+    let answered: Vec<usize> = (0..responses.len()).filter(|&i| responses[i].response.is_some()).collect();
+    let judge = match req.judge {
+        Some(judge) if answered.len() >= 2 => {
+            let question = match &req.messages {
+                Some(messages) if req.prompt.is_empty() => render_messages(messages),
+                _ => req.prompt.clone(),
+            };
+            let candidates: Vec<Candidate> = answered
+                .iter()
+                .enumerate()
+                .map(|(n, &i)| Candidate {
+                    number: n + 1,
+                    text: responses[i].response.as_ref().map(|r| r.text.as_str()).unwrap_or_default(),
+                })
+                .collect();
+            let criteria = judge.criteria.as_deref().unwrap_or(ensemble::DEFAULT_CRITERIA);
+            let prompt = ensemble::judge_prompt(&question, criteria, &candidates);
+
+            let verdict = complete_request(
+                state.clone(),
+                caller.clone(),
+                request_id.clone(),
+                InferenceRequest {
+                    temperature: Some(0.0),
+                    ..InferenceRequest::prompt(&judge.model_id, prompt, judge.max_tokens)
+                },
+            )
+            .await;
+
+            Some(match verdict {
+                Ok(verdict) => {
+                    let ranking = ensemble::parse_ranking(&verdict.text, answered.len());
+                    for (rank, number) in ranking.iter().enumerate() {
+                        responses[answered[number - 1]].rank = Some(rank + 1);
+                    }
+                    EnsembleJudgement {
+                        model_id: judge.model_id,
+                        ranking: ranking
+                            .iter()
+                            .map(|number| responses[answered[number - 1]].model_id.clone())
+                            .collect(),
+                        text: Some(verdict.text),
+                        tokens_generated: Some(verdict.tokens_generated),
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Ensemble judge '{}' failed: {}", judge.model_id, e);
+                    EnsembleJudgement {
+                        model_id: judge.model_id,
+                        ranking: Vec::new(),
+                        text: None,
+                        tokens_generated: None,
+                        error: Some(ErrorObject::for_request(&e, &request_id)),
+                    }
+                }
+            })
+        }
+        _ => None,
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(EnsembleResponse {
+            responses,
+            judge,
+            total_ms: started.elapsed().as_millis() as u64,
+        }),
+    ))
+}
@@ -6,21 +6,168 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::sync::atomic::Ordering;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+use super::super::admission::SaturationStatus;
+use super::super::backend_health::{
+    configured_backends, probe_backends, BackendHealthReport, BackendStatus, ProbeStatus,
+};
+use super::super::upstream_limits::RateLimitStatus;
+use super::super::{AppState, InferenceBackend};
+
+#[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub timestamp: DateTime<Utc>,
     pub models_loaded: usize,
+    /// Requests running and queued, with `--max-concurrent-requests`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<SaturationStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BackendHealthResponse {
+    pub status: String,
+    #[serde(flatten)]
+    pub report: BackendHealthReport,
+    /// Rate limits backends reported on recent requests.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rate_limits: Vec<RateLimitStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LivenessResponse {
+    pub status: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub startup_complete: bool,
+    pub shutting_down: bool,
+    pub backends: Vec<BackendStatus>,
 }
 
-pub async fn health_check(State(state): State<super::super::AppState>) -> impl IntoResponse {
+fn overall_status(report: Option<&BackendHealthReport>) -> String {
+    match report {
+        Some(report) if report.is_degraded() => "degraded".to_string(),
+        _ => "healthy".to_string(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    security(()),
+    responses((status = 200, body = HealthResponse))
+)]
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
     let models = state.models.lock().await;
+    let backend_health = state.backend_health.lock().await;
     let response = HealthResponse {
-        status: "healthy".to_string(),
+        status: overall_status(backend_health.latest()),
         timestamp: Utc::now(),
         models_loaded: models.len(),
+        saturation: state.admission.status(),
+    };
+
+    (StatusCode::OK, Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/backends",
+    tag = "health",
+    security(()),
+    responses((status = 200, body = BackendHealthResponse))
+)]
+pub async fn backend_health(State(state): State<AppState>) -> impl IntoResponse {
+    let rate_limits = state.upstream_limits.lock().await.report(Utc::now());
+    if let Some(report) = state.backend_health.lock().await.fresh() {
+        let response = BackendHealthResponse {
+            status: overall_status(Some(report)),
+            report: report.clone(),
+            rate_limits,
+        };
+        return (StatusCode::OK, Json(response));
+    }
+
+    let registered: Vec<InferenceBackend> = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .map(|m| m.registry_entry.inference.clone())
+        .collect();
+    let report = probe_backends(&state.backend_clients, &configured_backends(&registered)).await;
+    state.backend_health.lock().await.store(report.clone());
+
+    let response = BackendHealthResponse {
+        status: overall_status(Some(&report)),
+        report,
+        rate_limits,
     };
-    
     (StatusCode::OK, Json(response))
 }
+
+#[utoipa::path(
+    get,
+    path = "/livez",
+    tag = "health",
+    security(()),
+    responses((status = 200, body = LivenessResponse))
+)]
+pub async fn liveness() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(LivenessResponse {
+            status: "alive".to_string(),
+        }),
+    )
+}
+
+/// Ready once startup has finished, the server is not draining for shutdown,
+/// and every `--required-backend` answers its probe. Recent `/health/backends` results are reused when they cover
+/// all required backends.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    security(()),
+    responses((status = 200, body = ReadinessResponse), (status = 503, body = ReadinessResponse))
+)]
+pub async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    let startup_complete = state.startup_complete.load(Ordering::SeqCst);
+    let shutting_down = state.shutting_down.load(Ordering::SeqCst);
+    let required = state.required_backends.as_slice();
+
+    let cached: Option<Vec<BackendStatus>> = state.backend_health.lock().await.fresh().and_then(|report| {
+        required
+            .iter()
+            .map(|backend| report.backends.iter().find(|b| &b.backend == backend).cloned())
+            .collect()
+    });
+    let backends = match cached {
+        Some(backends) => backends,
+        None => probe_backends(&state.backend_clients, required).await.backends,
+    };
+
+    let ready = startup_complete && !shutting_down && backends.iter().all(|b| b.status == ProbeStatus::Up);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            startup_complete,
+            shutting_down,
+            backends,
+        }),
+    )
+}
@@ -1,64 +1,370 @@
 use axum::{
+    body::Body,
     extract::State,
-    http::{header, StatusCode},
-    response::sse::{Event, KeepAlive},
-    response::IntoResponse,
+    Extension,
+    http::{header, HeaderMap, StatusCode},
+    response::sse::Event,
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
 use async_stream::stream;
+use chrono::Utc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
 
-use super::super::{AppState, InferenceBackend};
+use super::super::{
+    AppState, InferenceBackend, ModelCapability, ModelPricing, ModelRegistryEntry, Placement, SpeculativeDecoding,
+};
+use super::super::admission;
+use super::super::accounting::estimate_cost;
+use super::super::audit::AuditRecord;
+use super::super::auth::Caller;
+use super::super::backends::{
+    anthropic, bedrock, candle, cohere, deepseek, gemini, groq, hosted_oss, koboldcpp, llama_local, lmstudio, mistral,
+    mlx, ollama, openai_compat, openrouter, replicate, sagemaker, tgi, triton, vertex, xai,
+};
+use super::super::cassettes;
+use super::super::config;
+use super::super::context::{self, TruncationStrategy};
+use super::super::error::{ApiError, ErrorBody};
+use super::super::guardrails::{ModerationAction, ModerationVerdict};
+use super::super::injection::{self, InjectionReport};
+use super::super::latency;
+use super::super::limits;
+use super::super::llama_slots::{self, LlamaSlots, SlotLease};
+use super::super::mock_backend::MockSettings;
+use super::super::pools;
+use super::super::postprocess::{self, PostProcessor};
+use super::super::request_id::{RequestId, REQUEST_ID_HEADER};
+use super::super::routing;
+use super::super::sse::{self, LAST_EVENT_ID};
+use super::super::stats::{self, DraftTokens, RequestOutcome};
+use super::super::templates::{self, VariableEscape};
+use super::super::upstream_limits::UpstreamLimits;
+use super::best_of::{complete_best_of, BestOfCandidate, CandidateScorer};
+use super::prompts::apply_prompt;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ChatMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: String,
+    /// Tools the assistant called in this turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// On `tool` messages, the call whose result this is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Images, audio or documents sent along with the text, on backends that
+    /// accept them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Vec<Attachment>>,
+}
+
+/// Inline media, base64-encoded.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Attachment {
+    pub mime_type: String,
+    pub data: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: &str) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A tool the model may call, described by a JSON schema of its arguments.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// A span of the generated text backed by source documents or tool results,
+/// on backends that report them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Citation {
+    pub start: u32,
+    pub end: u32,
+    pub text: String,
+    #[serde(default)]
+    pub document_ids: Vec<String>,
+}
+
+/// What a routing backend reports about how it served a request.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpstreamMetadata {
+    /// The model that actually answered, which may differ from the one
+    /// requested when the backend falls back.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Cost billed by the backend, as opposed to `estimated_cost_usd`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
 
-#[derive(Debug, Deserialize)]
+/// Sampling controls beyond `temperature`. Backends pass on the ones they
+/// support and ignore the rest.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct SamplingParams {
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    #[serde(default)]
+    pub min_p: Option<f64>,
+    #[serde(default)]
+    pub repetition_penalty: Option<f64>,
+    #[serde(default)]
+    pub presence_penalty: Option<f64>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f64>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Mirostat mode: `0` off, `1` or `2` to target `mirostat_tau`
+    /// perplexity, adjusting at rate `mirostat_eta`.
+    #[serde(default)]
+    pub mirostat: Option<u8>,
+    #[serde(default)]
+    pub mirostat_tau: Option<f64>,
+    #[serde(default)]
+    pub mirostat_eta: Option<f64>,
+    /// KoboldCpp's sampler ids, in the order the samplers run.
+    #[serde(default)]
+    pub sampler_order: Option<Vec<u32>>,
+    /// Exclude Top Choices: with this probability, drops the likeliest
+    /// tokens above `xtc_threshold`.
+    #[serde(default)]
+    pub xtc_probability: Option<f64>,
+    #[serde(default)]
+    pub xtc_threshold: Option<f64>,
+    /// How many recent tokens `repetition_penalty` looks back over.
+    #[serde(default)]
+    pub repetition_context_size: Option<u32>,
+    /// A GBNF grammar the output must follow, on llama.cpp and KoboldCpp.
+    #[serde(default)]
+    pub grammar: Option<String>,
+    /// A JSON schema the output must follow, which llama.cpp turns into a
+    /// grammar itself.
+    #[serde(default)]
+    pub json_schema: Option<serde_json::Value>,
+    /// Added to the logits of tokens, keyed by token id or, on backends that
+    /// can tokenize it, by text.
+    #[serde(default)]
+    pub logit_bias: Option<HashMap<String, f64>>,
+}
+
+/// A `logit_bias` key: keys made of digits are token ids, anything else is
+/// text for the backend's tokenizer.
+pub enum BiasedToken<'a> {
+    Id(u32),
+    Text(&'a str),
+}
+
+impl SamplingParams {
+    pub fn logit_bias(&self) -> impl Iterator<Item = (BiasedToken<'_>, f64)> {
+        self.logit_bias.iter().flatten().map(|(key, bias)| {
+            let token = key.parse().map_or(BiasedToken::Text(key), BiasedToken::Id);
+            (token, *bias)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct InferenceRequest {
+    /// Required unless `prompt_id` names a prompt with a model.
+    #[serde(default)]
     pub model_id: String,
+    #[serde(default)]
     pub prompt: String,
-    #[serde(default = "default_max_tokens")]
-    pub max_tokens: u32,
+    /// Chat history, rendered into `prompt` when given instead of a prompt.
+    #[serde(default)]
+    pub messages: Option<Vec<ChatMessage>>,
+    /// Values for `{{ name }}` placeholders in `prompt` and message
+    /// contents. Without it, prompts are sent as they are.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub variables: Option<serde_json::Map<String, serde_json::Value>>,
+    /// How `variables` are escaped when rendered; `none` by default.
+    #[serde(default)]
+    pub variable_escape: Option<VariableEscape>,
+    /// A stored prompt to send instead of `prompt` or `messages`, rendered
+    /// with `variables`.
+    #[serde(default)]
+    pub prompt_id: Option<String>,
+    /// The version of `prompt_id` to use; the latest by default.
+    #[serde(default)]
+    pub prompt_version: Option<u32>,
+    /// Overrides `--truncation` for this request.
+    #[serde(default)]
+    pub truncation: Option<TruncationStrategy>,
+    /// 512 unless set here or by the prompt.
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
     #[serde(default)]
     pub temperature: Option<f32>,
+    /// Replaces the model's post-processing pipeline for this request.
+    #[serde(default)]
+    pub post_process: Option<Vec<PostProcessor>>,
+    /// Tools offered to the model, on backends that support tool use.
+    #[serde(default)]
+    pub tools: Option<Vec<ToolDefinition>>,
+    /// Keeps a conversation's turns on the same llama.cpp slot, so the
+    /// server reuses the KV cache of the earlier turns.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Generates this many candidates, up to 8, and returns the one `scorer`
+    /// rates highest. Not supported when streaming.
+    #[serde(default)]
+    pub best_of: Option<u32>,
+    /// How `best_of` candidates are compared; by default the model judges
+    /// its own candidates.
+    #[serde(default)]
+    pub scorer: Option<CandidateScorer>,
+    /// With `best_of`, also returns every candidate and its score.
+    #[serde(default)]
+    pub return_candidates: bool,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
 }
 
-fn default_max_tokens() -> u32 {
-    512
+const DEFAULT_MAX_TOKENS: u32 = 512;
+
+impl InferenceRequest {
+    /// A bare prompt the engine sends on its own behalf, e.g. to a judge or
+    /// for a RAG answer.
+    pub fn prompt(model_id: &str, prompt: String, max_tokens: u32) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            prompt,
+            messages: None,
+            variables: None,
+            variable_escape: None,
+            prompt_id: None,
+            prompt_version: None,
+            truncation: None,
+            max_tokens: Some(max_tokens),
+            temperature: None,
+            post_process: None,
+            tools: None,
+            session_id: None,
+            best_of: None,
+            scorer: None,
+            return_candidates: false,
+            sampling: SamplingParams::default(),
+        }
+    }
+
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct InferenceResponse {
     pub model_id: String,
     pub text: String,
     pub tokens_generated: u32,
-    pub finish_reason: String,
+    pub finish_reason: FinishReason,
+    pub timings: InferenceTimings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+    /// Categories flagged by moderation on content that was let through.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moderation: Vec<ModerationVerdict>,
+    /// Present when the prompt was flagged as a possible injection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub injection: Option<InjectionReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    /// The model's reasoning, kept apart from `text`, for backends such as
+    /// DeepSeek and xAI that return it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamMetadata>,
+    /// Every `best_of` candidate, when `return_candidates` was set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<BestOfCandidate>,
 }
 
-#[derive(Serialize)]
-pub struct StreamToken {
-    pub token: String,
-    pub token_id: u32,
-    pub complete: bool,
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InferenceTimings {
+    pub time_to_first_token_ms: u64,
+    pub total_ms: u64,
+    pub tokens_per_second: f64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct OllamaGenerateRequest {
-    model: String,
-    prompt: String,
-    stream: bool,
-    options: OllamaOptions,
+/// What `RequestRecord::finish` reports back to the handler.
+struct RequestSummary {
+    timings: InferenceTimings,
+    estimated_cost_usd: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct OllamaOptions {
-    num_predict: u32,
-    temperature: f32,
+#[derive(Serialize, ToSchema)]
+pub struct StreamUsage {
+    pub request_id: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    #[serde(flatten)]
+    pub timings: InferenceTimings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+    pub finish_reason: FinishReason,
 }
 
-#[derive(Serialize, Deserialize)]
-struct OllamaGenerateResponse {
-    response: String,
-    done: bool,
+/// Why a generation ended, in OpenAI's terms whatever the backend calls it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The model finished or hit a stop sequence.
+    #[default]
+    Stop,
+    /// `max_tokens` or the context window ran out.
+    Length,
+    /// The model stopped to call tools.
+    ToolCalls,
+    /// A safety filter, the backend's or the engine's, cut the output off.
+    ContentFilter,
+    /// The backend failed partway through.
+    Error,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StreamToken {
+    pub token: String,
+    pub token_id: u32,
+    pub complete: bool,
+    pub request_id: String,
+    /// Set on the `complete` token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<FinishReason>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -68,12 +374,8 @@ struct OpenAIChatCompletionRequest {
     max_tokens: u32,
     temperature: f32,
     stream: bool,
-}
-
-#[derive(Serialize, Deserialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logit_bias: Option<HashMap<String, f64>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -104,6 +406,8 @@ struct OpenAIUsage {
 struct HuggingFaceRequest {
     inputs: String,
     parameters: HuggingFaceParameters,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -113,134 +417,579 @@ struct HuggingFaceParameters {
     return_full_text: bool,
 }
 
-const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434";
 const LLAMA_CPP_DEFAULT_URL: &str = "http://localhost:8080";
 const HUGGINGFACE_DEFAULT_URL: &str = "https://api-inference.huggingface.co";
 const OPENAI_DEFAULT_URL: &str = "https://api.openai.com/v1";
+const AZURE_OPENAI_DEFAULT_API_VERSION: &str = "2024-10-21";
+const MOCK_URL: &str = "mock://";
+
+/// Everything a backend adapter needs to issue a single generation call.
+#[derive(Debug, Clone)]
+pub(crate) struct BackendRequest {
+    pub client: reqwest::Client,
+    pub base_url: String,
+    pub model: String,
+    /// The rendered prompt, for completion-style backends.
+    pub prompt: String,
+    /// The conversation, for chat-native backends. A plain prompt arrives as
+    /// a single user message.
+    pub messages: Vec<ChatMessage>,
+    /// Whether the model has the `chat` capability, for backends that serve
+    /// chat and raw completion from different endpoints.
+    pub chat: bool,
+    pub tools: Vec<ToolDefinition>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub sampling: SamplingParams,
+    /// The model's draft model settings, for backends that decode
+    /// speculatively.
+    pub speculative: Option<SpeculativeDecoding>,
+    /// Where the model was loaded, for backends that need it on every
+    /// request.
+    pub placement: Option<Placement>,
+    pub request_id: String,
+    /// Rate limits reported by backends, for adapters that honour them.
+    pub upstream_limits: Arc<Mutex<UpstreamLimits>>,
+    /// The caller's session, scoped to the caller, for llama.cpp slots.
+    pub session: Option<String>,
+    pub llama_slots: Arc<Mutex<LlamaSlots>>,
+    pub outcome: StreamOutcome,
+}
+
+/// Where a streaming adapter notes why its stream ended, and what it learnt
+/// along the way, since the events it yields cannot be read back, for the
+/// closing `usage` event and the stats.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StreamOutcome(Arc<std::sync::Mutex<OutcomeState>>);
+
+#[derive(Debug, Default)]
+struct OutcomeState {
+    finish_reason: Option<FinishReason>,
+    draft: Option<DraftTokens>,
+}
+
+impl StreamOutcome {
+    /// Keeps the finish reason of a `complete` token.
+    pub fn record(&self, token: &StreamToken) {
+        if let Some(reason) = token.finish_reason {
+            self.0.lock().unwrap().finish_reason = Some(reason);
+        }
+    }
+
+    /// Keeps the draft tokens a speculatively decoded stream reports.
+    pub fn record_draft(&self, draft: DraftTokens) {
+        self.0.lock().unwrap().draft = Some(draft);
+    }
+
+    fn finish_reason(&self) -> Option<FinishReason> {
+        self.0.lock().unwrap().finish_reason
+    }
+
+    fn draft(&self) -> Option<DraftTokens> {
+        self.0.lock().unwrap().draft
+    }
+}
+
+/// What a backend adapter returns for a non-streaming call.
+pub(crate) struct Generation {
+    pub text: String,
+    pub tokens: u32,
+    pub tool_calls: Vec<ToolCall>,
+    pub citations: Vec<Citation>,
+    /// The model's reasoning, for backends that return it apart from the
+    /// answer.
+    pub reasoning: Option<String>,
+    pub finish_reason: FinishReason,
+    pub upstream: Option<UpstreamMetadata>,
+    /// Set by backends that decoded the output speculatively.
+    pub draft: Option<DraftTokens>,
+}
+
+impl Generation {
+    pub fn new(text: String, tokens: u32) -> Self {
+        Self {
+            text,
+            tokens,
+            tool_calls: Vec::new(),
+            citations: Vec::new(),
+            reasoning: None,
+            finish_reason: FinishReason::Stop,
+            upstream: None,
+            draft: None,
+        }
+    }
+}
 
-fn get_backend_url(backend: &InferenceBackend) -> String {
+pub fn get_backend_url(backend: &InferenceBackend) -> String {
+    if let Some(url) = cassettes::proxy_url(backend) {
+        return url;
+    }
     match backend {
-        InferenceBackend::Ollama => std::env::var("OLLAMA_URL").unwrap_or_else(|_| OLLAMA_DEFAULT_URL.to_string()),
-        InferenceBackend::Llama => std::env::var("LLAMA_CPP_URL").unwrap_or_else(|_| LLAMA_CPP_DEFAULT_URL.to_string()),
-        InferenceBackend::HuggingFace => std::env::var("HUGGINGFACE_URL").unwrap_or_else(|_| HUGGINGFACE_DEFAULT_URL.to_string()),
-        InferenceBackend::OpenAI => std::env::var("OPENAI_URL").unwrap_or_else(|_| OPENAI_DEFAULT_URL.to_string()),
+        InferenceBackend::Ollama => config::var("OLLAMA_URL").unwrap_or_else(|_| ollama::DEFAULT_URL.to_string()),
+        InferenceBackend::Llama => config::var("LLAMA_CPP_URL").unwrap_or_else(|_| LLAMA_CPP_DEFAULT_URL.to_string()),
+        InferenceBackend::HuggingFace => config::var("HUGGINGFACE_URL").unwrap_or_else(|_| HUGGINGFACE_DEFAULT_URL.to_string()),
+        InferenceBackend::OpenAI => config::var("OPENAI_URL").unwrap_or_else(|_| OPENAI_DEFAULT_URL.to_string()),
+        // Azure endpoints are per resource, so there is no default.
+        InferenceBackend::AzureOpenAI => config::var("AZURE_OPENAI_URL").unwrap_or_default(),
+        InferenceBackend::Anthropic => config::var("ANTHROPIC_URL").unwrap_or_else(|_| anthropic::DEFAULT_URL.to_string()),
+        InferenceBackend::Gemini => config::var("GEMINI_URL").unwrap_or_else(|_| gemini::DEFAULT_URL.to_string()),
+        InferenceBackend::Bedrock => config::var("BEDROCK_URL").unwrap_or_else(|_| bedrock::default_url()),
+        InferenceBackend::Mistral => config::var("MISTRAL_URL").unwrap_or_else(|_| mistral::DEFAULT_URL.to_string()),
+        InferenceBackend::Cohere => config::var("COHERE_URL").unwrap_or_else(|_| cohere::DEFAULT_URL.to_string()),
+        InferenceBackend::Groq => config::var("GROQ_URL").unwrap_or_else(|_| groq::DEFAULT_URL.to_string()),
+        InferenceBackend::OpenRouter => config::var("OPENROUTER_URL").unwrap_or_else(|_| openrouter::DEFAULT_URL.to_string()),
+        InferenceBackend::Together => config::var("TOGETHER_URL").unwrap_or_else(|_| hosted_oss::TOGETHER_DEFAULT_URL.to_string()),
+        InferenceBackend::Fireworks => config::var("FIREWORKS_URL").unwrap_or_else(|_| hosted_oss::FIREWORKS_DEFAULT_URL.to_string()),
+        InferenceBackend::Replicate => config::var("REPLICATE_URL").unwrap_or_else(|_| replicate::DEFAULT_URL.to_string()),
+        InferenceBackend::Tgi => config::var("TGI_URL").unwrap_or_else(|_| tgi::DEFAULT_URL.to_string()),
+        InferenceBackend::LmStudio => config::var("LM_STUDIO_URL").unwrap_or_else(|_| lmstudio::DEFAULT_URL.to_string()),
+        InferenceBackend::KoboldCpp => config::var("KOBOLDCPP_URL").unwrap_or_else(|_| koboldcpp::DEFAULT_URL.to_string()),
+        InferenceBackend::Mlx => config::var("MLX_URL").unwrap_or_else(|_| mlx::DEFAULT_URL.to_string()),
+        InferenceBackend::LlamaLocal => llama_local::URL.to_string(),
+        InferenceBackend::Candle => candle::URL.to_string(),
+        InferenceBackend::Triton => config::var("TRITON_URL").unwrap_or_else(|_| triton::DEFAULT_URL.to_string()),
+        InferenceBackend::SageMaker => config::var("SAGEMAKER_URL").unwrap_or_else(|_| sagemaker::default_url()),
+        InferenceBackend::Vertex => config::var("VERTEX_URL").unwrap_or_else(|_| vertex::default_url()),
+        InferenceBackend::DeepSeek => config::var("DEEPSEEK_URL").unwrap_or_else(|_| deepseek::DEFAULT_URL.to_string()),
+        InferenceBackend::Xai => config::var("XAI_URL").unwrap_or_else(|_| xai::DEFAULT_URL.to_string()),
+        InferenceBackend::Mock => MOCK_URL.to_string(),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/inference",
+    tag = "inference",
+    request_body = InferenceRequest,
+    responses(
+        (status = 200, body = InferenceResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
 pub async fn inference_complete(
     State(state): State<AppState>,
-    Json(req): Json<InferenceRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(mut req): Json<InferenceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    apply_prompt(&state, &caller, &mut req).await?;
+    apply_variables(&mut req)?;
+    routing::route(&state, &caller, "/v1/inference", &mut req).await?;
+    let _lease = pools::resolve(&state, &caller, &mut req).await?;
+    let response = match req.best_of {
+        Some(_) => complete_best_of(state, caller, request_id, req).await?,
+        None => complete_request(state, caller, request_id, req).await?,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Renders `variables` into the prompt and messages, before any check sees
+/// them. Handlers call this once; the rendered text is not rendered again.
+fn apply_variables(req: &mut InferenceRequest) -> Result<(), ApiError> {
+    let Some(variables) = req.variables.take() else {
+        return Ok(());
+    };
+    let escape = req.variable_escape.unwrap_or_default();
+    let render = |template: &str| {
+        templates::render_variables(template, &variables, escape).map_err(|e| ApiError::Unprocessable {
+            message: format!("Failed to render variables: {}", e),
+            param: Some("variables"),
+        })
+    };
+    req.prompt = render(&req.prompt)?;
+    for message in req.messages.iter_mut().flatten() {
+        message.content = render(&message.content)?;
+    }
+    Ok(())
+}
+
+/// Runs a non-streaming request through the same checks as
+/// `/v1/inference`: injection screening, context preparation, limits and
+/// moderation of the prompt and the output.
+pub(crate) async fn complete_request(
+    state: AppState,
+    caller: Caller,
+    request_id: RequestId,
+    req: InferenceRequest,
+) -> Result<InferenceResponse, ApiError> {
+    if let Some(processors) = &req.post_process {
+        postprocess::validate(processors).map_err(|message| ApiError::Unprocessable {
+            message,
+            param: Some("post_process"),
+        })?;
+    }
+    let injection = injection::check(&state, &caller, &req).await?;
+    let req = context::prepare(&state, &caller, &request_id.0, req).await?;
+    limits::check_inference(&state, &caller, &req).await?;
+    let input_moderation = moderate_input(&state, &req).await?;
+
+    let guardrails = state.guardrails.clone();
+    let mut response = complete(state, caller, request_id, req).await?;
+    response.moderation.extend(input_moderation);
+    response.injection = injection;
+
+    if let Some(guardrails) = guardrails.filter(|g| g.moderates_output()) {
+        let verdict = guardrails.check_output(&mut response.text).await?;
+        if verdict.as_ref().is_some_and(|v| v.action == ModerationAction::Redact) {
+            response.finish_reason = FinishReason::ContentFilter;
+        }
+        response.moderation.extend(verdict);
+    }
+
+    Ok(response)
+}
+
+/// Runs the prompt through the input guardrails, if enabled.
+async fn moderate_input(state: &AppState, req: &InferenceRequest) -> Result<Option<ModerationVerdict>, ApiError> {
+    match &state.guardrails {
+        Some(guardrails) if guardrails.moderates_input() => Ok(guardrails.check_input(&req.prompt).await?),
+        _ => Ok(None),
+    }
+}
+
+async fn complete(
+    state: AppState,
+    caller: Caller,
+    request_id: RequestId,
+    req: InferenceRequest,
+) -> Result<InferenceResponse, ApiError> {
     let models = state.models.lock().await;
 
     let model_entry = models
         .iter()
-        .find(|m| m.registry_entry.id == req.model_id)
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                format!("Model '{}' not found or not loaded. Please register and load it first.", req.model_id),
-            )
-        })?;
+        .find(|m| m.registry_entry.id == req.model_id && m.registry_entry.visible_to(&caller))
+        .ok_or_else(|| ApiError::ModelNotFound(req.model_id.clone()))?;
 
     if !model_entry.registry_entry.loaded {
-        return Err((
-            StatusCode::PRECONDITION_FAILED,
-            format!("Model '{}' is not loaded. Load it first.", req.model_id),
-        ));
+        return Err(ApiError::ModelNotLoaded(req.model_id.clone()));
     }
 
     let backend_url = get_backend_url(&model_entry.registry_entry.inference);
-    let model_id = model_entry.registry_entry.id.clone();
+    let model_id = model_entry.registry_entry.backend_model().to_string();
     let inference_backend = model_entry.registry_entry.inference.clone();
+    let chat = model_entry.registry_entry.capabilities.contains(&ModelCapability::Chat);
+    check_grammar(&inference_backend, &req.sampling)?;
+    check_logit_bias(&inference_backend, &req.sampling)?;
     let temperature = req.temperature.unwrap_or(0.7);
+    let pricing = model_entry.registry_entry.pricing.clone();
+    let model_tenant = model_entry.registry_entry.tenant.clone();
+    let speculative = model_entry.registry_entry.speculative.clone();
+    let placement = model_entry.registry_entry.placement.clone();
+    let max_concurrency = model_entry.registry_entry.max_concurrency;
+    let post_process = req
+        .post_process
+        .clone()
+        .unwrap_or_else(|| model_entry.registry_entry.post_process.clone());
 
     drop(models);
 
-    let result = match inference_backend {
-        InferenceBackend::Ollama => ollama_generate(&backend_url, &model_id, &req.prompt, req.max_tokens, temperature).await,
-        InferenceBackend::Llama => llama_cpp_completion(&backend_url, &model_id, &req.prompt, req.max_tokens, temperature).await,
-        InferenceBackend::HuggingFace => huggingface_inference(&backend_url, &model_id, &req.prompt, req.max_tokens, temperature).await,
-        InferenceBackend::OpenAI => openai_chat_completion(&backend_url, &model_id, &req.prompt, req.max_tokens, temperature).await,
+    let prompt = outbound_prompt(&state, &inference_backend, &req.prompt)
+        .await
+        .map_err(ApiError::Unavailable)?;
+    let messages = outbound_messages(&state, &inference_backend, &req, &prompt)
+        .await
+        .map_err(ApiError::Unavailable)?;
+
+    let record = RequestRecord::new(&req, &caller, &request_id, pricing, model_tenant, false);
+    let call = BackendRequest {
+        client: state.backend_clients.get(&inference_backend),
+        base_url: backend_url,
+        model: model_id,
+        prompt,
+        messages,
+        chat,
+        tools: req.tools.clone().unwrap_or_default(),
+        max_tokens: req.max_tokens(),
+        temperature,
+        sampling: req.sampling.clone(),
+        speculative,
+        placement,
+        request_id: request_id.0,
+        upstream_limits: state.upstream_limits.clone(),
+        session: session_key(&caller, &req),
+        llama_slots: state.llama_slots.clone(),
+        outcome: StreamOutcome::default(),
     };
 
-    let (text, tokens) = result.map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    let turn = admission::model_turn(&state, &req.model_id, max_concurrency).await;
+    let result = generate(&inference_backend, call).await;
+    drop(turn);
+
+    let summary = match &result {
+        Ok(generation) => record.finish(&state, generation.tokens, None, generation.draft, None).await,
+        Err(e) => record.finish(&state, 0, None, None, Some(e.clone())).await,
+    };
+
+    let generation = result.map_err(ApiError::Backend)?;
+    let text = postprocess::apply(&post_process, generation.text);
 
     let response = InferenceResponse {
         model_id: req.model_id,
         text,
-        tokens_generated: tokens,
-        finish_reason: "stop".to_string(),
+        tokens_generated: generation.tokens,
+        finish_reason: generation.finish_reason,
+        timings: summary.timings,
+        estimated_cost_usd: summary.estimated_cost_usd,
+        moderation: Vec::new(),
+        injection: None,
+        tool_calls: generation.tool_calls,
+        citations: generation.citations,
+        reasoning: generation.reasoning,
+        upstream: generation.upstream,
+        candidates: Vec::new(),
     };
 
-    Ok((StatusCode::OK, Json(response)))
+    Ok(response)
 }
 
-async fn ollama_generate(
-    base_url: &str,
-    model: &str,
+/// The request's session, prefixed with the caller so callers cannot take
+/// over each other's slots.
+fn session_key(caller: &Caller, req: &InferenceRequest) -> Option<String> {
+    req.session_id.as_ref().map(|session| format!("{}/{}", caller.id, session))
+}
+
+async fn generate(backend: &InferenceBackend, call: BackendRequest) -> Result<Generation, String> {
+    match backend {
+        InferenceBackend::Ollama => ollama::generate(call).await,
+        InferenceBackend::Llama => llama_cpp_completion(call).await,
+        InferenceBackend::HuggingFace => huggingface_inference(call).await,
+        InferenceBackend::OpenAI | InferenceBackend::AzureOpenAI => openai_chat_completion(backend, call).await,
+        InferenceBackend::Anthropic => anthropic::generate(call).await,
+        InferenceBackend::Gemini => gemini::generate(call).await,
+        InferenceBackend::Bedrock => bedrock::generate(call).await,
+        InferenceBackend::Mistral => mistral::generate(call).await,
+        InferenceBackend::Cohere => cohere::generate(call).await,
+        InferenceBackend::Groq => groq::generate(call).await,
+        InferenceBackend::OpenRouter => openrouter::generate(call).await,
+        InferenceBackend::Together | InferenceBackend::Fireworks => hosted_oss::generate(backend, call).await,
+        InferenceBackend::Replicate => replicate::generate(call).await,
+        InferenceBackend::Tgi => tgi::generate(call).await,
+        InferenceBackend::LmStudio => lmstudio::generate(call).await,
+        InferenceBackend::KoboldCpp => koboldcpp::generate(call).await,
+        InferenceBackend::Mlx => mlx::generate(call).await,
+        InferenceBackend::LlamaLocal => llama_local::generate(call).await,
+        InferenceBackend::Candle => candle::generate(call).await,
+        InferenceBackend::Triton => triton::generate(call).await,
+        InferenceBackend::SageMaker => sagemaker::generate(call).await,
+        InferenceBackend::Vertex => vertex::generate(call).await,
+        InferenceBackend::DeepSeek => deepseek::generate(call).await,
+        InferenceBackend::Xai => xai::generate(call).await,
+        InferenceBackend::Mock => mock_generate(call).await,
+    }
+}
+
+/// Masks personal data in prompts bound for backends covered by
+/// `--redact-pii`. Other backends get the prompt unchanged.
+async fn outbound_prompt(state: &AppState, backend: &InferenceBackend, prompt: &str) -> Result<String, String> {
+    match &state.pii {
+        Some(pii) if pii.applies_to(backend) => pii.redact(prompt).await,
+        _ => Ok(prompt.to_string()),
+    }
+}
+
+/// The conversation for chat-native backends: the request's messages, or the
+/// prompt as a lone user message. Message contents are masked like prompts.
+async fn outbound_messages(
+    state: &AppState,
+    backend: &InferenceBackend,
+    req: &InferenceRequest,
     prompt: &str,
+) -> Result<Vec<ChatMessage>, String> {
+    let Some(messages) = &req.messages else {
+        return Ok(vec![ChatMessage::user(prompt)]);
+    };
+    let mut outbound = Vec::with_capacity(messages.len());
+    for message in messages {
+        outbound.push(ChatMessage {
+            content: outbound_prompt(state, backend, &message.content).await?,
+            ..message.clone()
+        });
+    }
+    Ok(outbound)
+}
+
+/// Runs a generation on behalf of the engine itself, e.g. to summarize
+/// truncated context. It is not recorded in stats, quotas or the audit log.
+pub async fn generate_internal(
+    state: &AppState,
+    model: &ModelRegistryEntry,
+    prompt: String,
     max_tokens: u32,
-    temperature: f32,
-) -> Result<(String, u32), String> {
-    let client = reqwest::Client::new();
+    request_id: &str,
+) -> Result<String, String> {
+    let call = internal_call(state, model, prompt, max_tokens, request_id).await?;
+    generate(&model.inference, call).await.map(|generation| generation.text)
+}
 
-    let request_body = OllamaGenerateRequest {
-        model: model.to_string(),
-        prompt: prompt.to_string(),
-        stream: false,
-        options: OllamaOptions {
-            num_predict: max_tokens,
-            temperature,
-        },
-    };
+/// A call the engine makes on its own behalf, with default sampling.
+pub(crate) async fn internal_call(
+    state: &AppState,
+    model: &ModelRegistryEntry,
+    prompt: String,
+    max_tokens: u32,
+    request_id: &str,
+) -> Result<BackendRequest, String> {
+    let prompt = outbound_prompt(state, &model.inference, &prompt).await?;
+    Ok(BackendRequest {
+        client: state.backend_clients.get(&model.inference),
+        base_url: get_backend_url(&model.inference),
+        model: model.backend_model().to_string(),
+        messages: vec![ChatMessage::user(&prompt)],
+        prompt,
+        chat: model.capabilities.contains(&ModelCapability::Chat),
+        tools: Vec::new(),
+        max_tokens,
+        temperature: 0.2,
+        sampling: SamplingParams::default(),
+        speculative: model.speculative.clone(),
+        placement: model.placement.clone(),
+        request_id: request_id.to_string(),
+        upstream_limits: state.upstream_limits.clone(),
+        session: None,
+        llama_slots: state.llama_slots.clone(),
+        outcome: StreamOutcome::default(),
+    })
+}
 
-    let response = client
-        .post(&format!("{}/api/generate", base_url))
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Ollama request failed: {}", e))?;
+/// Rejects grammars on backends that would ignore them, since callers rely on
+/// them for output they can parse.
+fn check_grammar(backend: &InferenceBackend, sampling: &SamplingParams) -> Result<(), ApiError> {
+    let unsupported = |param: &'static str| ApiError::Unprocessable {
+        message: format!("{} is not supported on the {:?} backend", param, backend),
+        param: Some(param),
+    };
 
-    if !response.status().is_success() {
-        return Err(format!("Ollama API error: {}", response.status()));
+    if sampling.grammar.is_some() && sampling.json_schema.is_some() {
+        return Err(ApiError::Unprocessable {
+            message: "Set either grammar or json_schema, not both".to_string(),
+            param: Some("json_schema"),
+        });
+    }
+    match backend {
+        InferenceBackend::Llama => Ok(()),
+        InferenceBackend::KoboldCpp if sampling.json_schema.is_none() => Ok(()),
+        _ if sampling.grammar.is_some() => Err(unsupported("grammar")),
+        _ if sampling.json_schema.is_some() => Err(unsupported("json_schema")),
+        _ => Ok(()),
     }
+}
 
-    let ollama_resp: OllamaGenerateResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+/// Rejects a `logit_bias` the backend would ignore or could not resolve.
+/// Only llama.cpp and candle have a tokenizer at hand for text keys.
+fn check_logit_bias(backend: &InferenceBackend, sampling: &SamplingParams) -> Result<(), ApiError> {
+    if sampling.logit_bias.is_none() {
+        return Ok(());
+    }
+    let has_text = sampling.logit_bias().any(|(token, _)| matches!(token, BiasedToken::Text(_)));
 
-    let tokens = ollama_resp.response.split_whitespace().count() as u32;
-    Ok((ollama_resp.response, tokens))
+    match backend {
+        InferenceBackend::Llama | InferenceBackend::Candle => Ok(()),
+        _ if has_text => Err(ApiError::Unprocessable {
+            message: format!("logit_bias keys must be token ids on the {:?} backend", backend),
+            param: Some("logit_bias"),
+        }),
+        InferenceBackend::OpenAI
+        | InferenceBackend::AzureOpenAI
+        | InferenceBackend::OpenRouter
+        | InferenceBackend::Together
+        | InferenceBackend::Fireworks
+        | InferenceBackend::LmStudio
+        | InferenceBackend::Mlx => Ok(()),
+        _ => Err(ApiError::Unprocessable {
+            message: format!("logit_bias is not supported on the {:?} backend", backend),
+            param: Some("logit_bias"),
+        }),
+    }
 }
 
-async fn llama_cpp_completion(
-    base_url: &str,
-    _model: &str,
-    prompt: &str,
-    max_tokens: u32,
-    temperature: f32,
-) -> Result<(String, u32), String> {
-    let client = reqwest::Client::new();
+fn llama_cpp_request_body(call: &BackendRequest, slot: Option<&SlotLease>, stream: bool) -> serde_json::Value {
+    let sampling = &call.sampling;
+    let mut body = serde_json::Map::new();
+    body.insert("prompt".to_string(), call.prompt.clone().into());
+    body.insert("n_predict".to_string(), call.max_tokens.into());
+    body.insert("temperature".to_string(), call.temperature.into());
+    body.insert("stream".to_string(), stream.into());
+    openai_compat::insert(&mut body, "grammar", &sampling.grammar);
+    openai_compat::insert(&mut body, "json_schema", &sampling.json_schema);
+    if sampling.logit_bias.is_some() {
+        // llama.cpp takes `[token, bias]` pairs, and tokenizes text tokens.
+        let logit_bias: Vec<serde_json::Value> = sampling
+            .logit_bias()
+            .map(|(token, bias)| match token {
+                BiasedToken::Id(id) => serde_json::json!([id, bias]),
+                BiasedToken::Text(text) => serde_json::json!([text, bias]),
+            })
+            .collect();
+        body.insert("logit_bias".to_string(), logit_bias.into());
+    }
+    // The draft model itself is loaded by llama-server, with `-md`.
+    if let Some(speculative) = &call.speculative {
+        openai_compat::insert(&mut body, "speculative.n_max", &speculative.draft_max);
+        openai_compat::insert(&mut body, "speculative.n_min", &speculative.draft_min);
+        openai_compat::insert(&mut body, "speculative.p_min", &speculative.draft_p_min);
+    }
+    if let Some(slot) = slot {
+        body.insert("id_slot".to_string(), slot.id.into());
+        body.insert("cache_prompt".to_string(), true.into());
+    }
+    body.into()
+}
 
-    let request_body = serde_json::json!({
-        "prompt": prompt,
-        "n_predict": max_tokens,
-        "temperature": temperature,
-        "stream": false
-    });
+/// Picks the llama-server slot for a request, fetching the server's slots
+/// first if they are not known yet. `None` leaves the server to pick.
+async fn llama_cpp_slot(call: &BackendRequest) -> Option<SlotLease> {
+    if !call.llama_slots.lock().await.knows(&call.base_url) {
+        let reported = llama_slots::fetch(&call.client, &call.base_url, &call.request_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::debug!("Leaving slot choice to llama.cpp at {}: {}", call.base_url, e);
+                Vec::new()
+            });
+        call.llama_slots.lock().await.sync(&call.base_url, &reported);
+    }
+    call.llama_slots
+        .lock()
+        .await
+        .assign(&call.base_url, call.session.as_deref(), Utc::now())
+}
 
-    let response = client
-        .post(&format!("{}/v1/completions", base_url))
-        .json(&request_body)
+/// Sends a completion request. After a failure the server's slots are
+/// fetched again, since it may have restarted with others.
+async fn llama_cpp_send(
+    call: &BackendRequest,
+    body: &serde_json::Value,
+    action: &str,
+) -> Result<reqwest::Response, String> {
+    let result = call
+        .client
+        .post(format!("{}/v1/completions", call.base_url))
+        .header(REQUEST_ID_HEADER, &call.request_id)
+        .json(body)
         .send()
-        .await
-        .map_err(|e| format!("llama.cpp request failed: {}", e))?;
+        .await;
 
-    if !response.status().is_success() {
-        return Err(format!("llama.cpp API error: {}", response.status()));
-    }
+    let error = match result {
+        Ok(response) if response.status().is_success() => return Ok(response),
+        Ok(response) => format!("llama.cpp API error: {}", response.status()),
+        Err(e) => format!("llama.cpp {} failed: {}", action, e),
+    };
+    call.llama_slots.lock().await.forget(&call.base_url);
+    Err(error)
+}
+
+/// Reads the draft tokens llama-server reports in `timings` when it runs a
+/// draft model.
+fn llama_cpp_draft(timings: &serde_json::Value) -> Option<DraftTokens> {
+    Some(DraftTokens {
+        drafted: timings["draft_n"].as_u64()? as u32,
+        accepted: timings["draft_n_accepted"].as_u64()? as u32,
+    })
+}
+
+async fn llama_cpp_completion(call: BackendRequest) -> Result<Generation, String> {
+    let slot = llama_cpp_slot(&call).await;
+    let request_body = llama_cpp_request_body(&call, slot.as_ref(), false);
+    let response = llama_cpp_send(&call, &request_body, "request").await?;
 
     let resp_json: serde_json::Value = response
         .json()
@@ -253,19 +1002,50 @@ async fn llama_cpp_completion(
         .to_string();
 
     let tokens = text.split_whitespace().count() as u32;
-    Ok((text, tokens))
+    Ok(Generation {
+        finish_reason: resp_json["choices"][0]["finish_reason"]
+            .as_str()
+            .map(openai_compat::finish_reason)
+            .unwrap_or_default(),
+        draft: llama_cpp_draft(&resp_json["timings"]),
+        ..Generation::new(text, tokens)
+    })
 }
 
-async fn huggingface_inference(
-    base_url: &str,
-    model: &str,
-    prompt: &str,
-    max_tokens: u32,
-    temperature: f32,
-) -> Result<(String, u32), String> {
-    let client = reqwest::Client::new();
+async fn mock_generate(call: BackendRequest) -> Result<Generation, String> {
+    let settings = MockSettings::from_env();
+    if settings.should_fail() {
+        return Err("Mock backend injected failure".to_string());
+    }
+
+    let tokens = settings.tokens(&call.prompt, call.max_tokens);
+    if let Some(limit) = settings.fail_after_tokens
+        && tokens.len() as u32 > limit
+    {
+        tokio::time::sleep(settings.token_delay * limit).await;
+        return Err(format!("Mock backend injected failure after {} tokens", limit));
+    }
+
+    tokio::time::sleep(settings.token_delay * tokens.len() as u32).await;
+    let count = tokens.len() as u32;
+    Ok(Generation {
+        finish_reason: mock_finish_reason(&settings, &call.prompt, call.max_tokens),
+        ..Generation::new(tokens.concat(), count)
+    })
+}
+
+fn mock_finish_reason(settings: &MockSettings, prompt: &str, max_tokens: u32) -> FinishReason {
+    if settings.truncated(prompt, max_tokens) {
+        FinishReason::Length
+    } else {
+        FinishReason::Stop
+    }
+}
+
+async fn huggingface_inference(call: BackendRequest) -> Result<Generation, String> {
+    let BackendRequest { client, base_url, model, prompt, max_tokens, temperature, request_id, .. } = call;
 
-    let hf_token = std::env::var("HUGGINGFACE_TOKEN")
+    let hf_token = config::var("HUGGINGFACE_TOKEN")
         .map_err(|_| "HUGGINGFACE_TOKEN not set. Set HF_TOKEN environment variable.")?;
 
     let request_body = HuggingFaceRequest {
@@ -275,10 +1055,12 @@ async fn huggingface_inference(
             temperature,
             return_full_text: false,
         },
+        stream: false,
     };
 
     let response = client
-        .post(&format!("{}/models/{}", base_url, model))
+        .post(format!("{}/models/{}", base_url, model))
+        .header(REQUEST_ID_HEADER, &request_id)
         .header("Authorization", format!("Bearer {}", hf_token))
         .json(&request_body)
         .send()
@@ -296,233 +1078,630 @@ async fn huggingface_inference(
         .await
         .map_err(|e| format!("Failed to parse HuggingFace response: {}", e))?;
 
-    let text = resp_json[0]["generated_text"]
+    let text = huggingface_text(&resp_json)?;
+    let tokens = text.split_whitespace().count() as u32;
+    Ok(Generation::new(text, tokens))
+}
+
+/// The generated text of a non-streamed Inference API response.
+fn huggingface_text(resp_json: &serde_json::Value) -> Result<String, String> {
+    resp_json[0]["generated_text"]
         .as_str()
         .or(resp_json[0].as_str())
-        .ok_or("Invalid HuggingFace response format")?
-        .to_string();
+        .map(str::to_string)
+        .ok_or_else(|| "Invalid HuggingFace response format".to_string())
+}
 
-    let tokens = text.split_whitespace().count() as u32;
-    Ok((text, tokens))
+/// Streams from the Inference API, which streams models it serves with
+/// text-generation-inference in TGI's event format. Other models answer with
+/// the whole generation at once, which is passed on as a single token.
+fn huggingface_stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    let BackendRequest { client, base_url, model, prompt, max_tokens, temperature, request_id, outcome, .. } = call;
+
+    stream! {
+        let Ok(hf_token) = config::var("HUGGINGFACE_TOKEN") else {
+            yield Err(std::io::Error::other("HUGGINGFACE_TOKEN not set. Set HF_TOKEN environment variable."));
+            return;
+        };
+
+        let request_body = HuggingFaceRequest {
+            inputs: prompt,
+            parameters: HuggingFaceParameters {
+                max_new_tokens: max_tokens,
+                temperature,
+                return_full_text: false,
+            },
+            stream: true,
+        };
+
+        let response = match client
+            .post(format!("{}/models/{}", base_url, model))
+            .header(REQUEST_ID_HEADER, &request_id)
+            .header("Authorization", format!("Bearer {}", hf_token))
+            .json(&request_body)
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                yield Err(std::io::Error::other(format!("HuggingFace stream failed: {}", e)));
+                return;
+            }
+        };
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            yield Err(std::io::Error::other(format!("HuggingFace API error: {} - {}", status, error_text)));
+            return;
+        }
+
+        let event_stream = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !event_stream {
+            let text = match response.json::<serde_json::Value>().await {
+                Ok(resp_json) => huggingface_text(&resp_json),
+                Err(e) => Err(format!("Failed to parse HuggingFace response: {}", e)),
+            };
+            let text = match text {
+                Ok(text) => text,
+                Err(e) => {
+                    yield Err(std::io::Error::other(e));
+                    return;
+                }
+            };
+
+            for (token_id, (token, complete)) in [(text, false), (String::new(), true)].into_iter().enumerate() {
+                let stream_token = StreamToken {
+                    token,
+                    token_id: token_id as u32,
+                    complete,
+                    request_id: request_id.clone(),
+                    finish_reason: complete.then_some(FinishReason::Stop),
+                };
+                outcome.record(&stream_token);
+                if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                    yield Ok(Event::default().event("token").data(json_data));
+                }
+            }
+            return;
+        }
+
+        let mut tokens = Box::pin(tgi::stream_tokens("HuggingFace", response, request_id, outcome));
+        while let Some(event) = tokens.next().await {
+            yield event;
+        }
+    }
+}
+
+/// Where an OpenAI-style chat completion is sent and how it authenticates.
+/// Azure OpenAI serves the same API from per-deployment URLs, with an
+/// `api-version` query parameter and an `api-key` header.
+struct ChatCompletionsEndpoint {
+    name: &'static str,
+    url: String,
+    auth_header: &'static str,
+    auth_value: String,
 }
 
-async fn openai_chat_completion(
+fn chat_completions_endpoint(
+    backend: &InferenceBackend,
     base_url: &str,
     model: &str,
-    prompt: &str,
-    max_tokens: u32,
-    temperature: f32,
-) -> Result<(String, u32), String> {
-    let client = reqwest::Client::new();
+) -> Result<ChatCompletionsEndpoint, String> {
+    match backend {
+        InferenceBackend::AzureOpenAI => {
+            if base_url.is_empty() {
+                return Err(
+                    "AZURE_OPENAI_URL not set. Set it to your resource endpoint, e.g. https://my-resource.openai.azure.com."
+                        .to_string(),
+                );
+            }
+            let api_key = config::var("AZURE_OPENAI_API_KEY")
+                .map_err(|_| "AZURE_OPENAI_API_KEY not set. Set AZURE_OPENAI_API_KEY environment variable.")?;
+            Ok(ChatCompletionsEndpoint {
+                name: "Azure OpenAI",
+                url: format!(
+                    "{}/openai/deployments/{}/chat/completions?api-version={}",
+                    base_url,
+                    model,
+                    azure_api_version()
+                ),
+                auth_header: "api-key",
+                auth_value: api_key,
+            })
+        }
+        _ => {
+            let api_key = config::var("OPENAI_API_KEY")
+                .map_err(|_| "OPENAI_API_KEY not set. Set OPENAI_API_KEY environment variable.")?;
+            Ok(ChatCompletionsEndpoint {
+                name: "OpenAI",
+                url: format!("{}/chat/completions", base_url),
+                auth_header: "Authorization",
+                auth_value: format!("Bearer {}", api_key),
+            })
+        }
+    }
+}
 
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| "OPENAI_API_KEY not set. Set OPENAI_API_KEY environment variable.")?;
+pub fn azure_api_version() -> String {
+    config::var("AZURE_OPENAI_API_VERSION").unwrap_or_else(|_| AZURE_OPENAI_DEFAULT_API_VERSION.to_string())
+}
+
+async fn openai_chat_completion(backend: &InferenceBackend, call: BackendRequest) -> Result<Generation, String> {
+    let BackendRequest { client, base_url, model, prompt, max_tokens, temperature, sampling, request_id, .. } = call;
+
+    let endpoint = chat_completions_endpoint(backend, &base_url, &model)?;
 
     let request_body = OpenAIChatCompletionRequest {
         model: model.to_string(),
-        messages: vec![ChatMessage {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }],
+        messages: vec![ChatMessage::user(&prompt)],
         max_tokens,
         temperature,
         stream: false,
+        logit_bias: sampling.logit_bias,
     };
 
     let response = client
-        .post(&format!("{}/chat/completions", base_url))
-        .header("Authorization", format!("Bearer {}", api_key))
+        .post(&endpoint.url)
+        .header(REQUEST_ID_HEADER, &request_id)
+        .header(endpoint.auth_header, &endpoint.auth_value)
         .json(&request_body)
         .send()
         .await
-        .map_err(|e| format!("OpenAI request failed: {}", e))?;
+        .map_err(|e| format!("{} request failed: {}", endpoint.name, e))?;
 
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("OpenAI API error: {} - {}", status, error_text));
+        return Err(format!("{} API error: {} - {}", endpoint.name, status, error_text));
     }
 
     let openai_resp: OpenAIChatCompletionResponse = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+        .map_err(|e| format!("Failed to parse {} response: {}", endpoint.name, e))?;
 
     let text = openai_resp.choices[0].message.content.clone();
     let tokens = openai_resp.usage.completion_tokens;
-    Ok((text, tokens))
+    Ok(Generation {
+        finish_reason: openai_compat::finish_reason(&openai_resp.choices[0].finish_reason),
+        ..Generation::new(text, tokens)
+    })
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/inference/stream",
+    tag = "inference",
+    params(
+        ("Last-Event-ID" = Option<String>, Header, description = "Resumes a dropped stream after this event"),
+    ),
+    request_body = InferenceRequest,
+    responses(
+        (
+            status = 200,
+            description = "Server-sent `token` events, then a `usage` event",
+            content_type = "text/event-stream",
+            body = StreamToken,
+        ),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
 pub async fn inference_stream(
     State(state): State<AppState>,
-    Json(req): Json<InferenceRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    headers: HeaderMap,
+    Json(mut req): Json<InferenceRequest>,
+) -> Result<Response, ApiError> {
+    if state.sse.resume_window.is_some()
+        && let Some(last_event_id) = headers.get(LAST_EVENT_ID).and_then(|value| value.to_str().ok())
+    {
+        let (replay, from) = state
+            .replays
+            .lock()
+            .await
+            .resume(last_event_id, &caller.id)
+            .ok_or_else(|| ApiError::NotFound(format!("Stream '{}' can no longer be resumed", last_event_id)))?;
+        return Ok(sse_response(&state, Box::pin(sse::follow(replay, from)), request_id.0));
+    }
+
+    if req.best_of.is_some_and(|n| n > 1) {
+        return Err(ApiError::InvalidRequest {
+            message: "best_of is not supported when streaming".to_string(),
+            param: Some("best_of"),
+        });
+    }
+
+    apply_prompt(&state, &caller, &mut req).await?;
+    apply_variables(&mut req)?;
+    routing::route(&state, &caller, "/v1/inference/stream", &mut req).await?;
+    let lease = pools::resolve(&state, &caller, &mut req).await?;
+    injection::check(&state, &caller, &req).await?;
+    let req = context::prepare(&state, &caller, &request_id.0, req).await?;
+    limits::check_inference(&state, &caller, &req).await?;
+    // Streamed tokens cannot be taken back, so only the prompt is moderated.
+    moderate_input(&state, &req).await?;
+
+    let response = stream_response(state, caller, request_id, req).await?;
+    let Some(lease) = lease else {
+        return Ok(response);
+    };
+    // The member stays busy until the stream ends.
+    let (parts, body) = response.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &lease;
+        chunk
+    }));
+    Ok(Response::from_parts(parts, body))
+}
+
+async fn stream_response(
+    state: AppState,
+    caller: Caller,
+    request_id: RequestId,
+    req: InferenceRequest,
+) -> Result<Response, ApiError> {
     let models = state.models.lock().await;
 
     let model_entry = models
         .iter()
-        .find(|m| m.registry_entry.id == req.model_id)
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                format!("Model '{}' not found or not loaded. Please register and load it first.", req.model_id),
-            )
-        })?;
+        .find(|m| m.registry_entry.id == req.model_id && m.registry_entry.visible_to(&caller))
+        .ok_or_else(|| ApiError::ModelNotFound(req.model_id.clone()))?;
 
     if !model_entry.registry_entry.loaded {
-        return Err((
-            StatusCode::PRECONDITION_FAILED,
-            format!("Model '{}' is not loaded. Load it first.", req.model_id),
-        ));
+        return Err(ApiError::ModelNotLoaded(req.model_id.clone()));
     }
 
     let backend_url = get_backend_url(&model_entry.registry_entry.inference);
-    let model_id = model_entry.registry_entry.id.clone();
+    let model_id = model_entry.registry_entry.backend_model().to_string();
     let inference_backend = model_entry.registry_entry.inference.clone();
+    let chat = model_entry.registry_entry.capabilities.contains(&ModelCapability::Chat);
+    check_grammar(&inference_backend, &req.sampling)?;
+    check_logit_bias(&inference_backend, &req.sampling)?;
     let temperature = req.temperature.unwrap_or(0.7);
-    let prompt = req.prompt.clone();
+    let pricing = model_entry.registry_entry.pricing.clone();
+    let model_tenant = model_entry.registry_entry.tenant.clone();
+    let speculative = model_entry.registry_entry.speculative.clone();
+    let placement = model_entry.registry_entry.placement.clone();
+    let max_concurrency = model_entry.registry_entry.max_concurrency;
 
     drop(models);
 
-    let stream: Pin<Box<dyn Stream<Item = Result<Event, std::io::Error>> + Send>> = match inference_backend {
-        InferenceBackend::Ollama => Box::pin(ollama_stream_events(backend_url.clone(), model_id.clone(), prompt, req.max_tokens, temperature)),
-        InferenceBackend::Llama => Box::pin(llama_cpp_stream_events(backend_url.clone(), model_id.clone(), prompt, req.max_tokens, temperature)),
-        InferenceBackend::OpenAI => Box::pin(openai_stream_events(backend_url.clone(), model_id.clone(), prompt, req.max_tokens, temperature)),
-        InferenceBackend::HuggingFace => {
-            return Err((
-                StatusCode::NOT_IMPLEMENTED,
-                "Streaming not yet supported for HuggingFace backend".to_string(),
-            ));
+    let prompt = outbound_prompt(&state, &inference_backend, &req.prompt)
+        .await
+        .map_err(ApiError::Unavailable)?;
+    let messages = outbound_messages(&state, &inference_backend, &req, &prompt)
+        .await
+        .map_err(ApiError::Unavailable)?;
+
+    let call = BackendRequest {
+        client: state.backend_clients.get(&inference_backend),
+        base_url: backend_url,
+        model: model_id,
+        prompt,
+        messages,
+        chat,
+        tools: req.tools.clone().unwrap_or_default(),
+        max_tokens: req.max_tokens(),
+        temperature,
+        sampling: req.sampling.clone(),
+        speculative,
+        placement,
+        request_id: request_id.0.clone(),
+        upstream_limits: state.upstream_limits.clone(),
+        session: session_key(&caller, &req),
+        llama_slots: state.llama_slots.clone(),
+        outcome: StreamOutcome::default(),
+    };
+    let outcome = call.outcome.clone();
+
+    let stream = stream_events(&inference_backend, call);
+    let stream = admission::after_model_turn(state.clone(), req.model_id.clone(), max_concurrency, stream);
+    let record = RequestRecord::new(&req, &caller, &request_id, pricing, model_tenant, true);
+    let stream: EventStream = Box::pin(track_stream(state.clone(), record, outcome, stream));
+    let stream: EventStream = match state.sse.resume_window {
+        Some(window) => {
+            let replay = state.replays.lock().await.start(&request_id.0, &caller.id, window, stream);
+            Box::pin(sse::follow(replay, 0))
         }
+        None => stream,
     };
 
-    let response = (
+    Ok(sse_response(&state, stream, request_id.0))
+}
+
+fn sse_response(state: &AppState, stream: EventStream, request_id: String) -> Response {
+    let stream = sse::with_pings(stream, state.sse.ping, request_id);
+    (
         [(header::CONTENT_TYPE, "text/event-stream"),
          (header::CACHE_CONTROL, "no-cache"),
          (header::CONNECTION, "keep-alive")],
         axum::response::Sse::new(stream)
-            .keep_alive(KeepAlive::default()),
-    );
+            .keep_alive(state.sse.keep_alive()),
+    )
+        .into_response()
+}
 
-    Ok(response)
+pub(crate) type EventStream = Pin<Box<dyn Stream<Item = Result<Event, std::io::Error>> + Send>>;
+
+/// Streams a call through its backend's adapter.
+pub(crate) fn stream_events(inference_backend: &InferenceBackend, call: BackendRequest) -> EventStream {
+    match inference_backend {
+        InferenceBackend::Ollama => Box::pin(ollama::stream_events(call)),
+        InferenceBackend::Llama => Box::pin(llama_cpp_stream_events(call)),
+        InferenceBackend::OpenAI | InferenceBackend::AzureOpenAI => {
+            Box::pin(openai_stream_events(inference_backend.clone(), call))
+        }
+        InferenceBackend::Anthropic => Box::pin(anthropic::stream_events(call)),
+        InferenceBackend::Gemini => Box::pin(gemini::stream_events(call)),
+        InferenceBackend::Bedrock => Box::pin(bedrock::stream_events(call)),
+        InferenceBackend::Mistral => Box::pin(mistral::stream_events(call)),
+        InferenceBackend::Cohere => Box::pin(cohere::stream_events(call)),
+        InferenceBackend::Groq => Box::pin(groq::stream_events(call)),
+        InferenceBackend::OpenRouter => Box::pin(openrouter::stream_events(call)),
+        InferenceBackend::Together | InferenceBackend::Fireworks => {
+            Box::pin(hosted_oss::stream_events(inference_backend.clone(), call))
+        }
+        InferenceBackend::Replicate => Box::pin(replicate::stream_events(call)),
+        InferenceBackend::Tgi => Box::pin(tgi::stream_events(call)),
+        InferenceBackend::HuggingFace => Box::pin(huggingface_stream_events(call)),
+        InferenceBackend::LmStudio => Box::pin(lmstudio::stream_events(call)),
+        InferenceBackend::KoboldCpp => Box::pin(koboldcpp::stream_events(call)),
+        InferenceBackend::Mlx => Box::pin(mlx::stream_events(call)),
+        InferenceBackend::LlamaLocal => Box::pin(llama_local::stream_events(call)),
+        InferenceBackend::Candle => Box::pin(candle::stream_events(call)),
+        InferenceBackend::Triton => Box::pin(triton::stream_events(call)),
+        InferenceBackend::SageMaker => Box::pin(sagemaker::stream_events(call)),
+        InferenceBackend::Vertex => Box::pin(vertex::stream_events(call)),
+        InferenceBackend::DeepSeek => Box::pin(deepseek::stream_events(call)),
+        InferenceBackend::Xai => Box::pin(xai::stream_events(call)),
+        InferenceBackend::Mock => Box::pin(mock_stream_events(call)),
+    }
 }
 
-fn ollama_stream_events(
-    base_url: String,
-    model: String,
+pub fn count_tokens(text: &str) -> u32 {
+    text.split_whitespace().count() as u32
+}
+
+/// Bookkeeping for a single inference call, reported to the usage stats and
+/// audit log once the call has finished.
+struct RequestRecord {
+    request_id: String,
+    model_id: String,
     prompt: String,
     max_tokens: u32,
     temperature: f32,
-) -> impl Stream<Item = Result<Event, std::io::Error>> {
-    stream! {
-        let client = reqwest::Client::new();
+    caller: String,
+    tenant: Option<String>,
+    rate_limit_key: String,
+    pricing: Option<ModelPricing>,
+    /// Tenant that owns the model, which its stats are partitioned under.
+    model_tenant: Option<String>,
+    stream: bool,
+    started: Instant,
+}
 
-        let request_body = OllamaGenerateRequest {
-            model: model.clone(),
-            prompt: prompt.clone(),
-            stream: true,
-            options: OllamaOptions {
-                num_predict: max_tokens,
-                temperature,
-            },
-        };
+impl RequestRecord {
+    fn new(
+        req: &InferenceRequest,
+        caller: &Caller,
+        request_id: &RequestId,
+        pricing: Option<ModelPricing>,
+        model_tenant: Option<String>,
+        stream: bool,
+    ) -> Self {
+        Self {
+            request_id: request_id.0.clone(),
+            model_id: req.model_id.clone(),
+            prompt: req.prompt.clone(),
+            max_tokens: req.max_tokens(),
+            temperature: req.temperature.unwrap_or(0.7),
+            caller: caller.id.clone(),
+            tenant: caller.tenant.clone(),
+            rate_limit_key: caller.rate_limit_key(),
+            pricing,
+            model_tenant,
+            stream,
+            started: Instant::now(),
+        }
+    }
 
-        let response = match client
-            .post(&format!("{}/api/generate", base_url))
-            .json(&request_body)
-            .send()
-            .await
-        {
-            Ok(r) => r,
-            Err(e) => {
-                yield Err(std::io::Error::other(format!("Ollama stream failed: {}", e)));
-                return;
+    /// Records the outcome and returns the request summary. Non-streaming
+    /// calls have no `first_token_at`, so their TTFT is the full latency and
+    /// throughput is measured over the whole call.
+    async fn finish(
+        self,
+        state: &AppState,
+        completion_tokens: u32,
+        first_token_at: Option<Instant>,
+        draft: Option<DraftTokens>,
+        error: Option<String>,
+    ) -> RequestSummary {
+        let latency_ms = self.started.elapsed().as_millis() as u64;
+        let prompt_tokens = count_tokens(&self.prompt);
+        let (ttft_ms, generation_ms) = match first_token_at {
+            Some(at) => {
+                let ttft_ms = at.duration_since(self.started).as_millis() as u64;
+                (ttft_ms, latency_ms.saturating_sub(ttft_ms))
             }
+            None => (latency_ms, latency_ms),
+        };
+        let timings = InferenceTimings {
+            time_to_first_token_ms: ttft_ms,
+            total_ms: latency_ms,
+            tokens_per_second: stats::tokens_per_second(completion_tokens as u64, generation_ms),
         };
+        let estimated_cost_usd = self
+            .pricing
+            .as_ref()
+            .map(|pricing| estimate_cost(pricing, prompt_tokens, completion_tokens));
+
+        state.stats.lock().await.record(
+            self.model_tenant.as_deref(),
+            &self.model_id,
+            RequestOutcome {
+                success: error.is_none(),
+                prompt_tokens,
+                completion_tokens,
+                latency_ms,
+                ttft_ms,
+                generation_ms,
+                draft,
+            },
+        );
 
-        if !response.status().is_success() {
-            yield Err(std::io::Error::other(format!("Ollama API error: {}", response.status())));
-            return;
+        if error.is_none() {
+            let sample = latency::Sample {
+                ttft_ms: first_token_at.is_some().then_some(ttft_ms),
+                completion_tokens,
+                generation_ms,
+            };
+            let measured = state.latency.lock().await.record(&self.model_id, sample, Utc::now());
+            if let Some(measured) = measured
+                && let Some(model) = state.models.lock().await.iter_mut().find(|m| {
+                    m.registry_entry.id == self.model_id && m.registry_entry.tenant == self.model_tenant
+                })
+            {
+                if !model.registry_entry.latency_pinned {
+                    model.registry_entry.latency = Some(measured.profile.clone());
+                }
+                model.registry_entry.measured_latency = Some(measured);
+                state.registry_revision.bump();
+            }
         }
 
-        let mut byte_stream = response.bytes_stream();
-        let mut buffer = Vec::new();
-        let mut token_id = 0u32;
+        state
+            .rate_limiter
+            .lock()
+            .await
+            .consume_tokens(&self.rate_limit_key, (prompt_tokens + completion_tokens) as u64);
 
-        while let Some(chunk) = byte_stream.next().await {
-            let chunk = match chunk {
-                Ok(c) => c,
-                Err(e) => {
-                    yield Err(std::io::Error::other(format!("Ollama read error: {}", e)));
-                    return;
-                }
-            };
+        state
+            .api_keys
+            .lock()
+            .await
+            .record_usage(&self.caller, (prompt_tokens + completion_tokens) as u64);
+
+        if let Some(tenant) = &self.tenant {
+            state
+                .tenants
+                .lock()
+                .await
+                .record_usage(tenant, (prompt_tokens + completion_tokens) as u64);
+        }
 
-            buffer.extend_from_slice(&chunk);
+        state.accounting.lock().await.record(
+            &self.caller,
+            self.tenant.as_deref(),
+            prompt_tokens,
+            completion_tokens,
+            estimated_cost_usd.unwrap_or(0.0),
+        );
+
+        if let Some(audit) = &state.audit {
+            let (prompt_hash, prompt) = audit.redact(&self.prompt);
+            audit.record(AuditRecord {
+                timestamp: Utc::now(),
+                request_id: self.request_id,
+                model_id: self.model_id,
+                stream: self.stream,
+                caller: Some(self.caller),
+                tenant: self.tenant,
+                estimated_cost_usd,
+                prompt_hash,
+                prompt,
+                max_tokens: self.max_tokens,
+                temperature: self.temperature,
+                prompt_tokens,
+                completion_tokens,
+                latency_ms,
+                success: error.is_none(),
+                error,
+            });
+        }
 
-            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                let line = String::from_utf8_lossy(&buffer[..pos]).to_string();
-                buffer.drain(..=pos);
+        RequestSummary {
+            timings,
+            estimated_cost_usd,
+        }
+    }
+}
 
-                if line.trim().is_empty() {
-                    continue;
+/// Records a stream once it ends and, unless it failed, closes it with a
+/// `usage` event.
+fn track_stream(
+    state: AppState,
+    record: RequestRecord,
+    outcome: StreamOutcome,
+    mut inner: EventStream,
+) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    stream! {
+        let prompt_tokens = count_tokens(&record.prompt);
+        let request_id = record.request_id.clone();
+        let mut completion_tokens = 0u32;
+        let mut first_token_at = None;
+        let mut error = None;
+
+        while let Some(item) = inner.next().await {
+            match &item {
+                Ok(_) => {
+                    completion_tokens += 1;
+                    first_token_at.get_or_insert_with(Instant::now);
                 }
+                Err(e) => error = Some(e.to_string()),
+            }
+            yield item;
+            if error.is_some() {
+                break;
+            }
+        }
 
-                if let Ok(ollama_resp) = serde_json::from_str::<OllamaGenerateResponse>(&line) {
-                    let stream_token = StreamToken {
-                        token: ollama_resp.response.clone(),
-                        token_id,
-                        complete: ollama_resp.done,
-                    };
-                    token_id += 1;
-
-                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
-                        yield Ok(Event::default().event("token").data(json_data));
-                    }
-
-                    if ollama_resp.done {
-                        return;
-                    }
-                }
+        let failed = error.is_some();
+        let summary = record
+            .finish(
+                &state,
+                completion_tokens,
+                Some(first_token_at.unwrap_or_else(Instant::now)),
+                outcome.draft(),
+                error,
+            )
+            .await;
+
+        if !failed {
+            let usage = StreamUsage {
+                request_id: request_id.clone(),
+                prompt_tokens,
+                completion_tokens,
+                timings: summary.timings,
+                estimated_cost_usd: summary.estimated_cost_usd,
+                finish_reason: outcome.finish_reason().unwrap_or_default(),
+            };
+            if let Ok(json_data) = serde_json::to_string(&usage) {
+                yield Ok(Event::default().event("usage").data(json_data));
             }
         }
     }
 }
 
-fn llama_cpp_stream_events(
-    base_url: String,
-    _model: String,
-    prompt: String,
-    max_tokens: u32,
-    temperature: f32,
-) -> impl Stream<Item = Result<Event, std::io::Error>> {
+fn llama_cpp_stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
     stream! {
-        let client = reqwest::Client::new();
-
-        let request_body = serde_json::json!({
-            "prompt": prompt,
-            "n_predict": max_tokens,
-            "temperature": temperature,
-            "stream": true
-        });
+        // Held until the stream ends, so the slot counts it as in flight.
+        let slot = llama_cpp_slot(&call).await;
+        let request_body = llama_cpp_request_body(&call, slot.as_ref(), true);
 
-        let response = match client
-            .post(&format!("{}/v1/completions", base_url))
-            .json(&request_body)
-            .send()
-            .await
-        {
+        let response = match llama_cpp_send(&call, &request_body, "stream").await {
             Ok(r) => r,
             Err(e) => {
-                yield Err(std::io::Error::other(format!("llama.cpp stream failed: {}", e)));
+                yield Err(std::io::Error::other(e));
                 return;
             }
         };
-
-        if !response.status().is_success() {
-            yield Err(std::io::Error::other(format!("llama.cpp API error: {}", response.status())));
-            return;
-        }
+        let BackendRequest { request_id, outcome, .. } = call;
 
         let mut byte_stream = response.bytes_stream();
         let mut buffer = Vec::new();
@@ -552,27 +1731,32 @@ fn llama_cpp_stream_events(
                     return;
                 }
 
-                if let Ok(resp_json) = serde_json::from_str::<serde_json::Value>(data) {
-                    if let Some(choices) = resp_json["choices"].as_array() {
-                        if let Some(choice) = choices.first() {
-                            let text = choice["text"].as_str().unwrap_or("");
-                            let finish = choice["finish_reason"].is_null() == false;
-
-                            let stream_token = StreamToken {
-                                token: text.to_string(),
-                                token_id,
-                                complete: finish,
-                            };
-                            token_id += 1;
-
-                            if let Ok(json_data) = serde_json::to_string(&stream_token) {
-                                yield Ok(Event::default().event("token").data(json_data));
-                            }
-
-                            if finish {
-                                return;
-                            }
-                        }
+                if let Ok(resp_json) = serde_json::from_str::<serde_json::Value>(data)
+                    && let Some(choices) = resp_json["choices"].as_array()
+                    && let Some(choice) = choices.first()
+                {
+                    let text = choice["text"].as_str().unwrap_or("");
+                    let finish = choice["finish_reason"].as_str().map(openai_compat::finish_reason);
+                    if let Some(draft) = llama_cpp_draft(&resp_json["timings"]) {
+                        outcome.record_draft(draft);
+                    }
+
+                    let stream_token = StreamToken {
+                        token: text.to_string(),
+                        token_id,
+                        complete: finish.is_some(),
+                        request_id: request_id.clone(),
+                        finish_reason: finish,
+                    };
+                    outcome.record(&stream_token);
+                    token_id += 1;
+
+                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                        yield Ok(Event::default().event("token").data(json_data));
+                    }
+
+                    if finish.is_some() {
+                        return;
                     }
                 }
             }
@@ -581,44 +1765,47 @@ fn llama_cpp_stream_events(
 }
 
 fn openai_stream_events(
-    base_url: String,
-    model: String,
-    prompt: String,
-    max_tokens: u32,
-    temperature: f32,
+    backend: InferenceBackend,
+    call: BackendRequest,
 ) -> impl Stream<Item = Result<Event, std::io::Error>> {
-    stream! {
-        let client = reqwest::Client::new();
+    let endpoint = chat_completions_endpoint(&backend, &call.base_url, &call.model);
+    let BackendRequest { client, model, prompt, max_tokens, temperature, sampling, request_id, outcome, .. } = call;
 
-        let api_key = std::env::var("OPENAI_API_KEY").unwrap_or_default();
+    stream! {
+        let endpoint = match endpoint {
+            Ok(endpoint) => endpoint,
+            Err(e) => {
+                yield Err(std::io::Error::other(e));
+                return;
+            }
+        };
 
         let request_body = OpenAIChatCompletionRequest {
             model: model.clone(),
-            messages: vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt.clone(),
-            }],
+            messages: vec![ChatMessage::user(&prompt)],
             max_tokens,
             temperature,
             stream: true,
+            logit_bias: sampling.logit_bias,
         };
 
         let response = match client
-            .post(&format!("{}/chat/completions", base_url))
-            .header("Authorization", format!("Bearer {}", api_key))
+            .post(&endpoint.url)
+            .header(REQUEST_ID_HEADER, &request_id)
+            .header(endpoint.auth_header, &endpoint.auth_value)
             .json(&request_body)
             .send()
             .await
         {
             Ok(r) => r,
             Err(e) => {
-                yield Err(std::io::Error::other(format!("OpenAI stream failed: {}", e)));
+                yield Err(std::io::Error::other(format!("{} stream failed: {}", endpoint.name, e)));
                 return;
             }
         };
 
         if !response.status().is_success() {
-            yield Err(std::io::Error::other(format!("OpenAI API error: {}", response.status())));
+            yield Err(std::io::Error::other(format!("{} API error: {}", endpoint.name, response.status())));
             return;
         }
 
@@ -630,7 +1817,7 @@ fn openai_stream_events(
             let chunk = match chunk {
                 Ok(c) => c,
                 Err(e) => {
-                    yield Err(std::io::Error::other(format!("OpenAI read error: {}", e)));
+                    yield Err(std::io::Error::other(format!("{} read error: {}", endpoint.name, e)));
                     return;
                 }
             };
@@ -650,35 +1837,75 @@ fn openai_stream_events(
                     return;
                 }
 
-                if let Ok(resp_json) = serde_json::from_str::<serde_json::Value>(data) {
-                    if let Some(choices) = resp_json["choices"].as_array() {
-                        if let Some(choice) = choices.first() {
-                            let delta = &choice["delta"];
-                            let text = delta["content"].as_str().unwrap_or("");
-                            let finish = choice["finish_reason"].is_null() == false;
-
-                            if text.is_empty() && !finish {
-                                continue;
-                            }
-
-                            let stream_token = StreamToken {
-                                token: text.to_string(),
-                                token_id,
-                                complete: finish,
-                            };
-                            token_id += 1;
-
-                            if let Ok(json_data) = serde_json::to_string(&stream_token) {
-                                yield Ok(Event::default().event("token").data(json_data));
-                            }
-
-                            if finish {
-                                return;
-                            }
-                        }
+                if let Ok(resp_json) = serde_json::from_str::<serde_json::Value>(data)
+                    && let Some(choices) = resp_json["choices"].as_array()
+                    && let Some(choice) = choices.first()
+                {
+                    let delta = &choice["delta"];
+                    let text = delta["content"].as_str().unwrap_or("");
+                    let finish = choice["finish_reason"].as_str().map(openai_compat::finish_reason);
+
+                    if text.is_empty() && finish.is_none() {
+                        continue;
+                    }
+
+                    let stream_token = StreamToken {
+                        token: text.to_string(),
+                        token_id,
+                        complete: finish.is_some(),
+                        request_id: request_id.clone(),
+                        finish_reason: finish,
+                    };
+                    outcome.record(&stream_token);
+                    token_id += 1;
+
+                    if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                        yield Ok(Event::default().event("token").data(json_data));
+                    }
+
+                    if finish.is_some() {
+                        return;
                     }
                 }
             }
         }
     }
 }
+
+fn mock_stream_events(call: BackendRequest) -> impl Stream<Item = Result<Event, std::io::Error>> {
+    let BackendRequest { prompt, max_tokens, request_id, outcome, .. } = call;
+
+    stream! {
+        let settings = MockSettings::from_env();
+        if settings.should_fail() {
+            yield Err(std::io::Error::other("Mock backend injected failure"));
+            return;
+        }
+
+        let tokens = settings.tokens(&prompt, max_tokens);
+        let last = tokens.len().saturating_sub(1);
+        let finish_reason = mock_finish_reason(&settings, &prompt, max_tokens);
+        for (token_id, token) in tokens.into_iter().enumerate() {
+            if settings.fail_after_tokens == Some(token_id as u32) {
+                yield Err(std::io::Error::other(format!(
+                    "Mock backend injected failure after {} tokens",
+                    token_id
+                )));
+                return;
+            }
+            tokio::time::sleep(settings.token_delay).await;
+
+            let stream_token = StreamToken {
+                token,
+                token_id: token_id as u32,
+                complete: token_id == last,
+                request_id: request_id.clone(),
+                finish_reason: (token_id == last).then_some(finish_reason),
+            };
+            outcome.record(&stream_token);
+            if let Ok(json_data) = serde_json::to_string(&stream_token) {
+                yield Ok(Event::default().event("token").data(json_data));
+            }
+        }
+    }
+}
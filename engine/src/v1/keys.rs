@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Path, State},
+    Extension,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::super::auth::{ApiKeyRecord, Caller, Role};
+use super::super::error::{ApiError, ErrorBody};
+use super::super::quota::{QuotaStatus, TokenQuota};
+use super::super::AppState;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub tier: Option<String>,
+    /// Tenant the key belongs to. Tenant admins can only issue keys for
+    /// their own tenant, which is also the default.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub quota: TokenQuota,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub role: Role,
+    pub tier: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub quota: TokenQuota,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<&ApiKeyRecord> for ApiKeyInfo {
+    fn from(record: &ApiKeyRecord) -> Self {
+        Self {
+            id: record.id.clone(),
+            name: record.name.clone(),
+            prefix: record.prefix.clone(),
+            role: record.role,
+            tier: record.tier.clone(),
+            tenant: record.tenant.clone(),
+            quota: record.quota.clone(),
+            created_at: record.created_at,
+            revoked_at: record.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateKeyResponse {
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+    pub key: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct KeyListResponse {
+    pub keys: Vec<ApiKeyInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/keys",
+    tag = "keys",
+    responses((status = 200, body = KeyListResponse))
+)]
+pub async fn list_keys(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let keys = state.api_keys.lock().await;
+    let keys = keys
+        .list()
+        .iter()
+        .filter(|k| caller.can_see(k.tenant.as_deref()))
+        .map(ApiKeyInfo::from)
+        .collect();
+
+    (StatusCode::OK, Json(KeyListResponse { keys }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/keys",
+    tag = "keys",
+    request_body = CreateKeyRequest,
+    responses(
+        (status = 201, body = CreateKeyResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn create_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(req): Json<CreateKeyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let tenant = match (caller.tenant, req.tenant) {
+        (Some(own), Some(requested)) if own != requested => {
+            return Err(ApiError::Forbidden(format!("Cannot issue keys for tenant '{}'", requested)));
+        }
+        (Some(own), _) => Some(own),
+        (None, requested) => requested,
+    };
+
+    let mut keys = state.api_keys.lock().await;
+    let (record, key) = keys
+        .create(req.name, req.role, req.tier, tenant, req.quota)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store API key: {}", e)))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateKeyResponse {
+            info: ApiKeyInfo::from(&record),
+            key,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/keys/{key_id}",
+    tag = "keys",
+    params(("key_id" = String, Path)),
+    responses(
+        (status = 200, body = ApiKeyInfo),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(key_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut keys = state.api_keys.lock().await;
+    if !keys
+        .list()
+        .iter()
+        .any(|k| k.id == key_id && caller.can_see(k.tenant.as_deref()))
+    {
+        return Err(ApiError::NotFound(format!("API key '{}' not found", key_id)));
+    }
+
+    let record = keys
+        .revoke(&key_id)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store API key: {}", e)))?
+        .ok_or_else(|| ApiError::NotFound(format!("API key '{}' not found", key_id)))?;
+
+    Ok((StatusCode::OK, Json(ApiKeyInfo::from(&record))))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsageResponse {
+    pub caller: String,
+    pub role: Role,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_quota: Option<QuotaStatus>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/usage",
+    tag = "keys",
+    responses((status = 200, body = UsageResponse))
+)]
+pub async fn usage(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let quota = state.api_keys.lock().await.quota_status(&caller.id);
+    let tenant_quota = match &caller.tenant {
+        Some(tenant) => state.tenants.lock().await.quota_status(tenant),
+        None => None,
+    };
+
+    (
+        StatusCode::OK,
+        Json(UsageResponse {
+            caller: caller.id,
+            role: caller.role,
+            tenant: caller.tenant,
+            quota,
+            tenant_quota,
+        }),
+    )
+}
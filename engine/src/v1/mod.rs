@@ -1,9 +1,47 @@
+pub mod accounting;
+pub mod benchmark;
+pub mod best_of;
+pub mod catalog;
+pub mod config;
+pub mod documents;
+pub mod embeddings;
+pub mod ensemble;
 pub mod health;
 pub mod models;
+pub mod pipelines;
+pub mod pools;
+pub mod prompts;
 pub mod inference;
+pub mod keys;
+pub mod rag;
+pub mod registry;
+pub mod stats;
+pub mod storage;
+pub mod tenants;
+pub mod vectors;
 
-pub use health::health_check;
+pub use accounting::accounting_summary;
+pub use benchmark::benchmark_model;
+pub use catalog::{import_catalog, list_catalog, list_llama_slots};
+pub use config::get_config;
+pub use documents::{document_job, ingest_document};
+pub use embeddings::create_embeddings;
+pub use ensemble::inference_ensemble;
+pub use health::{backend_health, health_check, liveness, readiness};
 pub use models::{
-    list_models, register_model, load_model, unload_model,
+    list_models, register_model, register_models, load_model, unload_model, unregister_model,
 };
 pub use inference::{inference_complete, inference_stream};
+pub use keys::{create_key, list_keys, revoke_key, usage};
+pub use pipelines::run_pipeline;
+pub use pools::{
+    add_pool_member, create_pool, delete_pool, get_pool, list_pools, remove_pool_member, set_pool_member_weight,
+    update_pool,
+};
+pub use prompts::{create_prompt, delete_prompt, get_prompt, list_prompts, update_prompt};
+pub use rag::rag_query;
+pub use registry::{export_registry, import_registry};
+pub use stats::{global_stats, model_stats};
+pub use storage::{prune_storage, storage_usage};
+pub use tenants::{list_tenants, update_tenant};
+pub use vectors::{search_vectors, upsert_vectors};
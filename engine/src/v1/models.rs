@@ -1,198 +1,951 @@
 use axum::{
     extract::State,
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
     Json,
 };
 use chrono::Utc;
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
+use utoipa::ToSchema;
 
+use super::super::auth::Caller;
+use super::super::backend_health::{self, ModelLookup};
+use super::super::backends::{candle, llama_local, lmstudio, ollama};
+use super::super::error::{ApiError, ErrorBody, ErrorObject};
+use super::super::etag;
+use super::super::model_metadata::{self, ModelMetadata};
+use super::super::postprocess::{self, PostProcessor};
+use super::super::request_id::RequestId;
+use super::super::templates;
 use super::super::{
     AppState, LoadedModel, ModelRegistryEntry, InferenceBackend, ModelCapability, LatencyProfile,
+    ModelPricing, Placement, QuantVariant, SpeculativeDecoding,
 };
+use super::inference::get_backend_url;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ModelListResponse {
     pub models: Vec<ModelRegistryEntry>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct RegisterModelRequest {
     pub id: String,
     pub name: String,
     pub inference: InferenceBackend,
-    pub context: u32,
+    /// Context window in tokens. Read from the backend when omitted, for
+    /// Ollama and in-process llama.cpp models.
+    #[serde(default)]
+    pub context: Option<u32>,
     #[serde(default)]
     pub quant: Option<String>,
+    #[serde(default)]
+    pub variants: Vec<QuantVariant>,
     pub capabilities: Vec<ModelCapability>,
     #[serde(default)]
     pub latency: Option<LatencyProfile>,
-    #[serde(default = "default_size_bytes")]
-    pub size_bytes: u64,
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+    #[serde(default)]
+    pub chat_template: Option<String>,
+    #[serde(default)]
+    pub post_process: Vec<PostProcessor>,
+    #[serde(default)]
+    pub speculative: Option<SpeculativeDecoding>,
+    #[serde(default)]
+    pub max_concurrency: Option<u32>,
+    /// Size of the weights in memory, 4 GB when omitted and the backend
+    /// does not report it.
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Reads the context length, quant and size from the backend, for
+    /// Ollama and in-process llama.cpp models, in place of the values given.
+    #[serde(default)]
+    pub discover: bool,
+    /// Whether to check with the backend that it is reachable and has the
+    /// model before registering it.
+    #[serde(default)]
+    pub validate: Validation,
+}
+
+/// How registration checks a model against its backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Validation {
+    /// Registers the model without asking the backend.
+    #[default]
+    Off,
+    /// Registers the model, returning what the check found as warnings.
+    Warn,
+    /// Rejects the model unless the backend is reachable and, where it can
+    /// say, has the model.
+    Strict,
 }
 
 fn default_size_bytes() -> u64 {
     4_000_000_000
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct RegisterModelResponse {
     pub success: bool,
     pub model: ModelRegistryEntry,
     pub message: String,
+    /// What the backend reported about the model, when it was asked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ModelMetadata>,
+    /// Given values that discovery replaced, and what `validate: warn`
+    /// found wrong with the model.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkRegisterRequest {
+    /// Registration requests, as `/v1/models/register` takes them.
+    pub models: Vec<RegisterModelRequest>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkRegisterResult {
+    pub id: String,
+    /// What `/v1/models/register` would have answered: `201` when the
+    /// model was registered, `409` when the id was already taken.
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<ModelRegistryEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ModelMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkRegisterResponse {
+    pub registered: usize,
+    pub failed: usize,
+    /// One result per requested model, in request order.
+    pub results: Vec<BulkRegisterResult>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoadModelRequest {
     pub model_id: String,
+    /// The quant variant to load. Picked by the memory available when unset.
+    #[serde(default)]
+    pub quant: Option<String>,
+    /// Hints for backends that place models themselves.
+    #[serde(flatten)]
+    pub placement: Placement,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoadModelResponse {
     pub success: bool,
     pub model_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quant: Option<String>,
     pub message: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UnloadModelResponse {
     pub success: bool,
     pub model_id: String,
     pub message: String,
 }
 
-pub async fn list_models(State(state): State<AppState>) -> impl IntoResponse {
+#[derive(Serialize, ToSchema)]
+pub struct UnregisterModelResponse {
+    pub success: bool,
+    pub model_id: String,
+    pub message: String,
+}
+
+/// Has backends that manage model memory themselves load or unload the
+/// model. For other backends loading only marks the model in the registry.
+pub(crate) async fn sync_backend(state: &AppState, entry: &ModelRegistryEntry, load: bool) -> Result<(), String> {
+    let backend = &entry.inference;
+    match backend {
+        InferenceBackend::LmStudio => {
+            let client = state.backend_clients.get(backend);
+            let url = get_backend_url(backend);
+            if load {
+                lmstudio::load(&client, &url, entry.backend_model(), entry.context).await
+            } else {
+                lmstudio::unload(&client, &url, entry.backend_model()).await
+            }
+        }
+        // Ollama loads models on first use, unless asked to place them.
+        InferenceBackend::Ollama if load => match &entry.placement {
+            Some(placement) => {
+                let client = state.backend_clients.get(backend);
+                ollama::load(&client, &get_backend_url(backend), entry.backend_model(), placement).await
+            }
+            None => Ok(()),
+        },
+        // Unloading only has to drop the weights, which the registry does.
+        InferenceBackend::LlamaLocal if load => llama_local::load(entry.backend_model(), entry.placement.as_ref()).await,
+        InferenceBackend::Candle if load => candle::load(entry.backend_model()).await,
+        InferenceBackend::Candle => {
+            candle::unload(entry.backend_model());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a model's draft is registered alongside it on a backend that
+/// decodes speculatively. llama.cpp loads the draft itself at launch, while
+/// LM Studio takes it per request.
+fn check_draft_model(
+    models: &[LoadedModel],
+    caller: &Caller,
+    entry: &ModelRegistryEntry,
+    speculative: &SpeculativeDecoding,
+) -> Result<(), ApiError> {
+    let invalid = |message: String| ApiError::Unprocessable {
+        message,
+        param: Some("speculative"),
+    };
+
+    if !matches!(entry.inference, InferenceBackend::Llama | InferenceBackend::LmStudio) {
+        return Err(invalid(format!(
+            "Speculative decoding is not supported on the {:?} backend",
+            entry.inference
+        )));
+    }
+    if speculative.draft_model == entry.id {
+        return Err(invalid("A model cannot be its own draft model".to_string()));
+    }
+    let draft = models
+        .iter()
+        .map(|m| &m.registry_entry)
+        .find(|m| m.id == speculative.draft_model && m.visible_to(caller))
+        .ok_or_else(|| invalid(format!("Draft model '{}' is not registered", speculative.draft_model)))?;
+    if draft.inference != entry.inference {
+        return Err(invalid(format!(
+            "Draft model '{}' runs on the {:?} backend, not {:?}",
+            draft.id, draft.inference, entry.inference
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that variants are told apart by their quant, and that a model's
+/// `quant`, if given alongside them, is one of them.
+/// Checks the settings of a model being registered or imported that can be
+/// checked without its backend.
+pub(crate) fn check_entry(entry: &ModelRegistryEntry) -> Result<(), ApiError> {
+    if let Some(template) = &entry.chat_template
+        && let Err(message) = templates::validate(template)
+    {
+        return Err(ApiError::InvalidRequest {
+            message,
+            param: Some("chat_template"),
+        });
+    }
+
+    if let Err(message) = check_variants(&entry.variants, entry.quant.as_deref()) {
+        return Err(ApiError::InvalidRequest {
+            message,
+            param: Some("variants"),
+        });
+    }
+
+    if entry.max_concurrency == Some(0) {
+        return Err(ApiError::InvalidRequest {
+            message: "max_concurrency must be at least 1".to_string(),
+            param: Some("max_concurrency"),
+        });
+    }
+
+    if let Err(message) = postprocess::validate(&entry.post_process) {
+        return Err(ApiError::InvalidRequest {
+            message,
+            param: Some("post_process"),
+        });
+    }
+    Ok(())
+}
+
+fn check_variants(variants: &[QuantVariant], quant: Option<&str>) -> Result<(), String> {
+    for (index, variant) in variants.iter().enumerate() {
+        if variants[..index].iter().any(|other| other.quant.eq_ignore_ascii_case(&variant.quant)) {
+            return Err(format!("Quant variant '{}' is listed twice", variant.quant));
+        }
+    }
+    match quant {
+        Some(quant) if !variants.is_empty() && !variants.iter().any(|variant| variant.quant == quant) => {
+            Err(format!("quant '{}' is not one of the model's variants", quant))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fills in the context length, quant and size the backend reported. Values
+/// the request gave are replaced only with `discover`, with a warning when
+/// they differ. Models with quant variants keep their variants' quants and
+/// sizes.
+fn apply_metadata(
+    entry: &mut ModelRegistryEntry,
+    req: &RegisterModelRequest,
+    metadata: &ModelMetadata,
+    warnings: &mut Vec<String>,
+) {
+    let mut replaced = |field: &str, given: String, reported: String| {
+        warnings.push(format!(
+            "{} {} was given, but {:?} reports {}; using {}",
+            field, given, entry.inference, reported, reported
+        ));
+    };
+
+    if let Some(context) = metadata.context {
+        match req.context {
+            None => entry.context = context,
+            Some(given) if req.discover && given != context => {
+                replaced("context", given.to_string(), context.to_string());
+                entry.context = context;
+            }
+            Some(_) => {}
+        }
+    }
+    if !entry.variants.is_empty() {
+        return;
+    }
+    if let Some(quant) = &metadata.quant {
+        match &req.quant {
+            None => entry.quant = Some(quant.clone()),
+            Some(given) if req.discover && !given.eq_ignore_ascii_case(quant) => {
+                replaced("quant", given.clone(), quant.clone());
+                entry.quant = Some(quant.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(size_bytes) = metadata.size_bytes {
+        match req.size_bytes {
+            None => entry.size_bytes = size_bytes,
+            Some(given) if req.discover && given != size_bytes => {
+                replaced("size_bytes", given.to_string(), size_bytes.to_string());
+                entry.size_bytes = size_bytes;
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// Asks the model's backend whether it is reachable, has the model, and
+/// can do what the model is registered for. Returns what is wrong as
+/// warnings, or with `strict` as an error: 502 when the backend cannot be
+/// asked, 422 when it does not have the model or reports other capabilities.
+async fn validate(state: &AppState, entry: &ModelRegistryEntry, strict: bool) -> Result<Vec<String>, ApiError> {
+    let client = state.backend_clients.get(&entry.inference);
+    let model = entry.backend_model();
+    let (message, param) = match backend_health::check_model(client, entry.inference.clone(), model).await {
+        Ok(ModelLookup::Found(None) | ModelLookup::Unknown) => return Ok(Vec::new()),
+        Ok(ModelLookup::Found(Some(reported))) => {
+            let mismatches = capability_mismatches(&entry.capabilities, &reported);
+            if mismatches.is_empty() {
+                return Ok(Vec::new());
+            }
+            let message = format!(
+                "{:?} does not report {} for '{}'; it reports {}",
+                entry.inference,
+                capability_names(&mismatches),
+                model,
+                capability_names(&reported)
+            );
+            (message, "capabilities")
+        }
+        Ok(ModelLookup::Missing) => (format!("{:?} has no model '{}'", entry.inference, model), "id"),
+        Err(e) => {
+            let url = get_backend_url(&entry.inference);
+            let message = format!("{:?} at {} could not be reached: {}", entry.inference, url, e);
+            if strict {
+                return Err(ApiError::Backend(message));
+            }
+            (message, "inference")
+        }
+    };
+    if strict {
+        return Err(ApiError::Unprocessable {
+            message,
+            param: Some(param),
+        });
+    }
+    Ok(vec![message])
+}
+
+/// The declared capabilities the backend does not report, so that routing
+/// does not send images to a text-only model or embeddings to a chat model.
+/// Chat and completion count as one: backends report text generation, not
+/// which endpoint serves it. Rerank is not reported reliably, so it is not
+/// checked.
+fn capability_mismatches(declared: &[ModelCapability], reported: &[ModelCapability]) -> Vec<ModelCapability> {
+    let generates = reported.contains(&ModelCapability::Chat) || reported.contains(&ModelCapability::Completion);
+    declared
+        .iter()
+        .filter(|capability| match capability {
+            ModelCapability::Chat | ModelCapability::Completion => !generates,
+            ModelCapability::Vision | ModelCapability::Embedding => !reported.contains(capability),
+            ModelCapability::Rerank => false,
+        })
+        .cloned()
+        .collect()
+}
+
+fn capability_names(capabilities: &[ModelCapability]) -> String {
+    if capabilities.is_empty() {
+        return "none".to_string();
+    }
+    capabilities
+        .iter()
+        .map(|capability| format!("{:?}", capability).to_lowercase())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The quant variant to load: the one asked for, or else the largest that
+/// fits in the memory available. `None` for models without variants.
+async fn choose_variant<'a>(
+    state: &AppState,
+    entry: &'a ModelRegistryEntry,
+    quant: Option<&str>,
+) -> Result<Option<&'a QuantVariant>, ApiError> {
+    let unknown = |message: String| ApiError::Unprocessable {
+        message,
+        param: Some("quant"),
+    };
+
+    if entry.variants.is_empty() {
+        return match quant {
+            Some(quant) if Some(quant) != entry.quant.as_deref() => {
+                Err(unknown(format!("Model '{}' has no quant variants to choose from", entry.id)))
+            }
+            _ => Ok(None),
+        };
+    }
+
+    if let Some(quant) = quant {
+        return match entry.variants.iter().find(|variant| variant.quant.eq_ignore_ascii_case(quant)) {
+            Some(variant) => Ok(Some(variant)),
+            None => {
+                let quants: Vec<&str> = entry.variants.iter().map(|variant| variant.quant.as_str()).collect();
+                Err(unknown(format!(
+                    "Model '{}' has no {} variant; it has {}",
+                    entry.id,
+                    quant,
+                    quants.join(", ")
+                )))
+            }
+        };
+    }
+
+    let loaded_bytes = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .filter(|m| m.registry_entry.loaded)
+        .map(|m| m.registry_entry.size_bytes)
+        .sum();
+    let Some(available) = available_memory(state.memory_budget, loaded_bytes).await else {
+        // With nothing to go by, the largest variant, which loses the least.
+        return Ok(entry.variants.iter().max_by_key(|variant| variant.size_bytes));
+    };
+    entry
+        .variants
+        .iter()
+        .filter(|variant| variant.size_bytes <= available)
+        .max_by_key(|variant| variant.size_bytes)
+        .map(Some)
+        .ok_or_else(|| {
+            ApiError::Unavailable(format!(
+                "No quant variant of '{}' fits in the {} bytes of memory available",
+                entry.id, available
+            ))
+        })
+}
+
+/// Memory free for another model: what `--memory-budget` leaves after the
+/// models already loaded or, without a budget, what the system reports
+/// available. `None` when neither is known.
+async fn available_memory(budget: Option<u64>, loaded_bytes: u64) -> Option<u64> {
+    if let Some(budget) = budget {
+        return Some(budget.saturating_sub(loaded_bytes));
+    }
+    // Linux only.
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo").await.ok()?;
+    let kib: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+/// Lists the models the caller can see. The response carries an `ETag` that
+/// changes with the registry; a matching `If-None-Match` gets an empty `304`.
+#[utoipa::path(
+    get,
+    path = "/v1/models",
+    tag = "models",
+    responses(
+        (status = 200, body = ModelListResponse),
+        (status = 304, description = "The registry has not changed since the tag in If-None-Match"),
+    )
+)]
+pub async fn list_models(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+) -> Response {
     let models = state.models.lock().await;
-    let model_entries: Vec<ModelRegistryEntry> = models.iter().map(|m| m.registry_entry.clone()).collect();
+    let etag = state.registry_revision.etag(caller.tenant.as_deref());
+    let cache_headers = [(header::ETAG, etag.clone()), (header::CACHE_CONTROL, "private, no-cache".to_string())];
+    if etag::matches(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+
+    let model_entries: Vec<ModelRegistryEntry> = models
+        .iter()
+        .filter(|m| m.registry_entry.visible_to(&caller))
+        .map(|m| m.registry_entry.clone())
+        .collect();
 
-    (StatusCode::OK, Json(ModelListResponse { models: model_entries }))
+    (StatusCode::OK, cache_headers, Json(ModelListResponse { models: model_entries })).into_response()
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/models/register",
+    tag = "models",
+    request_body = RegisterModelRequest,
+    responses(
+        (status = 201, body = RegisterModelResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
 pub async fn register_model(
     State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
     Json(req): Json<RegisterModelRequest>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ApiError> {
+    let (registry_entry, metadata, warnings) = prepare(&state, &caller, &req).await?;
+
     let mut models = state.models.lock().await;
+    insert(&mut models, &caller, &registry_entry, req.speculative.as_ref())?;
+    state.registry_revision.bump();
 
-    if models.iter().any(|m| m.registry_entry.id == req.id) {
-        return (
-            StatusCode::CONFLICT,
-            Json(RegisterModelResponse {
-                success: false,
-                model: ModelRegistryEntry {
-                    id: req.id.clone(),
-                    name: req.name.clone(),
-                    inference: req.inference.clone(),
-                    context: req.context,
-                    quant: req.quant.clone(),
-                    capabilities: req.capabilities.clone(),
-                    latency: req.latency.clone(),
-                    size_bytes: req.size_bytes,
-                    loaded: false,
-                    loaded_at: None,
+    Ok((
+        StatusCode::CREATED,
+        Json(RegisterModelResponse {
+            success: true,
+            model: registry_entry,
+            message: "Model registered successfully".to_string(),
+            metadata,
+            warnings,
+        }),
+    ))
+}
+
+/// Registers several models in one request. Each is checked, and asked of
+/// its backend, independently and at the same time; they are then
+/// registered in request order, so a draft model can be listed before the
+/// models that use it. One model failing does not stop the others.
+#[utoipa::path(
+    post,
+    path = "/v1/models/register/bulk",
+    tag = "models",
+    request_body = BulkRegisterRequest,
+    responses(
+        (status = 200, body = BulkRegisterResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn register_models(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<BulkRegisterRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.models.is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "models must not be empty".to_string(),
+            param: Some("models"),
+        });
+    }
+
+    let prepared = join_all(req.models.iter().map(|model| prepare(&state, &caller, model))).await;
+
+    let mut models = state.models.lock().await;
+    let results: Vec<BulkRegisterResult> = req
+        .models
+        .iter()
+        .zip(prepared)
+        .map(|(model, prepared)| {
+            let registered = prepared.and_then(|(entry, metadata, warnings)| {
+                insert(&mut models, &caller, &entry, model.speculative.as_ref())?;
+                Ok((entry, metadata, warnings))
+            });
+            match registered {
+                Ok((entry, metadata, warnings)) => BulkRegisterResult {
+                    id: model.id.clone(),
+                    status: StatusCode::CREATED.as_u16(),
+                    model: Some(entry),
+                    metadata,
+                    warnings,
+                    error: None,
                 },
-                message: "Model with this ID already registered".to_string(),
-            }),
-        );
+                Err(e) => BulkRegisterResult {
+                    id: model.id.clone(),
+                    status: e.status().as_u16(),
+                    model: None,
+                    metadata: None,
+                    warnings: Vec::new(),
+                    error: Some(ErrorObject::for_request(&e, &request_id)),
+                },
+            }
+        })
+        .collect();
+    let registered = results.iter().filter(|result| result.model.is_some()).count();
+    if registered > 0 {
+        state.registry_revision.bump();
     }
+    drop(models);
+
+    Ok((
+        StatusCode::OK,
+        Json(BulkRegisterResponse {
+            registered,
+            failed: results.len() - registered,
+            results,
+        }),
+    ))
+}
 
-    let registry_entry = ModelRegistryEntry {
+/// Builds the registry entry for a registration request and checks it:
+/// its settings, then what its backend reports when discovery or
+/// validation asks. Returns the entry, the backend's metadata and any
+/// warnings.
+async fn prepare(
+    state: &AppState,
+    caller: &Caller,
+    req: &RegisterModelRequest,
+) -> Result<(ModelRegistryEntry, Option<ModelMetadata>, Vec<String>), ApiError> {
+    let mut registry_entry = ModelRegistryEntry {
         id: req.id.clone(),
         name: req.name.clone(),
         inference: req.inference.clone(),
-        context: req.context,
+        context: req.context.unwrap_or_default(),
         quant: req.quant.clone(),
+        variants: req.variants.clone(),
         capabilities: req.capabilities.clone(),
         latency: req.latency.clone(),
-        size_bytes: req.size_bytes,
+        latency_pinned: req.latency.is_some(),
+        measured_latency: None,
+        pricing: req.pricing.clone(),
+        chat_template: req.chat_template.clone(),
+        post_process: req.post_process.clone(),
+        speculative: req.speculative.clone(),
+        max_concurrency: req.max_concurrency,
+        size_bytes: req.size_bytes.unwrap_or_else(default_size_bytes),
         loaded: false,
         loaded_at: None,
+        placement: None,
+        benchmark: None,
+        tenant: caller.tenant.clone(),
     };
 
+    check_entry(&registry_entry)?;
+    if state.pools.lock().await.contains(&req.id) {
+        return Err(ApiError::Conflict(format!("A pool is named '{}'", req.id)));
+    }
+
+    let mut warnings = Vec::new();
+    let metadata = match req.discover || req.context.is_none() {
+        true => model_metadata::discover(state, &registry_entry).await.map_err(|e| {
+            ApiError::Backend(format!("Could not read the metadata of model '{}': {}", req.id, e))
+        })?,
+        false => None,
+    };
+    if let Some(metadata) = &metadata {
+        apply_metadata(&mut registry_entry, req, metadata, &mut warnings);
+    }
+    if registry_entry.context == 0 {
+        let message = match req.context {
+            Some(_) => "context must be at least 1".to_string(),
+            None => format!("context is required; {:?} does not report it for '{}'", req.inference, req.id),
+        };
+        return Err(ApiError::InvalidRequest {
+            message,
+            param: Some("context"),
+        });
+    }
+
+    if req.validate != Validation::Off {
+        warnings.extend(validate(state, &registry_entry, req.validate == Validation::Strict).await?);
+    }
+
+    Ok((registry_entry, metadata, warnings))
+}
+
+/// Adds a prepared entry to the registry, unless its id is taken or its
+/// draft model is not registered.
+fn insert(
+    models: &mut Vec<LoadedModel>,
+    caller: &Caller,
+    entry: &ModelRegistryEntry,
+    speculative: Option<&SpeculativeDecoding>,
+) -> Result<(), ApiError> {
+    if models.iter().any(|m| m.registry_entry.id == entry.id) {
+        return Err(ApiError::Conflict(format!("Model '{}' is already registered", entry.id)));
+    }
+
+    if let Some(speculative) = speculative {
+        check_draft_model(models, caller, entry, speculative)?;
+    }
+
     models.push(LoadedModel {
-        registry_entry: registry_entry.clone(),
+        registry_entry: entry.clone(),
         last_accessed: SystemTime::now(),
     });
-
-    (
-        StatusCode::CREATED,
-        Json(RegisterModelResponse {
-            success: true,
-            model: registry_entry,
-            message: "Model registered successfully".to_string(),
-        }),
-    )
+    Ok(())
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/models/load",
+    tag = "models",
+    request_body = LoadModelRequest,
+    responses(
+        (status = 200, body = LoadModelResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
 pub async fn load_model(
     State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
     Json(req): Json<LoadModelRequest>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ApiError> {
+    let quant = load(&state, &caller, &req.model_id, req.quant.as_deref(), req.placement).await?;
+    Ok((
+        StatusCode::OK,
+        Json(LoadModelResponse {
+            success: true,
+            model_id: req.model_id,
+            quant,
+            message: "Model loaded successfully".to_string(),
+        }),
+    ))
+}
+
+/// Loads a registered model, picking its quant variant, and returns the
+/// quant it was loaded with. Used by the load endpoint and the lifecycle
+/// scheduler.
+pub(crate) async fn load(
+    state: &AppState,
+    caller: &Caller,
+    model_id: &str,
+    quant: Option<&str>,
+    placement: Placement,
+) -> Result<Option<String>, ApiError> {
+    let entry = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.clone());
+
+    let Some(entry) = entry else {
+        return Err(ApiError::ModelNotFound(model_id.to_string()));
+    };
+    if entry.loaded {
+        return Err(ApiError::Conflict(format!("Model '{}' is already loaded", model_id)));
+    }
+
+    let placement = (!placement.is_empty()).then_some(placement);
+    if placement.is_some() && !matches!(entry.inference, InferenceBackend::Ollama | InferenceBackend::LlamaLocal) {
+        return Err(ApiError::unprocessable(format!(
+            "Placement hints are only supported on the ollama and llama_local backends, not {:?}",
+            entry.inference
+        )));
+    }
+
+    let variant = choose_variant(state, &entry, quant).await?;
+    let entry = ModelRegistryEntry {
+        quant: variant.map(|variant| variant.quant.clone()).or(entry.quant.clone()),
+        size_bytes: variant.map_or(entry.size_bytes, |variant| variant.size_bytes),
+        placement,
+        ..entry.clone()
+    };
+
+    // The backend may take a while, so the registry is not held meanwhile.
+    sync_backend(state, &entry, true).await.map_err(ApiError::Backend)?;
+    state.latency.lock().await.forget(&entry.id);
+
+    mark_loaded(state, caller, &entry).await?;
+    Ok(entry.quant)
+}
+
+/// Records a model as loaded with the quant and placement of `entry`.
+async fn mark_loaded(state: &AppState, caller: &Caller, entry: &ModelRegistryEntry) -> Result<(), ApiError> {
     let mut models = state.models.lock().await;
 
-    if let Some(model) = models.iter_mut().find(|m| m.registry_entry.id == req.model_id) {
-        if model.registry_entry.loaded {
-            return (
-                StatusCode::CONFLICT,
-                Json(LoadModelResponse {
-                    success: false,
-                    model_id: req.model_id,
-                    message: "Model already loaded".to_string(),
-                }),
-            );
-        }
+    let Some(model) = models
+        .iter_mut()
+        .find(|m| m.registry_entry.id == entry.id && m.registry_entry.visible_to(caller))
+    else {
+        return Err(ApiError::ModelNotFound(entry.id.clone()));
+    };
+    model.registry_entry.loaded = true;
+    model.registry_entry.loaded_at = Some(Utc::now());
+    model.registry_entry.quant = entry.quant.clone();
+    model.registry_entry.size_bytes = entry.size_bytes;
+    model.registry_entry.placement = entry.placement.clone();
+    model.last_accessed = SystemTime::now();
+    state.registry_revision.bump();
+    Ok(())
+}
 
-        model.registry_entry.loaded = true;
-        model.registry_entry.loaded_at = Some(Utc::now());
-        model.last_accessed = SystemTime::now();
+/// Brings a model that was loaded before a restart back to loaded, with the
+/// quant and placement it had. Backends that keep models in memory on their
+/// own are asked first, and a model they still hold is not loaded again.
+/// Returns whether the backend still held it.
+pub(crate) async fn restore(state: &AppState, caller: &Caller, saved: &ModelRegistryEntry) -> Result<bool, ApiError> {
+    let client = state.backend_clients.get(&saved.inference);
+    let url = get_backend_url(&saved.inference);
+    let held = match saved.inference {
+        InferenceBackend::LmStudio => lmstudio::is_loaded(&client, &url, saved.backend_model()).await,
+        InferenceBackend::Ollama => ollama::is_running(&client, &url, saved.backend_model()).await,
+        _ => Ok(false),
+    }
+    .map_err(ApiError::Backend)?;
 
-        return (
-            StatusCode::OK,
-            Json(LoadModelResponse {
-                success: true,
-                model_id: req.model_id,
-                message: "Model loaded successfully".to_string(),
-            }),
-        );
+    if held {
+        mark_loaded(state, caller, saved).await?;
+    } else {
+        let placement = saved.placement.clone().unwrap_or_default();
+        load(state, caller, &saved.id, saved.quant.as_deref(), placement).await?;
     }
+    Ok(held)
+}
 
-    (
-        StatusCode::NOT_FOUND,
-        Json(LoadModelResponse {
-            success: false,
-            model_id: req.model_id,
-            message: "Model not found in registry".to_string(),
-        }),
-    )
+/// Drops what the engine measured and cached for a model that is removed
+/// from the registry or replaced.
+pub(crate) async fn forget(state: &AppState, entry: &ModelRegistryEntry) {
+    state.latency.lock().await.forget(&entry.id);
+    state.model_concurrency.lock().await.forget(&entry.id);
+    if let Some(cache) = &state.embedding_cache {
+        cache.lock().await.forget(&entry.id);
+    }
+    // In-process weights would otherwise stay in memory with no way to
+    // unload them.
+    if entry.inference == InferenceBackend::Candle {
+        candle::unload(entry.backend_model());
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/models/unload/{model_id}",
+    tag = "models",
+    params(("model_id" = String, Path)),
+    responses(
+        (status = 200, body = UnloadModelResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
 pub async fn unload_model(
     State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
     axum::extract::Path(model_id): axum::extract::Path<String>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ApiError> {
+    unload(&state, &caller, &model_id).await?;
+    Ok((
+        StatusCode::OK,
+        Json(UnloadModelResponse {
+            success: true,
+            model_id,
+            message: "Model unloaded successfully".to_string(),
+        }),
+    ))
+}
+
+/// Unloads a registered model. Used by the unload endpoint and the
+/// lifecycle scheduler.
+pub(crate) async fn unload(state: &AppState, caller: &Caller, model_id: &str) -> Result<(), ApiError> {
+    let entry = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(caller))
+        .map(|m| m.registry_entry.clone());
+
+    if let Some(entry) = &entry {
+        sync_backend(state, entry, false).await.map_err(ApiError::Backend)?;
+    }
+
     let mut models = state.models.lock().await;
 
-    if let Some(model) = models.iter_mut().find(|m| m.registry_entry.id == model_id) {
-        model.registry_entry.loaded = false;
-        model.registry_entry.loaded_at = None;
+    let Some(model) = models
+        .iter_mut()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(caller))
+    else {
+        return Err(ApiError::ModelNotFound(model_id.to_string()));
+    };
+    model.registry_entry.loaded = false;
+    model.registry_entry.loaded_at = None;
+    model.registry_entry.placement = None;
+    state.registry_revision.bump();
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/models/{model_id}",
+    tag = "models",
+    params(("model_id" = String, Path)),
+    responses(
+        (status = 200, body = UnregisterModelResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn unregister_model(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    axum::extract::Path(model_id): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut models = state.models.lock().await;
 
-        return (
+    if let Some(index) = models
+        .iter()
+        .position(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(&caller))
+    {
+        let removed = models.remove(index);
+        state.registry_revision.bump();
+        forget(&state, &removed.registry_entry).await;
+
+        return Ok((
             StatusCode::OK,
-            Json(UnloadModelResponse {
+            Json(UnregisterModelResponse {
                 success: true,
                 model_id,
-                message: "Model unloaded successfully".to_string(),
+                message: "Model removed from registry".to_string(),
             }),
-        );
+        ));
     }
 
-    (
-        StatusCode::NOT_FOUND,
-        Json(UnloadModelResponse {
-            success: false,
-            model_id,
-            message: "Model not found in registry".to_string(),
-        }),
-    )
+    Err(ApiError::ModelNotFound(model_id))
 }
@@ -0,0 +1,194 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::pipeline::{self, MAX_STEPS};
+use super::super::postprocess::PostProcessor;
+use super::super::request_id::RequestId;
+use super::super::AppState;
+use super::inference::{complete_request, FinishReason, InferenceRequest, InferenceTimings};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PipelineRequest {
+    /// Variables every step's template can use, e.g. the user's question.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub inputs: Map<String, Value>,
+    /// Run in order, up to 16.
+    pub steps: Vec<PipelineStep>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PipelineStep {
+    pub model_id: String,
+    /// A Jinja template for the step's prompt. It sees the inputs and the
+    /// outputs of earlier steps by name.
+    pub template: String,
+    /// The variable the step's output is stored in.
+    pub output: String,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Replaces the model's post-processing, e.g. to `extract_json` before
+    /// the output is passed on.
+    #[serde(default)]
+    pub post_process: Option<Vec<PostProcessor>>,
+}
+
+fn default_max_tokens() -> u32 {
+    512
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PipelineStepResult {
+    /// The variable the step's output was stored in.
+    pub output: String,
+    pub model_id: String,
+    /// The prompt the template rendered.
+    pub prompt: String,
+    pub text: String,
+    pub tokens_generated: u32,
+    pub finish_reason: FinishReason,
+    pub timings: InferenceTimings,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PipelineResponse {
+    /// The last step's output.
+    pub output: String,
+    /// The inputs and every step's output.
+    #[schema(value_type = Object)]
+    pub variables: Map<String, Value>,
+    pub steps: Vec<PipelineStepResult>,
+    pub tokens_generated: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+    pub total_ms: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/pipelines/run",
+    tag = "inference",
+    request_body = PipelineRequest,
+    responses(
+        (status = 200, body = PipelineResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn run_pipeline(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<PipelineRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate(&req)?;
+
+    let started = Instant::now();
+    let mut variables = req.inputs;
+    let mut steps = Vec::with_capacity(req.steps.len());
+    let mut tokens_generated = 0;
+    let mut estimated_cost_usd = None;
+
+    for (i, step) in req.steps.into_iter().enumerate() {
+        let prompt = pipeline::render(&step.template, &variables).map_err(|e| ApiError::Unprocessable {
+            message: format!("Step {} ('{}'): {}", i + 1, step.output, e),
+            param: Some("steps"),
+        })?;
+        let response = complete_request(
+            state.clone(),
+            caller.clone(),
+            request_id.clone(),
+            InferenceRequest {
+                temperature: step.temperature,
+                post_process: step.post_process,
+                ..InferenceRequest::prompt(&step.model_id, prompt.clone(), step.max_tokens)
+            },
+        )
+        .await
+        .inspect_err(|e| tracing::warn!("Pipeline step {} ('{}') failed: {}", i + 1, step.output, e))?;
+
+        tokens_generated += response.tokens_generated;
+        if let Some(cost) = response.estimated_cost_usd {
+            *estimated_cost_usd.get_or_insert(0.0) += cost;
+        }
+        variables.insert(step.output.clone(), Value::from(response.text.as_str()));
+        steps.push(PipelineStepResult {
+            output: step.output,
+            model_id: step.model_id,
+            prompt,
+            text: response.text,
+            tokens_generated: response.tokens_generated,
+            finish_reason: response.finish_reason,
+            timings: response.timings,
+        });
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(PipelineResponse {
+            output: steps.last().map(|step| step.text.clone()).unwrap_or_default(),
+            variables,
+            steps,
+            tokens_generated,
+            estimated_cost_usd,
+            total_ms: started.elapsed().as_millis() as u64,
+        }),
+    ))
+}
+
+/// Rejects pipelines that could not run to the end before any step runs:
+/// bad variable names, outputs that overwrite each other or an input,
+/// templates that do not compile, and templates that read a variable no
+/// input or earlier step provides.
+fn validate(req: &PipelineRequest) -> Result<(), ApiError> {
+    let invalid = |message: String| ApiError::InvalidRequest {
+        message,
+        param: Some("steps"),
+    };
+    if req.steps.is_empty() || req.steps.len() > MAX_STEPS {
+        return Err(invalid(format!("steps must have between 1 and {} steps", MAX_STEPS)));
+    }
+
+    let mut names: HashSet<&str> = req.inputs.keys().map(String::as_str).collect();
+    for (i, step) in req.steps.iter().enumerate() {
+        if !pipeline::valid_variable(&step.output) {
+            return Err(invalid(format!(
+                "Step {}: output '{}' must be a name of letters, digits and '_', not starting with a digit",
+                i + 1,
+                step.output
+            )));
+        }
+        let used = pipeline::compile(&step.template).map_err(|e| ApiError::Unprocessable {
+            message: format!("Step {} ('{}'): {}", i + 1, step.output, e),
+            param: Some("steps"),
+        })?;
+        if let Some(unknown) = used.iter().find(|name| !names.contains(name.as_str())) {
+            return Err(invalid(format!(
+                "Step {}: template uses '{}', which is not an input or an earlier step's output",
+                i + 1,
+                unknown
+            )));
+        }
+        if !names.insert(&step.output) {
+            return Err(invalid(format!(
+                "Step {}: output '{}' is already an input or an earlier step's output",
+                i + 1,
+                step.output
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::pools::ModelPool;
+use super::super::AppState;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePoolRequest {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Registered model ids, e.g. `["phi3", "llama3-8b", "mistral-7b"]`.
+    pub members: Vec<String>,
+    /// Shares of the requests by member; unlisted members weigh 1.
+    #[serde(default)]
+    pub weights: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdatePoolRequest {
+    /// Replaces the description; kept when unset.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Replaces the members; kept when unset. Weights of members left out
+    /// are dropped.
+    #[serde(default)]
+    pub members: Option<Vec<String>>,
+    /// Replaces the weights; kept when unset.
+    #[serde(default)]
+    pub weights: Option<BTreeMap<String, u32>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddPoolMemberRequest {
+    pub model_id: String,
+    #[serde(default)]
+    pub weight: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetPoolWeightRequest {
+    pub weight: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PoolListResponse {
+    pub pools: Vec<ModelPool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/pools",
+    tag = "pools",
+    responses((status = 200, body = PoolListResponse))
+)]
+pub async fn list_pools(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let pools = state.pools.lock().await;
+    let pools = pools
+        .list()
+        .iter()
+        .filter(|p| caller.can_see(p.tenant.as_deref()))
+        .cloned()
+        .collect();
+
+    (StatusCode::OK, Json(PoolListResponse { pools }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/pools",
+    tag = "pools",
+    request_body = CreatePoolRequest,
+    responses(
+        (status = 201, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn create_pool(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(req): Json<CreatePoolRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.id.is_empty()
+        || req.id.len() > 128
+        || !req.id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+    {
+        return Err(ApiError::InvalidRequest {
+            message: "id must be 1 to 128 letters, digits, '_', '-', '.' or ':'".to_string(),
+            param: Some("id"),
+        });
+    }
+    check_members(&state, &caller, &req.members).await?;
+    check_weights(&req.members, &req.weights)?;
+    if state.models.lock().await.iter().any(|m| m.registry_entry.id == req.id) {
+        return Err(ApiError::Conflict(format!("A model is registered as '{}'", req.id)));
+    }
+
+    let mut pools = state.pools.lock().await;
+    if pools.contains(&req.id) {
+        return Err(ApiError::Conflict(format!("Pool '{}' already exists", req.id)));
+    }
+    let now = Utc::now();
+    let pool = ModelPool {
+        id: req.id,
+        description: req.description,
+        members: req.members,
+        weights: req.weights,
+        tenant: caller.tenant.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+    pools
+        .insert(pool.clone())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pool: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(pool)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/pools/{pool_id}",
+    tag = "pools",
+    params(("pool_id" = String, Path)),
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn get_pool(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(pool_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pools = state.pools.lock().await;
+    let pool = pools.get(&pool_id, &caller).cloned().ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/pools/{pool_id}",
+    tag = "pools",
+    params(("pool_id" = String, Path)),
+    request_body = UpdatePoolRequest,
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn update_pool(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(pool_id): Path<String>,
+    Json(req): Json<UpdatePoolRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(members) = &req.members {
+        check_members(&state, &caller, members).await?;
+    }
+    let mut pools = state.pools.lock().await;
+    let pool = pools.get(&pool_id, &caller).ok_or_else(|| not_found(&pool_id))?;
+    let members = req.members.unwrap_or_else(|| pool.members.clone());
+    let weights = req.weights.unwrap_or_else(|| {
+        let mut weights = pool.weights.clone();
+        weights.retain(|member, _| members.contains(member));
+        weights
+    });
+    check_weights(&members, &weights)?;
+    let pool = pools
+        .update(&pool_id, &caller, |pool| {
+            if let Some(description) = req.description {
+                pool.description = Some(description);
+            }
+            pool.members = members;
+            pool.weights = weights;
+        })
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pool: {}", e)))?
+        .ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/pools/{pool_id}",
+    tag = "pools",
+    params(("pool_id" = String, Path)),
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn delete_pool(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(pool_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pool = state
+        .pools
+        .lock()
+        .await
+        .remove(&pool_id, &caller)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pools: {}", e)))?
+        .ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+/// Adds a model to a pool. Adding a member again only sets its weight, if
+/// given.
+#[utoipa::path(
+    post,
+    path = "/v1/pools/{pool_id}/members",
+    tag = "pools",
+    params(("pool_id" = String, Path)),
+    request_body = AddPoolMemberRequest,
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn add_pool_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(pool_id): Path<String>,
+    Json(req): Json<AddPoolMemberRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_members(&state, &caller, std::slice::from_ref(&req.model_id)).await?;
+    let pool = state
+        .pools
+        .lock()
+        .await
+        .update(&pool_id, &caller, |pool| {
+            if let Some(weight) = req.weight {
+                pool.weights.insert(req.model_id.clone(), weight);
+            }
+            if !pool.members.contains(&req.model_id) {
+                pool.members.push(req.model_id);
+            }
+        })
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pool: {}", e)))?
+        .ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+/// Takes a model out of a pool. Its requests in flight finish on it.
+#[utoipa::path(
+    delete,
+    path = "/v1/pools/{pool_id}/members/{model_id}",
+    tag = "pools",
+    params(("pool_id" = String, Path), ("model_id" = String, Path)),
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn remove_pool_member(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((pool_id, model_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut pools = state.pools.lock().await;
+    let pool = pools.get(&pool_id, &caller).ok_or_else(|| not_found(&pool_id))?;
+    if !pool.members.contains(&model_id) {
+        return Err(ApiError::NotFound(format!(
+            "Model '{}' is not in pool '{}'",
+            model_id, pool_id
+        )));
+    }
+    if pool.members.len() == 1 {
+        return Err(ApiError::Conflict(format!(
+            "Model '{}' is the last member of pool '{}'; delete the pool instead",
+            model_id, pool_id
+        )));
+    }
+    let pool = pools
+        .update(&pool_id, &caller, |pool| {
+            pool.members.retain(|member| *member != model_id);
+            pool.weights.remove(&model_id);
+        })
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pool: {}", e)))?
+        .ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+/// Sets a member's weight, taking effect from the pool's next request.
+#[utoipa::path(
+    put,
+    path = "/v1/pools/{pool_id}/members/{model_id}",
+    tag = "pools",
+    params(("pool_id" = String, Path), ("model_id" = String, Path)),
+    request_body = SetPoolWeightRequest,
+    responses(
+        (status = 200, body = ModelPool),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn set_pool_member_weight(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((pool_id, model_id)): Path<(String, String)>,
+    Json(req): Json<SetPoolWeightRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut pools = state.pools.lock().await;
+    let pool = pools.get(&pool_id, &caller).ok_or_else(|| not_found(&pool_id))?;
+    if !pool.members.contains(&model_id) {
+        return Err(ApiError::NotFound(format!(
+            "Model '{}' is not in pool '{}'",
+            model_id, pool_id
+        )));
+    }
+    let pool = pools
+        .update(&pool_id, &caller, |pool| {
+            pool.weights.insert(model_id, req.weight);
+        })
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store pool: {}", e)))?
+        .ok_or_else(|| not_found(&pool_id))?;
+    Ok((StatusCode::OK, Json(pool)))
+}
+
+fn not_found(pool_id: &str) -> ApiError {
+    ApiError::NotFound(format!("Pool '{}' not found", pool_id))
+}
+
+/// Checks that weights are only given for members.
+fn check_weights(members: &[String], weights: &BTreeMap<String, u32>) -> Result<(), ApiError> {
+    match weights.keys().find(|member| !members.contains(member)) {
+        Some(member) => Err(ApiError::InvalidRequest {
+            message: format!("Model '{}' has a weight but is not a member", member),
+            param: Some("weights"),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that a pool's members are registered models the caller can see,
+/// each listed once.
+async fn check_members(state: &AppState, caller: &Caller, members: &[String]) -> Result<(), ApiError> {
+    if members.is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "A pool needs at least one member".to_string(),
+            param: Some("members"),
+        });
+    }
+    let models = state.models.lock().await;
+    for (i, member) in members.iter().enumerate() {
+        if members[..i].contains(member) {
+            return Err(ApiError::InvalidRequest {
+                message: format!("Model '{}' is listed twice", member),
+                param: Some("members"),
+            });
+        }
+        if !models
+            .iter()
+            .any(|m| m.registry_entry.id == *member && m.registry_entry.visible_to(caller))
+        {
+            return Err(ApiError::Unprocessable {
+                message: format!("Model '{}' is not registered", member),
+                param: Some("members"),
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::postprocess;
+use super::super::prompts::{PromptDefaults, PromptVersion, StoredPrompt};
+use super::super::templates;
+use super::super::AppState;
+use super::inference::{ChatMessage, InferenceRequest};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PromptVersionRequest {
+    /// A prompt template, e.g. `Summarize: {{ document }}`.
+    #[serde(default)]
+    pub prompt: String,
+    /// Chat messages whose contents are templates, instead of `prompt`.
+    #[serde(default)]
+    pub messages: Option<Vec<ChatMessage>>,
+    /// The model requests go to unless they name one.
+    #[serde(default)]
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub defaults: PromptDefaults,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreatePromptRequest {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub version: PromptVersionRequest,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdatePromptRequest {
+    /// Replaces the description; kept when unset.
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub version: PromptVersionRequest,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PromptListResponse {
+    pub prompts: Vec<StoredPrompt>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/prompts",
+    tag = "prompts",
+    responses((status = 200, body = PromptListResponse))
+)]
+pub async fn list_prompts(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let prompts = state.prompts.lock().await;
+    let prompts = prompts
+        .list()
+        .iter()
+        .filter(|p| caller.can_see(p.tenant.as_deref()))
+        .cloned()
+        .collect();
+
+    (StatusCode::OK, Json(PromptListResponse { prompts }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/prompts",
+    tag = "prompts",
+    request_body = CreatePromptRequest,
+    responses(
+        (status = 201, body = StoredPrompt),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn create_prompt(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(req): Json<CreatePromptRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.id.is_empty()
+        || req.id.len() > 128
+        || !req.id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(ApiError::InvalidRequest {
+            message: "id must be 1 to 128 letters, digits, '_', '-' or '.'".to_string(),
+            param: Some("id"),
+        });
+    }
+    let version = check_version(req.version)?;
+
+    let mut prompts = state.prompts.lock().await;
+    if prompts.contains(&req.id) {
+        return Err(ApiError::Conflict(format!("Prompt '{}' already exists", req.id)));
+    }
+    let prompt = StoredPrompt {
+        id: req.id,
+        description: req.description,
+        tenant: caller.tenant.clone(),
+        created_at: version.created_at,
+        updated_at: version.created_at,
+        versions: vec![version],
+    };
+    prompts
+        .insert(prompt.clone())
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store prompt: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(prompt)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/prompts/{prompt_id}",
+    tag = "prompts",
+    params(("prompt_id" = String, Path)),
+    responses(
+        (status = 200, body = StoredPrompt),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn get_prompt(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(prompt_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let prompts = state.prompts.lock().await;
+    let prompt = prompts.get(&prompt_id, &caller).cloned().ok_or_else(|| not_found(&prompt_id))?;
+    Ok((StatusCode::OK, Json(prompt)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/prompts/{prompt_id}",
+    tag = "prompts",
+    params(("prompt_id" = String, Path)),
+    request_body = UpdatePromptRequest,
+    responses(
+        (status = 200, body = StoredPrompt),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn update_prompt(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(prompt_id): Path<String>,
+    Json(req): Json<UpdatePromptRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let version = check_version(req.version)?;
+    let prompt = state
+        .prompts
+        .lock()
+        .await
+        .add_version(&prompt_id, &caller, req.description, version)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store prompt: {}", e)))?
+        .ok_or_else(|| not_found(&prompt_id))?;
+    Ok((StatusCode::OK, Json(prompt)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/prompts/{prompt_id}",
+    tag = "prompts",
+    params(("prompt_id" = String, Path)),
+    responses(
+        (status = 200, body = StoredPrompt),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn delete_prompt(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(prompt_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let prompt = state
+        .prompts
+        .lock()
+        .await
+        .remove(&prompt_id, &caller)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store prompts: {}", e)))?
+        .ok_or_else(|| not_found(&prompt_id))?;
+    Ok((StatusCode::OK, Json(prompt)))
+}
+
+fn not_found(prompt_id: &str) -> ApiError {
+    ApiError::NotFound(format!("Prompt '{}' not found", prompt_id))
+}
+
+/// Checks a new version the way a request using it would be checked, so a
+/// broken template is rejected when it is saved rather than when it is used.
+fn check_version(req: PromptVersionRequest) -> Result<PromptVersion, ApiError> {
+    let templates: Vec<(&str, &'static str)> = match &req.messages {
+        Some(messages) if req.prompt.is_empty() && !messages.is_empty() => {
+            messages.iter().map(|m| (m.content.as_str(), "messages")).collect()
+        }
+        None if !req.prompt.is_empty() => vec![(req.prompt.as_str(), "prompt")],
+        _ => {
+            return Err(ApiError::InvalidRequest {
+                message: "A prompt needs either a prompt or messages".to_string(),
+                param: Some("prompt"),
+            });
+        }
+    };
+    for (template, param) in templates {
+        templates::template_variables(template).map_err(|e| ApiError::Unprocessable {
+            message: format!("Invalid prompt template: {}", e),
+            param: Some(param),
+        })?;
+    }
+    if let Some(processors) = &req.defaults.post_process {
+        postprocess::validate(processors).map_err(|message| ApiError::InvalidRequest {
+            message,
+            param: Some("defaults"),
+        })?;
+    }
+
+    Ok(PromptVersion {
+        version: 1,
+        prompt: req.prompt,
+        messages: req.messages,
+        model_id: req.model_id,
+        defaults: req.defaults,
+        created_at: Utc::now(),
+    })
+}
+
+/// Fills a request that references a stored prompt from the prompt's
+/// version: its template, its model unless the request names one, and its
+/// defaults for settings the request leaves unset.
+pub(super) async fn apply_prompt(state: &AppState, caller: &Caller, req: &mut InferenceRequest) -> Result<(), ApiError> {
+    if let Some(prompt_id) = req.prompt_id.take() {
+        if !req.prompt.is_empty() || req.messages.is_some() {
+            return Err(ApiError::InvalidRequest {
+                message: "Send either prompt_id or a prompt, not both".to_string(),
+                param: Some("prompt_id"),
+            });
+        }
+        let prompts = state.prompts.lock().await;
+        let prompt = prompts.get(&prompt_id, caller).ok_or_else(|| not_found(&prompt_id))?;
+        let version = prompt.version(req.prompt_version).cloned().ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "Prompt '{}' has no version {}",
+                prompt_id,
+                req.prompt_version.unwrap_or_default()
+            ))
+        })?;
+        drop(prompts);
+
+        req.prompt = version.prompt;
+        req.messages = version.messages;
+        if req.model_id.is_empty() {
+            req.model_id = version.model_id.unwrap_or_default();
+        }
+        let defaults = version.defaults;
+        req.max_tokens = req.max_tokens.or(defaults.max_tokens);
+        req.temperature = req.temperature.or(defaults.temperature);
+        req.variable_escape = req.variable_escape.or(defaults.variable_escape);
+        req.post_process = req.post_process.take().or(defaults.post_process);
+        let mut variables = defaults.variables.unwrap_or_default();
+        variables.extend(req.variables.take().unwrap_or_default());
+        // Stored prompts are always rendered, so a missing variable is
+        // reported rather than sent as a literal placeholder.
+        req.variables = Some(variables);
+    } else if req.prompt_version.is_some() {
+        return Err(ApiError::InvalidRequest {
+            message: "prompt_version needs a prompt_id".to_string(),
+            param: Some("prompt_version"),
+        });
+    }
+
+    if req.model_id.is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "model_id is required".to_string(),
+            param: Some("model_id"),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::rag::{self, Source};
+use super::super::request_id::RequestId;
+use super::super::AppState;
+use super::inference::{complete_request, FinishReason, InferenceRequest, InferenceTimings};
+use super::vectors::{default_top_k, search, SearchVectorsRequest};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RagQueryRequest {
+    /// The model that writes the answer.
+    pub model_id: String,
+    /// The embedding model the collection was filled with.
+    pub embedding_model_id: String,
+    pub collection: String,
+    pub query: String,
+    /// Chunks to retrieve, 1 to 100.
+    #[serde(default = "default_top_k")]
+    pub top_k: u32,
+    /// Only chunks whose metadata has all of these key-value pairs.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub filter: Map<String, Value>,
+    /// Leaves out chunks less similar to the query than this.
+    #[serde(default)]
+    pub min_score: Option<f64>,
+    /// A Jinja template for the prompt, given `question` and `sources`.
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+fn default_max_tokens() -> u32 {
+    512
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RagSource {
+    /// The number the prompt gave the chunk, which the answer cites as `[n]`.
+    pub number: usize,
+    pub id: String,
+    pub score: f64,
+    pub text: String,
+    #[schema(value_type = Object)]
+    pub metadata: Map<String, Value>,
+    /// Whether the answer cites the chunk.
+    pub cited: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RagQueryResponse {
+    pub model_id: String,
+    pub answer: String,
+    pub finish_reason: FinishReason,
+    /// The retrieved chunks, closest first.
+    pub sources: Vec<RagSource>,
+    pub embedding_tokens: u32,
+    pub tokens_generated: u32,
+    pub timings: InferenceTimings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/rag/query",
+    tag = "vectors",
+    request_body = RagQueryRequest,
+    responses(
+        (status = 200, body = RagQueryResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn rag_query(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<RagQueryRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.query.trim().is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "query must not be empty".to_string(),
+            param: Some("query"),
+        });
+    }
+
+    let search_request = SearchVectorsRequest {
+        model_id: req.embedding_model_id,
+        collection: req.collection,
+        query: req.query,
+        top_k: req.top_k,
+        filter: req.filter,
+    };
+    let (mut matches, embedding_tokens) = search(&state, &caller, &request_id, &search_request).await?;
+    if let Some(min_score) = req.min_score {
+        matches.retain(|m| m.score >= min_score);
+    }
+
+    let sources: Vec<Source> = matches
+        .iter()
+        .enumerate()
+        .map(|(i, m)| Source {
+            number: i + 1,
+            id: &m.id,
+            score: m.score,
+            text: &m.text,
+            metadata: &m.metadata,
+        })
+        .collect();
+    let prompt = rag::render_prompt(req.template.as_deref(), &search_request.query, &sources).map_err(|message| {
+        ApiError::Unprocessable {
+            message,
+            param: Some("template"),
+        }
+    })?;
+
+    let completion = complete_request(
+        state,
+        caller,
+        request_id,
+        InferenceRequest {
+            temperature: req.temperature,
+            ..InferenceRequest::prompt(&req.model_id, prompt, req.max_tokens)
+        },
+    )
+    .await?;
+
+    let cited = rag::cited(&completion.text, matches.len());
+    let sources = matches
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| RagSource {
+            number: i + 1,
+            id: m.id,
+            score: m.score,
+            text: m.text,
+            metadata: m.metadata,
+            cited: cited.contains(&(i + 1)),
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(RagQueryResponse {
+            model_id: completion.model_id,
+            answer: completion.text,
+            finish_reason: completion.finish_reason,
+            sources,
+            embedding_tokens,
+            tokens_generated: completion.tokens_generated,
+            timings: completion.timings,
+            estimated_cost_usd: completion.estimated_cost_usd,
+        }),
+    ))
+}
@@ -0,0 +1,272 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::config;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::{AppState, InferenceBackend, LoadedModel, ModelRegistryEntry};
+use super::models;
+
+/// The version of the export format, raised when it changes incompatibly.
+const EXPORT_VERSION: u32 = 1;
+
+/// The settings a backend's models need, by name. Values are never
+/// exported.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CredentialReference {
+    pub backend: InferenceBackend,
+    /// e.g. `OPENAI_API_KEY`, `OPENAI_URL`.
+    pub variables: Vec<String>,
+}
+
+/// The routing script the exporting engine ran.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoutingExport {
+    pub path: String,
+    pub script: String,
+}
+
+/// A dump of the registry, for moving it to another engine or seeding a
+/// new deployment.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegistryExport {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: Option<DateTime<Utc>>,
+    /// Every registered model, across tenants, as `/v1/models` shows it.
+    pub models: Vec<ModelRegistryEntry>,
+    #[serde(default)]
+    pub credentials: Vec<CredentialReference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingExport>,
+}
+
+fn default_version() -> u32 {
+    EXPORT_VERSION
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ImportRegistryRequest {
+    #[serde(flatten)]
+    pub registry: RegistryExport,
+    /// Replaces registered models with the same id, unless they are loaded.
+    /// They are skipped otherwise.
+    #[serde(default)]
+    pub overwrite: bool,
+    /// Loads the imported models that were loaded when they were exported,
+    /// with the quant and placement they had.
+    #[serde(default)]
+    pub load: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportRegistryResponse {
+    pub registered: Vec<String>,
+    pub replaced: Vec<String>,
+    /// Ids that were already registered.
+    pub skipped: Vec<String>,
+    pub loaded: Vec<String>,
+    /// Settings missing here, models that failed to load, and what else
+    /// the import could not carry over.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/models/export",
+    tag = "models",
+    responses((status = 200, body = RegistryExport))
+)]
+pub async fn export_registry(State(state): State<AppState>) -> impl IntoResponse {
+    let models: Vec<ModelRegistryEntry> = state
+        .models
+        .lock()
+        .await
+        .iter()
+        .map(|m| m.registry_entry.clone())
+        .collect();
+
+    let mut credentials: Vec<CredentialReference> = Vec::new();
+    for model in &models {
+        if credentials.iter().any(|c| c.backend == model.inference) {
+            continue;
+        }
+        let prefixes = config::backend_prefixes(&model.inference);
+        let variables: Vec<String> = state
+            .config
+            .variables
+            .keys()
+            .filter(|name| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+            .cloned()
+            .collect();
+        if !variables.is_empty() {
+            credentials.push(CredentialReference {
+                backend: model.inference.clone(),
+                variables,
+            });
+        }
+    }
+
+    let routing = match &state.routing {
+        Some(policy) => tokio::fs::read_to_string(policy.path())
+            .await
+            .ok()
+            .map(|script| RoutingExport {
+                path: policy.path().display().to_string(),
+                script,
+            }),
+        None => None,
+    };
+
+    (
+        StatusCode::OK,
+        Json(RegistryExport {
+            version: EXPORT_VERSION,
+            exported_at: Some(Utc::now()),
+            models,
+            credentials,
+            routing,
+        }),
+    )
+}
+
+/// Registers the models in an export. Every model is checked before any is
+/// registered, so a bad export changes nothing.
+#[utoipa::path(
+    post,
+    path = "/v1/models/import",
+    tag = "models",
+    request_body = ImportRegistryRequest,
+    responses(
+        (status = 200, body = ImportRegistryResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn import_registry(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(req): Json<ImportRegistryRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let registry = req.registry;
+    if registry.version > EXPORT_VERSION {
+        return Err(ApiError::Unprocessable {
+            message: format!(
+                "Export version {} is newer than this engine reads ({})",
+                registry.version, EXPORT_VERSION
+            ),
+            param: Some("version"),
+        });
+    }
+
+    let mut ids = HashSet::new();
+    for entry in &registry.models {
+        if !ids.insert(entry.id.as_str()) {
+            return Err(ApiError::InvalidRequest {
+                message: format!("Model '{}' is listed twice", entry.id),
+                param: Some("models"),
+            });
+        }
+        if entry.context == 0 {
+            return Err(ApiError::InvalidRequest {
+                message: format!("Model '{}': context must be at least 1", entry.id),
+                param: Some("models"),
+            });
+        }
+        models::check_entry(entry).map_err(|e| match e {
+            ApiError::InvalidRequest { message, param } => ApiError::InvalidRequest {
+                message: format!("Model '{}': {}", entry.id, message),
+                param,
+            },
+            e => e,
+        })?;
+    }
+
+    let pools = state.pools.lock().await;
+    if let Some(entry) = registry.models.iter().find(|entry| pools.contains(&entry.id)) {
+        return Err(ApiError::Conflict(format!("A pool is named '{}'", entry.id)));
+    }
+    drop(pools);
+
+    let mut registered = Vec::new();
+    let mut replaced = Vec::new();
+    let mut skipped = Vec::new();
+    let mut warnings = Vec::new();
+    let mut to_load = Vec::new();
+
+    let mut models = state.models.lock().await;
+    for exported in &registry.models {
+        let entry = ModelRegistryEntry {
+            loaded: false,
+            loaded_at: None,
+            placement: None,
+            ..exported.clone()
+        };
+        match models.iter_mut().find(|m| m.registry_entry.id == entry.id) {
+            Some(_) if !req.overwrite => {
+                skipped.push(entry.id);
+                continue;
+            }
+            Some(existing) if existing.registry_entry.loaded => {
+                warnings.push(format!("Model '{}' is loaded here; unload it to replace it", entry.id));
+                skipped.push(entry.id);
+                continue;
+            }
+            Some(existing) => {
+                let previous = std::mem::replace(&mut existing.registry_entry, entry);
+                models::forget(&state, &previous).await;
+                replaced.push(previous.id);
+            }
+            None => {
+                registered.push(entry.id.clone());
+                models.push(LoadedModel {
+                    registry_entry: entry,
+                    last_accessed: std::time::SystemTime::now(),
+                });
+            }
+        }
+        if req.load && exported.loaded {
+            to_load.push(exported.clone());
+        }
+    }
+    if !registered.is_empty() || !replaced.is_empty() {
+        state.registry_revision.bump();
+    }
+    drop(models);
+
+    for reference in &registry.credentials {
+        for variable in &reference.variables {
+            if config::var(variable).is_err() {
+                warnings.push(format!("{} is not set here, which {:?} models may need", variable, reference.backend));
+            }
+        }
+    }
+    if registry.routing.is_some() && state.routing.is_none() {
+        warnings.push("The export has a routing script; start the engine with --routing-script to use it".to_string());
+    }
+
+    let results = join_all(to_load.iter().map(|entry| models::restore(&state, &caller, entry))).await;
+    let mut loaded = Vec::new();
+    for (entry, result) in to_load.into_iter().zip(results) {
+        match result {
+            Ok(_) => loaded.push(entry.id),
+            Err(e) => warnings.push(format!("Failed to load model '{}': {}", entry.id, e)),
+        }
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(ImportRegistryResponse {
+            registered,
+            replaced,
+            skipped,
+            loaded,
+            warnings,
+        }),
+    ))
+}
@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::stats::{ModelStatsSnapshot, StatsSnapshot};
+use super::super::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/v1/models/{model_id}/stats",
+    tag = "stats",
+    params(("model_id" = String, Path)),
+    responses(
+        (status = 200, body = ModelStatsSnapshot),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn model_stats(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(model_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let models = state.models.lock().await;
+    let tenant = models
+        .iter()
+        .find(|m| m.registry_entry.id == model_id && m.registry_entry.visible_to(&caller))
+        .map(|m| m.registry_entry.tenant.clone())
+        .ok_or_else(|| ApiError::ModelNotFound(model_id.clone()))?;
+    drop(models);
+
+    let stats = state.stats.lock().await;
+    Ok((StatusCode::OK, Json(stats.model_snapshot(tenant.as_deref(), &model_id))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/stats",
+    tag = "stats",
+    responses((status = 200, body = StatsSnapshot))
+)]
+pub async fn global_stats(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> impl IntoResponse {
+    let mut snapshot = state.stats.lock().await.snapshot(caller.tenant.as_deref());
+    // The cache is shared by all tenants.
+    if caller.tenant.is_none()
+        && let Some(cache) = &state.embedding_cache
+    {
+        snapshot.embedding_cache = Some(cache.lock().await.snapshot());
+    }
+    (StatusCode::OK, Json(snapshot))
+}
@@ -0,0 +1,42 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::super::storage::{self, PruneReport, StorageReport};
+use super::super::AppState;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PruneStorageRequest {
+    /// Lists what would be deleted without deleting it.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also deletes Ollama models no registered model uses. Off by default,
+    /// since other applications may use Ollama's models.
+    #[serde(default)]
+    pub ollama: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/models/storage",
+    tag = "models",
+    responses((status = 200, body = StorageReport))
+)]
+pub async fn storage_usage(State(state): State<AppState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(storage::scan(&state).await))
+}
+
+/// Deletes model files that no registered model uses.
+#[utoipa::path(
+    post,
+    path = "/v1/models/storage/prune",
+    tag = "models",
+    request_body = PruneStorageRequest,
+    responses((status = 200, body = PruneReport))
+)]
+pub async fn prune_storage(
+    State(state): State<AppState>,
+    Json(req): Json<PruneStorageRequest>,
+) -> impl IntoResponse {
+    (StatusCode::OK, Json(storage::prune(&state, req.dry_run, req.ollama).await))
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::super::error::{ApiError, ErrorBody};
+use super::super::injection::InjectionPolicy;
+use super::super::quota::{QuotaStatus, TokenQuota};
+use super::super::tenants::TenantRecord;
+use super::super::AppState;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateTenantRequest {
+    #[serde(default)]
+    pub tier: Option<String>,
+    #[serde(default)]
+    pub quota: TokenQuota,
+    #[serde(default)]
+    pub injection: Option<InjectionPolicy>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TenantInfo {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    pub quota: QuotaStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub injection: Option<InjectionPolicy>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&TenantRecord> for TenantInfo {
+    fn from(record: &TenantRecord) -> Self {
+        Self {
+            id: record.id.clone(),
+            tier: record.tier.clone(),
+            quota: QuotaStatus::compute(&record.quota, &record.usage, Utc::now()),
+            injection: record.injection,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TenantListResponse {
+    pub tenants: Vec<TenantInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tenants",
+    tag = "tenants",
+    responses((status = 200, body = TenantListResponse))
+)]
+pub async fn list_tenants(State(state): State<AppState>) -> impl IntoResponse {
+    let tenants = state.tenants.lock().await;
+    let tenants = tenants.list().iter().map(TenantInfo::from).collect();
+
+    (StatusCode::OK, Json(TenantListResponse { tenants }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/tenants/{tenant_id}",
+    tag = "tenants",
+    params(("tenant_id" = String, Path)),
+    request_body = UpdateTenantRequest,
+    responses(
+        (status = 200, body = TenantInfo),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn update_tenant(
+    State(state): State<AppState>,
+    Path(tenant_id): Path<String>,
+    Json(req): Json<UpdateTenantRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut tenants = state.tenants.lock().await;
+    let record = tenants
+        .upsert(&tenant_id, req.tier, req.quota, req.injection)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to store tenant: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(TenantInfo::from(&record))))
+}
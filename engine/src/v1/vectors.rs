@@ -0,0 +1,249 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use super::super::auth::Caller;
+use super::super::error::{ApiError, ErrorBody};
+use super::super::request_id::RequestId;
+use super::super::vector_store::{StoredPoint, VectorStore};
+use super::super::AppState;
+use super::embeddings::embed_texts;
+
+const MAX_TOP_K: u32 = 100;
+
+/// Cohere's input types; other backends ignore them.
+pub(super) const DOCUMENT_INPUT_TYPE: &str = "search_document";
+const QUERY_INPUT_TYPE: &str = "search_query";
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VectorPoint {
+    /// Replaces the point with the same id. Generated when omitted.
+    #[serde(default)]
+    pub id: Option<String>,
+    pub text: String,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpsertVectorsRequest {
+    /// The embedding model; search the collection with the same one.
+    pub model_id: String,
+    pub collection: String,
+    pub points: Vec<VectorPoint>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UpsertVectorsResponse {
+    pub collection: String,
+    /// The points' ids, in order.
+    pub ids: Vec<String>,
+    pub tokens: u32,
+    pub cached: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SearchVectorsRequest {
+    pub model_id: String,
+    pub collection: String,
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: u32,
+    /// Only points whose metadata has all of these key-value pairs.
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub filter: Map<String, Value>,
+}
+
+pub(super) fn default_top_k() -> u32 {
+    5
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VectorMatch {
+    pub id: String,
+    /// Cosine similarity to the query, higher is closer.
+    pub score: f64,
+    pub text: String,
+    #[schema(value_type = Object)]
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchVectorsResponse {
+    pub collection: String,
+    pub matches: Vec<VectorMatch>,
+    pub tokens: u32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vectors/upsert",
+    tag = "vectors",
+    request_body = UpsertVectorsRequest,
+    responses(
+        (status = 200, body = UpsertVectorsResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn upsert_vectors(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<UpsertVectorsRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let store = vector_store(&state)?;
+    let collection = collection_name(&caller, &req.collection)?;
+    if req.points.is_empty() {
+        return Err(ApiError::InvalidRequest {
+            message: "points must not be empty".to_string(),
+            param: Some("points"),
+        });
+    }
+
+    let texts: Vec<String> = req.points.iter().map(|point| point.text.clone()).collect();
+    let embedded = embed_texts(
+        &state,
+        &caller,
+        &req.model_id,
+        &texts,
+        Some(DOCUMENT_INPUT_TYPE),
+        &request_id.0,
+    )
+    .await?;
+
+    let points: Vec<StoredPoint> = req
+        .points
+        .into_iter()
+        .zip(embedded.vectors)
+        .map(|(point, vector)| StoredPoint {
+            id: point.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            vector,
+            text: point.text,
+            metadata: point.metadata,
+        })
+        .collect();
+    let ids = points.iter().map(|point| point.id.clone()).collect();
+    store.upsert(&collection, points).await.map_err(ApiError::Backend)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(UpsertVectorsResponse {
+            collection: req.collection,
+            ids,
+            tokens: embedded.tokens,
+            cached: embedded.cached,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vectors/search",
+    tag = "vectors",
+    request_body = SearchVectorsRequest,
+    responses(
+        (status = 200, body = SearchVectorsResponse),
+        (status = "default", description = "Error", body = ErrorBody),
+    )
+)]
+pub async fn search_vectors(
+    State(state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Extension(request_id): Extension<RequestId>,
+    Json(req): Json<SearchVectorsRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (matches, tokens) = search(&state, &caller, &request_id, &req).await?;
+    Ok((
+        StatusCode::OK,
+        Json(SearchVectorsResponse {
+            collection: req.collection,
+            matches,
+            tokens,
+        }),
+    ))
+}
+
+/// The points closest to the request's query, with the tokens embedding the
+/// query took. Also used to retrieve sources for `/v1/rag/query`.
+pub(super) async fn search(
+    state: &AppState,
+    caller: &Caller,
+    request_id: &RequestId,
+    req: &SearchVectorsRequest,
+) -> Result<(Vec<VectorMatch>, u32), ApiError> {
+    let store = vector_store(state)?;
+    let collection = collection_name(caller, &req.collection)?;
+    if req.top_k == 0 || req.top_k > MAX_TOP_K {
+        return Err(ApiError::InvalidRequest {
+            message: format!("top_k must be between 1 and {}", MAX_TOP_K),
+            param: Some("top_k"),
+        });
+    }
+
+    let mut embedded = embed_texts(
+        state,
+        caller,
+        &req.model_id,
+        std::slice::from_ref(&req.query),
+        Some(QUERY_INPUT_TYPE),
+        &request_id.0,
+    )
+    .await?;
+    let vector = embedded.vectors.pop().unwrap_or_default();
+
+    let matches = store
+        .search(&collection, vector, req.top_k, &req.filter)
+        .await
+        .map_err(ApiError::Backend)?
+        .into_iter()
+        .map(|m| VectorMatch {
+            id: m.id,
+            score: m.score,
+            text: m.text,
+            metadata: m.metadata,
+        })
+        .collect();
+    Ok((matches, embedded.tokens))
+}
+
+pub(super) fn vector_store(state: &AppState) -> Result<&VectorStore, ApiError> {
+    state
+        .vector_store
+        .as_deref()
+        .ok_or_else(|| ApiError::NotFound("No vector store is configured; start the engine with --vector-store".to_string()))
+}
+
+/// The collection as it is named in the store. A tenant's collections are
+/// prefixed with a hash of the tenant, so tenants cannot reach each other's;
+/// names given by callers cannot contain the `.` that separates them.
+pub(super) fn collection_name(caller: &Caller, collection: &str) -> Result<String, ApiError> {
+    let valid = !collection.is_empty()
+        && collection.len() <= 64
+        && collection.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(ApiError::InvalidRequest {
+            message: "collection must be 1 to 64 letters, digits, '_' or '-'".to_string(),
+            param: Some("collection"),
+        });
+    }
+    Ok(match &caller.tenant {
+        Some(tenant) => {
+            let digest: String = Sha256::digest(tenant.as_bytes())[..8]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("{}.{}", digest, collection)
+        }
+        None => collection.to_string(),
+    })
+}
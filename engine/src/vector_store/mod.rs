@@ -0,0 +1,73 @@
+pub mod pgvector;
+pub mod qdrant;
+
+use serde_json::{Map, Value};
+
+/// A text with its embedding, as it is kept in the store.
+pub struct StoredPoint {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub text: String,
+    pub metadata: Map<String, Value>,
+}
+
+pub struct Match {
+    pub id: String,
+    /// Cosine similarity to the query, higher is closer.
+    pub score: f64,
+    pub text: String,
+    pub metadata: Map<String, Value>,
+}
+
+/// Where `/v1/vectors` keeps embeddings, chosen by the scheme of the
+/// `--vector-store` URL.
+pub enum VectorStore {
+    Qdrant(qdrant::Qdrant),
+    Pgvector(pgvector::Pgvector),
+}
+
+impl VectorStore {
+    pub fn new(url: &str) -> Result<Self, String> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            pgvector::Pgvector::new(url).map(Self::Pgvector)
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Self::Qdrant(qdrant::Qdrant::new(url)))
+        } else {
+            Err(format!(
+                "unsupported vector store '{}': expected an http(s):// Qdrant URL or a postgres:// connection string",
+                url
+            ))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Qdrant(_) => "Qdrant",
+            Self::Pgvector(_) => "pgvector",
+        }
+    }
+
+    /// Inserts the points, replacing those with the same ids. Collections
+    /// are created on first use.
+    pub async fn upsert(&self, collection: &str, points: Vec<StoredPoint>) -> Result<(), String> {
+        match self {
+            Self::Qdrant(store) => store.upsert(collection, points).await,
+            Self::Pgvector(store) => store.upsert(collection, points).await,
+        }
+    }
+
+    /// The `top_k` points closest to `vector` whose metadata has every
+    /// key-value pair in `filter`.
+    pub async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        top_k: u32,
+        filter: &Map<String, Value>,
+    ) -> Result<Vec<Match>, String> {
+        match self {
+            Self::Qdrant(store) => store.search(collection, vector, top_k, filter).await,
+            Self::Pgvector(store) => store.search(collection, vector, top_k, filter).await,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use serde_json::{Map, Value};
+
+use super::{Match, StoredPoint};
+
+/// PostgreSQL with the pgvector extension. Every collection shares one
+/// table, created on first use.
+pub struct Pgvector {
+    #[cfg(feature = "pgvector")]
+    url: String,
+    #[cfg(feature = "pgvector")]
+    client: tokio::sync::Mutex<Option<std::sync::Arc<tokio_postgres::Client>>>,
+}
+
+#[cfg(not(feature = "pgvector"))]
+const UNAVAILABLE: &str = "openllm-server was built without the pgvector feature";
+
+#[cfg(not(feature = "pgvector"))]
+impl Pgvector {
+    pub fn new(_url: &str) -> Result<Self, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub async fn upsert(&self, _collection: &str, _points: Vec<StoredPoint>) -> Result<(), String> {
+        Err(UNAVAILABLE.to_string())
+    }
+
+    pub async fn search(
+        &self,
+        _collection: &str,
+        _vector: Vec<f32>,
+        _top_k: u32,
+        _filter: &Map<String, Value>,
+    ) -> Result<Vec<Match>, String> {
+        Err(UNAVAILABLE.to_string())
+    }
+}
+
+#[cfg(feature = "pgvector")]
+const SCHEMA: &str = "
+    CREATE EXTENSION IF NOT EXISTS vector;
+    CREATE TABLE IF NOT EXISTS openllm_vectors (
+        collection text NOT NULL,
+        id text NOT NULL,
+        embedding vector NOT NULL,
+        text text NOT NULL,
+        metadata jsonb NOT NULL DEFAULT '{}',
+        PRIMARY KEY (collection, id)
+    );
+";
+
+#[cfg(feature = "pgvector")]
+impl Pgvector {
+    pub fn new(url: &str) -> Result<Self, String> {
+        url.parse::<tokio_postgres::Config>()
+            .map_err(|e| format!("invalid PostgreSQL connection string: {}", e))?;
+        Ok(Self {
+            url: url.to_string(),
+            client: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// The open connection, or a new one if there is none or it was lost.
+    async fn client(&self) -> Result<std::sync::Arc<tokio_postgres::Client>, String> {
+        let mut client = self.client.lock().await;
+        if let Some(client) = client.as_ref()
+            && !client.is_closed()
+        {
+            return Ok(client.clone());
+        }
+
+        let (connected, connection) = tokio_postgres::connect(&self.url, tokio_postgres::NoTls)
+            .await
+            .map_err(|e| format!("Failed to connect to PostgreSQL: {}", describe(&e)))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!("PostgreSQL connection failed: {}", e);
+            }
+        });
+        connected
+            .batch_execute(SCHEMA)
+            .await
+            .map_err(|e| format!("Failed to create the vector table: {}", describe(&e)))?;
+
+        let connected = std::sync::Arc::new(connected);
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+
+    pub async fn upsert(&self, collection: &str, points: Vec<StoredPoint>) -> Result<(), String> {
+        let client = self.client().await?;
+        let statement = client
+            .prepare(
+                "INSERT INTO openllm_vectors (collection, id, embedding, text, metadata)
+                 VALUES ($1, $2, $3::text::vector, $4, $5)
+                 ON CONFLICT (collection, id) DO UPDATE
+                 SET embedding = excluded.embedding, text = excluded.text, metadata = excluded.metadata",
+            )
+            .await
+            .map_err(|e| format!("pgvector error: {}", describe(&e)))?;
+        for point in points {
+            let metadata = Value::Object(point.metadata);
+            client
+                .execute(
+                    &statement,
+                    &[&collection, &point.id, &vector_literal(&point.vector), &point.text, &metadata],
+                )
+                .await
+                .map_err(|e| format!("pgvector error: {}", describe(&e)))?;
+        }
+        Ok(())
+    }
+
+    pub async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        top_k: u32,
+        filter: &Map<String, Value>,
+    ) -> Result<Vec<Match>, String> {
+        let client = self.client().await?;
+        let filter = Value::Object(filter.clone());
+        let rows = client
+            .query(
+                "SELECT id, text, metadata, 1 - (embedding <=> $1::text::vector) AS score
+                 FROM openllm_vectors
+                 WHERE collection = $2 AND metadata @> $3
+                 ORDER BY embedding <=> $1::text::vector
+                 LIMIT $4",
+                &[&vector_literal(&vector), &collection, &filter, &(top_k as i64)],
+            )
+            .await
+            .map_err(|e| format!("pgvector error: {}", describe(&e)))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Match {
+                id: row.get("id"),
+                score: row.get("score"),
+                text: row.get("text"),
+                metadata: match row.get::<_, Value>("metadata") {
+                    Value::Object(metadata) => metadata,
+                    _ => Map::new(),
+                },
+            })
+            .collect())
+    }
+}
+
+/// pgvector's text form, `[1,2,3]`, which the queries cast to `vector`.
+#[cfg(feature = "pgvector")]
+fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+/// The server's message for database errors, which `tokio_postgres` only
+/// shows as "db error".
+#[cfg(feature = "pgvector")]
+fn describe(error: &tokio_postgres::Error) -> String {
+    match error.as_db_error() {
+        Some(db) => db.message().to_string(),
+        None => error.to_string(),
+    }
+}
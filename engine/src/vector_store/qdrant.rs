@@ -0,0 +1,156 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::super::config;
+use super::{Match, StoredPoint};
+
+/// Qdrant's REST API. `QDRANT_API_KEY` is sent as its `api-key` header.
+pub struct Qdrant {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    result: Vec<ScoredPoint>,
+}
+
+#[derive(Deserialize)]
+struct ScoredPoint {
+    score: f64,
+    #[serde(default)]
+    payload: Map<String, Value>,
+}
+
+impl Qdrant {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: config::var("QDRANT_API_KEY").ok(),
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.request(method, format!("{}{}", self.url, path));
+        match &self.api_key {
+            Some(key) => builder.header("api-key", key),
+            None => builder,
+        }
+    }
+
+    pub async fn upsert(&self, collection: &str, points: Vec<StoredPoint>) -> Result<(), String> {
+        let Some(size) = points.first().map(|point| point.vector.len()) else {
+            return Ok(());
+        };
+        let body = json!({
+            "points": points
+                .into_iter()
+                .map(|point| json!({
+                    "id": point_id(&point.id),
+                    "vector": point.vector,
+                    "payload": {"id": point.id, "text": point.text, "metadata": point.metadata},
+                }))
+                .collect::<Vec<_>>(),
+        });
+        let path = format!("/collections/{}/points?wait=true", collection);
+
+        let response = self.send(self.request(reqwest::Method::PUT, &path).json(&body)).await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            return check(response).await.map(drop);
+        }
+
+        // Qdrant needs the vector size up front, so collections are created
+        // once the first vectors arrive.
+        let create = json!({"vectors": {"size": size, "distance": "Cosine"}});
+        let created = self
+            .send(self.request(reqwest::Method::PUT, &format!("/collections/{}", collection)).json(&create))
+            .await?;
+        check(created).await?;
+        let response = self.send(self.request(reqwest::Method::PUT, &path).json(&body)).await?;
+        check(response).await.map(drop)
+    }
+
+    pub async fn search(
+        &self,
+        collection: &str,
+        vector: Vec<f32>,
+        top_k: u32,
+        filter: &Map<String, Value>,
+    ) -> Result<Vec<Match>, String> {
+        let mut body = json!({"vector": vector, "limit": top_k, "with_payload": true});
+        if !filter.is_empty() {
+            let must: Vec<Value> = filter
+                .iter()
+                .map(|(key, value)| json!({"key": format!("metadata.{}", key), "match": {"value": value}}))
+                .collect();
+            body["filter"] = json!({"must": must});
+        }
+
+        let response = self
+            .send(
+                self.request(reqwest::Method::POST, &format!("/collections/{}/points/search", collection))
+                    .json(&body),
+            )
+            .await?;
+        // Nothing was stored in the collection yet.
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let response: SearchResponse = check(response)
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Qdrant response: {}", e))?;
+
+        Ok(response
+            .result
+            .into_iter()
+            .map(|mut point| Match {
+                id: take_string(&mut point.payload, "id"),
+                score: point.score,
+                text: take_string(&mut point.payload, "text"),
+                metadata: match point.payload.remove("metadata") {
+                    Some(Value::Object(metadata)) => metadata,
+                    _ => Map::new(),
+                },
+            })
+            .collect())
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        request.send().await.map_err(|e| format!("Qdrant request failed: {}", e))
+    }
+}
+
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, String> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|v| v["status"]["error"].as_str().map(str::to_string))
+        .unwrap_or(body);
+    Err(format!("Qdrant error: {} - {}", status, message))
+}
+
+/// Qdrant only takes integers and UUIDs as point ids, so the caller's id is
+/// hashed into a UUID and kept in the payload.
+fn point_id(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_random_bytes(bytes).into_uuid().to_string()
+}
+
+fn take_string(payload: &mut Map<String, Value>, key: &str) -> String {
+    match payload.remove(key) {
+        Some(Value::String(value)) => value,
+        _ => String::new(),
+    }
+}
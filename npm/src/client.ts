@@ -1,24 +1,57 @@
 import type {
+  ApiErrorResponse,
+  BenchmarkRequest,
+  BenchmarkResponse,
+  CatalogResponse,
+  CreatePoolRequest,
+  CreatePromptRequest,
+  DocumentJob,
+  EmbeddingRequest,
+  EmbeddingResponse,
+  EnsembleRequest,
+  EnsembleResponse,
   HealthResponse,
+  ImportCatalogRequest,
+  ImportCatalogResponse,
+  IngestDocumentOptions,
+  InferenceBackend,
   InferenceRequest,
   InferenceResponse,
   LoadModelRequest,
   LoadModelResponse,
   ModelListResponse,
-  ModelNotFoundError,
-  ModelNotLoadedError,
+  RagQueryRequest,
+  RagQueryResponse,
   OpenLLMConfig,
   OpenLLMError,
+  PipelineRequest,
+  PipelineResponse,
+  ModelPool,
+  PoolListResponse,
+  PromptListResponse,
   RegisterModelRequest,
   RegisterModelResponse,
+  BulkRegisterRequest,
+  BulkRegisterResponse,
+  SearchVectorsRequest,
+  SearchVectorsResponse,
   StreamOptions,
+  StreamPing,
   StreamToken,
+  StoredPrompt,
+  StreamUsage,
   UnloadModelResponse,
+  UnregisterModelResponse,
+  UpdatePoolRequest,
+  UpdatePromptRequest,
+  UpsertVectorsRequest,
+  UpsertVectorsResponse,
 } from "./types.js";
 
 export class OpenLLMClient {
   private baseUrl: string;
   private timeout: number;
+  private apiKey?: string;
 
   constructor(config: OpenLLMConfig = {}) {
     if (typeof config.engine === "number") {
@@ -29,6 +62,11 @@ export class OpenLLMClient {
       this.baseUrl = "http://localhost:8080";
     }
     this.timeout = config.timeout ?? 30000;
+    this.apiKey = config.apiKey;
+  }
+
+  private authHeaders(): Record<string, string> {
+    return this.apiKey ? { Authorization: `Bearer ${this.apiKey}` } : {};
   }
 
   private async request<T>(
@@ -44,6 +82,7 @@ export class OpenLLMClient {
         signal: controller.signal,
         headers: {
           "Content-Type": "application/json",
+          ...this.authHeaders(),
           ...options.headers,
         },
       });
@@ -68,32 +107,25 @@ export class OpenLLMClient {
     }
   }
 
-  private createError(status: number, message: string): OpenLLMError {
-    if (status === 404) {
-      const match = message.match(/Model '([^']+)'/);
-      if (match) {
-        const error = new Error(message) as ModelNotFoundError;
-        error.name = "ModelNotFoundError";
-        error.code = "MODEL_NOT_FOUND";
-        error.statusCode = status;
-        return error;
-      }
-    }
-    if (status === 412) {
-      const match = message.match(/Model '([^']+)'/);
-      if (match) {
-        const error = new Error(message) as ModelNotLoadedError;
-        error.name = "ModelNotLoadedError";
-        error.code = "MODEL_NOT_LOADED";
-        error.statusCode = status;
-        return error;
-      }
+  private createError(status: number, body: string): OpenLLMError {
+    let detail: ApiErrorResponse["error"] | undefined;
+    try {
+      detail = (JSON.parse(body) as ApiErrorResponse).error;
+    } catch {
+      // Not an engine error body, e.g. from a proxy in between.
     }
 
-    const error = new Error(message) as OpenLLMError;
-    error.name = "OpenLLMError";
-    error.code = "API_ERROR";
+    const error = new Error(detail?.message ?? body) as OpenLLMError;
+    error.code = detail?.code.toUpperCase() ?? "API_ERROR";
     error.statusCode = status;
+    error.requestId = detail?.request_id ?? undefined;
+    if (detail?.code === "model_not_found") {
+      error.name = "ModelNotFoundError";
+    } else if (detail?.code === "model_not_loaded") {
+      error.name = "ModelNotLoadedError";
+    } else {
+      error.name = "OpenLLMError";
+    }
     return error;
   }
 
@@ -114,6 +146,15 @@ export class OpenLLMClient {
     });
   }
 
+  async registerModels(
+    data: BulkRegisterRequest,
+  ): Promise<BulkRegisterResponse> {
+    return this.request<BulkRegisterResponse>("/v1/models/register/bulk", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
   async loadModel(data: LoadModelRequest): Promise<LoadModelResponse> {
     return this.request<LoadModelResponse>("/v1/models/load", {
       method: "POST",
@@ -130,6 +171,39 @@ export class OpenLLMClient {
     );
   }
 
+  async unregisterModel(modelId: string): Promise<UnregisterModelResponse> {
+    return this.request<UnregisterModelResponse>(`/v1/models/${modelId}`, {
+      method: "DELETE",
+    });
+  }
+
+  async benchmarkModel(
+    modelId: string,
+    data: BenchmarkRequest = {},
+  ): Promise<BenchmarkResponse> {
+    return this.request<BenchmarkResponse>(`/v1/models/${modelId}/benchmark`, {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async listCatalog(backend: InferenceBackend): Promise<CatalogResponse> {
+    return this.request<CatalogResponse>(`/v1/backends/${backend}/models`);
+  }
+
+  async importCatalog(
+    backend: InferenceBackend,
+    data: ImportCatalogRequest = {},
+  ): Promise<ImportCatalogResponse> {
+    return this.request<ImportCatalogResponse>(
+      `/v1/backends/${backend}/models/import`,
+      {
+        method: "POST",
+        body: JSON.stringify(data),
+      },
+    );
+  }
+
   async inference(data: InferenceRequest): Promise<InferenceResponse> {
     return this.request<InferenceResponse>("/v1/inference", {
       method: "POST",
@@ -137,6 +211,141 @@ export class OpenLLMClient {
     });
   }
 
+  async ensemble(data: EnsembleRequest): Promise<EnsembleResponse> {
+    return this.request<EnsembleResponse>("/v1/inference/ensemble", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async runPipeline(data: PipelineRequest): Promise<PipelineResponse> {
+    return this.request<PipelineResponse>("/v1/pipelines/run", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async embeddings(data: EmbeddingRequest): Promise<EmbeddingResponse> {
+    return this.request<EmbeddingResponse>("/v1/embeddings", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async upsertVectors(data: UpsertVectorsRequest): Promise<UpsertVectorsResponse> {
+    return this.request<UpsertVectorsResponse>("/v1/vectors/upsert", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async searchVectors(data: SearchVectorsRequest): Promise<SearchVectorsResponse> {
+    return this.request<SearchVectorsResponse>("/v1/vectors/search", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  /** Starts indexing a document; poll `getDocumentJob` for its progress. */
+  async ingestDocument(
+    document: string | Blob | ArrayBuffer,
+    options: IngestDocumentOptions,
+  ): Promise<DocumentJob> {
+    const { content_type, ...params } = options;
+    const query = new URLSearchParams();
+    for (const [key, value] of Object.entries(params)) {
+      if (value !== undefined) query.set(key, String(value));
+    }
+    return this.request<DocumentJob>(`/v1/documents?${query}`, {
+      method: "POST",
+      headers: { "Content-Type": content_type ?? "text/plain" },
+      body: document,
+    });
+  }
+
+  async getDocumentJob(jobId: string): Promise<DocumentJob> {
+    return this.request<DocumentJob>(`/v1/documents/jobs/${jobId}`);
+  }
+
+  async ragQuery(data: RagQueryRequest): Promise<RagQueryResponse> {
+    return this.request<RagQueryResponse>("/v1/rag/query", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async listPrompts(): Promise<PromptListResponse> {
+    return this.request<PromptListResponse>("/v1/prompts");
+  }
+
+  async createPrompt(data: CreatePromptRequest): Promise<StoredPrompt> {
+    return this.request<StoredPrompt>("/v1/prompts", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async getPrompt(promptId: string): Promise<StoredPrompt> {
+    return this.request<StoredPrompt>(`/v1/prompts/${promptId}`);
+  }
+
+  async updatePrompt(
+    promptId: string,
+    data: UpdatePromptRequest,
+  ): Promise<StoredPrompt> {
+    return this.request<StoredPrompt>(`/v1/prompts/${promptId}`, {
+      method: "PUT",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async deletePrompt(promptId: string): Promise<StoredPrompt> {
+    return this.request<StoredPrompt>(`/v1/prompts/${promptId}`, {
+      method: "DELETE",
+    });
+  }
+
+  async listPools(): Promise<PoolListResponse> {
+    return this.request<PoolListResponse>("/v1/pools");
+  }
+
+  async createPool(data: CreatePoolRequest): Promise<ModelPool> {
+    return this.request<ModelPool>("/v1/pools", {
+      method: "POST",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async getPool(poolId: string): Promise<ModelPool> {
+    return this.request<ModelPool>(`/v1/pools/${poolId}`);
+  }
+
+  async updatePool(poolId: string, data: UpdatePoolRequest): Promise<ModelPool> {
+    return this.request<ModelPool>(`/v1/pools/${poolId}`, {
+      method: "PUT",
+      body: JSON.stringify(data),
+    });
+  }
+
+  async deletePool(poolId: string): Promise<ModelPool> {
+    return this.request<ModelPool>(`/v1/pools/${poolId}`, {
+      method: "DELETE",
+    });
+  }
+
+  async addPoolMember(poolId: string, modelId: string): Promise<ModelPool> {
+    return this.request<ModelPool>(`/v1/pools/${poolId}/members`, {
+      method: "POST",
+      body: JSON.stringify({ model_id: modelId }),
+    });
+  }
+
+  async removePoolMember(poolId: string, modelId: string): Promise<ModelPool> {
+    return this.request<ModelPool>(`/v1/pools/${poolId}/members/${modelId}`, {
+      method: "DELETE",
+    });
+  }
+
   async inferenceStream(
     data: InferenceRequest,
     options: StreamOptions,
@@ -149,6 +358,8 @@ export class OpenLLMClient {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
+          ...this.authHeaders(),
+          ...(options.lastEventId && { "Last-Event-ID": options.lastEventId }),
         },
         body: JSON.stringify(data),
         signal: controller.signal,
@@ -173,8 +384,10 @@ export class OpenLLMClient {
       const decoder = new TextDecoder();
       let buffer = "";
       let accumulatedText = "";
+      let accumulatedReasoning = "";
       let tokenCount = 0;
-      const modelId = data.model_id;
+      let eventName = "token";
+      const modelId = data.model_id ?? "";
 
       while (true) {
         const { done, value } = await reader.read();
@@ -185,10 +398,41 @@ export class OpenLLMClient {
         buffer = lines.pop() ?? "";
 
         for (const line of lines) {
-          if (line.trim().startsWith("event: token")) {
+          if (line.trim().startsWith("event: ")) {
+            eventName = line.trim().slice(7);
+            continue;
+          }
+          if (line.trim().startsWith("id: ")) {
+            options.onEventId?.(line.trim().slice(4));
+            continue;
+          }
+          if (line.trim().startsWith("data: ") && eventName === "reasoning") {
+            try {
+              const token = JSON.parse(line.trim().slice(6)) as StreamToken;
+              accumulatedReasoning += token.token;
+              options.onReasoning?.(token);
+            } catch (e) {
+              console.error("Failed to parse SSE data:", line, e);
+            }
+            continue;
+          }
+          if (line.trim().startsWith("data: ") && eventName === "ping") {
+            try {
+              options.onPing?.(JSON.parse(line.trim().slice(6)) as StreamPing);
+            } catch (e) {
+              console.error("Failed to parse SSE data:", line, e);
+            }
+            continue;
+          }
+          if (line.trim().startsWith("data: ") && eventName === "usage") {
+            try {
+              options.onUsage?.(JSON.parse(line.trim().slice(6)) as StreamUsage);
+            } catch (e) {
+              console.error("Failed to parse SSE data:", line, e);
+            }
             continue;
           }
-          if (line.trim().startsWith("data: ")) {
+          if (line.trim().startsWith("data: ") && eventName === "token") {
             const data = line.trim().slice(6);
             if (data) {
               try {
@@ -202,7 +446,8 @@ export class OpenLLMClient {
                     model_id: modelId,
                     text: accumulatedText,
                     tokens_generated: tokenCount,
-                    finish_reason: "stop",
+                    finish_reason: token.finish_reason ?? "stop",
+                    ...(accumulatedReasoning && { reasoning: accumulatedReasoning }),
                   });
                 }
               } catch (e) {
@@ -69,6 +69,10 @@ export function openllm(config: APIConfig = {}) {
             throw new Error(`Model '${options.model}' not found in registry`);
           }
         } else {
+          // Latency profiles are measured by the engine as it serves.
+          if (options.latency) {
+            registry.sync((await client.listModels()).models);
+          }
           model = registry.findOne({
             capability: "chat",
             latency: options.latency as any,
@@ -2,25 +2,99 @@ export type {
   InferenceBackend,
   ModelCapability,
   LatencyProfile,
+  MeasuredLatency,
   ModelRegistryEntry,
   RegistryEntryInput,
   ModelRegistryConfig,
   HealthResponse,
+  SaturationStatus,
   ModelListResponse,
   RegisterModelResponse,
+  BulkRegisterRequest,
+  BulkRegisterResult,
+  BulkRegisterResponse,
+  ModelMetadata,
   LoadModelRequest,
   LoadModelResponse,
   UnloadModelResponse,
+  UnregisterModelResponse,
+  BenchmarkRequest,
+  BenchmarkResult,
+  BenchmarkReport,
+  BenchmarkResponse,
+  ChatMessage,
+  Attachment,
+  ToolDefinition,
+  ToolCall,
+  TruncationStrategy,
+  VariableEscape,
+  ModerationAction,
+  ModerationVerdict,
+  InjectionReport,
+  PostProcessor,
   InferenceRequest,
   InferenceResponse,
+  CandidateScorer,
+  BestOfCandidate,
+  FinishReason,
+  Citation,
+  UpstreamMetadata,
+  EnsembleJudge,
+  EnsembleRequest,
+  EnsembleMember,
+  EnsembleJudgement,
+  EnsembleResponse,
+  PipelineStep,
+  PipelineRequest,
+  PipelineStepResult,
+  PipelineResponse,
+  PromptDefaults,
+  PromptVersionRequest,
+  CreatePromptRequest,
+  UpdatePromptRequest,
+  PromptVersion,
+  StoredPrompt,
+  PromptListResponse,
+  ModelPool,
+  CreatePoolRequest,
+  UpdatePoolRequest,
+  PoolListResponse,
+  ModelPricing,
+  SpeculativeDecoding,
+  Placement,
+  QuantVariant,
+  CatalogModel,
+  CatalogResponse,
+  ImportCatalogRequest,
+  ImportCatalogResponse,
+  EmbeddingRequest,
+  EmbeddingResponse,
+  VectorPoint,
+  UpsertVectorsRequest,
+  UpsertVectorsResponse,
+  SearchVectorsRequest,
+  SearchVectorsResponse,
+  VectorMatch,
+  ChunkStrategy,
+  IngestDocumentOptions,
+  DocumentJobStatus,
+  DocumentJob,
+  RagQueryRequest,
+  RagSource,
+  RagQueryResponse,
   StreamToken,
   StreamCallback,
   StreamCompleteCallback,
   StreamErrorCallback,
+  StreamUsageCallback,
+  StreamPingCallback,
+  StreamPing,
+  StreamUsage,
   StreamOptions,
   OpenLLMConfig,
   FindModelOptions,
   APIConfig,
+  ApiErrorResponse,
   OpenLLMError,
   ModelNotFoundError,
   ModelNotLoadedError,
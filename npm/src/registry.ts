@@ -96,6 +96,26 @@ export class ModelRegistryImpl {
     return updated;
   }
 
+  /**
+   * Takes the engine's view of the models it shares with the registry: the
+   * latency profiles it measured and whether they are loaded.
+   */
+  sync(models: ModelRegistryEntry[]): void {
+    for (const model of models) {
+      const existing = this.entries.get(model.id);
+      if (!existing) {
+        continue;
+      }
+      this.entries.set(model.id, {
+        ...existing,
+        latency: model.latency ?? existing.latency,
+        measured_latency: model.measured_latency,
+        loaded: model.loaded,
+        loaded_at: model.loaded_at,
+      });
+    }
+  }
+
   remove(id: string): boolean {
     return this.entries.delete(id);
   }
@@ -1,6 +1,38 @@
-export type InferenceBackend = "ollama" | "llama" | "huggingface" | "openai";
+export type InferenceBackend =
+  | "ollama"
+  | "llama"
+  | "huggingface"
+  | "openai"
+  | "azure_openai"
+  | "anthropic"
+  | "gemini"
+  | "bedrock"
+  | "sagemaker"
+  | "vertex"
+  | "mistral"
+  | "cohere"
+  | "groq"
+  | "openrouter"
+  | "deepseek"
+  | "xai"
+  | "together"
+  | "fireworks"
+  | "replicate"
+  | "tgi"
+  | "lmstudio"
+  | "koboldcpp"
+  | "mlx"
+  | "triton"
+  | "llama_local"
+  | "candle"
+  | "mock";
 
-export type ModelCapability = "chat" | "vision" | "embedding" | "completion";
+export type ModelCapability =
+  | "chat"
+  | "vision"
+  | "embedding"
+  | "completion"
+  | "rerank";
 
 export type LatencyProfile = "extreme" | "fast" | "slow";
 
@@ -9,12 +41,51 @@ export interface ModelRegistryEntry {
   name: string;
   inference: InferenceBackend;
   context: number;
+  /** With `variants`, the one loaded last. */
   quant?: string;
+  variants?: QuantVariant[];
   capabilities: ModelCapability[];
+  /** Measured from recent requests unless given at registration. */
   latency?: LatencyProfile;
+  /** Whether `latency` was given at registration and is kept as is. */
+  latency_pinned?: boolean;
+  measured_latency?: MeasuredLatency;
+  post_process?: PostProcessor[];
+  speculative?: SpeculativeDecoding;
+  max_concurrency?: number;
   size_bytes: number;
   loaded: boolean;
   loaded_at?: string;
+  /** The placement the model was loaded with. */
+  placement?: Placement;
+  /** The model's latest benchmark. */
+  benchmark?: BenchmarkReport;
+}
+
+/** A registered draft model that proposes tokens for the model to verify. */
+export interface SpeculativeDecoding {
+  draft_model: string;
+  draft_max?: number;
+  draft_min?: number;
+  draft_p_min?: number;
+}
+
+/** A model's latency over its recent requests, and the profile it earns. */
+export interface MeasuredLatency {
+  profile: LatencyProfile;
+  tokens_per_second: number;
+  /** Averaged over streamed requests, if there were any. */
+  time_to_first_token_ms?: number;
+  requests: number;
+  measured_at: string;
+}
+
+/** One quantization of a model, served by the backend as `model`. */
+export interface QuantVariant {
+  quant: string;
+  /** The backend's model name; the registered id when omitted. */
+  model?: string;
+  size_bytes: number;
 }
 
 export interface RegistryEntryInput {
@@ -34,6 +105,17 @@ export interface HealthResponse {
   status: string;
   timestamp: string;
   models_loaded: number;
+  /** Set when the engine bounds concurrent requests. */
+  saturation?: SaturationStatus;
+}
+
+export interface SaturationStatus {
+  running: number;
+  max_running: number;
+  queued: number;
+  max_queued: number;
+  /** Whether new requests are being turned away. */
+  saturated: boolean;
 }
 
 export interface ModelListResponse {
@@ -44,26 +126,83 @@ export interface RegisterModelRequest {
   id: string;
   name: string;
   inference: InferenceBackend;
-  context: number;
+  /** Read from Ollama or the GGUF file when omitted. */
+  context?: number;
   quant?: string;
+  variants?: QuantVariant[];
   capabilities: ModelCapability[];
   latency?: LatencyProfile;
+  chat_template?: string;
+  post_process?: PostProcessor[];
+  speculative?: SpeculativeDecoding;
+  max_concurrency?: number;
   size_bytes?: number;
+  /** Takes the context, quant and size Ollama or the GGUF file reports over the values given. */
+  discover?: boolean;
+  /** Checks with the backend that it has the model before registering it. */
+  validate?: "off" | "warn" | "strict";
 }
 
 export interface RegisterModelResponse {
   success: boolean;
   model: ModelRegistryEntry;
   message: string;
+  /** What the backend reported about the model, when it was asked. */
+  metadata?: ModelMetadata;
+  /** Given values that discovery replaced, and what `validate: "warn"` found wrong with the model. */
+  warnings?: string[];
 }
 
-export interface LoadModelRequest {
+export interface BulkRegisterRequest {
+  models: RegisterModelRequest[];
+}
+
+export interface BulkRegisterResult {
+  id: string;
+  /** What `/v1/models/register` would have answered: 201 when registered, 409 when the id was taken. */
+  status: number;
+  model?: ModelRegistryEntry;
+  metadata?: ModelMetadata;
+  warnings?: string[];
+  error?: ApiErrorResponse["error"];
+}
+
+export interface BulkRegisterResponse {
+  registered: number;
+  failed: number;
+  /** One result per requested model, in request order. */
+  results: BulkRegisterResult[];
+}
+
+export interface ModelMetadata {
+  architecture?: string;
+  parameters?: number;
+  context?: number;
+  quant?: string;
+  size_bytes?: number;
+  /** The model's own prompt template: Jinja in GGUF files, Go templates in Ollama. */
+  template?: string;
+}
+
+/** Where Ollama or in-process llama.cpp runs a model. */
+export interface Placement {
+  main_gpu?: number;
+  /** Layers offloaded to the GPU; `0` keeps the model on the CPU. */
+  n_gpu_layers?: number;
+  threads?: number;
+  numa?: "distribute" | "isolate" | "numactl";
+}
+
+export interface LoadModelRequest extends Placement {
   model_id: string;
+  /** The variant to load; picked by available memory when omitted. */
+  quant?: string;
 }
 
 export interface LoadModelResponse {
   success: boolean;
   model_id: string;
+  quant?: string;
   message: string;
 }
 
@@ -73,39 +212,607 @@ export interface UnloadModelResponse {
   message: string;
 }
 
-export interface InferenceRequest {
+export interface UnregisterModelResponse {
+  success: boolean;
   model_id: string;
-  prompt: string;
+  message: string;
+}
+
+export interface BenchmarkRequest {
+  /** Prompt lengths in tokens; those that fit of 128, 512, 2048 and 8192 by default. */
+  context_lengths?: number[];
+  max_tokens?: number;
+}
+
+export interface BenchmarkResult {
+  prompt_tokens: number;
+  completion_tokens: number;
+  time_to_first_token_ms: number;
+  prompt_tokens_per_second: number;
+  tokens_per_second: number;
+  total_ms: number;
+}
+
+export interface BenchmarkReport {
+  ran_at: string;
+  quant?: string;
+  max_tokens: number;
+  results: BenchmarkResult[];
+}
+
+export interface BenchmarkResponse extends BenchmarkReport {
+  model_id: string;
+}
+
+export interface ChatMessage {
+  role: "system" | "user" | "assistant" | "tool" | (string & {});
+  content: string;
+  /** Tools the assistant called in this turn. */
+  tool_calls?: ToolCall[];
+  /** On `tool` messages, the call whose result this is. */
+  tool_call_id?: string;
+  /** Images, audio or documents, on backends that accept them. */
+  attachments?: Attachment[];
+}
+
+export interface Attachment {
+  mime_type: string;
+  /** Base64-encoded content. */
+  data: string;
+}
+
+export interface ToolDefinition {
+  name: string;
+  description?: string;
+  /** JSON schema of the tool's arguments. */
+  parameters: Record<string, unknown>;
+}
+
+export interface ToolCall {
+  id: string;
+  name: string;
+  arguments: Record<string, unknown>;
+}
+
+export type TruncationStrategy =
+  | "drop_oldest"
+  | "sliding_window"
+  | "middle"
+  | "summarize";
+
+export type VariableEscape = "none" | "html" | "json";
+
+export type PostProcessor =
+  | { type: "strip_stop"; sequences: string[] }
+  | { type: "trim" }
+  | { type: "extract_json" }
+  | { type: "strip_reasoning"; tags?: string[] }
+  | { type: "replace"; pattern: string; replacement: string };
+
+export interface InferenceRequest {
+  /** Required unless `prompt_id` names a prompt with a model. */
+  model_id?: string;
+  /** Required unless `messages` or `prompt_id` is given. */
+  prompt?: string;
+  messages?: ChatMessage[];
+  /** A stored prompt to send instead of `prompt` or `messages`. */
+  prompt_id?: string;
+  /** The version of `prompt_id`; the latest by default. */
+  prompt_version?: number;
+  /** Values for `{{ name }}` placeholders in `prompt` and message contents. */
+  variables?: Record<string, unknown>;
+  /** How `variables` are escaped; `none` by default. */
+  variable_escape?: VariableEscape;
+  truncation?: TruncationStrategy;
   max_tokens?: number;
   temperature?: number;
+  /** Replaces the model's post-processing pipeline. */
+  post_process?: PostProcessor[];
+  /** Tools offered to the model, on backends that support tool use. */
+  tools?: ToolDefinition[];
+  /** Keeps a conversation on one llama.cpp slot, reusing its KV cache. */
+  session_id?: string;
+  /** Generates this many candidates, up to 8, and returns the best. */
+  best_of?: number;
+  /** How `best_of` candidates are compared; the model judges them by default. */
+  scorer?: CandidateScorer;
+  /** With `best_of`, also returns every candidate and its score. */
+  return_candidates?: boolean;
+  top_p?: number;
+  top_k?: number;
+  min_p?: number;
+  repetition_penalty?: number;
+  presence_penalty?: number;
+  frequency_penalty?: number;
+  stop?: string[];
+  seed?: number;
+  /** Mirostat mode, `0`, `1` or `2`. */
+  mirostat?: number;
+  mirostat_tau?: number;
+  mirostat_eta?: number;
+  /** KoboldCpp sampler ids, in the order they run. */
+  sampler_order?: number[];
+  xtc_probability?: number;
+  xtc_threshold?: number;
+  /** How many recent tokens `repetition_penalty` looks back over. */
+  repetition_context_size?: number;
+  /** GBNF grammar the output must follow, on llama.cpp and KoboldCpp. */
+  grammar?: string;
+  /** JSON schema the output must follow, on llama.cpp. */
+  json_schema?: Record<string, unknown>;
+  /**
+   * Bias added to tokens' logits, keyed by token id, or by text on llama.cpp
+   * and candle.
+   */
+  logit_bias?: Record<string, number>;
+}
+
+export interface InferenceTimings {
+  time_to_first_token_ms: number;
+  total_ms: number;
+  tokens_per_second: number;
 }
 
+export type FinishReason =
+  | "stop"
+  | "length"
+  | "tool_calls"
+  | "content_filter"
+  | "error";
+
 export interface InferenceResponse {
   model_id: string;
   text: string;
   tokens_generated: number;
-  finish_reason: string;
+  finish_reason: FinishReason;
+  timings?: InferenceTimings;
+  moderation?: ModerationVerdict[];
+  injection?: InjectionReport;
+  tool_calls?: ToolCall[];
+  citations?: Citation[];
+  /** The model's reasoning, from backends that return it apart from `text`. */
+  reasoning?: string;
+  upstream?: UpstreamMetadata;
+  /** Every `best_of` candidate, with `return_candidates`. */
+  candidates?: BestOfCandidate[];
+}
+
+export type CandidateScorer =
+  | { type: "judge"; model_id?: string; criteria?: string }
+  | { type: "reranker"; model_id: string };
+
+export interface BestOfCandidate {
+  index: number;
+  text?: string;
+  finish_reason?: FinishReason;
+  tokens_generated?: number;
+  /** Higher is better. */
+  score?: number;
+  error?: ApiErrorResponse["error"];
+}
+
+export interface EnsembleJudge {
+  model_id: string;
+  /** What makes an answer better; accuracy, helpfulness and clarity by default. */
+  criteria?: string;
+  max_tokens?: number;
+}
+
+export interface EnsembleRequest
+  extends Omit<
+    InferenceRequest,
+    | "model_id"
+    | "truncation"
+    | "post_process"
+    | "tools"
+    | "session_id"
+    | "variables"
+    | "variable_escape"
+    | "prompt_id"
+    | "prompt_version"
+  > {
+  /** 2 to 8 models, each sent the same prompt. */
+  model_ids: string[];
+  /** A model that ranks the answers. */
+  judge?: EnsembleJudge;
+}
+
+export interface EnsembleMember {
+  model_id: string;
+  response?: InferenceResponse;
+  /** Why this model failed; the others still answer. */
+  error?: ApiErrorResponse["error"];
+  /** The judge's rank for the answer, 1 being the best. */
+  rank?: number;
+}
+
+export interface EnsembleJudgement {
+  model_id: string;
+  /** Model ids, best answer first. */
+  ranking: string[];
+  text?: string;
+  tokens_generated?: number;
+  error?: ApiErrorResponse["error"];
+}
+
+export interface EnsembleResponse {
+  /** One entry per requested model, in request order. */
+  responses: EnsembleMember[];
+  judge?: EnsembleJudgement;
+  total_ms: number;
+}
+
+export interface PromptDefaults {
+  max_tokens?: number;
+  temperature?: number;
+  /** Values for variables the request does not send. */
+  variables?: Record<string, unknown>;
+  variable_escape?: VariableEscape;
+  post_process?: PostProcessor[];
+}
+
+export interface PromptVersionRequest {
+  /** A prompt template, e.g. `Summarize: {{ document }}`. */
+  prompt?: string;
+  /** Chat messages whose contents are templates, instead of `prompt`. */
+  messages?: ChatMessage[];
+  /** The model requests go to unless they name one. */
+  model_id?: string;
+  defaults?: PromptDefaults;
+}
+
+export interface CreatePromptRequest extends PromptVersionRequest {
+  id: string;
+  description?: string;
+}
+
+export interface UpdatePromptRequest extends PromptVersionRequest {
+  /** Replaces the description; kept when unset. */
+  description?: string;
+}
+
+export interface PromptVersion extends PromptVersionRequest {
+  version: number;
+  defaults: PromptDefaults;
+  created_at: string;
+}
+
+export interface StoredPrompt {
+  id: string;
+  description?: string;
+  tenant?: string;
+  /** Oldest first. */
+  versions: PromptVersion[];
+  created_at: string;
+  updated_at: string;
+}
+
+export interface PromptListResponse {
+  prompts: StoredPrompt[];
+}
+
+/** A named group of models that requests can target as one. */
+export interface ModelPool {
+  id: string;
+  description?: string;
+  /** Registered model ids, in the order ties between them are broken. */
+  members: string[];
+  tenant?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface CreatePoolRequest {
+  id: string;
+  description?: string;
+  members: string[];
+}
+
+export interface UpdatePoolRequest {
+  /** Replaces the description; kept when unset. */
+  description?: string;
+  /** Replaces the members; kept when unset. */
+  members?: string[];
+}
+
+export interface PoolListResponse {
+  pools: ModelPool[];
+}
+
+export interface PipelineStep {
+  model_id: string;
+  /** A Jinja template that sees the inputs and earlier steps' outputs by name. */
+  template: string;
+  /** The variable the step's output is stored in. */
+  output: string;
+  max_tokens?: number;
+  temperature?: number;
+  post_process?: PostProcessor[];
+}
+
+export interface PipelineRequest {
+  inputs?: Record<string, unknown>;
+  /** Run in order, up to 16. */
+  steps: PipelineStep[];
+}
+
+export interface PipelineStepResult {
+  output: string;
+  model_id: string;
+  prompt: string;
+  text: string;
+  tokens_generated: number;
+  finish_reason: FinishReason;
+  timings: InferenceTimings;
+}
+
+export interface PipelineResponse {
+  /** The last step's output. */
+  output: string;
+  /** The inputs and every step's output. */
+  variables: Record<string, unknown>;
+  steps: PipelineStepResult[];
+  tokens_generated: number;
+  estimated_cost_usd?: number;
+  total_ms: number;
+}
+
+/** How a routing backend such as OpenRouter served the request. */
+export interface UpstreamMetadata {
+  model?: string;
+  provider?: string;
+  /** Cost billed by the backend. */
+  cost_usd?: number;
+}
+
+/** A span of the response grounded in documents or tool results. */
+export interface Citation {
+  start: number;
+  end: number;
+  text: string;
+  document_ids: string[];
+}
+
+export interface EmbeddingRequest {
+  model_id: string;
+  input: string[];
+  /** E.g. Cohere's `search_document` or `search_query`. */
+  input_type?: string;
+}
+
+export interface EmbeddingResponse {
+  model_id: string;
+  embeddings: number[][];
+  tokens: number;
+  /** Inputs answered from the engine's embedding cache; they count no tokens. */
+  cached: number;
+}
+
+export interface VectorPoint {
+  /** Replaces the point with the same id. Generated when omitted. */
+  id?: string;
+  text: string;
+  metadata?: Record<string, unknown>;
+}
+
+export interface UpsertVectorsRequest {
+  /** The embedding model; search the collection with the same one. */
+  model_id: string;
+  collection: string;
+  points: VectorPoint[];
+}
+
+export interface UpsertVectorsResponse {
+  collection: string;
+  ids: string[];
+  tokens: number;
+  cached: number;
+}
+
+export interface SearchVectorsRequest {
+  model_id: string;
+  collection: string;
+  query: string;
+  /** Defaults to 5, at most 100. */
+  top_k?: number;
+  /** Only points whose metadata has all of these key-value pairs. */
+  filter?: Record<string, unknown>;
+}
+
+export interface VectorMatch {
+  id: string;
+  /** Cosine similarity to the query, higher is closer. */
+  score: number;
+  text: string;
+  metadata: Record<string, unknown>;
+}
+
+export interface SearchVectorsResponse {
+  collection: string;
+  matches: VectorMatch[];
+  tokens: number;
+}
+
+export type ChunkStrategy = "fixed" | "paragraph" | "markdown";
+
+export interface IngestDocumentOptions {
+  /** The embedding model; search the collection with the same one. */
+  model_id: string;
+  collection: string;
+  /** `text/plain` (the default), `text/markdown` or `application/pdf`. */
+  content_type?: string;
+  /** Uploading a document again under its id replaces its chunks. */
+  document_id?: string;
+  /** A file name or title, kept in every chunk's metadata. */
+  name?: string;
+  /** Defaults to `paragraph`. */
+  strategy?: ChunkStrategy;
+  /** Tokens per chunk, defaults to 256. */
+  chunk_size?: number;
+  /** Tokens shared by consecutive chunks, defaults to 32. */
+  chunk_overlap?: number;
+}
+
+export type DocumentJobStatus =
+  | "queued"
+  | "extracting"
+  | "indexing"
+  | "completed"
+  | "failed";
+
+export interface DocumentJob {
+  id: string;
+  document_id: string;
+  collection: string;
+  status: DocumentJobStatus;
+  bytes: number;
+  /** Chunks the document was cut into, known once its text is extracted. */
+  chunks: number;
+  /** Chunks embedded and stored so far. */
+  indexed: number;
+  tokens: number;
+  cached: number;
+  error: string | null;
+  /** Unix timestamps in seconds. */
+  created_at: number;
+  finished_at: number | null;
+}
+
+export interface RagQueryRequest {
+  /** The model that writes the answer. */
+  model_id: string;
+  /** The embedding model the collection was filled with. */
+  embedding_model_id: string;
+  collection: string;
+  query: string;
+  /** Chunks to retrieve, defaults to 5. */
+  top_k?: number;
+  filter?: Record<string, unknown>;
+  /** Leaves out chunks less similar to the query than this. */
+  min_score?: number;
+  /** A Jinja template for the prompt, given `question` and `sources`. */
+  template?: string;
+  max_tokens?: number;
+  temperature?: number;
+}
+
+export interface RagSource {
+  /** The number the answer cites the chunk by, as `[n]`. */
+  number: number;
+  id: string;
+  score: number;
+  text: string;
+  metadata: Record<string, unknown>;
+  cited: boolean;
+}
+
+export interface RagQueryResponse {
+  model_id: string;
+  answer: string;
+  finish_reason: FinishReason;
+  sources: RagSource[];
+  embedding_tokens: number;
+  tokens_generated: number;
+  timings: InferenceTimings;
+  estimated_cost_usd?: number;
+}
+
+/** USD per 1,000 tokens. */
+export interface ModelPricing {
+  prompt_per_1k: number;
+  completion_per_1k: number;
+}
+
+/** A model from a hosted backend's catalog. */
+export interface CatalogModel {
+  id: string;
+  name: string;
+  context: number;
+  capabilities: ModelCapability[];
+  quant?: string;
+  pricing?: ModelPricing;
+}
+
+export interface CatalogResponse {
+  backend: InferenceBackend;
+  models: CatalogModel[];
+}
+
+export interface ImportCatalogRequest {
+  /** Catalog ids to register; the whole catalog when omitted. */
+  models?: string[];
+  load?: boolean;
+}
+
+export interface ImportCatalogResponse {
+  registered: string[];
+  skipped: string[];
+  not_found: string[];
+}
+
+export interface InjectionReport {
+  score: number;
+  signals: string[];
+}
+
+export type ModerationAction = "annotate" | "redact" | "block";
+
+export interface ModerationVerdict {
+  stage: "input" | "output";
+  categories: string[];
+  action: ModerationAction;
+}
+
+export interface StreamUsage extends InferenceTimings {
+  prompt_tokens: number;
+  completion_tokens: number;
+  estimated_cost_usd?: number;
+  finish_reason: FinishReason;
+}
+
+/** Sent by engines started with `--sse-ping` while a stream is quiet. */
+export interface StreamPing {
+  request_id: string;
+  elapsed_ms: number;
 }
 
 export interface StreamToken {
   token: string;
   token_id: number;
   complete: boolean;
+  /** Set on the `complete` token. */
+  finish_reason?: FinishReason;
 }
 
 export type StreamCallback = (token: StreamToken) => void;
 export type StreamCompleteCallback = (response: InferenceResponse) => void;
 export type StreamErrorCallback = (error: Error) => void;
+export type StreamUsageCallback = (usage: StreamUsage) => void;
+export type StreamPingCallback = (ping: StreamPing) => void;
 
 export interface StreamOptions {
   onToken: StreamCallback;
+  /** Called with `reasoning` events, from backends such as DeepSeek and xAI. */
+  onReasoning?: StreamCallback;
   onComplete?: StreamCompleteCallback;
+  /** Called with the `usage` event that closes a successful stream. */
+  onUsage?: StreamUsageCallback;
+  /** Called with `ping` events, while the engine has nothing else to send. */
+  onPing?: StreamPingCallback;
   onError?: StreamErrorCallback;
+  /**
+   * Called with the id of each event, on engines started with
+   * `--sse-resume-window`. Pass the last one back as `lastEventId`, with the
+   * same request, to resume a stream that was cut off.
+   */
+  onEventId?: (id: string) => void;
+  lastEventId?: string;
 }
 
 export interface OpenLLMConfig {
   engine?: string | number;
   timeout?: number;
+  apiKey?: string;
 }
 
 export interface FindModelOptions {
@@ -123,11 +830,24 @@ export interface APIConfig {
   prefix?: string;
 }
 
+/** The body of every error response from the engine. */
+export interface ApiErrorResponse {
+  error: {
+    type: string;
+    code: string;
+    message: string;
+    /** The request field the error is about. */
+    param: string | null;
+    request_id: string | null;
+  };
+}
+
 export class OpenLLMError extends Error {
   constructor(
     message: string,
     public code?: string,
     public statusCode?: number,
+    public requestId?: string,
   ) {
     super(message);
     this.name = "OpenLLMError";
@@ -159,6 +879,7 @@ export interface ModelRegistryInstance {
   list(): ReturnType<typeof import("./registry").ModelRegistryImpl.prototype.list>;
   get(id: string): ReturnType<typeof import("./registry").ModelRegistryImpl.prototype.get>;
   findOne(options: FindModelOptions): ReturnType<typeof import("./registry").ModelRegistryImpl.prototype.findOne>;
+  sync(models: ModelRegistryEntry[]): void;
 }
 
 export type ModelRegistryImpl = ModelRegistryInstance;